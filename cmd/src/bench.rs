@@ -0,0 +1,497 @@
+//! Benchmarks: users signing up, posting, following, and reading their home
+//! timelines at a target rate, through a [Server] in the process or the HTTP
+//! API of a running front-end, with the throughput and the latencies the
+//! calls saw reported at the end.
+use log::debug;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::time::MissedTickBehavior;
+use tribbler::{
+    err::{TribResult, TribblerError},
+    trib::Server,
+};
+
+// the password the users a benchmark makes over HTTP sign up and sign in with
+const BENCH_PASSWORD: &str = "bench";
+
+/// A call a benchmark makes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Op {
+    /// signs up a user never seen before
+    SignUp,
+    /// posts as a user
+    Post,
+    /// has a user follow another
+    Follow,
+    /// reads the home timeline of a user
+    Home,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Op::SignUp => "sign_up",
+            Op::Post => "post",
+            Op::Follow => "follow",
+            Op::Home => "home",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Op {
+    type Err = TribblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sign_up" => Ok(Op::SignUp),
+            "post" => Ok(Op::Post),
+            "follow" => Ok(Op::Follow),
+            "home" => Ok(Op::Home),
+            _ => Err(TribblerError::Unknown(format!(
+                "{} is not a call to benchmark; use sign_up, post, follow or home",
+                s
+            ))),
+        }
+    }
+}
+
+/// How often each call is made, as `call=weight` pairs separated by commas,
+/// e.g. `post=30,home=70`, each call being picked in proportion to its
+/// weight.
+///
+/// ```rust
+/// use cmd::bench::{Mix, Op};
+/// let mix: Mix = "post=1,home=3".parse().unwrap();
+/// assert_eq!(vec![(Op::Post, 1), (Op::Home, 3)], mix.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mix(pub Vec<(Op, u32)>);
+
+impl FromStr for Mix {
+    type Err = TribblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = vec![];
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (op, weight) = pair.split_once('=').ok_or_else(|| {
+                TribblerError::Unknown(format!("{} is not of the form call=weight", pair))
+            })?;
+            mix.push((op.trim().parse()?, weight.trim().parse()?));
+        }
+        if mix.iter().all(|(_, weight)| *weight == 0) {
+            return Err(TribblerError::Unknown(
+                "the mix gives no call any weight".to_string(),
+            ));
+        }
+        Ok(Mix(mix))
+    }
+}
+
+impl Mix {
+    // picks a call, in proportion to the weights
+    fn pick(&self, rng: &mut impl Rng) -> Op {
+        self.0
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map(|(op, _)| *op)
+            .unwrap_or(Op::Home)
+    }
+}
+
+/// What a benchmark calls: a [Server] in the process, or the HTTP API of a
+/// front-end.
+pub enum Target {
+    Server(Arc<dyn Server + Send + Sync>),
+    Http(HttpTarget),
+}
+
+impl Target {
+    /// Targets the `/api/v1` of the front-end serving at `base`, e.g.
+    /// `http://localhost:9000`.
+    pub fn http(base: &str) -> Target {
+        Target::Http(HttpTarget {
+            http: reqwest::Client::new(),
+            base: format!("{}/api/v1", base.trim_end_matches('/')),
+            sessions: HashMap::new(),
+        })
+    }
+
+    // signs up the users the benchmark calls as, taking on those who signed
+    // up already, and over HTTP signs them in; a front-end which just started
+    // is given a while to hear from its keeper
+    async fn prepare(&mut self, users: &[String]) -> TribResult<()> {
+        for user in users.iter() {
+            let mut tries = 0;
+            let mut signed_up = self.sign_up(user).await;
+            while matches!(&signed_up, Err(code) if code == "cluster_not_ready") && tries < 50 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                tries += 1;
+                signed_up = self.sign_up(user).await;
+            }
+            match signed_up {
+                Ok(()) => {}
+                Err(code) if code == "username_taken" => {}
+                Err(code) => {
+                    return Err(TribblerError::Unknown(format!(
+                        "cannot sign up {}: {}",
+                        user, code
+                    )))
+                }
+            }
+            if let Target::Http(http) = self {
+                let session = http.login(user).await?;
+                http.sessions.insert(user.clone(), session);
+            }
+        }
+        Ok(())
+    }
+
+    async fn sign_up(&self, user: &str) -> Result<(), String> {
+        match self {
+            Target::Server(server) => server.sign_up(user).await.map_err(code),
+            Target::Http(http) => {
+                let creds = serde_json::json!({ "user": user, "password": BENCH_PASSWORD });
+                http.call("add-user", None, &creds).await
+            }
+        }
+    }
+
+    // makes `op` as `user`, with `other` the user followed
+    async fn call(&self, op: Op, user: &str, other: &str, n: u64) -> Result<(), String> {
+        match (self, op) {
+            (_, Op::SignUp) => self.sign_up(other).await,
+            (Target::Server(server), Op::Post) => server
+                .post(user, &format!("bench post {}", n), 0)
+                .await
+                .map_err(code),
+            (Target::Server(server), Op::Follow) => server.follow(user, other).await.map_err(code),
+            (Target::Server(server), Op::Home) => server.home(user).await.map(|_| ()).map_err(code),
+            (Target::Http(http), Op::Post) => {
+                let post = serde_json::json!({
+                    "who": user,
+                    "message": format!("bench post {}", n),
+                    "clock": 0,
+                });
+                http.call("post", Some(user), &post).await
+            }
+            (Target::Http(http), Op::Follow) => {
+                let follow = serde_json::json!({ "who": user, "whom": other });
+                http.call("follow", Some(user), &follow).await
+            }
+            (Target::Http(http), Op::Home) => http.home(user).await,
+        }
+    }
+}
+
+// the code a failed call is counted under
+fn code(e: TribblerError) -> String {
+    e.code().to_string()
+}
+
+/// The `/api/v1` of a front-end, with the session each user is signed in
+/// with.
+pub struct HttpTarget {
+    http: reqwest::Client,
+    base: String,
+    sessions: HashMap<String, Session>,
+}
+
+// the cookie a user is signed in with, and the CSRF token their calls carry
+struct Session {
+    cookie: String,
+    csrf: String,
+}
+
+impl HttpTarget {
+    // signs `user` in, keeping the session the front-end hands out
+    async fn login(&self, user: &str) -> TribResult<Session> {
+        let resp = self
+            .http
+            .post(format!("{}/login", self.base))
+            .json(&serde_json::json!({ "user": user, "password": BENCH_PASSWORD }))
+            .send()
+            .await
+            .map_err(|e| TribblerError::Unknown(format!("cannot sign in {}: {}", user, e)))?;
+        let cookie = resp
+            .headers()
+            .get_all(reqwest::header::SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next())
+            .collect::<Vec<&str>>()
+            .join("; ");
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(TribblerError::Unknown(format!(
+                "cannot sign in {}: {} {}",
+                user, status, body
+            )));
+        }
+        let csrf = body["csrf"].as_str().unwrap_or_default().to_string();
+        Ok(Session { cookie, csrf })
+    }
+
+    // posts `arg` to the route `route`, in the session of `user` if any
+    async fn call(
+        &self,
+        route: &str,
+        user: Option<&str>,
+        arg: &impl serde::Serialize,
+    ) -> Result<(), String> {
+        let mut request = self.http.post(format!("{}/{}", self.base, route)).json(arg);
+        if let Some(session) = user.and_then(|user| self.sessions.get(user)) {
+            request = request
+                .header(reqwest::header::COOKIE, &session.cookie)
+                .header("x-csrf-token", &session.csrf);
+        }
+        answer(request.send().await).await
+    }
+
+    async fn home(&self, user: &str) -> Result<(), String> {
+        let request = self
+            .http
+            .get(format!("{}/list-home", self.base))
+            .query(&[("user", user)]);
+        answer(request.send().await).await
+    }
+}
+
+// reads a response to the end, as a client would, counting a failed one
+// under the code the front-end gave it, or its status
+async fn answer(resp: reqwest::Result<reqwest::Response>) -> Result<(), String> {
+    let resp = resp.map_err(|e| {
+        debug!("a call failed: {}", e);
+        "transport".to_string()
+    })?;
+    let status = resp.status();
+    let code = resp
+        .headers()
+        .get("x-error-code")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let _ = resp.bytes().await;
+    match status.is_success() {
+        true => Ok(()),
+        false => Err(code.unwrap_or_else(|| status.as_u16().to_string())),
+    }
+}
+
+/// What a benchmark does.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// how many users the calls are made as, signed up before it starts
+    pub users: usize,
+    /// the calls started each second
+    pub qps: u32,
+    /// how long the calls go on
+    pub duration: Duration,
+    /// how many calls may be under way at once; a call due while as many
+    /// are is skipped, and counted as dropped
+    pub concurrency: usize,
+    /// how often each call is made
+    pub mix: Mix,
+}
+
+/// How the calls of one kind went.
+#[derive(Debug, Default, Clone)]
+pub struct OpStats {
+    /// the latency of every call which succeeded
+    latencies: Vec<Duration>,
+    /// the calls which failed, by the code they failed with
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl OpStats {
+    /// How many calls succeeded.
+    pub fn ok(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// The latency under which the share `p` of the successful calls came
+    /// back, none without any.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        percentile(&self.latencies, p)
+    }
+}
+
+// the latency under which the share `p` of the sorted `latencies` fall
+fn percentile(latencies: &[Duration], p: f64) -> Option<Duration> {
+    if latencies.is_empty() {
+        return None;
+    }
+    let rank = (p * latencies.len() as f64).ceil() as usize;
+    Some(latencies[rank.clamp(1, latencies.len()) - 1])
+}
+
+/// What a benchmark found.
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    /// how long the calls went on for, until the last one came back
+    pub elapsed: Duration,
+    /// the calls due while as many as allowed were under way
+    pub dropped: usize,
+    /// the calls made, by kind
+    pub ops: BTreeMap<Op, OpStats>,
+}
+
+impl BenchReport {
+    /// The calls which came back, successful or not.
+    pub fn completed(&self) -> usize {
+        self.ops
+            .values()
+            .map(|s| s.ok() + s.errors.values().sum::<usize>())
+            .sum()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        writeln!(
+            f,
+            "{} calls in {:.1}s, {:.1} calls/s, {} dropped",
+            self.completed(),
+            secs,
+            self.completed() as f64 / secs,
+            self.dropped
+        )?;
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "call", "ok", "failed", "p50", "p90", "p99", "max"
+        )?;
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.2}ms", d.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        for (op, stats) in self.ops.iter() {
+            writeln!(
+                f,
+                "{:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+                op.to_string(),
+                stats.ok(),
+                stats.errors.values().sum::<usize>(),
+                ms(stats.percentile(0.5)),
+                ms(stats.percentile(0.9)),
+                ms(stats.percentile(0.99)),
+                ms(stats.percentile(1.0)),
+            )?;
+            for (code, n) in stats.errors.iter() {
+                writeln!(f, "{:>8} {} failed with {}", "", n, code)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Signs up the users of a benchmark on `target`, then makes calls at the
+/// rate and in the mix `opts` asks for, and reports how they went.
+pub async fn run(mut target: Target, opts: BenchOptions) -> TribResult<BenchReport> {
+    if opts.users < 2 || opts.qps == 0 || opts.concurrency == 0 {
+        return Err(TribblerError::Unknown(
+            "a benchmark needs two users, a call a second, and a call at once at least".to_string(),
+        ));
+    }
+    // the users of a run are its own, so that runs against one cluster start
+    // out alike
+    let run = rand::thread_rng().gen_range(0..100_000);
+    let users: Vec<String> = (0..opts.users).map(|i| format!("b{}u{}", run, i)).collect();
+    target.prepare(&users).await?;
+
+    let target = Arc::new(target);
+    let users = Arc::new(users);
+    let stats: Arc<Mutex<BTreeMap<Op, OpStats>>> = Arc::default();
+    let calls = Arc::new(AtomicU64::new(0));
+    let slots = Arc::new(Semaphore::new(opts.concurrency));
+    let mut dropped = 0;
+    let mut ticks = tokio::time::interval(Duration::from_secs(1) / opts.qps);
+    // a call which came due while the client was behind is skipped rather
+    // than made late, so a slow target is not flooded when it recovers
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let start = Instant::now();
+    while start.elapsed() < opts.duration {
+        ticks.tick().await;
+        let slot = match slots.clone().try_acquire_owned() {
+            Ok(slot) => slot,
+            Err(_) => {
+                dropped += 1;
+                continue;
+            }
+        };
+        let mut rng = rand::thread_rng();
+        let op = opts.mix.pick(&mut rng);
+        let n = calls.fetch_add(1, Ordering::Relaxed);
+        let user = users.choose(&mut rng).unwrap().clone();
+        let other = match op {
+            Op::SignUp => format!("b{}s{}", run, n),
+            _ => users.choose(&mut rng).unwrap().clone(),
+        };
+        let (target, stats) = (target.clone(), stats.clone());
+        tokio::spawn(async move {
+            let called = Instant::now();
+            let result = target.call(op, &user, &other, n).await;
+            let latency = called.elapsed();
+            let mut stats = stats.lock().unwrap();
+            let stats = stats.entry(op).or_default();
+            match result {
+                Ok(()) => stats.latencies.push(latency),
+                Err(code) => *stats.errors.entry(code).or_default() += 1,
+            }
+            drop(slot);
+        });
+    }
+    // wait for the calls under way to come back
+    let _ = slots.acquire_many(opts.concurrency as u32).await;
+    let elapsed = start.elapsed();
+    let mut ops = std::mem::take(&mut *stats.lock().unwrap());
+    for stats in ops.values_mut() {
+        stats.latencies.sort();
+    }
+    Ok(BenchReport {
+        elapsed,
+        dropped,
+        ops,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{percentile, Mix, Op};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_mix() {
+        let mix: Mix = "sign_up=5, post=25,follow=20,home=50".parse().unwrap();
+        assert_eq!(
+            vec![
+                (Op::SignUp, 5),
+                (Op::Post, 25),
+                (Op::Follow, 20),
+                (Op::Home, 50)
+            ],
+            mix.0
+        );
+        assert!("post".parse::<Mix>().is_err());
+        assert!("like=3".parse::<Mix>().is_err());
+        assert!("post=x".parse::<Mix>().is_err());
+        assert!("post=0".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let ms: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(Some(Duration::from_millis(50)), percentile(&ms, 0.5));
+        assert_eq!(Some(Duration::from_millis(99)), percentile(&ms, 0.99));
+        assert_eq!(Some(Duration::from_millis(100)), percentile(&ms, 1.0));
+        assert_eq!(Some(Duration::from_millis(1)), percentile(&ms, 0.0));
+        assert_eq!(None, percentile(&[], 0.5));
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::process;
+
+use clap::Parser;
+use lab::lab1::new_client;
+use lab::lab2::{bin_replicas, cluster_status};
+use tribbler::{
+    addr,
+    colon::{escape, unescape},
+    config::Config,
+    err::TribResult,
+    storage::Pattern,
+};
+
+/// prints the backends a bin is kept on, the way the bin clients of the
+/// config place it, and what the bin holds there, with the keys as the bin
+/// sees them rather than as stored
+#[derive(Parser, Debug)]
+#[clap(name = "bin-inspect")]
+struct Options {
+    /// the config of the cluster
+    config: String,
+    /// the name of the bin, e.g. a user name
+    bin: String,
+    /// compare the replicas, printing only the keys they disagree on
+    #[clap(short, long)]
+    diff: bool,
+}
+
+// what one replica of a bin holds, by key
+#[derive(Default, PartialEq)]
+struct Contents {
+    values: BTreeMap<String, String>,
+    lists: BTreeMap<String, Vec<String>>,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let args = Options::parse();
+    let cfg = Config::read(Some(&args.config))?;
+    if cfg.backs.is_empty() {
+        eprintln!("no backends in {}", args.config);
+        process::exit(1)
+    }
+
+    // the keeper says which backends are in the write path; without one the
+    // bin clients route over all of them
+    let status = cluster_status(&cfg.backs, &cfg.keepers).await;
+    let eligible: Vec<bool> = match status.keeper {
+        Some(_) => status.backends.iter().map(|b| b.eligible).collect(),
+        None => vec![true; cfg.backs.len()],
+    };
+    let replicas = bin_replicas(&args.bin, &cfg.backs, &eligible, cfg.placement);
+    if replicas.is_empty() {
+        eprintln!("no backend is in the write path to keep {}", args.bin);
+        process::exit(1)
+    }
+    match &status.keeper {
+        Some(keeper) => println!("bin {:?}, placed by keeper {}", args.bin, keeper),
+        None => println!("bin {:?}, placed without a keeper", args.bin),
+    }
+
+    let mut contents = vec![];
+    for (n, i) in replicas.iter().enumerate() {
+        let back = &cfg.backs[*i];
+        let role = if n == 0 { "serving" } else { "replica" };
+        match read(back, &args.bin).await {
+            Ok(held) => {
+                println!(
+                    "  {} {} ({}): {} keys, {} lists",
+                    role,
+                    back,
+                    i,
+                    held.values.len(),
+                    held.lists.len()
+                );
+                contents.push((back, held));
+            }
+            Err(e) => println!("  {} {} ({}): cannot read: {}", role, back, i, e),
+        }
+    }
+
+    if args.diff {
+        diff(&contents);
+        return Ok(());
+    }
+    for (back, held) in contents.iter() {
+        println!();
+        println!("on {}:", back);
+        for (key, value) in held.values.iter() {
+            println!("{:?} = {:?}", key, value);
+        }
+        for (key, list) in held.lists.iter() {
+            println!("{:?} = {:?}", key, list);
+        }
+    }
+    Ok(())
+}
+
+// reads every value and list of the bin `bin` kept on the backend `back`
+async fn read(back: &str, bin: &str) -> TribResult<Contents> {
+    let client = new_client(&addr::uri(back)).await?;
+    // the bin client stores a key of the bin as the escaped bin name, "::",
+    // and the escaped key
+    let prefix = escape(bin) + "::";
+    let pattern = Pattern {
+        prefix: prefix.clone(),
+        suffix: "".to_string(),
+    };
+    let key_of = |stored: &str| unescape(&stored[prefix.len()..]);
+    let mut held = Contents::default();
+    for stored in client.keys(&pattern).await?.0 {
+        let value = client.get(&stored).await?.unwrap_or_default();
+        held.values.insert(key_of(&stored), value);
+    }
+    for stored in client.list_keys(&pattern).await?.0 {
+        let list = client.list_get(&stored).await?.0;
+        held.lists.insert(key_of(&stored), list);
+    }
+    Ok(held)
+}
+
+// prints the keys which the replicas do not all hold alike, with what each
+// holds under them
+fn diff(contents: &[(&String, Contents)]) {
+    println!();
+    if contents.len() < 2 {
+        println!("fewer than two replicas read, nothing to compare");
+        return;
+    }
+    if contents.iter().all(|(_, held)| *held == contents[0].1) {
+        println!("the replicas agree");
+        return;
+    }
+    let keys: BTreeSet<&String> = contents
+        .iter()
+        .flat_map(|(_, held)| held.values.keys())
+        .collect();
+    for key in keys {
+        let values: Vec<Option<&String>> =
+            contents.iter().map(|(_, h)| h.values.get(key)).collect();
+        if values.iter().any(|v| *v != values[0]) {
+            println!("{:?}:", key);
+            for ((back, _), value) in contents.iter().zip(values) {
+                println!("  {} has {}", back, shown(value));
+            }
+        }
+    }
+    let keys: BTreeSet<&String> = contents
+        .iter()
+        .flat_map(|(_, held)| held.lists.keys())
+        .collect();
+    for key in keys {
+        let lists: Vec<Option<&Vec<String>>> =
+            contents.iter().map(|(_, h)| h.lists.get(key)).collect();
+        if lists.iter().any(|l| *l != lists[0]) {
+            println!("list {:?}:", key);
+            for ((back, _), list) in contents.iter().zip(lists) {
+                println!("  {} has {}", back, shown(list));
+            }
+        }
+    }
+}
+
+// what a replica holds under a key, if anything
+fn shown(held: Option<&impl std::fmt::Debug>) -> String {
+    match held {
+        Some(held) => format!("{:?}", held),
+        None => "nothing".to_string(),
+    }
+}
@@ -1,6 +1,7 @@
 use clap::{Arg, ArgMatches, Command, Parser};
 use cmd::client_cmds::{app_commands, match_storage_cmds, print_result, repl};
 use lab::lab2;
+use tokio::sync::watch;
 use tribbler::{
     config::{Config, DEFAULT_CONFIG_LOCATION},
     err::{TribResult, TribblerError},
@@ -23,8 +24,9 @@ fn bin_cmd() -> [Command<'static>; 1] {
 async fn main() -> TribResult<()> {
     let args = Options::parse();
     let cfg = Config::read(Some(&args.config))?;
-    let addrs = cfg.backs;
-    let bc = lab2::new_bin_client(addrs).await?;
+    // place the bins the way the config says, as the keepers do
+    let (_, cfg) = watch::channel(cfg);
+    let bc = lab2::new_bin_client_following(cfg).await?;
     let app = Command::new("bin-client")
         .subcommands(app_commands())
         .subcommands(bin_cmd());
@@ -57,7 +59,7 @@ pub async fn match_cmds(
             }
             Err(e) => {
                 // turbofish ::<String> just to pass a concrete type in order to compile
-                print_result::<String>(Err(Box::new(TribblerError::Unknown(e.to_string()))));
+                print_result::<String>(Err(TribblerError::Unknown(e.to_string())));
                 true
             }
         },
@@ -1,5 +1,6 @@
 use clap::Parser;
 use cmd::bins_run;
+use cmd::gen_config::GenerateConfig;
 use log::LevelFilter;
 use tribbler::config::DEFAULT_CONFIG_LOCATION;
 use tribbler::err::TribResult;
@@ -11,7 +12,7 @@ struct Args {
     /// log level to use when starting the backends
     #[clap(short, long, default_value = "INFO")]
     log_level: LevelFilter,
-    /// bin configuration file
+    /// bin configuration file, in JSON, TOML, or YAML by its extension
     #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
     config: String,
     /// addresses to send ready notifications to
@@ -20,18 +21,30 @@ struct Args {
 
     #[clap(long, default_value = "10")]
     recv_timeout: u64,
+
+    #[clap(flatten)]
+    generate: GenerateConfig,
+
+    /// take on the backends of the config whenever it changes, or on SIGHUP,
+    /// rather than only at start
+    #[clap(long)]
+    watch_config: bool,
 }
 
 #[tokio::main]
 async fn main() -> TribResult<()> {
     let pt = bins_run::ProcessType::Keep;
     let args = Args::parse();
+    if args.generate.run(&args.config)? {
+        return Ok(());
+    }
     bins_run::main(
         pt,
         args.log_level,
         args.config,
         args.ready_addrs,
         args.recv_timeout,
+        args.watch_config,
     )
     .await
 }
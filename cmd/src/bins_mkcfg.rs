@@ -22,7 +22,8 @@ struct Options {
     /// number of keepers
     #[clap(short, long, default_value = "1")]
     keeps: usize,
-    /// location to write the config file. Use `-` for stdout
+    /// location to write the config file, as JSON, or as TOML or YAML when it
+    /// ends in `.toml` or `.yaml`. Use `-` for stdout
     #[clap(long, default_value = DEFAULT_CONFIG_LOCATION)]
     file: String,
     /// whether or not to used fixed versus random port numbers
@@ -59,7 +60,15 @@ fn main() -> TribResult<()> {
         p += 1;
     }
 
-    let cfg = config::Config { backs, keepers };
+    let cfg = config::Config {
+        backs,
+        keepers,
+        placement: config::Placement::default(),
+        discovery: None,
+        front: config::FrontSection::default(),
+        back: config::BackSection::default(),
+        keeper: config::KeeperSection::default(),
+    };
 
     cfg.write(Some(&args.file))
 }
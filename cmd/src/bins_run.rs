@@ -1,5 +1,5 @@
 use std::{
-    process,
+    fs, process,
     sync::{
         mpsc::{self, Sender},
         Arc,
@@ -7,10 +7,20 @@ use std::{
     time::Duration,
 };
 
+use crate::config_watch;
+use crate::shutdown;
 use lab::{lab1, lab2};
 use log::{error, info, warn, LevelFilter};
 use tokio::join;
-use tribbler::{addr, config::Config, err::TribResult, storage::MemStorage};
+use tokio::sync::mpsc::{self as async_mpsc, Receiver};
+use tokio::sync::watch;
+use tonic::transport::Identity;
+use tribbler::{
+    addr,
+    config::{discovery, BackSection, Config},
+    err::{TribResult, TribblerError},
+    storage::MemStorage,
+};
 
 #[derive(Debug, Clone)]
 pub enum ProcessType {
@@ -24,28 +34,67 @@ pub async fn main(
     cfg: String,
     _ready_addrs: Vec<String>,
     recv_timeout: u64,
+    watch_config: bool,
 ) -> TribResult<()> {
     env_logger::builder()
         .default_format()
         .filter_level(log_level)
         .init();
-    let config = Arc::new(Config::read(Some(&cfg))?);
+    let mut config = Config::read(Some(&cfg))?;
+    match t {
+        ProcessType::Back => {
+            // the backends registered are started by the hosts they name
+            if let Some(registry) = &config.discovery {
+                let found = discovery::fetch(&reqwest::Client::new(), registry).await?;
+                (config.backs, config.keepers) = (found.backs, found.keepers);
+            }
+            // a backend named by SRV records is started by the host it resolves to
+            config.backs = lab2::discovery::resolve_backs(&config.backs).await?;
+        }
+        ProcessType::Keep => {
+            config.keeper.tuning.check()?;
+            config.placement.check()?;
+        }
+    }
+    // the keepers follow the config when asked to, and the registry it names
+    // if any; the backends have nothing to take from either
+    let reloads = match (&t, watch_config) {
+        (ProcessType::Keep, true) => Some(config_watch::watch_config(cfg, config.clone())),
+        (ProcessType::Keep, false) if config.discovery.is_some() => {
+            Some(watch::channel(config.clone()).1)
+        }
+        _ => None,
+    };
+    let reloads = match reloads {
+        Some(reloads) => {
+            let reloads = discovery::discover(reloads).await?;
+            config = reloads.borrow().clone();
+            Some(reloads)
+        }
+        None => None,
+    };
+    let config = Arc::new(config);
 
     println!("{:?}", config);
     let (tx, rdy) = mpsc::channel();
 
     let mut handles = vec![];
+    let mut stops = vec![];
     let it = match t {
         ProcessType::Back => &config.backs,
         ProcessType::Keep => &config.keepers,
     };
     for (i, srv) in it.iter().enumerate() {
         if addr::check(srv)? {
+            let (stop, stopped) = async_mpsc::channel(1);
+            stops.push(stop);
             handles.push(tokio::spawn(run_srv(
                 t.clone(),
                 i,
                 config.clone(),
                 Some(tx.clone()),
+                stopped,
+                reloads.clone(),
             )));
         }
     }
@@ -71,6 +120,16 @@ pub async fn main(
             process::exit(1);
         }
     }
+
+    // on Ctrl-C or SIGTERM, let every server finish the calls it is serving
+    // before going down, rather than cutting them off
+    tokio::spawn(async move {
+        shutdown::signalled().await;
+        info!("shutting down the {}s...", proc_name);
+        for stop in stops {
+            let _ = stop.send(()).await;
+        }
+    });
     for h in handles {
         match join!(h) {
             (Ok(_),) => (),
@@ -79,21 +138,69 @@ pub async fn main(
             }
         };
     }
+    info!("all {}s stopped", proc_name);
     Ok(())
 }
 
 #[allow(unused_must_use)]
-async fn run_srv(t: ProcessType, idx: usize, config: Arc<Config>, tx: Option<Sender<bool>>) {
+async fn run_srv(
+    t: ProcessType,
+    idx: usize,
+    config: Arc<Config>,
+    tx: Option<Sender<bool>>,
+    shutdown: Receiver<()>,
+    reloads: Option<watch::Receiver<Config>>,
+) {
     match t {
         ProcessType::Back => {
-            let cfg = config.back_config(idx, Box::new(MemStorage::default()), tx, None);
+            let storage = MemStorage::default();
+            let back = &config.backs[idx];
+            if let Some(dir) = &config.back.restore_dir {
+                match lab2::restore(dir, back, &storage).await {
+                    Ok(Some(path)) => info!("restored {} from {}", back, path.display()),
+                    Ok(None) => info!("no snapshot of {} to restore in {}", back, dir),
+                    Err(e) => {
+                        // serving it empty would lose what it kept
+                        error!("failed to restore {} from {}: {}", back, dir, e);
+                        if let Some(tx) = tx {
+                            tx.send(false);
+                        }
+                        return;
+                    }
+                }
+            }
+            let identity = match back_identity(&config.back) {
+                Ok(identity) => identity,
+                Err(e) => {
+                    error!("failed to load the TLS certificate of {}: {}", back, e);
+                    if let Some(tx) = tx {
+                        tx.send(false);
+                    }
+                    return;
+                }
+            };
+            let cfg = config.back_config(idx, Box::new(storage), tx, Some(shutdown));
             info!("starting backend on {}", cfg.addr);
-            lab1::serve_back(cfg).await;
+            if let Err(e) = lab1::serve_back_with_tls(cfg, identity).await {
+                error!("backend {} failed: {}", back, e);
+            }
         }
         ProcessType::Keep => {
-            let cfg = config.keeper_config(idx, tx, None).unwrap();
+            let cfg = config.keeper_config(idx, tx, Some(shutdown)).unwrap();
             info!("starting keeper on {}", cfg.addr());
-            lab2::serve_keeper(cfg).await;
+            lab2::serve_keeper_following(cfg, reloads).await;
         }
     };
 }
+
+// the certificate and key the `back` section has the backends serve their
+// `https://` addresses with, if any
+fn back_identity(back: &BackSection) -> TribResult<Option<Identity>> {
+    match (&back.tls_cert, &back.tls_key) {
+        (Some(cert), Some(key)) => Ok(Some(Identity::from_pem(fs::read(cert)?, fs::read(key)?))),
+        (None, None) => Ok(None),
+        _ => Err(TribblerError::Unknown(
+            "the back section needs both tls_cert and tls_key, or neither".to_string(),
+        )),
+    }
+}
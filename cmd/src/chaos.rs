@@ -0,0 +1,653 @@
+//! Chaos runs: a cluster started inside the process, with users driving
+//! traffic through a front-end while backends and keepers are killed and
+//! brought back at random. Once every server is back up, what the users were
+//! told is checked against what the cluster kept: no post it acknowledged is
+//! lost, and no user follows another twice, or other than the follow or
+//! unfollow last acknowledged says.
+use lab::{lab1, lab2};
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tribbler::{
+    config::{BackSection, Config, FrontSection, KeeperSection, KeeperTuning, Placement},
+    err::{TribResult, TribblerError},
+    storage::MemStorage,
+    trib::{Server, MAX_TRIB_FETCH},
+};
+
+// how long a server gets to come up
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how long the cluster gets to take every backend back into the write path
+// once the chaos is over
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// how often the cluster is looked at while it settles, and reads retried
+const POLL: Duration = Duration::from_millis(100);
+
+// how many times a call setting the users up, or checking the invariants, is
+// tried
+const TRIES: usize = 20;
+
+// the pause each user takes between two calls
+const THINK_TIME: Duration = Duration::from_millis(20);
+
+// keeps the keeper quick to notice servers going and coming back, and to take
+// them back, so that a run of seconds sees plenty of both
+fn tuning() -> KeeperTuning {
+    KeeperTuning {
+        sync_interval_ms: 200,
+        stable_window_ms: 500,
+        max_hold_down_ms: 2_000,
+        probe_timeout_ms: 500,
+        failure_threshold: 1,
+        retry_backoff_ms: 200,
+        max_retry_backoff_ms: 1_000,
+    }
+}
+
+/// What a chaos run does.
+#[derive(Debug, Clone)]
+pub struct ChaosOptions {
+    /// how many backends the cluster has
+    pub backs: usize,
+    /// how many keepers the cluster has
+    pub keepers: usize,
+    /// how many users drive traffic, each calling the front-end in turn
+    pub users: usize,
+    /// how long the chaos goes on
+    pub duration: Duration,
+    /// how often a server is killed or brought back
+    pub interval: Duration,
+    /// how many of the backends, and of the keepers, may be down at once
+    pub max_down: usize,
+    /// how the bins are kept on the backends
+    pub placement: Placement,
+    /// the seed of the choices of the run: which server goes down when, and
+    /// what the users call
+    pub seed: u64,
+}
+
+/// How many calls of a kind the front-end acknowledged, and how many failed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Calls {
+    pub acked: usize,
+    pub failed: usize,
+}
+
+/// What a chaos run found.
+#[derive(Debug, Default)]
+pub struct ChaosReport {
+    pub seed: u64,
+    /// the calls the users made, by the method called
+    pub calls: BTreeMap<&'static str, Calls>,
+    /// how many times a server was killed
+    pub kills: usize,
+    /// how many times a server was brought back
+    pub restarts: usize,
+    /// every invariant found broken, which a run passes without
+    pub violations: Vec<String>,
+}
+
+impl ChaosReport {
+    /// Whether the run found every invariant held.
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for ChaosReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "chaos run with seed {}", self.seed)?;
+        writeln!(
+            f,
+            "servers killed {} times, brought back {} times",
+            self.kills, self.restarts
+        )?;
+        for (method, calls) in self.calls.iter() {
+            writeln!(
+                f,
+                "{:>10}: {} acknowledged, {} failed",
+                method, calls.acked, calls.failed
+            )?;
+        }
+        if self.passed() {
+            return write!(f, "every invariant held");
+        }
+        write!(f, "{} invariants broken:", self.violations.len())?;
+        for violation in self.violations.iter() {
+            write!(f, "\n  {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a chaos run as `opts` says, and reports what it found. It is an
+/// error only for the cluster not to start; a broken invariant is reported.
+pub async fn run(opts: ChaosOptions) -> TribResult<ChaosReport> {
+    if opts.backs == 0 || opts.keepers == 0 || opts.users < 2 {
+        return Err(TribblerError::Unknown(
+            "a chaos run needs a backend, a keeper, and two users at least".to_string(),
+        ));
+    }
+    let config = Config {
+        backs: (0..opts.backs)
+            .map(|_| free_addr())
+            .collect::<TribResult<_>>()?,
+        keepers: (0..opts.keepers)
+            .map(|_| free_addr())
+            .collect::<TribResult<_>>()?,
+        placement: opts.placement,
+        discovery: None,
+        front: FrontSection::default(),
+        back: BackSection::default(),
+        keeper: KeeperSection {
+            tuning: tuning(),
+            backup: None,
+        },
+    };
+    let mut cluster = Cluster::new(config);
+    cluster.start().await?;
+    let front: Arc<dyn Server + Send + Sync> =
+        Arc::from(lab2::new_front_following(watch::channel(cluster.config.clone()).1).await?);
+
+    let users: Vec<String> = (0..opts.users).map(|i| format!("user{}", i)).collect();
+    // the front-end takes writes once the view of a keeper reaches it
+    for user in users.iter() {
+        match retry(|| front.sign_up(user)).await {
+            Ok(()) | Err(TribblerError::UsernameTaken(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    // the users call the front-end until told to stop, while the servers
+    // come and go
+    let (stop, stopped) = watch::channel(false);
+    let drivers: Vec<JoinHandle<UserLog>> = users
+        .iter()
+        .enumerate()
+        .map(|(i, user)| {
+            tokio::spawn(drive(
+                front.clone(),
+                user.clone(),
+                users.clone(),
+                opts.seed.wrapping_add(i as u64 + 1),
+                stopped.clone(),
+            ))
+        })
+        .collect();
+    let mut report = ChaosReport {
+        seed: opts.seed,
+        ..ChaosReport::default()
+    };
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    let deadline = Instant::now() + opts.duration;
+    while Instant::now() < deadline {
+        tokio::time::sleep(opts.interval).await;
+        match cluster.upset(&mut rng, opts.max_down).await {
+            Some(true) => report.kills += 1,
+            Some(false) => report.restarts += 1,
+            None => {}
+        }
+    }
+    let _ = stop.send(true);
+    let mut logs = Vec::with_capacity(drivers.len());
+    for driver in drivers {
+        match driver.await {
+            Ok(log) => logs.push(log),
+            Err(e) => report.violations.push(format!("a user failed: {}", e)),
+        }
+    }
+
+    // bring everything back, and check what the users were told held
+    report.restarts += cluster.revive_all().await;
+    if !cluster.settled().await {
+        report.violations.push(format!(
+            "the cluster did not take every backend back within {:?}",
+            SETTLE_TIMEOUT
+        ));
+    }
+    for log in logs.iter() {
+        for (method, calls) in log.calls.iter() {
+            let total = report.calls.entry(method).or_default();
+            total.acked += calls.acked;
+            total.failed += calls.failed;
+        }
+        report.violations.extend(check(&*front, log).await);
+    }
+    cluster.stop();
+    Ok(report)
+}
+
+// a backend of the cluster: the storage it keeps, which it comes back with
+// after going down as a backend on disk would, and the task serving it while
+// it is up
+struct Back {
+    storage: Arc<MemStorage>,
+    task: Option<JoinHandle<()>>,
+}
+
+// the servers of a cluster, up or down
+struct Cluster {
+    config: Config,
+    backs: Vec<Back>,
+    keepers: Vec<Option<JoinHandle<()>>>,
+}
+
+impl Cluster {
+    fn new(config: Config) -> Cluster {
+        Cluster {
+            backs: config
+                .backs
+                .iter()
+                .map(|_| Back {
+                    storage: Arc::new(MemStorage::new()),
+                    task: None,
+                })
+                .collect(),
+            keepers: config.keepers.iter().map(|_| None).collect(),
+            config,
+        }
+    }
+
+    // starts every server, and waits for the cluster to take writes
+    async fn start(&mut self) -> TribResult<()> {
+        for i in 0..self.backs.len() {
+            self.start_back(i).await?;
+        }
+        for i in 0..self.keepers.len() {
+            self.start_keeper(i).await?;
+        }
+        if !self.settled().await {
+            return Err(TribblerError::ClusterNotReady);
+        }
+        Ok(())
+    }
+
+    async fn start_back(&mut self, i: usize) -> TribResult<()> {
+        let (ready, rdy) = mpsc::channel();
+        let storage = Box::new(self.backs[i].storage.clone());
+        let cfg = self.config.back_config(i, storage, Some(ready), None);
+        let back = cfg.addr.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = lab1::serve_back(cfg).await {
+                warn!("backend {} failed: {}", back, e);
+            }
+        });
+        self.backs[i].task = Some(task);
+        started(rdy, &self.config.backs[i]).await
+    }
+
+    async fn start_keeper(&mut self, i: usize) -> TribResult<()> {
+        let (ready, rdy) = mpsc::channel();
+        let cfg = self.config.keeper_config(i, Some(ready), None)?;
+        let keeper = cfg.addr().to_string();
+        let task = tokio::spawn(async move {
+            if let Err(e) = lab2::serve_keeper(cfg).await {
+                warn!("keeper {} failed: {}", keeper, e);
+            }
+        });
+        self.keepers[i] = Some(task);
+        started(rdy, &self.config.keepers[i]).await
+    }
+
+    // kills a server up, or brings back one down, as the seed picks, and says
+    // which it did, if either
+    async fn upset(&mut self, rng: &mut StdRng, max_down: usize) -> Option<bool> {
+        let backs_down = self.backs.iter().filter(|b| b.task.is_none()).count();
+        let keepers_down = self.keepers.iter().filter(|k| k.is_none()).count();
+        let mut choices = vec![];
+        for (i, back) in self.backs.iter().enumerate() {
+            match back.task {
+                Some(_) if backs_down < max_down => choices.push(Upset::KillBack(i)),
+                Some(_) => {}
+                None => choices.push(Upset::ReviveBack(i)),
+            }
+        }
+        for (i, keeper) in self.keepers.iter().enumerate() {
+            match keeper {
+                Some(_) if keepers_down < max_down => choices.push(Upset::KillKeeper(i)),
+                Some(_) => {}
+                None => choices.push(Upset::ReviveKeeper(i)),
+            }
+        }
+        let upset = *choices.choose(rng)?;
+        match upset {
+            Upset::KillBack(i) => {
+                info!("killing backend {}", self.config.backs[i]);
+                kill(self.backs[i].task.take()).await;
+                Some(true)
+            }
+            Upset::KillKeeper(i) => {
+                info!("killing keeper {}", self.config.keepers[i]);
+                kill(self.keepers[i].take()).await;
+                Some(true)
+            }
+            Upset::ReviveBack(i) => {
+                info!("bringing back backend {}", self.config.backs[i]);
+                match self.start_back(i).await {
+                    Ok(()) => Some(false),
+                    Err(e) => {
+                        warn!("failed to bring back {}: {}", self.config.backs[i], e);
+                        kill(self.backs[i].task.take()).await;
+                        None
+                    }
+                }
+            }
+            Upset::ReviveKeeper(i) => {
+                info!("bringing back keeper {}", self.config.keepers[i]);
+                match self.start_keeper(i).await {
+                    Ok(()) => Some(false),
+                    Err(e) => {
+                        warn!("failed to bring back {}: {}", self.config.keepers[i], e);
+                        kill(self.keepers[i].take()).await;
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    // brings back every server down, returning how many it did
+    async fn revive_all(&mut self) -> usize {
+        let mut revived = 0;
+        for i in 0..self.backs.len() {
+            if self.backs[i].task.is_none() {
+                match self.start_back(i).await {
+                    Ok(()) => revived += 1,
+                    Err(e) => warn!("failed to bring back {}: {}", self.config.backs[i], e),
+                }
+            }
+        }
+        for i in 0..self.keepers.len() {
+            if self.keepers[i].is_none() {
+                match self.start_keeper(i).await {
+                    Ok(()) => revived += 1,
+                    Err(e) => warn!("failed to bring back {}: {}", self.config.keepers[i], e),
+                }
+            }
+        }
+        revived
+    }
+
+    // waits for the keepers to have every backend in the write path, with
+    // their clocks converged
+    async fn settled(&self) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < SETTLE_TIMEOUT {
+            let status = lab2::cluster_status(&self.config.backs, &self.config.keepers).await;
+            if status.consistent && status.backends.iter().all(|b| b.eligible) {
+                return true;
+            }
+            tokio::time::sleep(POLL).await;
+        }
+        false
+    }
+
+    // takes every server down
+    fn stop(&mut self) {
+        for back in self.backs.iter_mut() {
+            if let Some(task) = back.task.take() {
+                task.abort();
+            }
+        }
+        for task in self.keepers.iter_mut().filter_map(Option::take) {
+            task.abort();
+        }
+    }
+}
+
+// a server killed or brought back, by its index among the servers of its kind
+#[derive(Debug, Clone, Copy)]
+enum Upset {
+    KillBack(usize),
+    ReviveBack(usize),
+    KillKeeper(usize),
+    ReviveKeeper(usize),
+}
+
+// takes down the server `task` serves as a crash would, waiting for it to
+// let go of its address
+async fn kill(task: Option<JoinHandle<()>>) {
+    if let Some(task) = task {
+        task.abort();
+        let _ = task.await;
+    }
+}
+
+// a local address with a port free at the moment, which the system picks
+// rather than [addr::rand], as the ephemeral ports the connections of the run
+// take overlap its range
+fn free_addr() -> TribResult<String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.to_string())
+}
+
+// waits for the server `addr` to say it serves
+async fn started(rdy: Receiver<bool>, addr: &str) -> TribResult<()> {
+    let ready = tokio::task::spawn_blocking(move || rdy.recv_timeout(READY_TIMEOUT)).await;
+    match ready {
+        Ok(Ok(true)) => Ok(()),
+        Ok(Ok(false)) => Err(TribblerError::Unknown(format!("{} failed to start", addr))),
+        _ => Err(TribblerError::Unknown(format!(
+            "timed out waiting for {} to start",
+            addr
+        ))),
+    }
+}
+
+// what a user was told: the posts acknowledged, in the order they were made,
+// and for every user it followed or unfollowed, whether it follows them
+// going by the last of those calls, none when that call failed, leaving it
+// unknown
+#[derive(Debug, Default)]
+struct UserLog {
+    user: String,
+    posts: Vec<String>,
+    follows: HashMap<String, Option<bool>>,
+    calls: BTreeMap<&'static str, Calls>,
+}
+
+impl UserLog {
+    fn count<T>(&mut self, method: &'static str, result: &TribResult<T>) {
+        let calls = self.calls.entry(method).or_default();
+        match result {
+            Ok(_) => calls.acked += 1,
+            Err(_) => calls.failed += 1,
+        }
+    }
+}
+
+// calls the front-end as `user`, picking the calls and whom they are about
+// from `seed`, until told to stop
+async fn drive(
+    front: Arc<dyn Server + Send + Sync>,
+    user: String,
+    users: Vec<String>,
+    seed: u64,
+    stop: watch::Receiver<bool>,
+) -> UserLog {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let others: Vec<&String> = users.iter().filter(|u| **u != user).collect();
+    let mut log = UserLog {
+        user: user.clone(),
+        ..UserLog::default()
+    };
+    // every post tried fits in what reading the user's tribs returns
+    let mut tried = 0;
+    while !*stop.borrow() {
+        let whom = others.choose(&mut rng).unwrap().to_string();
+        match rng.gen_range(0..100) {
+            0..=39 if tried < MAX_TRIB_FETCH => {
+                tried += 1;
+                let message = format!("{} #{}", user, tried);
+                let result = front.post(&user, &message, 0).await;
+                log.count("post", &result);
+                if result.is_ok() {
+                    log.posts.push(message);
+                }
+            }
+            40..=64 => {
+                let result = front.follow(&user, &whom).await;
+                log.count("follow", &result);
+                let follows = match result {
+                    Ok(()) | Err(TribblerError::AlreadyFollowing(..)) => Some(true),
+                    Err(TribblerError::FollowingTooMany) => {
+                        log.follows.get(&whom).copied().flatten()
+                    }
+                    Err(_) => None,
+                };
+                log.follows.insert(whom, follows);
+            }
+            65..=79 => {
+                let result = front.unfollow(&user, &whom).await;
+                log.count("unfollow", &result);
+                let follows = match result {
+                    Ok(()) | Err(TribblerError::NotFollowing(..)) => Some(false),
+                    Err(_) => None,
+                };
+                log.follows.insert(whom, follows);
+            }
+            _ => {
+                let result = front.home(&user).await;
+                log.count("home", &result);
+            }
+        }
+        tokio::time::sleep(THINK_TIME).await;
+    }
+    log
+}
+
+// checks what the cluster kept of the user of `log` against what they were
+// told, returning every invariant broken
+async fn check(front: &(dyn Server + Send + Sync), log: &UserLog) -> Vec<String> {
+    let user = &log.user;
+    let tribs = match retry(|| front.tribs(user)).await {
+        Ok(tribs) => tribs,
+        Err(e) => return vec![format!("cannot read the tribs of {}: {}", user, e)],
+    };
+    let stored: Vec<String> = tribs.iter().map(|t| t.message.clone()).collect();
+    let following = match retry(|| front.following(user)).await {
+        Ok(following) => following,
+        Err(e) => return vec![format!("cannot read whom {} follows: {}", user, e)],
+    };
+    let mut violations = lost_posts(user, &log.posts, &stored);
+    violations.extend(follow_violations(user, &log.follows, &following));
+    violations
+}
+
+// makes a call until it succeeds, or has failed too often
+async fn retry<T, F, Fut>(read: F) -> TribResult<T>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = TribResult<T>>,
+{
+    let mut tries = 1;
+    loop {
+        match read().await {
+            Err(e) if tries < TRIES => {
+                info!("retrying a call which failed: {}", e);
+                tries += 1;
+                tokio::time::sleep(POLL).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// the acknowledged posts of `user` missing from the posts `stored`
+fn lost_posts(user: &str, acked: &[String], stored: &[String]) -> Vec<String> {
+    acked
+        .iter()
+        .filter(|post| !stored.contains(post))
+        .map(|post| format!("the acknowledged post {:?} of {} is lost", post, user))
+        .collect()
+}
+
+// the ways whom `user` is `following` breaks with what the follows and
+// unfollows acknowledged last said
+fn follow_violations(
+    user: &str,
+    follows: &HashMap<String, Option<bool>>,
+    following: &[String],
+) -> Vec<String> {
+    let mut violations = vec![];
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for whom in following.iter() {
+        *counts.entry(whom).or_default() += 1;
+    }
+    for (whom, count) in counts.iter() {
+        if *count > 1 {
+            violations.push(format!("{} follows {} {} times", user, whom, count));
+        }
+    }
+    let mut known: Vec<(&String, bool)> = follows
+        .iter()
+        .filter_map(|(whom, follows)| follows.map(|follows| (whom, follows)))
+        .collect();
+    known.sort();
+    for (whom, follows) in known {
+        match (follows, counts.contains_key(whom.as_str())) {
+            (true, false) => violations.push(format!(
+                "{} does not follow {}, though following them was acknowledged last",
+                user, whom
+            )),
+            (false, true) => violations.push(format!(
+                "{} follows {}, though unfollowing them was acknowledged last",
+                user, whom
+            )),
+            _ => {}
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::{follow_violations, lost_posts};
+    use std::collections::HashMap;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_lost_posts() {
+        let acked = strings(&["a #1", "a #2", "a #3"]);
+        assert!(lost_posts("a", &acked, &strings(&["a #1", "a #2", "a #3", "a #4"])).is_empty());
+        assert_eq!(
+            vec!["the acknowledged post \"a #2\" of a is lost"],
+            lost_posts("a", &acked, &strings(&["a #1", "a #3"]))
+        );
+    }
+
+    #[test]
+    fn test_follow_violations() {
+        let follows = HashMap::from([
+            ("b".to_string(), Some(true)),
+            ("c".to_string(), Some(false)),
+            ("d".to_string(), None),
+        ]);
+        assert!(follow_violations("a", &follows, &strings(&["b"])).is_empty());
+        assert!(follow_violations("a", &follows, &strings(&["b", "d"])).is_empty());
+        assert_eq!(
+            vec![
+                "a follows b 2 times",
+                "a follows c, though unfollowing them was acknowledged last",
+            ],
+            follow_violations("a", &follows, &strings(&["b", "c", "b"]))
+        );
+        assert_eq!(
+            vec!["a does not follow b, though following them was acknowledged last"],
+            follow_violations("a", &follows, &[])
+        );
+    }
+}
@@ -1,5 +1,4 @@
 use clap::{Arg, ArgMatches, Command};
-use std::error::Error;
 use std::fmt::Debug;
 use std::io;
 use std::io::Write;
@@ -8,7 +7,7 @@ use tribbler::{
     storage::{KeyValue, Pattern, Storage},
 };
 
-pub fn app_commands() -> [Command<'static>; 9] {
+pub fn app_commands() -> [Command<'static>; 11] {
     let k = &[Arg::new("key").required(true)];
     let kv = &[
         Arg::new("key").required(true),
@@ -19,6 +18,15 @@ pub fn app_commands() -> [Command<'static>; 9] {
         Arg::new("suffix").required(false).default_value(""),
     ];
     let clk = &[Arg::new("clock").required(false).default_value("0")];
+    let delta = &[
+        Arg::new("key").required(true),
+        Arg::new("delta").required(false).default_value("1"),
+    ];
+    let swap = &[
+        Arg::new("key").required(true),
+        Arg::new("expected").required(true),
+        Arg::new("value").required(true),
+    ];
     [
         Command::new("get").args(k),
         Command::new("set").args(kv),
@@ -28,6 +36,8 @@ pub fn app_commands() -> [Command<'static>; 9] {
         Command::new("list-remove").args(kv),
         Command::new("list-keys").args(patt),
         Command::new("clock").args(clk),
+        Command::new("incr").args(delta),
+        Command::new("cas").args(swap),
         Command::new("exit"),
     ]
 }
@@ -36,17 +46,20 @@ pub fn repl(app: &Command) -> TribResult<ArgMatches> {
     print!("> ");
     io::stdout().flush().expect("Couldn't flush stdout");
     let mut input = String::new();
-    io::stdin()
+    let read = io::stdin()
         .read_line(&mut input)
         .expect("Error reading input.");
+    // the end of the input ends the session, as exit does
+    if read == 0 {
+        println!();
+        input = "exit".to_string();
+    }
 
     let mut args = match shlex::split(&input) {
         Some(v) => v,
         None => {
             println!("error splitting args");
-            return Err(Box::new(TribblerError::Unknown(
-                "failed to split args".to_string(),
-            )));
+            return Err(TribblerError::Unknown("failed to split args".to_string()));
         }
     };
     let mut client_args = vec![app.get_name().to_string()];
@@ -57,9 +70,7 @@ pub fn repl(app: &Command) -> TribResult<ArgMatches> {
         Ok(v) => Ok(v),
         Err(e) => {
             println!("Failed to parse args: {}", e);
-            Err(Box::new(TribblerError::Unknown(
-                "failed to parse args".to_string(),
-            )))
+            Err(TribblerError::Unknown("failed to parse args".to_string()))
         }
     }
 }
@@ -92,6 +103,19 @@ pub async fn match_storage_cmds(client: &dyn Storage, subcmd: Option<(&str, &Arg
             Ok(clk) => print_result(client.clock(clk).await),
             Err(e) => println!("{:?}", e),
         },
+        Some(("incr", v)) => match v.value_of("delta").unwrap().parse::<i64>() {
+            Ok(delta) => print_result(client.incr(v.value_of("key").unwrap(), delta).await),
+            Err(e) => println!("{:?}", e),
+        },
+        Some(("cas", v)) => print_result(
+            client
+                .compare_and_swap(
+                    v.value_of("key").unwrap(),
+                    v.value_of("expected").unwrap(),
+                    v.value_of("value").unwrap(),
+                )
+                .await,
+        ),
         Some(("exit", _)) => return false,
         _ => println!("unexpected command. try again."),
     }
@@ -112,6 +136,6 @@ fn get_pattern(matches: &ArgMatches) -> Pattern {
     }
 }
 
-pub fn print_result<T: Debug>(x: Result<T, Box<dyn Error + Send + Sync>>) {
+pub fn print_result<T: Debug>(x: TribResult<T>) {
     println!("{:?}", x);
 }
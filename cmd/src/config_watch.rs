@@ -0,0 +1,91 @@
+//! Following edits to the config file, so that the processes of a cluster
+//! take on a changed list of backends or keepers without being restarted.
+use log::{info, warn};
+use std::fs;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tribbler::config::Config;
+
+/// how often the config file is checked for changes
+pub const CONFIG_POLL: Duration = Duration::from_secs(2);
+
+/// Hands out the config read from `location`, which was `config` at start,
+/// reloading it whenever the file changes, or on SIGHUP. Only a reload which
+/// changes the backends or the keepers is handed out; a config which fails to
+/// read is reported and the last one kept.
+pub fn watch_config(location: String, config: Config) -> watch::Receiver<Config> {
+    let (tx, rx) = watch::channel(config);
+    tokio::spawn(async move {
+        let mut modified = modified_at(&location);
+        let mut hangup = hangups();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CONFIG_POLL) => {
+                    let now = modified_at(&location);
+                    if now == modified {
+                        continue;
+                    }
+                    modified = now;
+                }
+                _ = hangup.recv() => info!("reloading {} on SIGHUP", location),
+            }
+            if tx.is_closed() {
+                return; // nothing follows the config anymore
+            }
+            let reloaded = match Config::read(Some(&location)) {
+                Ok(reloaded) => reloaded,
+                Err(e) => {
+                    warn!("keeping the config, as {} failed to read: {}", location, e);
+                    continue;
+                }
+            };
+            tx.send_if_modified(|config| {
+                if config.backs == reloaded.backs && config.keepers == reloaded.keepers {
+                    return false;
+                }
+                info!(
+                    "reloaded {}: backends {:?}, keepers {:?}",
+                    location, reloaded.backs, reloaded.keepers
+                );
+                *config = reloaded;
+                true
+            });
+        }
+    });
+    rx
+}
+
+// when the file at `location` was last changed, none when that can't be told
+fn modified_at(location: &str) -> Option<SystemTime> {
+    fs::metadata(location).and_then(|m| m.modified()).ok()
+}
+
+// the SIGHUPs the process receives, none off unix
+fn hangups() -> Hangups {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::hangup()) {
+            Ok(signal) => return Hangups(Some(signal)),
+            Err(e) => warn!("cannot listen for SIGHUP: {}", e),
+        }
+    }
+    Hangups(None)
+}
+
+#[cfg(unix)]
+struct Hangups(Option<tokio::signal::unix::Signal>);
+
+#[cfg(not(unix))]
+struct Hangups(Option<()>);
+
+impl Hangups {
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.0.as_mut() {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,107 @@
+//! A whole cluster, in-memory backends and a keeper, run inside the process
+//! using it, so trying the service out locally takes a single command.
+use lab::{lab1, lab2};
+use log::{error, info};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+use tribbler::{
+    addr,
+    config::{BackSection, Config, FrontSection, KeeperSection, Limits, Placement},
+    err::{TribResult, TribblerError},
+    storage::MemStorage,
+};
+
+// how long the backends, the keeper, and then the keeper's view of them, get
+// to come up
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how often the keeper is asked whether the backends converged
+const CONSISTENT_POLL: Duration = Duration::from_millis(100);
+
+/// Starts `backs` backends, each keeping its bins in a [MemStorage], and a
+/// keeper over them, on random local ports, and hands back the [Config]
+/// naming them once the cluster takes writes. They serve for as long as the process
+/// runs, and everything they store goes with it.
+pub async fn start_cluster(backs: usize, limits: Limits) -> TribResult<Config> {
+    if backs == 0 {
+        return Err(TribblerError::Unknown(
+            "a cluster needs a backend at least".to_string(),
+        ));
+    }
+    let config = Config {
+        backs: (0..backs).map(|_| addr::rand::local()).collect(),
+        keepers: vec![addr::rand::local()],
+        placement: Placement::default(),
+        discovery: None,
+        front: FrontSection {
+            limits,
+            ..FrontSection::default()
+        },
+        back: BackSection::default(),
+        keeper: KeeperSection::default(),
+    };
+    let (ready, rdy) = mpsc::channel();
+    for i in 0..backs {
+        let cfg = config.back_config(
+            i,
+            Box::new(MemStorage::default()),
+            Some(ready.clone()),
+            None,
+        );
+        info!("starting backend on {}", cfg.addr);
+        tokio::spawn(async move {
+            if let Err(e) = lab1::serve_back(cfg).await {
+                error!("a backend failed: {}", e);
+            }
+        });
+    }
+    wait_ready(&rdy, backs, "backend")?;
+
+    let cfg = config.keeper_config(0, Some(ready), None)?;
+    info!("starting keeper on {}", cfg.addr());
+    tokio::spawn(async move {
+        if let Err(e) = lab2::serve_keeper(cfg).await {
+            error!("the keeper failed: {}", e);
+        }
+    });
+    wait_ready(&rdy, 1, "keeper")?;
+    wait_consistent(&config).await?;
+    Ok(config)
+}
+
+// waits for the keeper to put the backends in the write path, which it does
+// once their clocks converge, so that the cluster takes writes right away
+async fn wait_consistent(config: &Config) -> TribResult<()> {
+    let start = Instant::now();
+    while start.elapsed() < READY_TIMEOUT {
+        let status = lab2::cluster_status(&config.backs, &config.keepers).await;
+        if status.consistent {
+            return Ok(());
+        }
+        tokio::time::sleep(CONSISTENT_POLL).await;
+    }
+    Err(TribblerError::ClusterNotReady)
+}
+
+// waits for `n` of the servers named `what` to say they serve
+fn wait_ready(rdy: &Receiver<bool>, n: usize, what: &str) -> TribResult<()> {
+    for _ in 0..n {
+        // the servers come up on the other workers meanwhile
+        match tokio::task::block_in_place(|| rdy.recv_timeout(READY_TIMEOUT)) {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(TribblerError::Unknown(format!(
+                    "a {} failed to start",
+                    what
+                )))
+            }
+            Err(_) => {
+                return Err(TribblerError::Unknown(format!(
+                    "timed out waiting for the {}s to start",
+                    what
+                )))
+            }
+        }
+    }
+    Ok(())
+}
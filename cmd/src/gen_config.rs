@@ -0,0 +1,59 @@
+//! The `--generate-config` flags the binaries share, which write a config
+//! for a cluster on this machine to start them with, in place of writing one
+//! by hand.
+use clap::Parser;
+use std::path::Path;
+use tribbler::{
+    config::Config,
+    err::{TribResult, TribblerError},
+};
+
+/// Flags for writing out a config for a local cluster.
+#[derive(Parser, Debug, Clone)]
+pub struct GenerateConfig {
+    /// write a config for a cluster on this machine to the config file, in
+    /// the format its extension names, and exit; an existing file is left
+    /// alone
+    #[clap(long)]
+    pub generate_config: bool,
+
+    /// the backends of a generated config
+    #[clap(long, default_value = "3")]
+    pub gen_backs: usize,
+
+    /// the keepers of a generated config
+    #[clap(long, default_value = "1")]
+    pub gen_keepers: usize,
+
+    /// the port the first server of a generated config serves on, the rest
+    /// following on the next ports
+    #[clap(long, default_value = "3000")]
+    pub gen_base_port: u16,
+}
+
+impl GenerateConfig {
+    /// Writes the config the flags ask for to `location`, when they ask for
+    /// one, returning whether they did.
+    pub fn run(&self, location: &str) -> TribResult<bool> {
+        if !self.generate_config {
+            return Ok(false);
+        }
+        if location != "-" && Path::new(location).exists() {
+            return Err(TribblerError::Unknown(format!(
+                "{} already exists; move it away to generate a new config",
+                location
+            )));
+        }
+        let config = Config::template(self.gen_backs, self.gen_keepers, self.gen_base_port)?;
+        config.write(Some(location))?;
+        if location != "-" {
+            println!(
+                "wrote a config for {} backends and {} keepers to {}",
+                config.backs.len(),
+                config.keepers.len(),
+                location
+            );
+        }
+        Ok(true)
+    }
+}
@@ -1,9 +1,9 @@
 use clap::{Command, Parser};
 use cmd::client_cmds::{app_commands, match_storage_cmds, repl};
 use lab::lab1::new_client;
-use tribbler::err::TribResult;
 #[allow(unused_imports)]
 use tribbler::storage::{KeyList, KeyString, KeyValue, Pattern};
+use tribbler::{addr, err::TribResult};
 
 #[derive(Parser, Debug)]
 #[clap(name = "kv-client")]
@@ -18,7 +18,7 @@ struct Options {
 #[tokio::main]
 async fn main() -> TribResult<()> {
     let options = Options::parse();
-    let client = new_client(&format!("http://{}", &options.address)).await?;
+    let client = new_client(&addr::uri(&options.address)).await?;
     let app = Command::new("kv-client").subcommands(app_commands());
 
     loop {
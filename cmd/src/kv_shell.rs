@@ -0,0 +1,68 @@
+use clap::{Arg, ArgMatches, Command, Parser};
+use cmd::client_cmds::{app_commands, match_storage_cmds, print_result, repl};
+use lab::lab1::new_client;
+use tribbler::storage::{Pattern, Storage};
+use tribbler::{addr, err::TribResult};
+
+/// A shell of key-value calls against one live backend, for looking into what
+/// a lab implementation actually wrote there. Keys are shown as stored, with
+/// the bin prefixes and escaping of the bin storage left in.
+#[derive(Parser, Debug)]
+#[clap(name = "kv-shell")]
+struct Options {
+    /// the address of the backend, e.g. 127.0.0.1:7799
+    addr: String,
+}
+
+// commands only the shell has, on top of the storage calls
+fn shell_commands() -> [Command<'static>; 1] {
+    [Command::new("dump")
+        .about("prints every value and list whose key starts with prefix")
+        .arg(Arg::new("prefix").required(false).default_value(""))]
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let options = Options::parse();
+    let client = new_client(&addr::uri(&options.addr)).await?;
+    // fails early on a backend which is not there
+    let clock = client.clock(0).await?;
+    println!("connected to {} at clock {}", options.addr, clock);
+    let app = Command::new("kv-shell")
+        .subcommands(app_commands())
+        .subcommands(shell_commands());
+
+    loop {
+        let matches = match repl(&app) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let more = match matches.subcommand() {
+            Some(("dump", v)) => {
+                print_result(dump(&*client, v).await);
+                true
+            }
+            subcmd => match_storage_cmds(&*client, subcmd).await,
+        };
+        if !more {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn dump(client: &dyn Storage, matches: &ArgMatches) -> TribResult<()> {
+    let pattern = Pattern {
+        prefix: matches.value_of("prefix").unwrap().to_string(),
+        suffix: "".to_string(),
+    };
+    for key in client.keys(&pattern).await?.0 {
+        let value = client.get(&key).await?.unwrap_or_default();
+        println!("{:?} = {:?}", key, value);
+    }
+    for key in client.list_keys(&pattern).await?.0 {
+        let list = client.list_get(&key).await?.0;
+        println!("{:?} = {:?}", key, list);
+    }
+    Ok(())
+}
@@ -4,5 +4,15 @@
 #![doc(
     html_favicon_url = "https://upload.wikimedia.org/wikipedia/commons/thumb/f/f8/Creative-Tail-Animal-penguin.svg/128px-Creative-Tail-Animal-penguin.svg.png?20160314145218"
 )]
+pub mod bench;
 pub mod bins_run;
+pub mod chaos;
 pub mod client_cmds;
+pub mod config_watch;
+pub mod dev;
+pub mod gen_config;
+pub mod oidc;
+pub mod rate_limit;
+pub mod session_store;
+pub mod shutdown;
+pub mod validate;
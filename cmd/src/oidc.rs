@@ -0,0 +1,261 @@
+//! Signing users in through an OpenID Connect provider, such as a campus
+//! single sign-on, with the authorization code flow.
+//!
+//! The ID token is taken straight from the provider's token endpoint over
+//! TLS, which lets a confidential client trust it without checking its
+//! signature (OpenID Connect Core 1.0, section 3.1.3.7); its issuer, audience,
+//! expiry and nonce are still checked.
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tribbler::err::TribblerError;
+use tribbler::trib::MAX_USERNAME_LEN;
+
+/// An OpenID Connect provider users can sign in through.
+pub struct Provider {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    redirect_url: String,
+    username_claim: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    http: reqwest::Client,
+}
+
+/// Who the provider says signed in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// the issuer and subject of the ID token, which together name the
+    /// signed in person for good
+    pub subject: String,
+    /// the tribbler username the person would like, made out of the
+    /// configured claim
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+impl Provider {
+    /// Looks up the endpoints of the provider at `issuer` in its discovery
+    /// document. `redirect_url` is where the provider sends browsers back
+    /// to, and `username_claim` the ID token claim usernames are made of.
+    pub async fn discover(
+        issuer: &str,
+        client_id: &str,
+        client_secret: &str,
+        redirect_url: &str,
+        username_claim: &str,
+    ) -> Result<Provider, TribblerError> {
+        let http = reqwest::Client::new();
+        let url = issuer.trim_end_matches('/').to_owned() + "/.well-known/openid-configuration";
+        let discovery: Discovery = http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(sso_failed)?
+            .json()
+            .await
+            .map_err(sso_failed)?;
+        Ok(Provider {
+            issuer: discovery.issuer,
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            redirect_url: redirect_url.to_string(),
+            username_claim: username_claim.to_string(),
+            authorization_endpoint: discovery.authorization_endpoint,
+            token_endpoint: discovery.token_endpoint,
+            http,
+        })
+    }
+
+    /// The URL of the provider's sign in page, which sends the browser back
+    /// to the redirect URL with a code and the given `state`.
+    pub fn authorize_url(&self, state: &str, nonce: &str) -> Result<String, TribblerError> {
+        let url = reqwest::Url::parse_with_params(
+            &self.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", "openid profile email"),
+                ("state", state),
+                ("nonce", nonce),
+            ],
+        )
+        .map_err(sso_failed)?;
+        Ok(url.to_string())
+    }
+
+    /// Trades the code the provider sent the browser back with for the
+    /// identity it stands for. `nonce` is the one the sign in started with.
+    pub async fn identity(&self, code: &str, nonce: &str) -> Result<Identity, TribblerError> {
+        let tokens: TokenResponse = self
+            .http
+            .post(&self.token_endpoint)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_url),
+            ])
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(sso_failed)?
+            .json()
+            .await
+            .map_err(sso_failed)?;
+        let claims = token_claims(&tokens.id_token)?;
+        check_claims(&claims, &self.issuer, &self.client_id, nonce, unix_now())?;
+        identity_of(&claims, &self.username_claim)
+    }
+}
+
+fn sso_failed(e: impl std::fmt::Display) -> TribblerError {
+    TribblerError::SsoFailed(e.to_string())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// reads the claims out of the payload of a JWT
+fn token_claims(token: &str) -> Result<Value, TribblerError> {
+    let payload = match token.split('.').collect::<Vec<_>>()[..] {
+        [_, payload, _] => payload,
+        _ => return Err(sso_failed("malformed ID token")),
+    };
+    let json = URL_SAFE_NO_PAD.decode(payload).map_err(sso_failed)?;
+    serde_json::from_slice(&json).map_err(sso_failed)
+}
+
+// checks that an ID token was issued by issuer, for client_id, to the sign
+// in that used nonce, and has not expired by now
+fn check_claims(
+    claims: &Value,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: u64,
+) -> Result<(), TribblerError> {
+    if claims["iss"].as_str() != Some(issuer) {
+        return Err(sso_failed("ID token from another issuer"));
+    }
+    let for_us = match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !for_us {
+        return Err(sso_failed("ID token for another client"));
+    }
+    if claims["nonce"].as_str() != Some(nonce) {
+        return Err(sso_failed("ID token for another sign in"));
+    }
+    match claims["exp"].as_u64() {
+        Some(exp) if exp > now => Ok(()),
+        _ => Err(sso_failed("ID token expired")),
+    }
+}
+
+// names who the claims of a checked ID token are about
+fn identity_of(claims: &Value, username_claim: &str) -> Result<Identity, TribblerError> {
+    let (issuer, subject) = match (claims["iss"].as_str(), claims["sub"].as_str()) {
+        (Some(issuer), Some(subject)) => (issuer, subject),
+        _ => return Err(sso_failed("ID token without a subject")),
+    };
+    let username = claims[username_claim]
+        .as_str()
+        .map(username_from)
+        .unwrap_or_default();
+    if username.is_empty() {
+        return Err(sso_failed(format!(
+            "no usable \"{}\" claim",
+            username_claim
+        )));
+    }
+    Ok(Identity {
+        subject: issuer.to_owned() + " " + subject,
+        username,
+    })
+}
+
+/// Makes a valid tribbler username out of an external name: the part of an
+/// email address before the `@`, lowercased, with everything but letters
+/// and digits dropped, cut to [MAX_USERNAME_LEN]. Empty when nothing is left.
+pub fn username_from(name: &str) -> String {
+    let local = name.split('@').next().unwrap_or("");
+    local
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .take(MAX_USERNAME_LEN)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_claims, identity_of, token_claims, username_from, Identity};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::json;
+
+    #[test]
+    fn test_username_from() {
+        assert_eq!("alice", username_from("Alice"));
+        assert_eq!("jdoe", username_from("j.doe@ucsd.edu"));
+        assert_eq!("maryann", username_from("Mary-Ann"));
+        assert_eq!(
+            "abcdefghijklmno",
+            username_from("abcdefghijklmnopqrstuvwxyz")
+        );
+        assert_eq!("", username_from("@ucsd.edu"));
+    }
+
+    #[test]
+    fn test_claims() {
+        let claims = json!({
+            "iss": "https://sso.example.edu",
+            "aud": ["tribbler", "other"],
+            "sub": "12345",
+            "nonce": "n",
+            "exp": 100,
+            "email": "J.Doe@example.edu",
+        });
+        let token = format!("e30.{}.c2ln", URL_SAFE_NO_PAD.encode(claims.to_string()));
+        let claims = token_claims(&token).unwrap();
+
+        assert!(check_claims(&claims, "https://sso.example.edu", "tribbler", "n", 99).is_ok());
+        assert!(check_claims(&claims, "https://sso.example.edu", "tribbler", "n", 100).is_err());
+        assert!(check_claims(&claims, "https://evil.example.com", "tribbler", "n", 99).is_err());
+        assert!(check_claims(&claims, "https://sso.example.edu", "trobbler", "n", 99).is_err());
+        assert!(check_claims(&claims, "https://sso.example.edu", "tribbler", "m", 99).is_err());
+
+        assert_eq!(
+            Identity {
+                subject: "https://sso.example.edu 12345".to_string(),
+                username: "jdoe".to_string(),
+            },
+            identity_of(&claims, "email").unwrap()
+        );
+        assert!(identity_of(&claims, "preferred_username").is_err());
+        assert!(token_claims("not a token").is_err());
+    }
+}
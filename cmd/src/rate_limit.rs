@@ -0,0 +1,301 @@
+//! Token buckets limiting how fast each client, be it an address or a signed
+//! in user, may call the front-end, apart from any limit the service itself
+//! puts on what users store.
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tribbler::err::TribResult;
+use tribbler::storage::{KeyValue, Pattern, Storage};
+use tribbler::time::{SystemClock, TimeSource};
+
+/// the bin the buckets every front-end shares are kept in; no username can
+/// take this name
+pub const LIMIT_BIN: &str = "@limits";
+
+// how many buckets are kept before the full ones, which are no different from
+// a fresh bucket, are dropped
+const PRUNE_AT: usize = 4096;
+
+/// Lets every key make `rate` calls a second on average, and up to `burst`
+/// at once.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    // when set, the buckets are kept there instead, for every front-end
+    shared: Option<SharedBuckets>,
+    time: Arc<dyn TimeSource>,
+}
+
+// buckets kept in [LIMIT_BIN], each as its tokens and the unix time in
+// milliseconds they were counted at, under the name of the limiter
+struct SharedBuckets {
+    bin: Box<dyn Storage>,
+    prefix: String,
+}
+
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+}
+
+impl RateLimiter {
+    /// A limiter of `rate` calls a second, which has to be positive, with
+    /// room for `burst` calls at once, at least one.
+    pub fn new(rate: f64, burst: u32) -> RateLimiter {
+        RateLimiter {
+            rate,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+            shared: None,
+            time: Arc::new(SystemClock),
+        }
+    }
+
+    /// A limiter like [RateLimiter::new] whose buckets are kept in `bin`, the
+    /// [LIMIT_BIN] of a cluster, under `name`, so the front-ends of the cluster
+    /// limit each key together. Calls racing through several front-ends at
+    /// once may each take the same token.
+    pub fn shared(rate: f64, burst: u32, bin: Box<dyn Storage>, name: &str) -> RateLimiter {
+        RateLimiter {
+            shared: Some(SharedBuckets {
+                bin,
+                prefix: name.to_string() + ":",
+            }),
+            ..RateLimiter::new(rate, burst)
+        }
+    }
+
+    /// The limiter, refilling its buckets by `time` rather than the clock of
+    /// the machine.
+    pub fn with_time(self, time: Arc<dyn TimeSource>) -> RateLimiter {
+        RateLimiter { time, ..self }
+    }
+
+    /// Takes a call from the bucket of `key`, or says how long until the
+    /// bucket has one again. A shared bucket which can't be reached lets the
+    /// call through.
+    pub async fn take(&self, key: &str) -> Result<(), Duration> {
+        match &self.shared {
+            Some(shared) => match self.take_shared(shared, key, self.unix_millis()).await {
+                Ok(taken) => taken,
+                Err(e) => {
+                    warn!("rate limit bucket of {} unavailable: {}", key, e);
+                    Ok(())
+                }
+            },
+            None => self.take_at(key, self.time.instant()),
+        }
+    }
+
+    /// Forgets the shared buckets which filled up again, being no different
+    /// from fresh ones, returning how many there were.
+    pub async fn prune(&self) -> TribResult<usize> {
+        self.prune_at(self.unix_millis()).await
+    }
+
+    async fn prune_at(&self, now: u64) -> TribResult<usize> {
+        let shared = match &self.shared {
+            Some(shared) => shared,
+            None => return Ok(0),
+        };
+        let pattern = Pattern {
+            prefix: shared.prefix.clone(),
+            suffix: "".to_string(),
+        };
+        let mut pruned = 0;
+        for key in shared.bin.keys(&pattern).await?.0 {
+            let full = match shared
+                .bin
+                .get(&key)
+                .await?
+                .as_deref()
+                .and_then(parse_bucket)
+            {
+                Some((tokens, at)) => self.refilled(tokens, now.saturating_sub(at)) >= self.burst,
+                None => false,
+            };
+            if full {
+                shared
+                    .bin
+                    .set(&KeyValue {
+                        key,
+                        value: "".to_string(),
+                    })
+                    .await?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    async fn take_shared(
+        &self,
+        shared: &SharedBuckets,
+        key: &str,
+        now: u64,
+    ) -> TribResult<Result<(), Duration>> {
+        let key = shared.prefix.clone() + key;
+        let tokens = match shared
+            .bin
+            .get(&key)
+            .await?
+            .as_deref()
+            .and_then(parse_bucket)
+        {
+            Some((tokens, at)) => self.refilled(tokens, now.saturating_sub(at)),
+            None => self.burst,
+        };
+        let (left, taken) = self.take_from(tokens);
+        shared
+            .bin
+            .set(&KeyValue {
+                key,
+                value: format!("{} {}", left, now),
+            })
+            .await?;
+        Ok(taken)
+    }
+
+    fn take_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_AT {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            at: now,
+        });
+        let (left, taken) = self.take_from(self.refill(bucket, now));
+        bucket.tokens = left;
+        bucket.at = now;
+        taken
+    }
+
+    // takes a call from a bucket of `tokens`, giving the tokens left
+    fn take_from(&self, tokens: f64) -> (f64, Result<(), Duration>) {
+        if tokens >= 1.0 {
+            (tokens - 1.0, Ok(()))
+        } else {
+            let wait = Duration::from_secs_f64((1.0 - tokens) / self.rate);
+            (tokens, Err(wait))
+        }
+    }
+
+    // the tokens a bucket holds by now
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.at).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    fn unix_millis(&self) -> u64 {
+        self.time
+            .since_epoch()
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    // the tokens a shared bucket of `tokens` holds `elapsed` milliseconds on
+    fn refilled(&self, tokens: f64, elapsed: u64) -> f64 {
+        (tokens + elapsed as f64 / 1000.0 * self.rate).min(self.burst)
+    }
+}
+
+// reads the tokens of a shared bucket and when they were counted; an emptied
+// bucket was forgotten
+fn parse_bucket(stored: &str) -> Option<(f64, u64)> {
+    let (tokens, at) = stored.split_once(' ')?;
+    Some((tokens.parse().ok()?, at.parse().ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimiter, PRUNE_AT};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use tribbler::storage::MemStorage;
+    use tribbler::time::ManualClock;
+
+    #[test]
+    fn test_take() {
+        let limiter = RateLimiter::new(2.0, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.take_at("alice", start).is_ok());
+        }
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            limiter.take_at("alice", start)
+        );
+        // others have buckets of their own
+        assert!(limiter.take_at("bob", start).is_ok());
+
+        assert!(limiter
+            .take_at("alice", start + Duration::from_millis(500))
+            .is_ok());
+        assert!(limiter
+            .take_at("alice", start + Duration::from_millis(500))
+            .is_err());
+        // a bucket never fills past the burst
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.take_at("alice", later).is_ok());
+        }
+        assert!(limiter.take_at("alice", later).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared() {
+        let limiter = RateLimiter::shared(2.0, 2, Box::new(MemStorage::default()), "ip");
+        let shared = limiter.shared.as_ref().unwrap();
+        let take = |key, at| limiter.take_shared(shared, key, at);
+        assert_eq!(Ok(()), take("alice", 1000).await.unwrap());
+        assert_eq!(Ok(()), take("alice", 1000).await.unwrap());
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            take("alice", 1000).await.unwrap()
+        );
+        assert_eq!(Ok(()), take("bob", 1000).await.unwrap());
+        assert_eq!(Ok(()), take("alice", 1500).await.unwrap());
+
+        // bob's bucket is full again by then, and alice's not yet
+        assert_eq!(
+            Some("0 1500".to_string()),
+            shared.bin.get("ip:alice").await.unwrap()
+        );
+        assert_eq!(1, limiter.prune_at(1500).await.unwrap());
+        assert_eq!(None, shared.bin.get("ip:bob").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_take_on_time() {
+        let clock = Arc::new(ManualClock::new(1));
+        let limiters = [
+            RateLimiter::new(2.0, 1),
+            RateLimiter::shared(2.0, 1, Box::new(MemStorage::default()), "ip"),
+        ];
+        for limiter in limiters {
+            let limiter = limiter.with_time(clock.clone());
+            assert_eq!(Ok(()), limiter.take("alice").await);
+            assert_eq!(Err(Duration::from_millis(500)), limiter.take("alice").await);
+            clock.advance(Duration::from_millis(500));
+            assert_eq!(Ok(()), limiter.take("alice").await);
+        }
+    }
+
+    #[test]
+    fn test_prune() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let start = Instant::now();
+        for i in 0..PRUNE_AT {
+            assert!(limiter.take_at(&i.to_string(), start).is_ok());
+        }
+        // full again, and so forgotten
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.take_at("alice", later).is_ok());
+        assert_eq!(1, limiter.buckets.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,238 @@
+//! Where the front-end keeps its sessions: in the signed cookie itself, or in
+//! a bin every front-end of a cluster shares, so that a browser stays signed
+//! in whichever front-end a load balancer hands it to, and signing out ends
+//! the session everywhere.
+use actix_session::storage::{
+    generate_session_key, CookieSessionStore, LoadError, SaveError, SessionKey, SessionStore,
+    UpdateError,
+};
+use actix_web::cookie::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tribbler::storage::{KeyValue, Pattern, Storage};
+use tribbler::time::{SystemClock, TimeSource};
+
+/// the bin the sessions of every front-end are kept in; no username can take
+/// this name
+pub const SESSION_BIN: &str = "@sessions";
+
+/// Keeps the sessions in the cookie, or in [SESSION_BIN].
+pub enum FrontSessionStore {
+    Cookie(CookieSessionStore),
+    Bin(BinSessionStore),
+}
+
+/// Keeps every session under its key in a bin, along with when it expires.
+/// The cookie only carries the key.
+#[derive(Clone)]
+pub struct BinSessionStore {
+    bin: Arc<dyn Storage>,
+    time: Arc<dyn TimeSource>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    state: HashMap<String, String>,
+    /// the unix time, in seconds, the session is good until
+    expires: i64,
+}
+
+impl BinSessionStore {
+    /// Keeps the sessions in `bin`, the [SESSION_BIN] of the cluster.
+    pub fn new(bin: Box<dyn Storage>) -> BinSessionStore {
+        BinSessionStore::with_time(bin, Arc::new(SystemClock))
+    }
+
+    /// Keeps the sessions in `bin`, expiring them on `time`.
+    pub fn with_time(bin: Box<dyn Storage>, time: Arc<dyn TimeSource>) -> BinSessionStore {
+        BinSessionStore {
+            bin: bin.into(),
+            time,
+        }
+    }
+
+    /// Forgets the sessions which expired, returning how many there were.
+    pub async fn prune(&self) -> tribbler::err::TribResult<usize> {
+        let now = self.unix_now();
+        let mut pruned = 0;
+        for key in self.bin.keys(&Pattern::default()).await?.0 {
+            let expired = match self.bin.get(&key).await? {
+                Some(stored) if !stored.is_empty() => {
+                    match serde_json::from_str::<StoredSession>(&stored) {
+                        Ok(session) => session.expires <= now,
+                        Err(_) => true,
+                    }
+                }
+                _ => false,
+            };
+            if expired {
+                self.forget(&key).await?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    async fn store(
+        &self,
+        key: &SessionKey,
+        state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> anyhow::Result<()> {
+        let stored = serde_json::to_string(&StoredSession {
+            state,
+            expires: self.unix_now() + ttl.whole_seconds(),
+        })?;
+        self.bin
+            .set(&KeyValue {
+                key: key.as_ref().to_string(),
+                value: stored,
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(())
+    }
+
+    fn unix_now(&self) -> i64 {
+        self.time.now().map(|now| now as i64).unwrap_or(0)
+    }
+
+    // an emptied key is how a bin forgets
+    async fn forget(&self, key: &str) -> tribbler::err::TribResult<()> {
+        self.bin
+            .set(&KeyValue {
+                key: key.to_string(),
+                value: "".to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl SessionStore for BinSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let stored = self
+            .bin
+            .get(session_key.as_ref())
+            .await
+            .map_err(|e| LoadError::Other(anyhow::anyhow!("{}", e)))?;
+        match stored {
+            Some(stored) if !stored.is_empty() => {
+                let session: StoredSession = serde_json::from_str(&stored)
+                    .map_err(|e| LoadError::Deserialization(e.into()))?;
+                Ok((session.expires > self.unix_now()).then_some(session.state))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let key = generate_session_key();
+        self.store(&key, session_state, ttl)
+            .await
+            .map_err(SaveError::Other)?;
+        Ok(key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        self.store(&session_key, session_state, ttl)
+            .await
+            .map_err(UpdateError::Other)?;
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self.load(session_key).await? {
+            Some(state) => self.store(session_key, state, ttl).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        self.forget(session_key.as_ref())
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+}
+
+impl SessionStore for FrontSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        match self {
+            FrontSessionStore::Cookie(store) => store.load(session_key).await,
+            FrontSessionStore::Bin(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            FrontSessionStore::Cookie(store) => store.save(session_state, ttl).await,
+            FrontSessionStore::Bin(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            FrontSessionStore::Cookie(store) => store.update(session_key, session_state, ttl).await,
+            FrontSessionStore::Bin(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self {
+            FrontSessionStore::Cookie(store) => store.update_ttl(session_key, ttl).await,
+            FrontSessionStore::Bin(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        match self {
+            FrontSessionStore::Cookie(store) => store.delete(session_key).await,
+            FrontSessionStore::Bin(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tribbler::storage::MemStorage;
+    use tribbler::time::ManualClock;
+
+    #[tokio::test]
+    async fn test_sessions_expire_on_time() -> anyhow::Result<()> {
+        let clock = Arc::new(ManualClock::new(1000));
+        let store = BinSessionStore::with_time(Box::new(MemStorage::default()), clock.clone());
+        let state = HashMap::from([("user".to_string(), "alice".to_string())]);
+        let key = store.save(state.clone(), &Duration::seconds(60)).await?;
+        assert_eq!(Some(state), store.load(&key).await?);
+        clock.advance(std::time::Duration::from_secs(61));
+        assert_eq!(None, store.load(&key).await?);
+        assert_eq!(1, store.prune().await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+//! Waiting for the operator to stop a process, so that it can wind down
+//! instead of dying mid-request.
+
+use log::warn;
+
+/// Resolves once the process is asked to stop, by Ctrl-C (SIGINT) or, on
+/// unix, by SIGTERM.
+pub async fn signalled() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("cannot listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use cmd::bench::{self, BenchOptions, Mix, Target};
+use cmd::dev;
+use lab::lab2;
+use log::{info, LevelFilter};
+use tokio::sync::watch;
+use tribbler::{
+    config::{Config, Limits, DEFAULT_CONFIG_LOCATION},
+    err::TribResult,
+    ref_impl::RefServer,
+};
+
+/// drives a front-end with users signing up, posting, following, and reading
+/// their home timelines at a target rate, and reports the throughput and the
+/// latencies seen
+#[derive(Parser, Debug)]
+#[clap(name = "trib-bench")]
+struct Args {
+    /// level to use when logging
+    #[clap(short, long, default_value = "WARN")]
+    log_level: LevelFilter,
+    /// what to call: ref for the reference server, lab for the lab front-end
+    /// over the cluster in the config, dev for the lab front-end over
+    /// in-memory backends and a keeper run in the process, or the URL of a
+    /// running front-end to call over HTTP, e.g. http://localhost:9000
+    #[clap(short, long, default_value = "dev")]
+    target: String,
+    #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
+    config: String,
+    /// the backends the dev target runs
+    #[clap(long, default_value = "3")]
+    dev_backs: usize,
+    /// number of users the calls are made as
+    #[clap(short, long, default_value = "100")]
+    users: usize,
+    /// the calls started each second
+    #[clap(short, long, default_value = "200")]
+    qps: u32,
+    /// the seconds the calls go on for
+    #[clap(short, long, default_value = "10")]
+    duration: u64,
+    /// how many calls may be under way at once
+    #[clap(long, default_value = "64")]
+    concurrency: usize,
+    /// how often each call is made, as call=weight pairs of sign_up, post,
+    /// follow and home
+    #[clap(short, long, default_value = "sign_up=5,post=25,follow=20,home=50")]
+    mix: Mix,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let args = Args::parse();
+    env_logger::builder()
+        .default_format()
+        .filter_level(args.log_level)
+        .init();
+    let target = match args.target.as_str() {
+        "ref" => Target::Server(Arc::new(RefServer::new())),
+        "lab" => {
+            let cfg = Config::read(Some(&args.config))?;
+            Target::Server(Arc::from(
+                lab2::new_front_following(watch::channel(cfg).1).await?,
+            ))
+        }
+        "dev" => {
+            let cfg = dev::start_cluster(args.dev_backs, Limits::default()).await?;
+            info!("Running {} in-memory backends and a keeper", args.dev_backs);
+            Target::Server(Arc::from(
+                lab2::new_front_following(watch::channel(cfg).1).await?,
+            ))
+        }
+        url => Target::http(url),
+    };
+    let report = bench::run(
+        target,
+        BenchOptions {
+            users: args.users,
+            qps: args.qps,
+            duration: Duration::from_secs(args.duration),
+            concurrency: args.concurrency,
+            mix: args.mix,
+        },
+    )
+    .await?;
+    print!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::process;
+use std::time::Duration;
+
+use clap::Parser;
+use cmd::chaos::{self, ChaosOptions};
+use log::LevelFilter;
+use tribbler::{
+    config::{Consistency, Placement},
+    err::TribResult,
+};
+
+/// starts a cluster in the process and drives user traffic at it while
+/// killing and bringing back its backends and keepers at random, then checks
+/// that nothing the users were told was lost
+#[derive(Parser, Debug)]
+#[clap(name = "trib-chaos")]
+struct Args {
+    /// level to use when logging
+    #[clap(short, long, default_value = "WARN")]
+    log_level: LevelFilter,
+    /// number of backends
+    #[clap(short, long, default_value = "5")]
+    backs: usize,
+    /// number of keepers
+    #[clap(short, long, default_value = "2")]
+    keeps: usize,
+    /// number of users driving traffic
+    #[clap(short, long, default_value = "6")]
+    users: usize,
+    /// the seconds the chaos goes on for
+    #[clap(short, long, default_value = "30")]
+    duration: u64,
+    /// the milliseconds between two servers killed or brought back
+    #[clap(long, default_value = "1000")]
+    interval: u64,
+    /// how many of the backends, and of the keepers, may be down at once
+    #[clap(long, default_value = "1")]
+    max_down: usize,
+    /// the backends each bin is kept on
+    #[clap(long, default_value = "3")]
+    replicas: usize,
+    /// how many replicas take a write for it to succeed
+    #[clap(long, default_value = "quorum", possible_values = ["one", "quorum", "all"])]
+    consistency: String,
+    /// the seed of the run, to replay the choices of an earlier one; random
+    /// by default
+    #[clap(long)]
+    seed: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let args = Args::parse();
+    env_logger::builder()
+        .default_format()
+        .filter_level(args.log_level)
+        .init();
+    let consistency = match args.consistency.as_str() {
+        "one" => Consistency::One,
+        "all" => Consistency::All,
+        _ => Consistency::Quorum,
+    };
+    let report = chaos::run(ChaosOptions {
+        backs: args.backs,
+        keepers: args.keeps,
+        users: args.users,
+        duration: Duration::from_secs(args.duration),
+        interval: Duration::from_millis(args.interval),
+        max_down: args.max_down,
+        placement: Placement {
+            replication_factor: args.replicas,
+            consistency,
+            ..Placement::default()
+        },
+        seed: args.seed.unwrap_or_else(rand::random),
+    })
+    .await?;
+    println!("{}", report);
+    if !report.passed() {
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand};
+use lab::lab2;
+use serde::Deserialize;
+use tokio::sync::watch;
+use tribbler::{
+    config::{Config, DEFAULT_CONFIG_LOCATION},
+    err::{TribResult, TribblerError},
+    time::{SystemClock, TimeSource},
+    trib::{Server, Trib},
+};
+
+// how long a call is retried for while the cluster is not ready, and how
+// often
+const READY_PATIENCE: Duration = Duration::from_secs(10);
+const READY_RETRY: Duration = Duration::from_millis(200);
+
+/// a terminal client of the tribbler service, to demo and script it without
+/// a browser: signs users up, posts, follows, and lists timelines, through
+/// the lab front-end over the cluster of the config, or the HTTP API of a
+/// running front-end
+#[derive(Parser, Debug)]
+#[clap(name = "trib-cli")]
+struct Options {
+    /// what to call: lab for the lab front-end over the cluster in the
+    /// config, or the URL of a running front-end to call over HTTP, e.g.
+    /// http://localhost:9000
+    #[clap(short, long, default_value = "lab")]
+    target: String,
+    #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
+    config: String,
+    /// the password a user signs up with, and signs in with to make calls
+    /// over HTTP
+    #[clap(short, long, default_value = "")]
+    password: String,
+    #[clap(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// signs up a new user
+    Signup { user: String },
+    /// posts a trib as a user
+    Post { user: String, message: String },
+    /// makes a user follow another
+    Follow { who: String, whom: String },
+    /// lists the home timeline of a user
+    Home {
+        user: String,
+        #[clap(flatten)]
+        watch: Watch,
+    },
+    /// lists the tribs a user posted
+    Tribs {
+        user: String,
+        #[clap(flatten)]
+        watch: Watch,
+    },
+}
+
+#[derive(Parser, Debug)]
+struct Watch {
+    /// keep listing the tribs which come in, until interrupted
+    #[clap(short, long)]
+    watch: bool,
+    /// the seconds between two looks for new tribs with --watch
+    #[clap(long, default_value = "2")]
+    every: u64,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let args = Options::parse();
+    let client = match args.target.as_str() {
+        "lab" => {
+            let cfg = Config::read(Some(&args.config))?;
+            Client::Server(lab2::new_front_following(watch::channel(cfg).1).await?)
+        }
+        url => Client::http(url, &args.password),
+    };
+    match args.command {
+        Cmd::Signup { user } => {
+            when_ready(|| client.sign_up(&user)).await?;
+            println!("signed up {}", user);
+        }
+        Cmd::Post { user, message } => {
+            when_ready(|| client.post(&user, &message)).await?;
+            println!("posted as {}", user);
+        }
+        Cmd::Follow { who, whom } => {
+            when_ready(|| client.follow(&who, &whom)).await?;
+            println!("{} follows {}", who, whom);
+        }
+        Cmd::Home { user, watch } => list(&client, Timeline::Home, &user, &watch).await?,
+        Cmd::Tribs { user, watch } => list(&client, Timeline::Tribs, &user, &watch).await?,
+    }
+    Ok(())
+}
+
+// makes a call, again and again while the cluster is not ready, as a lab
+// front-end which just started has not heard from the keeper yet
+async fn when_ready<T, F>(call: impl Fn() -> F) -> TribResult<T>
+where
+    F: Future<Output = TribResult<T>>,
+{
+    let start = Instant::now();
+    loop {
+        match call().await {
+            Err(TribblerError::ClusterNotReady) if start.elapsed() < READY_PATIENCE => {
+                tokio::time::sleep(READY_RETRY).await
+            }
+            result => return result,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Timeline {
+    Home,
+    Tribs,
+}
+
+// prints the timeline, oldest first, then with --watch the tribs which show
+// up in it later, each once
+async fn list(client: &Client, timeline: Timeline, user: &str, watch: &Watch) -> TribResult<()> {
+    let mut seen = HashSet::new();
+    let mut every = tokio::time::interval(Duration::from_secs(watch.every.max(1)));
+    loop {
+        every.tick().await;
+        let tribs = match when_ready(|| client.timeline(timeline, user)).await {
+            Ok(tribs) => tribs,
+            // a watch rides out a cluster which is down for a while
+            Err(e) if watch.watch && !seen.is_empty() => {
+                eprintln!("failed to list the tribs of {}: {}", user, e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let now = SystemClock.now()?;
+        for trib in tribs.iter() {
+            if seen.insert((trib.user.clone(), trib.clock)) {
+                println!("{}", show(trib, now));
+            }
+        }
+        if !watch.watch {
+            return Ok(());
+        }
+    }
+}
+
+// one trib on a line: how long ago it was posted, by whom, and what it says
+fn show(trib: &Trib, now: u64) -> String {
+    let message = match &trib.retrib_of {
+        Some(of) => format!("(retrib of {}) {}", of.user, trib.message),
+        None => trib.message.clone(),
+    };
+    format!(
+        "{:>4} ago  {:<15}  {}",
+        age(now.saturating_sub(trib.time)),
+        trib.user,
+        message
+    )
+}
+
+// the coarsest unit of a number of seconds, e.g. 3m for 200
+fn age(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+/// What the client calls: the lab front-end in the process, or the HTTP API
+/// of a front-end.
+enum Client {
+    Server(Box<dyn Server + Send + Sync>),
+    Http(HttpApi),
+}
+
+/// The `/api/v1` of a front-end, and the password its users sign in with.
+struct HttpApi {
+    http: reqwest::Client,
+    base: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct TribList {
+    tribs: Vec<Arc<Trib>>,
+}
+
+impl Client {
+    // the `/api/v1` of the front-end serving at `base`
+    fn http(base: &str, password: &str) -> Client {
+        Client::Http(HttpApi {
+            http: reqwest::Client::new(),
+            base: format!("{}/api/v1", base.trim_end_matches('/')),
+            password: password.to_string(),
+        })
+    }
+
+    async fn sign_up(&self, user: &str) -> TribResult<()> {
+        match self {
+            Client::Server(server) => server.sign_up(user).await,
+            Client::Http(api) => {
+                let creds = serde_json::json!({ "user": user, "password": api.password });
+                api.call("add-user", &creds, None).await.map(|_| ())
+            }
+        }
+    }
+
+    // posts as `user`, who has seen no clock yet
+    async fn post(&self, user: &str, message: &str) -> TribResult<()> {
+        match self {
+            Client::Server(server) => server.post(user, message, 0).await,
+            Client::Http(api) => {
+                let post = serde_json::json!({ "who": user, "message": message, "clock": 0 });
+                api.call("post", &post, Some(user)).await.map(|_| ())
+            }
+        }
+    }
+
+    async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
+        match self {
+            Client::Server(server) => server.follow(who, whom).await,
+            Client::Http(api) => {
+                let follow = serde_json::json!({ "who": who, "whom": whom });
+                api.call("follow", &follow, Some(who)).await.map(|_| ())
+            }
+        }
+    }
+
+    async fn timeline(&self, timeline: Timeline, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+        match (self, timeline) {
+            (Client::Server(server), Timeline::Home) => server.home(user).await,
+            (Client::Server(server), Timeline::Tribs) => server.tribs(user).await,
+            (Client::Http(api), _) => {
+                let route = match timeline {
+                    Timeline::Home => "list-home",
+                    Timeline::Tribs => "list-tribs",
+                };
+                let listed = api.call(route, &user, None).await?;
+                Ok(serde_json::from_str::<TribList>(&listed)?.tribs)
+            }
+        }
+    }
+}
+
+impl HttpApi {
+    // posts `arg` to `route`, signed in as `user` if any, and hands back the
+    // body of the answer
+    async fn call(
+        &self,
+        route: &str,
+        arg: &impl serde::Serialize,
+        user: Option<&str>,
+    ) -> TribResult<String> {
+        let (http, base) = (&self.http, &self.base);
+        let mut request = http.post(format!("{}/{}", base, route)).json(arg);
+        if let Some(user) = user {
+            let (cookie, csrf) = login(http, base, user, &self.password).await?;
+            request = request
+                .header(reqwest::header::COOKIE, cookie)
+                .header("x-csrf-token", csrf);
+        }
+        answer(request).await
+    }
+}
+
+// signs `user` in, handing back the session cookie and the CSRF token the
+// calls in the session carry
+async fn login(
+    http: &reqwest::Client,
+    base: &str,
+    user: &str,
+    password: &str,
+) -> TribResult<(String, String)> {
+    let resp = http
+        .post(format!("{}/login", base))
+        .json(&serde_json::json!({ "user": user, "password": password }))
+        .send()
+        .await
+        .map_err(|e| TribblerError::Unknown(e.to_string()))?;
+    let cookie = resp
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .collect::<Vec<&str>>()
+        .join("; ");
+    let body: serde_json::Value = serde_json::from_str(&answer_body(resp).await?)?;
+    let csrf = body["csrf"].as_str().unwrap_or_default().to_string();
+    Ok((cookie, csrf))
+}
+
+// sends a request, and turns an answer other than a success into the error
+// the front-end gave
+async fn answer(request: reqwest::RequestBuilder) -> TribResult<String> {
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| TribblerError::Unknown(e.to_string()))?;
+    answer_body(resp).await
+}
+
+async fn answer_body(resp: reqwest::Response) -> TribResult<String> {
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| TribblerError::Unknown(e.to_string()))?;
+    match status.is_success() {
+        true => Ok(body),
+        false => {
+            // the front-end explains what went wrong in a JSON error
+            let explained: Option<serde_json::Value> = serde_json::from_str(&body).ok();
+            let message = explained
+                .as_ref()
+                .and_then(|v| v["error"]["message"].as_str())
+                .unwrap_or(&body);
+            Err(TribblerError::Unknown(format!("{}: {}", status, message)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_age() {
+        assert_eq!("0s", age(0));
+        assert_eq!("59s", age(59));
+        assert_eq!("3m", age(200));
+        assert_eq!("2h", age(2 * 60 * 60 + 5));
+        assert_eq!("3d", age(3 * 24 * 60 * 60));
+    }
+}
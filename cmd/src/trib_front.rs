@@ -1,23 +1,97 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use actix_files::Files;
-use actix_web::{web, App, HttpServer};
+use actix_cors::Cors;
+use actix_files::{Files, NamedFile};
+use actix_session::config::CookieContentSecurity;
+use actix_session::storage::CookieSessionStore;
+use actix_session::{Session, SessionMiddleware};
+use actix_web::body::{BoxBody, EitherBody, MessageBody};
+use actix_web::cookie::Key;
+use actix_web::dev::{fn_service, RequestHead, ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{KeepAlive, Method, StatusCode};
+use actix_web::middleware::{from_fn, Condition, Next};
+use actix_web::{web, App, HttpResponse, HttpServer};
 use clap::Parser;
+use cmd::config_watch;
+use cmd::dev;
+use cmd::gen_config::GenerateConfig;
+use cmd::oidc::Provider;
+use cmd::rate_limit::{RateLimiter, LIMIT_BIN};
+use cmd::session_store::{BinSessionStore, FrontSessionStore, SESSION_BIN};
+use cmd::shutdown;
+use cmd::validate::check_body;
+use lab::lab1::{record, REQUEST_ID, REQUEST_ID_HEADER};
 use lab::lab2;
+use lab::lab2::{Auth, BinAuth, MemAuth};
 use log::{info, warn, LevelFilter};
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rust_embed::RustEmbed;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tokio::sync::watch;
 use tribbler::config::Config;
 use tribbler::config::DEFAULT_CONFIG_LOCATION;
+use tribbler::config::{FrontSection, Limits};
 use tribbler::err::{TribResult, TribblerError};
 use tribbler::ref_impl::RefServer;
-use tribbler::trib::MAX_FOLLOWING;
+use tribbler::storage::BinStorage;
+use tribbler::testing::conformance;
 use tribbler::trib::Server;
+use tribbler::trib::UserExport;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 type Srv = Box<dyn Server + Send + Sync>;
+type Creds = Box<dyn Auth>;
+
+// how long a shutdown waits on the requests in flight before dropping them
+const SHUTDOWN_GRACE: u64 = 10;
+
+// the length of the request IDs made up for requests which came without one,
+// and the longest one taken from a client
+const REQUEST_ID_LEN: usize = 16;
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+// the header a failed call carries the code of its error in, as
+// [TribblerError::code] has it, for /api/v1 to put in its envelope
+const ERROR_CODE_HEADER: &str = "x-error-code";
+
+// how long seeding waits for a cluster which was just started to take writes,
+// and how often it tries meanwhile
+const SEED_PATIENCE: Duration = Duration::from_secs(10);
+const SEED_RETRY: Duration = Duration::from_millis(200);
+
+// how often expired sessions and full rate limit buckets are dropped from the
+// cluster, when they are kept there
+const SHARED_PRUNE_EVERY: Duration = Duration::from_secs(600);
+
+// the buckets, in seconds, of the request and backend call latencies
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// where the front-end serves when neither the command line nor the config
+// says
+const DEFAULT_HOST: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 9000;
 
 #[derive(Debug, Clone)]
 enum ServerType {
     Ref,
     Lab,
+    /// the lab front-end over a cluster run in-process
+    Dev,
 }
 
 impl FromStr for ServerType {
@@ -27,6 +101,7 @@ impl FromStr for ServerType {
         match s.to_lowercase().as_str() {
             "ref" => Ok(ServerType::Ref),
             "lab" => Ok(ServerType::Lab),
+            "dev" => Ok(ServerType::Dev),
             _ => Err(TribblerError::Unknown(format!(
                 "{} not a valid ServerType",
                 s
@@ -35,6 +110,32 @@ impl FromStr for ServerType {
     }
 }
 
+/// What the service is seeded with on startup.
+#[derive(Debug, Clone)]
+enum Populate {
+    Off,
+    Default,
+    File(String),
+}
+
+impl FromStr for Populate {
+    type Err = TribblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(Populate::File(path.to_string())),
+            _ => match s.to_lowercase().as_str() {
+                "off" => Ok(Populate::Off),
+                "default" => Ok(Populate::Default),
+                _ => Err(TribblerError::Unknown(format!(
+                    "{} not a valid seed, use off, default or file:<path>",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
 /// A program which runs the tribbler front-end service.
 #[derive(Parser, Debug)]
 #[clap(name = "trib-front")]
@@ -43,388 +144,1252 @@ struct Cfg {
     #[clap(short, long, default_value = "INFO")]
     log_level: LevelFilter,
 
-    /// server type to run the front-end against
+    /// server type to run the front-end against: ref, lab over the cluster
+    /// in the config, or dev over in-memory backends and a keeper it runs
+    /// itself
     #[clap(short, long, default_value = "ref")]
     server_type: ServerType,
 
     #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
     config: String,
 
-    /// the host address to bind to. e.g. 127.0.0.1 or 0.0.0.0
-    #[clap(long, default_value = "0.0.0.0")]
-    host: String,
+    /// with the lab server type, take on the backends and keepers of the
+    /// config whenever it changes, or on SIGHUP, rather than only at start
+    #[clap(long)]
+    watch_config: bool,
+
+    /// with the lab or dev server type, record the RPCs made to the backends
+    /// to this file, to be served back by trib-replay
+    #[clap(long)]
+    record: Option<String>,
+
+    #[clap(flatten)]
+    generate: GenerateConfig,
+
+    /// the backends the dev server type runs
+    #[clap(long, default_value = "3")]
+    dev_backs: usize,
+
+    /// the host address to bind to. e.g. 127.0.0.1 or 0.0.0.0; by default
+    /// the host of the front section of the config, or 0.0.0.0
+    #[clap(long)]
+    host: Option<String>,
+
+    /// the host port to bind; by default the port of the front section of
+    /// the config, or 9000
+    #[clap(long)]
+    port: Option<u16>,
+
+    /// the seconds a call to the API may take before it is answered with 504
+    /// Gateway Timeout, 0 for no deadline
+    #[clap(long, default_value = "30")]
+    timeout: u64,
+
+    /// the deadline of one route over --timeout, as route=seconds, e.g.
+    /// list-home=5; repeat for more routes
+    #[clap(long = "route-timeout")]
+    route_timeouts: Vec<String>,
+
+    /// the worker threads serving requests; by default one for each physical
+    /// core
+    #[clap(long)]
+    workers: Option<usize>,
+
+    /// the connections each worker serves at once before it stops accepting
+    /// more; by default 25k
+    #[clap(long)]
+    max_connections: Option<usize>,
+
+    /// the seconds an idle connection is kept open for another request, 0 to
+    /// close connections after every response; by default 5
+    #[clap(long)]
+    keep_alive: Option<u64>,
+
+    /// a PEM file with the certificate chain to serve HTTPS with; without it,
+    /// or one in the front section of the config, the front-end serves plain
+    /// HTTP
+    #[clap(long)]
+    tls_cert: Option<String>,
+
+    /// a PEM file with the private key of the certificate
+    #[clap(long)]
+    tls_key: Option<String>,
+
+    /// a directory to serve the web UI from instead of the copy built into
+    /// the binary, for working on the UI without rebuilding
+    #[clap(long)]
+    www: Option<String>,
+
+    /// the token admin requests have to carry; without it the admin API
+    /// refuses every request
+    #[clap(long)]
+    admin_token: Option<String>,
+
+    /// what to seed the service with on startup: off, default for a few demo
+    /// users, or file:<path> for a JSON seed of users, posts and follows; by
+    /// default what the front section of the config says, or default
+    #[clap(long)]
+    populate: Option<Populate>,
 
-    /// the host port to bind
-    #[clap(long, default_value = "9000")]
-    port: u16,
+    /// a JSON array of user exports to import before serving; what an
+    /// earlier start imported is left as it is
+    #[clap(long)]
+    import: Option<String>,
+
+    /// the password the users seeded or imported on startup sign in with;
+    /// without it they get none, and nobody can sign in as them
+    #[clap(long)]
+    seed_password: Option<String>,
+
+    /// the secret, at least 64 bytes, session cookies are signed with; without
+    /// it a random one is picked, and restarting signs everyone out
+    #[clap(long)]
+    session_key: Option<String>,
+
+    /// keep the sessions and the rate limit buckets in the cluster's bins,
+    /// where every front-end over the cluster finds them, rather than in the
+    /// cookie and in this process; the front-ends need the same session key
+    #[clap(long, requires = "session-key")]
+    shared_state: bool,
+
+    /// the issuer URL of an OpenID Connect provider, such as a campus single
+    /// sign-on, users may sign in through; without it single sign-on is off
+    #[clap(
+        long,
+        requires_all = &["oidc-client-id", "oidc-client-secret", "oidc-redirect-url"]
+    )]
+    oidc_issuer: Option<String>,
+
+    /// the client id trib-front is registered under with the provider
+    #[clap(long)]
+    oidc_client_id: Option<String>,
+
+    /// the client secret trib-front is registered under with the provider
+    #[clap(long)]
+    oidc_client_secret: Option<String>,
+
+    /// where the provider sends browsers back to: /api/oidc/callback, under
+    /// the address browsers reach this front-end at
+    #[clap(long)]
+    oidc_redirect_url: Option<String>,
+
+    /// the ID token claim new users get their username from
+    #[clap(long, default_value = "preferred_username")]
+    oidc_username_claim: String,
+
+    /// an origin, such as https://tribbler.example.edu, whose pages may call
+    /// the API; repeat for more, * lets any page call it but without the
+    /// session cookie. Without any only the bundled UI may call the API
+    #[clap(long = "cors-origin")]
+    cors_origins: Vec<String>,
+
+    /// the methods pages on allowed origins may call the API with
+    #[clap(long, use_value_delimiter = true, default_value = "GET,POST")]
+    cors_methods: Vec<String>,
+
+    /// the request headers pages on allowed origins may send
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        default_value = "content-type,x-csrf-token"
+    )]
+    cors_headers: Vec<String>,
+
+    /// the calls a second each client address may make to the API, on
+    /// average; without it addresses are not limited
+    #[clap(long)]
+    ip_rate: Option<f64>,
+
+    /// the calls each client address may make at once, above its rate
+    #[clap(long, default_value = "20")]
+    ip_burst: u32,
+
+    /// the calls a second each signed in user may make to the API, on
+    /// average, from wherever; without it users are not limited
+    #[clap(long)]
+    user_rate: Option<f64>,
+
+    /// the calls each signed in user may make at once, above the rate
+    #[clap(long, default_value = "20")]
+    user_burst: u32,
+
+    /// the largest body, in bytes, an API call may come with
+    #[clap(long, default_value = "65536")]
+    max_body: usize,
+
+    /// rather than serve, run the conformance suite once against a new
+    /// server of the server type, and exit nonzero if it fails. The lab
+    /// server type needs the cluster of the config to have no users yet
+    #[clap(long)]
+    self_test: bool,
+
+    /// rather than serve, run the conformance suite over and over for this
+    /// many seconds, each round against a new server of the server type, and
+    /// exit nonzero on the first round which fails. The dev server type
+    /// starts a cluster for every round
+    #[clap(long, conflicts_with = "self-test")]
+    soak: Option<u64>,
+}
+
+impl Cfg {
+    // fills in the options left off the command line from the front section
+    // of the config
+    fn take_front(&mut self, front: &FrontSection) -> TribResult<()> {
+        if self.host.is_none() {
+            self.host = front.host.clone();
+        }
+        if self.port.is_none() {
+            self.port = front.port;
+        }
+        if let (None, Some(populate)) = (&self.populate, &front.populate) {
+            self.populate = Some(populate.parse()?);
+        }
+        if self.import.is_none() {
+            self.import = front.import.clone();
+        }
+        if self.seed_password.is_none() {
+            self.seed_password = front.seed_password.clone();
+        }
+        if self.tls_cert.is_none() && self.tls_key.is_none() {
+            self.tls_cert = front.tls_cert.clone();
+            self.tls_key = front.tls_key.clone();
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err(TribblerError::Unknown(
+                "serving HTTPS takes both a certificate and its key".to_string(),
+            ));
+        }
+        if self.www.is_none() {
+            self.www = front.www.clone();
+        }
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> TribResult<()> {
-    let args = Cfg::parse();
+    let mut args = Cfg::parse();
+    if args.generate.run(&args.config)? {
+        return Ok(());
+    }
 
     env_logger::builder()
         .default_format()
         .filter_level(args.log_level)
         .init();
-    let srv_impl: Srv = match args.server_type {
-        ServerType::Ref => Box::new(RefServer::new()),
+    // installed first, so the bin client records its calls from the start
+    let metrics_handle = web::Data::new(metrics_recorder()?);
+    // the options left off the command line come from the front section of
+    // the config, if one is around; the servers without a cluster to read it
+    // from need nothing else of it
+    let front = match Config::read(Some(&args.config)) {
+        Ok(cfg) => cfg.front,
+        Err(_) => FrontSection::default(),
+    };
+    args.take_front(&front)?;
+    if let Some(path) = &args.record {
+        record::record_to(path)?;
+        info!("Recording the RPCs made to the backends to {}", path);
+    }
+    let limits = front.limits;
+    if args.self_test || args.soak.is_some() {
+        return self_test(&args, limits).await;
+    }
+    let (srv_impl, auth_impl, cluster): (Srv, Creds, api::Cluster) = match args.server_type {
+        ServerType::Ref => (
+            Box::new(RefServer::with_limits(limits)),
+            Box::new(MemAuth::default()),
+            api::Cluster::default(),
+        ),
         ServerType::Lab => {
             let cfg = Config::read(Some(&args.config))?;
-            let bc = lab2::new_bin_client(cfg.backs).await?;
-            lab2::new_front(bc).await?
+            let config = match args.watch_config {
+                true => config_watch::watch_config(args.config.clone(), cfg),
+                false => watch::channel(cfg).1,
+            };
+            lab_front(config).await?
+        }
+        ServerType::Dev => {
+            let cfg = dev::start_cluster(args.dev_backs, limits).await?;
+            info!("Running {} in-memory backends and a keeper", args.dev_backs);
+            lab_front(watch::channel(cfg).1).await?
         }
     };
     let server: web::Data<Srv> = web::Data::new(srv_impl);
-    match populate(&server).await {
-        Ok(_) => info!("Pre-populated test-server successfully"),
-        Err(e) => warn!("Failed to pre-populate test server: {}", e),
+    let auth: web::Data<Creds> = web::Data::new(auth_impl);
+    let admin_token = web::Data::new(api::AdminToken(args.admin_token.clone()));
+    let cluster = web::Data::new(cluster);
+    let posts = web::Data::new(api::Posts::new());
+    let live_feeds = posts.clone();
+    let session_key = match &args.session_key {
+        Some(secret) => Key::try_from(secret.as_bytes())
+            .map_err(|e| TribblerError::Unknown(format!("bad session key: {}", e)))?,
+        None => Key::generate(),
+    };
+    let sso = match &args.oidc_issuer {
+        Some(issuer) => {
+            let provider = Provider::discover(
+                issuer,
+                args.oidc_client_id.as_deref().unwrap_or_default(),
+                args.oidc_client_secret.as_deref().unwrap_or_default(),
+                args.oidc_redirect_url.as_deref().unwrap_or_default(),
+                &args.oidc_username_claim,
+            )
+            .await?;
+            info!("Users may sign in through {}", issuer);
+            Some(provider)
+        }
+        None => None,
+    };
+    let sso = web::Data::new(api::Sso(sso));
+    let seed = match args.populate.as_ref().unwrap_or(&Populate::Default) {
+        Populate::Off => None,
+        Populate::Default => Some(Seed::demo()),
+        Populate::File(path) => {
+            let seed = std::fs::read_to_string(path)
+                .map_err(|e| TribblerError::Unknown(format!("cannot read {}: {}", path, e)))?;
+            Some(serde_json::from_str(&seed)?)
+        }
+    };
+    if let Some(seed) = seed {
+        // seeding a cluster seeded before runs into the users it made
+        if args.seed_password.is_none() {
+            warn!("The seeded users have no password, so nobody can sign in as them");
+        }
+        match populate_when_ready(&server, &auth, args.seed_password.as_deref(), &seed).await {
+            Ok(_) => info!("Pre-populated test-server successfully"),
+            Err(e) => warn!("Failed to pre-populate test server: {}", e),
+        }
+    }
+    if let Some(path) = &args.import {
+        import(&server, &auth, args.seed_password.as_deref(), path).await?;
+        info!("Imported users from {}", path);
+    }
+    let (cors_origins, cors_methods, cors_headers) =
+        (args.cors_origins, args.cors_methods, args.cors_headers);
+    let www = args.www;
+    let timeouts = web::Data::new(Timeouts::parse(args.timeout, &args.route_timeouts)?);
+    let max_body = args.max_body;
+    let limits = web::Data::new(limits);
+    let shared = match (args.shared_state, &cluster.0) {
+        (true, Some(config)) => Some(lab2::new_bin_client_following(config.clone()).await?),
+        (true, None) => {
+            return Err(TribblerError::Unknown(
+                "shared state needs a cluster, which the ref server has none of".to_string(),
+            ))
+        }
+        (false, _) => None,
+    };
+    let rate_limits = web::Data::new(RateLimits {
+        ip: rate_limiter(args.ip_rate, args.ip_burst, &shared, "ip").await?,
+        user: rate_limiter(args.user_rate, args.user_burst, &shared, "user").await?,
+    });
+    let session_bin = match &shared {
+        Some(bins) => Some(BinSessionStore::new(bins.bin(SESSION_BIN).await?)),
+        None => None,
+    };
+    if let Some(sessions) = session_bin.clone() {
+        info!("Keeping sessions and rate limits in the cluster");
+        tokio::spawn(prune_shared_state(sessions, rate_limits.clone()));
     }
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls_config(cert, key)?),
+        _ => None,
+    };
+    // browsers only send a secure cookie back over HTTPS
+    let secure_cookies = tls.is_some();
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let api_doc = api::ApiDoc::openapi();
     let srv = HttpServer::new(move || {
         App::new()
             .app_data(server.clone())
+            .app_data(auth.clone())
+            .app_data(admin_token.clone())
+            .app_data(cluster.clone())
+            .app_data(posts.clone())
+            .app_data(sso.clone())
+            .app_data(metrics_handle.clone())
+            .app_data(rate_limits.clone())
+            .app_data(timeouts.clone())
+            .app_data(limits.clone())
+            // bodies over the limit are answered with 413 Payload Too Large
+            .app_data(web::PayloadConfig::new(max_body))
+            .app_data(web::FormConfig::default().limit(max_body))
+            .wrap(
+                SessionMiddleware::builder(session_store(&session_bin), session_key.clone())
+                    .cookie_content_security(CookieContentSecurity::Signed)
+                    .cookie_secure(secure_cookies)
+                    .build(),
+            )
+            .wrap(Condition::new(
+                !cors_origins.is_empty(),
+                cors(&cors_origins, &cors_methods, &cors_headers),
+            ))
+            .wrap(from_fn(record_request))
+            .wrap(from_fn(log_request))
+            // ahead of the /api scope, which would take its requests
+            .service(SwaggerUi::new("/api/docs/{_:.*}").url("/api/openapi.json", api_doc.clone()))
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(deadline))
+                    .wrap(from_fn(validate))
+                    .wrap(from_fn(check_csrf))
+                    .wrap(from_fn(limit_rate))
+                    .wrap(from_fn(v1_contract))
+                    .configure(api_routes),
+            )
             .service(
                 web::scope("/api")
-                    .service(api::add_user)
-                    .service(api::list_users)
-                    .service(api::list_tribs)
-                    .service(api::list_home)
-                    .service(api::is_following)
-                    .service(api::follow)
-                    .service(api::unfollow)
-                    .service(api::following)
-                    .service(api::post),
+                    .wrap(from_fn(deadline))
+                    .wrap(from_fn(validate))
+                    // inside the session middleware, to know who is calling
+                    .wrap(from_fn(check_csrf))
+                    .wrap(from_fn(limit_rate))
+                    .configure(api_routes),
             )
-            .service(Files::new("/", "./www").index_file("index.html"))
+            .service(api::live)
+            .route("/metrics", web::get().to(render_metrics))
+            .configure(|cfg| match &www {
+                Some(dir) => {
+                    let index = Path::new(dir).join("index.html");
+                    cfg.service(
+                        Files::new("/", dir)
+                            .index_file("index.html")
+                            .default_handler(fn_service(move |req: ServiceRequest| {
+                                ui_fallback(index.clone(), req)
+                            })),
+                    );
+                }
+                None => {
+                    cfg.route("/{path:.*}", web::get().to(bundled_www));
+                }
+            })
     })
-    .bind((args.host.as_str(), args.port))?
+    // the signals are handled below, to end the live feeds first
+    .disable_signals()
+    .shutdown_timeout(SHUTDOWN_GRACE);
+    let srv = match args.workers {
+        Some(workers) => srv.workers(workers),
+        None => srv,
+    };
+    let srv = match args.max_connections {
+        Some(max) => srv.max_connections(max),
+        None => srv,
+    };
+    let srv = match args.keep_alive {
+        Some(0) => srv.keep_alive(KeepAlive::Disabled),
+        Some(secs) => srv.keep_alive(Duration::from_secs(secs)),
+        None => srv,
+    };
+    let host = args.host.as_deref().unwrap_or(DEFAULT_HOST);
+    let port = args.port.unwrap_or(DEFAULT_PORT);
+    let srv = match tls {
+        Some(tls) => srv.bind_rustls_0_23((host, port), tls)?,
+        None => srv.bind((host, port))?,
+    }
     .run();
+    let handle = srv.handle();
+    tokio::spawn(async move {
+        shutdown::signalled().await;
+        info!("Shutting down, finishing the requests in flight...");
+        live_feeds.close();
+        // waits for the handlers running, and so for the writes they made
+        // to the backends, before closing the connections
+        handle.stop(true).await;
+    });
     info!("============================================");
-    info!(
-        "TRIBBLER SERVING AT ::: http://{}:{}",
-        &args.host, &args.port
-    );
+    info!("TRIBBLER SERVING AT ::: {}://{}:{}", scheme, host, port);
     info!("============================================");
     srv.await?;
+    info!("Stopped");
     Ok(())
 }
 
-async fn populate(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    server.sign_up("h8liu").await?;
-    server.sign_up("fenglu").await?;
-    server.sign_up("rkapoor").await?;
-    server.post("h8liu", "Hello, world.", 0).await?;
-    server.post("h8liu", "Just tribble it.", 0).await?;
-    server.post("fenglu", "Double tribble.", 0).await?;
-    server.post("rkapoor", "Triple tribble.", 0).await?;
-    server.follow("fenglu", "h8liu").await?;
-    server.follow("fenglu", "rkapoor").await?;
-    server.follow("rkapoor", "h8liu").await?;
-    Ok(())
+// reads the certificate chain and private key to serve HTTPS with
+fn tls_config(cert: &str, key: &str) -> TribResult<rustls::ServerConfig> {
+    let bad_file = |path: &str, e: rustls::pki_types::pem::Error| {
+        TribblerError::Unknown(format!("cannot read {}: {}", path, e))
+    };
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| bad_file(cert, e))?;
+    let key_der = PrivateKeyDer::from_pem_file(key).map_err(|e| bad_file(key, e))?;
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .and_then(|builder| {
+        builder
+            .with_no_client_auth()
+            .with_single_cert(chain, key_der)
+    })
+    .map_err(|e| TribblerError::Unknown(format!("bad TLS certificate or key: {}", e)))?;
+    Ok(config)
 }
 
-async fn all_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    sign_up_test(server).await?;
-    list_users_test(server).await?;
-    post_test(server).await?;
-    tribs_test(server).await?;
-    follow_test(server).await?;
-    unfollow_test(server).await?;
-    following_test(server).await?;
-    is_following_test(server).await?;
-    Ok(())
+// the REST API, served both under /api, as the bundled UI calls it, and under
+// /api/v1 with the versioned contract
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(api::add_user)
+        .service(api::login)
+        .service(api::logout)
+        .service(api::whoami)
+        .service(api::oidc_login)
+        .service(api::oidc_callback)
+        .service(api::list_users)
+        .service(api::clock)
+        .service(api::list_tribs)
+        .service(api::list_home)
+        .service(api::is_following)
+        .service(api::follow)
+        .service(api::unfollow)
+        .service(api::following)
+        .service(api::mutuals)
+        .service(api::last_active)
+        .service(api::post)
+        .service(api::like)
+        .service(api::unlike)
+        .service(api::likes)
+        .service(api::replies)
+        .service(api::tag)
+        .service(api::set_profile)
+        .service(api::get_profile)
+        .service(api::trending)
+        .service(api::notifications)
+        .service(api::mark_read)
+        .service(api::unread_count)
+        .service(api::create_list)
+        .service(api::add_to_list)
+        .service(api::list_timeline)
+        .service(api::save_draft)
+        .service(api::list_drafts)
+        .service(api::delete_draft)
+        .service(api::schedule_post)
+        .service(api::admin_delete_trib)
+        .service(api::admin_ban_user)
+        .service(api::admin_unban)
+        .service(api::admin_cluster)
+        .service(api::export_user)
+        .service(api::stream_home);
 }
 
-// pass
-async fn sign_up_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // name too long
-    let mut result = server.sign_up("qwertyuiopasdfghjklzxcvbnm").await;
-    println!("{:?}", result);
-    // uppercase letters are not allowed
-    result = server.sign_up("aaA9").await;
-    println!("{:?}", result);
-
-    // signing up repetitively
-    result = server.sign_up("user1").await;
-    println!("{:?}", result);
-    result = server.sign_up("user1").await;
-    println!("{:?}", result);
-    Ok(())
+/// The web UI, built into the binary so that it runs from anywhere.
+#[derive(RustEmbed)]
+#[folder = "../www"]
+struct Www;
+
+// serves the web UI built into the binary
+async fn bundled_www(path: web::Path<String>) -> HttpResponse {
+    let path = match path.as_str() {
+        "" => "index.html",
+        path => path,
+    };
+    let file = match Www::get(path) {
+        Some(file) => Some(file),
+        None if ui_route(path) => Www::get("index.html"),
+        None => None,
+    };
+    match file {
+        Some(file) => HttpResponse::Ok()
+            .content_type(file.metadata.mimetype())
+            .body(file.data.into_owned()),
+        None => HttpResponse::NotFound().finish(),
+    }
 }
 
-// pass
-async fn list_users_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // get the users from the bin storage
-    let mut result = server.sign_up("z1").await;
-    let mut result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("y2").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("x3").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("w4").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("v5").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("u6").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("t7").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("s8").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("r9").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("q10").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("p11").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("o12").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("n13").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("m14").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("l15").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("k16").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("j17").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("i18").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("h19").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("g20").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-
-    // get the users from the cache
-    result = server.sign_up("f21").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("e22").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("d23").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("c24").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("b25").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    result = server.sign_up("a26").await;
-    result2 = server.list_users().await;
-    println!("{:?}", result);
-    println!("{:?}", result2);
-    Ok(())
+// serves the UI's index at the paths the UI routes itself, for what is missing
+// from a web UI served from a directory
+async fn ui_fallback(index: PathBuf, req: ServiceRequest) -> actix_web::Result<ServiceResponse> {
+    if !ui_route(req.path().trim_start_matches('/')) {
+        return Ok(req.into_response(HttpResponse::NotFound().finish()));
+    }
+    let file = NamedFile::open_async(index).await?;
+    let (req, _) = req.into_parts();
+    let resp = file.into_response(&req);
+    Ok(ServiceResponse::new(req, resp))
 }
 
-// pass
-async fn post_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // The user doesn't exist.
-    let mut result = server.post("user doesn't exist", "", 0).await;
-    println!("{:?}", result);
+// whether a path missing from the web UI is one the UI routes itself, such as
+// user/bob, which is answered with the UI's index so it works when reloaded;
+// files, whose last segment has an extension, and the API are not
+fn ui_route(path: &str) -> bool {
+    let last = path.rsplit('/').next().unwrap_or_default();
+    !last.contains('.') && path != "api" && !path.starts_with("api/")
+}
 
-    // The post is too long.
-    let post = "A".repeat(141);
-    result = server.post("a26", &post, 0).await;
-    println!("{:?}", result);
-    Ok(())
+// collects the metrics of the whole process, the bin client's calls to the
+// backends included, for /metrics to render
+fn metrics_recorder() -> TribResult<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(LATENCY_BUCKETS)
+        .and_then(|builder| builder.install_recorder())
+        .map_err(|e| TribblerError::Unknown(format!("cannot collect metrics: {}", e)))?;
+    // the histograms only drain into what is rendered on upkeep
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            upkeep.run_upkeep();
+        }
+    });
+    Ok(handle)
+}
+
+// counts and times every request, by route and by the status it got
+async fn record_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl MessageBody>> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let res = next.call(req).await;
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    counter!(
+        "tribbler_http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status.as_str().to_string(),
+    )
+    .increment(1);
+    histogram!(
+        "tribbler_http_request_duration_seconds",
+        "method" => method,
+        "route" => route,
+    )
+    .record(start.elapsed().as_secs_f64());
+    res
+}
+
+/// The seconds calls to the API may take, by route, 0 for no deadline.
+struct Timeouts {
+    default: u64,
+    routes: HashMap<String, u64>,
 }
 
-// pass
-async fn tribs_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // check the order of the posts
-    let mut i = 0;
-    while i < 150 {
-        let result = server.post("a26", &("aaa".to_owned() + &i.to_string()), 0).await;
-        println!("{:?}", result);
-        i += 1;
+impl Timeouts {
+    // reads the deadlines of single routes, given as route=seconds
+    fn parse(default: u64, routes: &[String]) -> TribResult<Timeouts> {
+        let mut timeouts = Timeouts {
+            default,
+            routes: HashMap::new(),
+        };
+        for route in routes {
+            let (name, secs) = route
+                .split_once('=')
+                .and_then(|(name, secs)| Some((name, secs.parse::<u64>().ok()?)))
+                .ok_or_else(|| {
+                    TribblerError::Unknown(format!(
+                        "{} not a valid route timeout, use route=seconds",
+                        route
+                    ))
+                })?;
+            timeouts
+                .routes
+                .insert(name.trim_start_matches('/').to_string(), secs);
+        }
+        Ok(timeouts)
     }
-    let result2 = server.tribs("a26").await;
-    println!("{:?}", result2);
-    println!("{:?}", result2.unwrap().len());
-    Ok(())
 }
 
-// pass
-async fn follow_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // A user can never follows himself.
-    let mut result = server.follow("a26", "a26").await;
-    println!("{:?}", result);
-
-    // The follower doesn't exist.
-    result = server.follow("x", "a26").await;
-    println!("{:?}", result);
-
-    // The followee doesn't exist.
-    result = server.follow("a26", "y").await;
-    println!("{:?}", result);
-
-    // Assume that a26 is not following b25,
-    // The first follow should succeed, and the second follow should fail.
-    result = server.follow("a26", "b25").await;
-    println!("{:?}", result);
-    result = server.follow("a26", "b25").await;
-    println!("{:?}", result);
-    result = server.unfollow("a26", "b25").await;
-    println!("{:?}", result);       
-
-    // should return error at the last iteration
-    let mut i = 0;
-    while i < MAX_FOLLOWING + 1 {
-        let cur_user = &("user".to_owned() + &i.to_string());
-        let result = server.sign_up(cur_user).await;
-        let result2 = server.follow("a26", cur_user).await;
-        println!("{:?}", result);
-        println!("{:?}", result2);
-        i += 1
-    }
-    
-    // Concurrent test cases are not written yet.
+// answers a call which runs past the deadline of its route with 504 Gateway
+// Timeout, dropping whatever it was waiting on, such as a dead backend
+async fn deadline(
+    timeouts: web::Data<Timeouts>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl MessageBody>> {
+    // the path within the API scope is the route
+    let route = req.match_info().unprocessed().trim_start_matches('/');
+    let secs = *timeouts.routes.get(route).unwrap_or(&timeouts.default);
+    if secs == 0 {
+        return next.call(req).await;
+    }
+    match tokio::time::timeout(Duration::from_secs(secs), next.call(req)).await {
+        Ok(res) => res,
+        Err(_) => {
+            let err = TribblerError::TimedOut(secs);
+            let resp = HttpResponse::GatewayTimeout()
+                .insert_header((ERROR_CODE_HEADER, err.code()))
+                .body(err.to_string());
+            Err(InternalError::from_response(err, resp).into())
+        }
+    }
+}
+
+/// How fast clients may call the API, by address and by signed in user.
+struct RateLimits {
+    ip: Option<RateLimiter>,
+    user: Option<RateLimiter>,
+}
+
+// the lab front-end over the cluster of a config, following its reloads, the
+// registry it names, and the backends it finds through DNS, with the
+// passwords kept in the users' bins, out of the service's sight
+async fn lab_front(config: watch::Receiver<Config>) -> TribResult<(Srv, Creds, api::Cluster)> {
+    let config = tribbler::config::discovery::discover(config).await?;
+    let config = lab2::discovery::discover(config).await?;
+    let auth: Creds = Box::new(BinAuth {
+        bin_storage: lab2::new_bin_client_following(config.clone()).await?,
+    });
+    let srv = lab2::new_front_following(config.clone()).await?;
+    Ok((srv, auth, api::Cluster(Some(config))))
+}
+
+// runs the conformance suite against new servers of the server type, once,
+// or round after round until --soak seconds are up
+async fn self_test(args: &Cfg, limits: Limits) -> TribResult<()> {
+    let soak = args.soak.map(Duration::from_secs);
+    if let (Some(_), ServerType::Lab) = (soak, &args.server_type) {
+        return Err(TribblerError::Unknown(
+            "the suite needs a cluster without users, so a lab cluster takes only one round; \
+             soak the dev server type instead"
+                .to_string(),
+        ));
+    }
+    let start = Instant::now();
+    let mut rounds = 0;
+    loop {
+        let server: Srv = match args.server_type {
+            ServerType::Ref => Box::new(RefServer::with_limits(limits)),
+            ServerType::Lab => {
+                let cfg = Config::read(Some(&args.config))?;
+                lab_front(watch::channel(cfg).1).await?.0
+            }
+            ServerType::Dev => {
+                let cfg = dev::start_cluster(args.dev_backs, limits).await?;
+                lab_front(watch::channel(cfg).1).await?.0
+            }
+        };
+        rounds += 1;
+        // the suite fails by panicking, which the task catches; the panic
+        // message is logged as it happens
+        let failed = match tokio::spawn(conformance::run(server, limits)).await {
+            Ok(result) => result.err().map(|e| e.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(e) = failed {
+            return Err(TribblerError::Unknown(format!(
+                "conformance round {} failed: {}",
+                rounds, e
+            )));
+        }
+        info!("Conformance round {} passed", rounds);
+        match soak {
+            Some(soak) if start.elapsed() < soak => continue,
+            _ => break,
+        }
+    }
+    info!(
+        "Passed {} conformance rounds in {:.1}s",
+        rounds,
+        start.elapsed().as_secs_f64()
+    );
     Ok(())
 }
 
-// pass
-async fn unfollow_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // A user can never follows himself.
-    let mut result = server.unfollow("a26", "a26").await;
-    println!("{:?}", result);
+fn session_store(session_bin: &Option<BinSessionStore>) -> FrontSessionStore {
+    match session_bin {
+        Some(store) => FrontSessionStore::Bin(store.clone()),
+        None => FrontSessionStore::Cookie(CookieSessionStore::default()),
+    }
+}
+
+// now and then forgets the shared sessions which expired and the shared rate
+// limit buckets which filled up, which are kept for good otherwise
+async fn prune_shared_state(sessions: BinSessionStore, limits: web::Data<RateLimits>) {
+    let mut every = tokio::time::interval(SHARED_PRUNE_EVERY);
+    loop {
+        every.tick().await;
+        let limiters = [&limits.ip, &limits.user];
+        for limiter in limiters.into_iter().flatten() {
+            if let Err(e) = limiter.prune().await {
+                warn!("failed to prune the shared rate limits: {}", e);
+            }
+        }
+        if let Err(e) = sessions.prune().await {
+            warn!("failed to prune the shared sessions: {}", e);
+        }
+    }
+}
+
+// a rate limiter named `name`, keeping its buckets in the cluster when `shared`
+// is the cluster's bins
+async fn rate_limiter(
+    rate: Option<f64>,
+    burst: u32,
+    shared: &Option<Box<dyn BinStorage>>,
+    name: &str,
+) -> TribResult<Option<RateLimiter>> {
+    match (rate, shared) {
+        (Some(rate), Some(bins)) if rate > 0.0 => Ok(Some(RateLimiter::shared(
+            rate,
+            burst,
+            bins.bin(LIMIT_BIN).await?,
+            name,
+        ))),
+        (Some(rate), None) if rate > 0.0 => Ok(Some(RateLimiter::new(rate, burst))),
+        (Some(rate), _) => Err(TribblerError::Unknown(format!(
+            "a rate limit of {} calls a second lets nothing through",
+            rate
+        ))),
+        (None, _) => Ok(None),
+    }
+}
+
+// turns calls away with 429 Too Many Requests once the address or the user
+// making them runs out of calls
+async fn limit_rate(
+    limits: web::Data<RateLimits>,
+    session: Session,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<EitherBody<impl MessageBody>>> {
+    // the peer address, since forwarded-for headers are the client's to forge
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+    let user = session.get::<String>(api::SESSION_USER).ok().flatten();
+    let mut limited = match (&limits.ip, ip) {
+        (Some(limiter), Some(ip)) => limiter.take(&ip).await.err(),
+        _ => None,
+    };
+    if limited.is_none() {
+        limited = match (&limits.user, user) {
+            (Some(limiter), Some(user)) => limiter.take(&user).await.err(),
+            _ => None,
+        };
+    }
+    match limited {
+        Some(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let resp = api::err_response(TribblerError::RateLimited { retry_after });
+            Ok(req.into_response(resp).map_into_right_body())
+        }
+        None => next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body),
+    }
+}
 
-    // The follower doesn't exist.
-    result = server.unfollow("x", "a26").await;
-    println!("{:?}", result);
+// turns away with 403 Forbidden the calls changing something for a signed in
+// browser which lack the CSRF token its session was issued; a page of another
+// site can make the browser send the session cookie, but can't read the token
+async fn check_csrf(
+    session: Session,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<EitherBody<impl MessageBody>>> {
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let signed_in = matches!(session.get::<String>(api::SESSION_USER), Ok(Some(_)));
+    let expected = session.get::<String>(api::SESSION_CSRF).ok().flatten();
+    let given = req
+        .headers()
+        .get(api::CSRF_HEADER)
+        .and_then(|token| token.to_str().ok());
+    // compared in constant time, so that timing can't tell how much of a
+    // guess was right
+    let matched: bool = match (expected, given) {
+        (Some(expected), Some(given)) => expected.as_bytes().ct_eq(given.as_bytes()).into(),
+        _ => false,
+    };
+    if !safe && signed_in && !matched {
+        let resp = HttpResponse::Forbidden().body("CSRF token missing or wrong");
+        return Ok(req.into_response(resp).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
 
-    // The followee doesn't exist.
-    result = server.unfollow("a26", "y").await;
-    println!("{:?}", result);
+// turns away with 400 Bad Request, naming the fields at fault, the calls whose
+// arguments are plainly invalid, before they reach the service
+async fn validate(
+    limits: web::Data<Limits>,
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<EitherBody<impl MessageBody>>> {
+    if req.method() != Method::POST {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    // read up to the size limit, and put back for the call
+    let body = req.extract::<web::Bytes>().await?;
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok());
+    if let Err(errors) = check_body(content_type, &body, limits.max_trib_len) {
+        let problems: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        let resp = HttpResponse::BadRequest().body(problems.join("; "));
+        return Ok(req.into_response(resp).map_into_right_body());
+    }
+    req.set_payload(body.into());
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
 
-    // Assume that a26 is following b25,
-    // The first unfollow should succeed, and the second unfollow should fail.
-    result = server.follow("a26", "b25").await;
-    println!("{:?}", result);
-    result = server.unfollow("a26", "b25").await;
-    println!("{:?}", result);
-    result = server.unfollow("a26", "b25").await;
-    println!("{:?}", result);
+// tags every request with an ID, the client's own if it sent a usable one,
+// which the response, the access log and the RPCs made for the request carry,
+// and logs the request as a line of JSON once it is answered
+async fn log_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl MessageBody>> {
+    let start = Instant::now();
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| usable_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(REQUEST_ID_LEN)
+                .map(char::from)
+                .collect()
+        });
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let res = REQUEST_ID.scope(id.clone(), next.call(req)).await;
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    info!(
+        target: "trib_front::access",
+        "{}",
+        serde_json::json!({
+            "request_id": id,
+            "method": method,
+            "path": path,
+            "status": status.as_u16(),
+            "latency_ms": start.elapsed().as_secs_f64() * 1000.0,
+        })
+    );
+    let mut res = res?;
+    if let Ok(id) = HeaderValue::from_str(&id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), id);
+    }
+    Ok(res)
+}
 
-    // Concurrent test cases are not written yet.
-    Ok(())
+// whether a request ID sent by a client is safe to log and pass on
+fn usable_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
-// pass
-async fn following_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    let result = server.following("a26").await;
-    println!("{:?}", result);
-    Ok(())
+// turns the answers of the legacy API into those of /api/v1: JSON sent as
+// such, without the empty `err` of a call which went through, and a failed
+// call, including a legacy one answered with a non-empty `err`, as an error
+// status with an `{"error": {"status", "code", "message"}}` envelope
+async fn v1_contract(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> actix_web::Result<ServiceResponse<BoxBody>> {
+    let res = match next.call(req).await {
+        Ok(res) => res.map_into_boxed_body(),
+        // what fails outside the handlers, such as a call running out of
+        // time, is answered by the error, which then has to be rewritten
+        Err(e) => {
+            let resp = e.error_response();
+            let status = resp.status();
+            let code = error_code(resp.headers());
+            let body = actix_web::body::to_bytes(resp.into_body())
+                .await
+                .unwrap_or_default();
+            let (status, json) = v1_body(status, code.as_deref(), &body);
+            let resp = HttpResponse::build(status)
+                .content_type("application/json")
+                .body(json);
+            return Err(InternalError::from_response(e.to_string(), resp).into());
+        }
+    };
+    // event streams and redirects pass as they are
+    let event_stream = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|ct| ct.as_bytes().starts_with(b"text/event-stream"));
+    if event_stream || res.status().is_redirection() {
+        return Ok(res);
+    }
+    let (http_req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body = actix_web::body::to_bytes(body)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("unreadable response"))?;
+    let code = error_code(res.headers());
+    let (status, json) = v1_body(res.status(), code.as_deref(), &body);
+    *res.status_mut() = status;
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok(ServiceResponse::new(
+        http_req,
+        res.set_body(json).map_into_boxed_body(),
+    ))
+}
+
+// the code a legacy answer carries for its error, if any
+fn error_code(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ERROR_CODE_HEADER)
+        .and_then(|code| code.to_str().ok())
+        .map(str::to_string)
+}
+
+// the status and body /api/v1 answers with for a legacy answer, which failed
+// with the error of `code` when it says; an error without one has a code made
+// of its status, such as `not_found`
+fn v1_body(status: StatusCode, code: Option<&str>, body: &[u8]) -> (StatusCode, String) {
+    let error = |status: StatusCode, message: &str| {
+        let code = code.map(str::to_string).unwrap_or_else(|| {
+            status
+                .canonical_reason()
+                .unwrap_or("unknown")
+                .to_ascii_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        });
+        let envelope = serde_json::json!({
+            "error": { "status": status.as_u16(), "code": code, "message": message },
+        });
+        (status, envelope.to_string())
+    };
+    if !status.is_success() {
+        let message = String::from_utf8_lossy(body);
+        return match message.trim() {
+            "" => error(status, status.canonical_reason().unwrap_or_default()),
+            message => error(status, message),
+        };
+    }
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Object(mut fields)) => match fields.remove("err") {
+            Some(serde_json::Value::String(err)) if !err.is_empty() => {
+                error(StatusCode::BAD_REQUEST, &err)
+            }
+            _ => (status, serde_json::Value::Object(fields).to_string()),
+        },
+        Ok(json) => (status, json.to_string()),
+        Err(_) => (
+            status,
+            serde_json::Value::String(String::from_utf8_lossy(body).into_owned()).to_string(),
+        ),
+    }
 }
 
-// pass
-async fn is_following_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // A user can never follows himself.
-    let mut result = server.is_following("a26", "a26").await;
-    println!("{:?}", result);
+// serves the metrics in the Prometheus text format
+async fn render_metrics(handle: web::Data<PrometheusHandle>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(handle.render())
+}
 
-    // The follower doesn't exist.
-    result = server.is_following("x", "a26").await;
-    println!("{:?}", result);
+// the CORS policy for pages on other origins calling the API; the bundled UI,
+// served from the front-end's own origin, is always let through
+fn cors(origins: &[String], methods: &[String], headers: &[String]) -> Cors {
+    let any = origins.iter().any(|origin| origin == "*");
+    let listed = origins.to_vec();
+    let cors = Cors::default()
+        .allowed_origin_fn(move |origin, req| {
+            let origin = origin.to_str().unwrap_or("");
+            any || listed.iter().any(|listed| listed == origin) || same_origin(origin, req)
+        })
+        .allowed_methods(methods.iter().map(String::as_str))
+        .allowed_headers(headers.iter().map(String::as_str))
+        .max_age(3600);
+    // any page sending the session cookie along could act for its user
+    if any {
+        cors
+    } else {
+        cors.supports_credentials()
+    }
+}
 
-    // The followee doesn't exist.
-    result = server.is_following("a26", "y").await;
-    println!("{:?}", result);
+// whether a request comes from a page of the origin it is sent to
+fn same_origin(origin: &str, req: &RequestHead) -> bool {
+    let host = match req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+    {
+        Some(host) => host,
+        None => return false,
+    };
+    match origin.split_once("://") {
+        Some((_, origin_host)) => origin_host == host,
+        None => false,
+    }
+}
 
-    // True and False
-    result = server.is_following("a26", "user1").await;
-    println!("{:?}", result);
-    result = server.is_following("a26", "user3000").await;
-    println!("{:?}", result);
-    Ok(())
+/// Users, posts and follows to seed the service with, as read from a seed
+/// file, e.g.
+/// `{"users": ["alice", "bob"], "posts": [{"user": "alice", "message": "hi"}],
+/// "follows": [{"who": "bob", "whom": "alice"}]}`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Seed {
+    users: Vec<String>,
+    /// posted in order, after every user signed up
+    posts: Vec<SeedPost>,
+    follows: Vec<SeedFollow>,
 }
 
-async fn home_test(server: &web::Data<Box<dyn Server + Send + Sync>>) -> TribResult<()> {
-    // check the order of the posts
-    let mut i = 0;
-    while i < 50 {
-        let result = server.post("a26", &("aaa".to_owned() + &i.to_string()), 0).await;
-        println!("{:?}", result);
-        i += 1;
+#[derive(Deserialize, Debug)]
+struct SeedPost {
+    user: String,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SeedFollow {
+    who: String,
+    whom: String,
+}
+
+impl Seed {
+    // the few demo users the front-end has always started with
+    fn demo() -> Seed {
+        let post = |user: &str, message: &str| SeedPost {
+            user: user.to_string(),
+            message: message.to_string(),
+        };
+        let follow = |who: &str, whom: &str| SeedFollow {
+            who: who.to_string(),
+            whom: whom.to_string(),
+        };
+        Seed {
+            users: vec![
+                "h8liu".to_string(),
+                "fenglu".to_string(),
+                "rkapoor".to_string(),
+            ],
+            posts: vec![
+                post("h8liu", "Hello, world."),
+                post("h8liu", "Just tribble it."),
+                post("fenglu", "Double tribble."),
+                post("rkapoor", "Triple tribble."),
+            ],
+            follows: vec![
+                follow("fenglu", "h8liu"),
+                follow("fenglu", "rkapoor"),
+                follow("rkapoor", "h8liu"),
+            ],
+        }
     }
+}
 
-    i = 0;
-    while i < 50 {
-        let result = server.post("user1", &("bbb".to_owned() + &i.to_string()), 0).await;
-        println!("{:?}", result);
-        i += 1;
+// seeds the service once it takes writes, which a cluster started along with
+// the front-end only does once the keeper's view reaches the front-end
+async fn populate_when_ready(
+    server: &web::Data<Srv>,
+    auth: &web::Data<Creds>,
+    password: Option<&str>,
+    seed: &Seed,
+) -> TribResult<()> {
+    let start = Instant::now();
+    loop {
+        match populate(server, auth, password, seed).await {
+            Err(TribblerError::ClusterNotReady) if start.elapsed() < SEED_PATIENCE => {
+                tokio::time::sleep(SEED_RETRY).await
+            }
+            result => return result,
+        }
     }
+}
 
-    i = 0;
-    while i < 50 {
-        let result = server.post("user2", &("ccc".to_owned() + &i.to_string()), 0).await;
-        println!("{:?}", result);
-        i += 1;
+// seeds the service, giving each user it signs up the password, if any
+async fn populate(
+    server: &web::Data<Srv>,
+    auth: &web::Data<Creds>,
+    password: Option<&str>,
+    seed: &Seed,
+) -> TribResult<()> {
+    for user in seed.users.iter() {
+        server.sign_up(user).await?;
+        if let Some(password) = password {
+            auth.set_password(user, password).await?;
+        }
+    }
+    for post in seed.posts.iter() {
+        server.post(&post.user, &post.message, 0).await?;
+    }
+    for follow in seed.follows.iter() {
+        server.follow(&follow.who, &follow.whom).await?;
     }
+    Ok(())
+}
 
-    // should show 100 posts in order
-    let result = server.home("a26").await;
-    println!("{:?}", result);
-    println!("{:?}", result.unwrap().len());
+// imports the user exports in the file at path, giving the users new to the
+// service the password, if any; the ones there before keep theirs
+async fn import(
+    server: &web::Data<Srv>,
+    auth: &web::Data<Creds>,
+    password: Option<&str>,
+    path: &str,
+) -> TribResult<()> {
+    let data = std::fs::read_to_string(path)?;
+    let exports: Vec<UserExport> = serde_json::from_str(&data)?;
+    let mut new_users = vec![];
+    for export in exports.iter() {
+        if !server.signed_up(&export.user).await? {
+            new_users.push(export.user.as_str());
+        }
+    }
+    server.import(&exports).await?;
+    if let Some(password) = password {
+        for user in new_users {
+            auth.set_password(user, password).await?;
+        }
+    }
     Ok(())
 }
 
 /// this module contains the REST API functions used by the front-end
 mod api {
     use std::error::Error;
-    use std::{collections::HashMap, sync::Arc};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
 
-    use actix_web::{get, http::header::ContentType, post, web, HttpResponse, Responder};
-    use log::debug;
+    use actix_session::{Session, SessionInsertError};
+    use actix_web::dev::Payload;
+    use actix_web::http::header::{self, ContentType};
+    use actix_web::http::header::{EntityTag, IfNoneMatch};
+    use actix_web::http::Method;
+    use actix_web::{
+        get, post, route, web, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder,
+    };
+    use actix_ws::Message;
+    use log::{debug, warn};
+    use std::convert::Infallible;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::iter;
+    use tokio::sync::broadcast::{self, error::RecvError};
+    use tokio::sync::{mpsc, watch};
 
-    use crate::Srv;
+    use crate::{Creds, Srv, ERROR_CODE_HEADER};
+    use cmd::oidc::{Identity, Provider};
+    use rand::distributions::Alphanumeric;
+    use rand::Rng;
+    use subtle::ConstantTimeEq;
+    use tribbler::trib::MAX_USERNAME_LEN;
 
     fn build_resp<T: Serialize>(d: &T) -> HttpResponse {
         HttpResponse::Ok()
@@ -432,110 +1397,823 @@ mod api {
             .body(serde_json::to_string(d).unwrap())
     }
 
-    fn err_response(err: Box<dyn Error>) -> HttpResponse {
-        HttpResponse::InternalServerError().body(err.to_string())
-    }
+    /// The argument of a call: the user name, or the JSON object, the route
+    /// takes. Standard clients send it as an `application/json` body, a user
+    /// name as a JSON string; the bundled UI sends it as the only key of a
+    /// form. The listings which may be fetched with a GET take the user name
+    /// as the `user` query parameter then.
+    pub struct Arg(String);
 
-    /// signs up a new user
-    #[post("/add-user")]
-    pub async fn add_user(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        debug!("add-user: {:?}", &s);
-        match data.sign_up(s.keys().next().unwrap()).await {
-            Ok(_) => build_resp(&UserList {
-                users: data.list_users().await.unwrap(),
-                err: "".to_string(),
-            }),
-            Err(e) => err_response(e),
+    impl FromRequest for Arg {
+        type Error = actix_web::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+        fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+            if req.method() == Method::GET {
+                let user = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+                    .ok()
+                    .and_then(|query| query.0.get("user").cloned());
+                return Box::pin(async move {
+                    user.map(Arg)
+                        .ok_or_else(|| actix_web::error::ErrorBadRequest("missing user"))
+                });
+            }
+            let json = req
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("application/json"));
+            if json {
+                let body = String::from_request(req, payload);
+                return Box::pin(async move {
+                    let body = body.await?;
+                    // a user name comes as a JSON string, an object as is
+                    match serde_json::from_str::<String>(&body) {
+                        Ok(name) => Ok(Arg(name)),
+                        Err(_) => Ok(Arg(body)),
+                    }
+                });
+            }
+            let form = web::Form::<HashMap<String, String>>::from_request(req, payload);
+            Box::pin(async move {
+                match form.await?.0.into_keys().next() {
+                    Some(raw) => Ok(Arg(raw)),
+                    None => Err(actix_web::error::ErrorBadRequest("missing argument")),
+                }
+            })
         }
     }
 
-    /// lists all the users registered
-    #[get("list-users")]
-    pub async fn list_users(data: web::Data<Srv>) -> impl Responder {
-        match data.list_users().await {
-            Ok(v) => {
-                let ul = UserList {
-                    users: v,
-                    err: "".to_string(),
-                };
-                build_resp(&ul)
+    // answers a failed call with the status that fits what went wrong,
+    // beneath whatever context it was given
+    pub fn err_response(err: TribblerError) -> HttpResponse {
+        let mut resp = match err.root() {
+            TribblerError::UserDoesNotExist(_)
+            | TribblerError::TribNotFound(_, _)
+            | TribblerError::NoSuchList(_, _)
+            | TribblerError::NoSuchDraft(_, _) => HttpResponse::NotFound(),
+            TribblerError::NotAuthorized
+            | TribblerError::NotSignedIn(_)
+            | TribblerError::WrongPassword(_)
+            | TribblerError::SsoFailed(_) => HttpResponse::Unauthorized(),
+            TribblerError::Banned(_) | TribblerError::BlockedBy(_, _) => HttpResponse::Forbidden(),
+            TribblerError::UsernameTaken(_)
+            | TribblerError::AlreadyFollowing(_, _)
+            | TribblerError::NotFollowing(_, _)
+            | TribblerError::AlreadyLiked(_, _, _)
+            | TribblerError::NotLiked(_, _, _)
+            | TribblerError::AlreadyBlocked(_, _)
+            | TribblerError::NotBlocked(_, _)
+            | TribblerError::AlreadyMuted(_, _)
+            | TribblerError::NotMuted(_, _)
+            | TribblerError::ListExists(_, _)
+            | TribblerError::AlreadyInList(_, _)
+            | TribblerError::AlreadyBanned(_)
+            | TribblerError::NotBanned(_) => HttpResponse::Conflict(),
+            TribblerError::ClusterNotReady
+            | TribblerError::ClusterDegraded(_)
+            | TribblerError::InvalidConfig(_) => HttpResponse::ServiceUnavailable(),
+            TribblerError::QuotaExceeded(_) => HttpResponse::Forbidden(),
+            TribblerError::RateLimited { retry_after } => {
+                let mut resp = HttpResponse::TooManyRequests();
+                resp.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+                resp
             }
-            Err(e) => err_response(e),
+            TribblerError::StorageFull(_) => HttpResponse::InsufficientStorage(),
+            TribblerError::TimedOut(_) => HttpResponse::GatewayTimeout(),
+            TribblerError::RpcError(_)
+            | TribblerError::Status(_)
+            | TribblerError::Transport(_)
+            | TribblerError::Registry(_)
+            | TribblerError::Io(_)
+            | TribblerError::Json(_)
+            | TribblerError::TomlDecode(_)
+            | TribblerError::TomlEncode(_)
+            | TribblerError::Yaml(_)
+            | TribblerError::Utf8(_)
+            | TribblerError::ParseInt(_)
+            | TribblerError::Time(_)
+            | TribblerError::MaxedSeq
+            | TribblerError::NotACounter(_)
+            | TribblerError::CorruptLogEntry(_)
+            | TribblerError::CacheFailure(_)
+            | TribblerError::Unknown(_) => HttpResponse::InternalServerError(),
+            _ => HttpResponse::BadRequest(),
+        };
+        resp.insert_header((ERROR_CODE_HEADER, err.code()))
+            .body(err.to_string())
+    }
+
+    // answers a call whose arguments could not be read
+    fn bad_request(err: impl Error) -> HttpResponse {
+        HttpResponse::BadRequest()
+            .insert_header((ERROR_CODE_HEADER, "bad_request"))
+            .body(err.to_string())
+    }
+
+    /// The OpenAPI description of the REST API, served at /api/openapi.json.
+    #[derive(OpenApi)]
+    #[openapi(
+        info(
+            title = "Tribbler",
+            description = "Every call takes its argument either as an `application/json` body \
+                (a user name as a JSON string, anything else as a JSON object) or, as the \
+                bundled web UI sends it, as the only key of a form. Every response is JSON, \
+                sent as `text/plain`; a failed call answers with the error as plain text, \
+                and the stable code of the error, such as `username_taken`, in an \
+                `X-Error-Code` header. \
+                Calls acting for a user need the session cookie of a browser signed in as \
+                that user; every POST of a signed in browser has to carry the `csrf` token \
+                `login` and `whoami` answer with in an `X-CSRF-Token` header, or is turned \
+                away with 403. The home timeline is also pushed, to a browser signed in as \
+                its user, over a websocket at `/ws?user=`.\n\n\
+                Under `/api/v1` the same calls answer as `application/json`, without the \
+                empty `err` field, and fail with an error status and a body of \
+                `{\"error\": {\"status\": ..., \"code\": ..., \"message\": ...}}`, where \
+                `code` names the error the same way across versions, and is the one to \
+                branch on; the message may change."
+        ),
+        servers(
+            (url = "/api", description = "the legacy contract the bundled UI calls"),
+            (url = "/api/v1", description = "the versioned contract"),
+        ),
+        modifiers(&LeadingSlash),
+        paths(
+            add_user,
+            login,
+            logout,
+            whoami,
+            oidc_login,
+            oidc_callback,
+            list_users,
+            clock,
+            list_tribs,
+            list_home,
+            is_following,
+            follow,
+            unfollow,
+            following,
+            mutuals,
+            last_active,
+            post,
+            stream_home,
+            like,
+            unlike,
+            likes,
+            replies,
+            tag,
+            set_profile,
+            get_profile,
+            trending,
+            notifications,
+            mark_read,
+            unread_count,
+            create_list,
+            add_to_list,
+            list_timeline,
+            save_draft,
+            list_drafts,
+            delete_draft,
+            schedule_post,
+            export_user,
+            admin_delete_trib,
+            admin_ban_user,
+            admin_unban,
+            admin_cluster,
+        )
+    )]
+    pub struct ApiDoc;
+
+    // the routes of the /api scope mostly go without the leading slash
+    // OpenAPI paths need
+    struct LeadingSlash;
+
+    impl Modify for LeadingSlash {
+        fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+            let paths = std::mem::take(&mut openapi.paths.paths);
+            openapi.paths.paths = paths
+                .into_iter()
+                .map(|(path, item)| match path.starts_with('/') {
+                    true => (path, item),
+                    false => ("/".to_owned() + &path, item),
+                })
+                .collect();
         }
     }
 
-    /// lists all the tribs for a particular user
-    #[post("list-tribs")]
-    pub async fn list_tribs(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        match data.tribs(s.keys().next().unwrap()).await {
-            Ok(v) => {
-                let ul = TribList {
-                    tribs: v,
-                    err: "".to_string(),
-                };
-                build_resp(&ul)
+    /// The token admin requests have to carry, none when the admin API is off.
+    pub struct AdminToken(pub Option<String>);
+
+    impl AdminToken {
+        // compares in constant time, so that how long a wrong token takes to
+        // turn away tells nothing of how much of it was right
+        fn allows(&self, token: &str) -> bool {
+            match &self.0 {
+                Some(expected) => expected.as_bytes().ct_eq(token.as_bytes()).into(),
+                None => false,
             }
-            Err(e) => err_response(e),
         }
     }
 
-    /// lists the home page for a particular user
-    #[post("list-home")]
-    pub async fn list_home(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        match data.home(s.keys().next().unwrap()).await {
-            Ok(v) => {
-                let ul = TribList {
-                    tribs: v,
-                    err: "".to_string(),
-                };
-                build_resp(&ul)
+    /// The config of the cluster behind the front-end, as last reloaded;
+    /// none for the reference server, which has no cluster.
+    #[derive(Default)]
+    pub struct Cluster(pub Option<watch::Receiver<Config>>);
+
+    impl Cluster {
+        /// the backends and the keepers of the cluster, both empty for the
+        /// reference server
+        fn members(&self) -> (Vec<String>, Vec<String>) {
+            match &self.0 {
+                Some(config) => {
+                    let config = config.borrow();
+                    (config.backs.clone(), config.keepers.clone())
+                }
+                None => (vec![], vec![]),
             }
-            Err(e) => err_response(e),
         }
     }
 
-    /// determines whether a user is following another user or not
-    #[post("is-following")]
-    pub async fn is_following(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
-        match data.is_following(&t.who, &t.whom).await {
-            Ok(v) => {
-                let ul = Bool {
-                    v,
+    fn unauthorized() -> HttpResponse {
+        HttpResponse::Unauthorized().body(TribblerError::NotAuthorized.to_string())
+    }
+
+    /// the session entry holding the user a browser signed in as
+    pub const SESSION_USER: &str = "user";
+
+    /// the session entry holding the CSRF token of a signed in browser, which
+    /// the calls it makes have to carry in [CSRF_HEADER]
+    pub const SESSION_CSRF: &str = "csrf";
+
+    /// the header carrying the CSRF token
+    pub const CSRF_HEADER: &str = "x-csrf-token";
+
+    // signs the browser in as `user` under a fresh session, and issues the
+    // session a CSRF token, which is handed back
+    fn sign_in(session: &Session, user: &str) -> Result<String, SessionInsertError> {
+        session.renew();
+        let csrf = random_token();
+        session.insert(SESSION_USER, user)?;
+        session.insert(SESSION_CSRF, &csrf)?;
+        Ok(csrf)
+    }
+
+    // whether the browser making a call signed in as `who`, a call made on
+    // behalf of anyone else changes nothing
+    fn signed_in_as(session: &Session, who: &str) -> bool {
+        matches!(session.get::<String>(SESSION_USER), Ok(Some(user)) if user == who)
+    }
+
+    // turns away with 403 Forbidden a browser reading what only `who` may
+    // see, without being signed in as them
+    fn not_yours(who: &str) -> HttpResponse {
+        let err = TribblerError::NotSignedIn(who.to_string());
+        HttpResponse::Forbidden()
+            .insert_header((ERROR_CODE_HEADER, err.code()))
+            .body(err.to_string())
+    }
+
+    // counts the likes of every trib, a trib whose likes can't be read counts as 0
+    async fn like_counts(data: &web::Data<Srv>, tribs: &[Arc<Trib>]) -> Vec<usize> {
+        let mut counts = Vec::with_capacity(tribs.len());
+        for trib in tribs {
+            let n = match data.likes(&trib.user, trib.clock).await {
+                Ok(users) => users.len(),
+                Err(_) => 0,
+            };
+            counts.push(n);
+        }
+        counts
+    }
+
+    // builds a user listing carrying the follow counts and the avatar of
+    // every user, a user whose profile can't be read counts as (0, 0) with no
+    // avatar
+    async fn user_listing(data: &web::Data<Srv>, users: Vec<String>) -> UserList {
+        let mut following_counts = Vec::with_capacity(users.len());
+        let mut follower_counts = Vec::with_capacity(users.len());
+        let mut avatars = Vec::with_capacity(users.len());
+        for user in &users {
+            let (n, m) = data.follow_counts(user).await.unwrap_or((0, 0));
+            following_counts.push(n);
+            follower_counts.push(m);
+            let profile = data.get_profile(user).await.unwrap_or_default();
+            avatars.push(profile.avatar);
+        }
+        UserList {
+            err: "".to_string(),
+            users,
+            following: following_counts,
+            followers: follower_counts,
+            avatars,
+        }
+    }
+
+    /// signs up a new user, with the password the user signs in with
+    #[utoipa::path(
+        tag = "users",
+        request_body(content = Credentials, content_type = "application/json", description = "the user name and a password"),
+        responses(
+            (status = 200, description = "every user, with their follow counts and avatars", body = UserList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 409, description = "the username is taken"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("/add-user")]
+    pub async fn add_user(
+        data: web::Data<Srv>,
+        auth: web::Data<Creds>,
+        arg: Arg,
+    ) -> impl Responder {
+        let creds = match serde_json::from_str::<Credentials>(&arg.0) {
+            Ok(creds) => creds,
+            Err(e) => return bad_request(e),
+        };
+        // a user signed up without a password could never sign in
+        if creds.password.is_empty() {
+            return err_response(TribblerError::InvalidPassword);
+        }
+        debug!("add-user: {:?}", creds.user);
+        // a taken name keeps the password it has
+        match data.signed_up(&creds.user).await {
+            Ok(false) => {}
+            Ok(true) => return err_response(TribblerError::UsernameTaken(creds.user)),
+            Err(e) => return err_response(e),
+        }
+        // the password goes in before the name, so that nobody is signed up
+        // without one, and is taken back if the sign-up fails
+        let hashed = match auth.claim_password(&creds.user, &creds.password).await {
+            Ok(Some(hashed)) => hashed,
+            Ok(None) => return err_response(TribblerError::UsernameTaken(creds.user)),
+            Err(e) => return err_response(e),
+        };
+        if let Err(e) = data.sign_up(&creds.user).await {
+            if let Err(release) = auth.release_password(&creds.user, &hashed).await {
+                warn!(
+                    "failed to take back the password of {}: {}",
+                    creds.user, release
+                );
+            }
+            return err_response(e);
+        }
+        match data.list_users().await {
+            Ok(v) => build_resp(&user_listing(&data, v).await),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// signs a browser in as a user, the calls it makes on the user's behalf
+    /// from then on are let through
+    #[utoipa::path(
+        tag = "sessions",
+        request_body(content = Credentials, content_type = "application/json", description = "the user name and the user's password"),
+        responses(
+            (status = 200, description = "signed in; the response sets the session cookie", body = SignedIn, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed"),
+            (status = 401, description = "the user does not exist, or the password is wrong"),
+        )
+    )]
+    #[post("login")]
+    pub async fn login(
+        data: web::Data<Srv>,
+        auth: web::Data<Creds>,
+        session: Session,
+        arg: Arg,
+    ) -> impl Responder {
+        let creds = match serde_json::from_str::<Credentials>(&arg.0) {
+            Ok(creds) => creds,
+            Err(e) => return bad_request(e),
+        };
+        // an unknown user is refused just like a wrong password, and only
+        // once the password is checked, so that signing in doesn't tell which
+        // users exist, not even by how long it takes
+        let signed_up = match data.signed_up(&creds.user).await {
+            Ok(signed_up) => signed_up,
+            Err(e) => return err_response(e),
+        };
+        match auth.check_password(&creds.user, &creds.password).await {
+            Ok(true) if signed_up => {}
+            Ok(_) => return err_response(TribblerError::WrongPassword(creds.user)),
+            Err(e) => return err_response(e),
+        }
+        match sign_in(&session, &creds.user) {
+            Ok(csrf) => build_resp(&SignedIn {
+                v: true,
+                csrf,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(TribblerError::Unknown(e.to_string())),
+        }
+    }
+
+    /// signs a browser out
+    #[utoipa::path(
+        tag = "sessions",
+        responses(
+            (status = 200, description = "signed out", body = Bool, content_type = "text/plain"),
+        )
+    )]
+    #[post("logout")]
+    pub async fn logout(session: Session) -> impl Responder {
+        session.purge();
+        build_resp(&Bool {
+            v: true,
+            err: "".to_string(),
+        })
+    }
+
+    /// tells who the browser making the call is signed in as, and whether it
+    /// may sign in through single sign-on
+    #[utoipa::path(
+        tag = "sessions",
+        responses(
+            (status = 200, description = "who the browser is signed in as", body = SessionInfo, content_type = "text/plain"),
+        )
+    )]
+    #[get("whoami")]
+    pub async fn whoami(sso: web::Data<Sso>, session: Session) -> impl Responder {
+        let user = session.get::<String>(SESSION_USER).ok().flatten();
+        let csrf = match (&user, session.get::<String>(SESSION_CSRF).ok().flatten()) {
+            (Some(_), Some(csrf)) => csrf,
+            // a browser signed in before CSRF tokens were issued gets one now
+            (Some(_), None) => {
+                let csrf = random_token();
+                if let Err(e) = session.insert(SESSION_CSRF, &csrf) {
+                    return err_response(TribblerError::Unknown(e.to_string()));
+                }
+                csrf
+            }
+            (None, _) => "".to_string(),
+        };
+        build_resp(&SessionInfo {
+            user: user.unwrap_or_default(),
+            csrf,
+            sso: sso.0.is_some(),
+            err: "".to_string(),
+        })
+    }
+
+    /// The single sign-on provider users may sign in through, none when
+    /// single sign-on is off.
+    pub struct Sso(pub Option<Provider>);
+
+    // the session entries tying a single sign-on in progress to the browser
+    // that started it
+    const SESSION_SSO_STATE: &str = "sso_state";
+    const SESSION_SSO_NONCE: &str = "sso_nonce";
+
+    // how many numbered variants of the username an identity would like are
+    // tried before giving up on signing it up
+    const SSO_NAME_TRIES: usize = 20;
+
+    fn sso_off() -> HttpResponse {
+        HttpResponse::NotFound().body("single sign-on is off")
+    }
+
+    fn random_token() -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    }
+
+    /// sends a browser to the single sign-on provider to sign in
+    #[utoipa::path(
+        tag = "sessions",
+        responses(
+            (status = 302, description = "off to the single sign-on provider"),
+            (status = 404, description = "single sign-on is off"),
+        )
+    )]
+    #[get("oidc/login")]
+    pub async fn oidc_login(sso: web::Data<Sso>, session: Session) -> HttpResponse {
+        let provider = match &sso.0 {
+            Some(provider) => provider,
+            None => return sso_off(),
+        };
+        let state = random_token();
+        let nonce = random_token();
+        let remembered = session
+            .insert(SESSION_SSO_STATE, &state)
+            .and_then(|_| session.insert(SESSION_SSO_NONCE, &nonce));
+        if let Err(e) = remembered {
+            return err_response(TribblerError::Unknown(e.to_string()));
+        }
+        match provider.authorize_url(&state, &nonce) {
+            Ok(url) => HttpResponse::Found()
+                .insert_header((header::LOCATION, url))
+                .finish(),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// signs in the browser the single sign-on provider sent back, as the user
+    /// its identity is linked to; an identity seen for the first time gets a
+    /// user of its own
+    #[utoipa::path(
+        tag = "sessions",
+        params(SsoCallback),
+        responses(
+            (status = 302, description = "signed in, back to the web UI"),
+            (status = 401, description = "single sign-on failed"),
+            (status = 404, description = "single sign-on is off"),
+        )
+    )]
+    #[get("oidc/callback")]
+    pub async fn oidc_callback(
+        data: web::Data<Srv>,
+        auth: web::Data<Creds>,
+        sso: web::Data<Sso>,
+        session: Session,
+        query: web::Query<SsoCallback>,
+    ) -> HttpResponse {
+        let provider = match &sso.0 {
+            Some(provider) => provider,
+            None => return sso_off(),
+        };
+        let state = session.get::<String>(SESSION_SSO_STATE).ok().flatten();
+        let nonce = session.get::<String>(SESSION_SSO_NONCE).ok().flatten();
+        session.remove(SESSION_SSO_STATE);
+        session.remove(SESSION_SSO_NONCE);
+        if let Some(e) = &query.error {
+            return err_response(TribblerError::SsoFailed(e.to_string()));
+        }
+        // a callback the browser did not start could sign it in as someone else
+        let (code, nonce) = match (&query.code, &query.state, state, nonce) {
+            (Some(code), Some(given), Some(state), Some(nonce)) if *given == state => (code, nonce),
+            _ => {
+                let e = TribblerError::SsoFailed("unexpected callback".to_string());
+                return err_response(e);
+            }
+        };
+        let identity = match provider.identity(code, &nonce).await {
+            Ok(identity) => identity,
+            Err(e) => return err_response(e),
+        };
+        let user = match auth.linked_user(&identity.subject).await {
+            Ok(Some(user)) => user,
+            Ok(None) => match sign_up_identity(&data, &auth, &identity).await {
+                Ok(user) => user,
+                Err(e) => return err_response(e),
+            },
+            Err(e) => return err_response(e),
+        };
+        if let Err(e) = sign_in(&session, &user) {
+            return err_response(TribblerError::Unknown(e.to_string()));
+        }
+        HttpResponse::Found()
+            .insert_header((header::LOCATION, "/"))
+            .finish()
+    }
+
+    // signs up a user for an identity signing in for the first time, under the
+    // username it would like or, when that is taken, under the first free one
+    // with a number appended, and links the two
+    async fn sign_up_identity(
+        data: &web::Data<Srv>,
+        auth: &web::Data<Creds>,
+        identity: &Identity,
+    ) -> Result<String, TribblerError> {
+        for n in 1..=SSO_NAME_TRIES {
+            let name = if n == 1 {
+                identity.username.to_string()
+            } else {
+                let suffix = n.to_string();
+                let stem: String = identity
+                    .username
+                    .chars()
+                    .take(MAX_USERNAME_LEN - suffix.len())
+                    .collect();
+                stem + &suffix
+            };
+            match data.sign_up(&name).await {
+                Ok(_) => {
+                    auth.link_identity(&identity.subject, &name).await?;
+                    return Ok(name);
+                }
+                Err(TribblerError::UsernameTaken(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(TribblerError::UsernameTaken(identity.username.to_string()))
+    }
+
+    /// lists all the users registered
+    #[utoipa::path(
+        tag = "users",
+        responses(
+            (status = 200, description = "some of the users, with their follow counts and avatars", body = UserList, content_type = "text/plain"),
+        )
+    )]
+    #[get("list-users")]
+    pub async fn list_users(data: web::Data<Srv>) -> impl Responder {
+        match data.list_users().await {
+            Ok(v) => build_resp(&user_listing(&data, v).await),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// gets the current logical clock of the service
+    #[utoipa::path(
+        tag = "tribs",
+        responses(
+            (status = 200, description = "the current logical clock", body = Clock, content_type = "text/plain"),
+        )
+    )]
+    #[get("clock")]
+    pub async fn clock(data: web::Data<Srv>) -> impl Responder {
+        match data.clock().await {
+            Ok(n) => build_resp(&Clock {
+                n,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists all the tribs for a particular user
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the recent tribs of the user, oldest first", body = TribList, content_type = "text/plain"),
+            (status = 304, description = "unchanged since the listing tagged with the `If-None-Match` of a GET"),
+            (status = 404, description = "the user does not exist"),
+        ),
+        params(
+            Page,
+            ("user" = Option<String>, Query, description = "the user name, when fetched with a GET"),
+        )
+    )]
+    #[route("list-tribs", method = "GET", method = "POST")]
+    pub async fn list_tribs(
+        data: web::Data<Srv>,
+        limits: web::Data<Limits>,
+        page: web::Query<Page>,
+        req: HttpRequest,
+        arg: Arg,
+    ) -> impl Responder {
+        let limit = page.limit.unwrap_or(limits.max_trib_fetch);
+        let listed = match (page.before, page.limit) {
+            (None, None) => data.tribs(&arg.0).await,
+            (before, _) => {
+                data.tribs_page(&arg.0, before.unwrap_or(u64::MAX), limit)
+                    .await
+            }
+        };
+        match listed {
+            Ok(v) => {
+                let ul = TribList {
+                    likes: like_counts(&data, &v).await,
+                    next_cursor: next_cursor(&v, limit.min(limits.max_trib_fetch)),
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                timeline_resp(&req, &ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the home page for a particular user
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the home timeline of the user, oldest first", body = TribList, content_type = "text/plain"),
+            (status = 304, description = "unchanged since the listing tagged with the `If-None-Match` of a GET"),
+            (status = 404, description = "the user does not exist"),
+        ),
+        params(
+            Page,
+            ("user" = Option<String>, Query, description = "the user name, when fetched with a GET"),
+        )
+    )]
+    #[route("list-home", method = "GET", method = "POST")]
+    pub async fn list_home(
+        data: web::Data<Srv>,
+        limits: web::Data<Limits>,
+        page: web::Query<Page>,
+        req: HttpRequest,
+        arg: Arg,
+    ) -> impl Responder {
+        let limit = page.limit.unwrap_or(limits.max_trib_fetch);
+        let listed = match (page.before, page.limit) {
+            (None, None) => data.home(&arg.0).await,
+            (before, _) => {
+                data.home_page(&arg.0, before.unwrap_or(u64::MAX), limit)
+                    .await
+            }
+        };
+        match listed {
+            Ok(v) => {
+                let ul = TribList {
+                    likes: like_counts(&data, &v).await,
+                    next_cursor: next_cursor(&v, limit.min(limits.max_trib_fetch)),
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                timeline_resp(&req, &ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    // answers with a listing of tribs tagged with its version, or with 304 Not
+    // Modified when a GET names the version the client has already
+    fn timeline_resp(req: &HttpRequest, list: &TribList) -> HttpResponse {
+        let etag = EntityTag::new_weak(timeline_version(list));
+        let unchanged = match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|given| given.weak_eq(&etag)),
+            None => false,
+        };
+        if unchanged && req.method() == Method::GET {
+            return HttpResponse::NotModified()
+                .insert_header(header::ETag(etag))
+                .finish();
+        }
+        let mut resp = build_resp(list);
+        if let Ok(value) = etag.to_string().parse() {
+            resp.headers_mut().insert(header::ETAG, value);
+        }
+        resp
+    }
+
+    // a version of a listing, which changes along with any trib in it or the
+    // likes of one, without telling anything about them
+    fn timeline_version(list: &TribList) -> String {
+        let mut hasher = DefaultHasher::new();
+        for (trib, n) in list.tribs.iter().zip(&list.likes) {
+            (&trib.user, trib.clock, n).hash(&mut hasher);
+        }
+        list.next_cursor.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    // the cursor to the page after one of `tribs`, oldest first, which may
+    // not be the last page when it came out full, holding `limit` tribs
+    fn next_cursor(tribs: &[Arc<Trib>], limit: usize) -> Option<u64> {
+        if tribs.len() < limit {
+            return None;
+        }
+        tribs.first().map(|t| t.clock)
+    }
+
+    /// determines whether a user is following another user or not
+    #[utoipa::path(
+        tag = "follows",
+        request_body(content = WhoWhom, content_type = "application/json"),
+        responses(
+            (status = 200, description = "whether who follows whom", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("is-following")]
+    pub async fn is_following(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return bad_request(e),
+        };
+        match data.is_following(&t.who, &t.whom).await {
+            Ok(v) => {
+                let ul = Bool {
+                    v,
                     err: "".to_string(),
                 };
                 build_resp(&ul)
             }
-            Err(e) => err_response(e)
+            Err(e) => err_response(e),
         }
     }
 
     /// makes a user follow another user
+    #[utoipa::path(
+        tag = "follows",
+        request_body(content = WhoWhom, content_type = "application/json"),
+        responses(
+            (status = 200, description = "followed", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 409, description = "who follows whom already"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
     #[post("follow")]
-    pub async fn follow(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
+    pub async fn follow(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return bad_request(e),
+        };
+        if !signed_in_as(&session, &t.who) {
+            return err_response(TribblerError::NotSignedIn(t.who));
+        }
         match data.follow(&t.who, &t.whom).await {
             Ok(_) => {
                 let ul = Bool {
@@ -549,14 +2227,28 @@ mod api {
     }
 
     /// makes a user unfollow another user
+    #[utoipa::path(
+        tag = "follows",
+        request_body(content = WhoWhom, content_type = "application/json"),
+        responses(
+            (status = 200, description = "unfollowed", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 409, description = "who does not follow whom"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
     #[post("unfollow")]
-    pub async fn unfollow(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        let raw = s.keys().next().unwrap();
-        let t = serde_json::from_str::<WhoWhom>(raw).unwrap();
+    pub async fn unfollow(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        let t = match serde_json::from_str::<WhoWhom>(raw) {
+            Ok(t) => t,
+            Err(e) => return bad_request(e),
+        };
+        if !signed_in_as(&session, &t.who) {
+            return err_response(TribblerError::NotSignedIn(t.who));
+        }
         match data.unfollow(&t.who, &t.whom).await {
             Ok(_) => {
                 let ul = Bool {
@@ -570,17 +2262,50 @@ mod api {
     }
 
     /// gets the list of users following a particular user
+    #[utoipa::path(
+        tag = "follows",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the users the user follows", body = UserList, content_type = "text/plain"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
     #[post("following")]
-    pub async fn following(
-        data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
-    ) -> impl Responder {
-        let s = form.0;
-        match data.following(s.keys().next().unwrap()).await {
+    pub async fn following(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        match data.following(&arg.0).await {
+            Ok(v) => {
+                let ul = UserList {
+                    users: v,
+                    err: "".to_string(),
+                    following: vec![],
+                    followers: vec![],
+                    avatars: vec![],
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the users who follow a user and whom the user follows back
+    #[utoipa::path(
+        tag = "follows",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the users who follow the user back, sorted", body = UserList, content_type = "text/plain"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("mutuals")]
+    pub async fn mutuals(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        match data.mutuals(&arg.0).await {
             Ok(v) => {
                 let ul = UserList {
                     users: v,
                     err: "".to_string(),
+                    following: vec![],
+                    followers: vec![],
+                    avatars: vec![],
                 };
                 build_resp(&ul)
             }
@@ -588,69 +2313,1214 @@ mod api {
         }
     }
 
+    /// tells when a user last acted
+    #[utoipa::path(
+        tag = "users",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "when the user last acted", body = LastActive, content_type = "text/plain"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("last-active")]
+    pub async fn last_active(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        match data.last_active(&arg.0).await {
+            Ok(at) => build_resp(&LastActive {
+                at,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
     /// adds a post for a particular user
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = Post, content_type = "application/json"),
+        responses(
+            (status = 200, description = "whether the trib was posted, and why not", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+        )
+    )]
     #[post("post")]
     pub async fn post(
         data: web::Data<Srv>,
-        form: web::Form<HashMap<String, String>>,
+        session: Session,
+        posts: web::Data<Posts>,
+        arg: Arg,
     ) -> impl Responder {
-        let s = form.0;
-        let raw = s.keys().next().unwrap();
+        let raw = &arg.0;
         match serde_json::from_str::<Post>(raw) {
+            Ok(p) if !signed_in_as(&session, &p.who) => {
+                err_response(TribblerError::NotSignedIn(p.who))
+            }
             Ok(p) => {
-                let x = match data.post(&p.who, &p.message, p.clock).await {
-                    Ok(_) => Bool {
-                        v: true,
-                        err: "".to_string(),
+                match data
+                    .post_with_attachments(&p.who, &p.message, p.clock, &p.attachments)
+                    .await
+                {
+                    Ok(_) => {
+                        posts.announce(&p.who);
+                        build_resp(&Bool {
+                            v: true,
+                            err: "".to_string(),
+                        })
+                    }
+                    // the legacy contract answers with the error in `err`,
+                    // its code in the header as for any other failed call
+                    Err(e) => {
+                        let mut resp = build_resp(&Bool {
+                            v: false,
+                            err: e.to_string(),
+                        });
+                        if let Ok(code) = header::HeaderValue::from_str(e.code()) {
+                            resp.headers_mut()
+                                .insert(header::HeaderName::from_static(ERROR_CODE_HEADER), code);
+                        }
+                        resp
+                    }
+                }
+            }
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// Tells the live timelines who just posted through this front-end.
+    pub struct Posts {
+        announcements: broadcast::Sender<String>,
+        // turns true once the server shuts down, which ends every live feed
+        closing: watch::Sender<bool>,
+    }
+
+    impl Posts {
+        pub fn new() -> Posts {
+            Posts {
+                // a live timeline which falls this far behind catches up
+                // from the home timeline anyway
+                announcements: broadcast::channel(LIVE_BACKLOG).0,
+                closing: watch::channel(false).0,
+            }
+        }
+
+        fn announce(&self, who: &str) {
+            // nobody may be listening
+            let _ = self.announcements.send(who.to_string());
+        }
+
+        /// Ends the live feeds, whose connections would otherwise hold up a
+        /// graceful shutdown for as long as the clients stay.
+        pub fn close(&self) {
+            self.closing.send_replace(true);
+        }
+    }
+
+    // how many posts a live timeline can fall behind before it skips some
+    // announcements
+    const LIVE_BACKLOG: usize = 256;
+
+    // how many events a home stream buffers for a slow reader
+    const STREAM_BACKLOG: usize = 16;
+
+    // Follows the home timeline of a user as the announced posts land on it.
+    // Both the websocket and the event stream feeds read from it.
+    struct HomeFeed {
+        data: web::Data<Srv>,
+        user: String,
+        announcements: broadcast::Receiver<String>,
+        closing: watch::Receiver<bool>,
+        seen: HashSet<(String, u64)>,
+    }
+
+    impl HomeFeed {
+        async fn start(
+            data: web::Data<Srv>,
+            posts: &Posts,
+            user: String,
+        ) -> Result<HomeFeed, TribblerError> {
+            // subscribe first so that nothing posted while reading the home
+            // timeline goes unannounced
+            let announcements = posts.announcements.subscribe();
+            let closing = posts.closing.subscribe();
+            let home = data.home(&user).await?;
+            let seen = home.iter().map(|t| (t.user.to_string(), t.clock)).collect();
+            Ok(HomeFeed {
+                data,
+                user,
+                announcements,
+                closing,
+                seen,
+            })
+        }
+
+        // waits until the home timeline gains tribs and returns them, or None
+        // once the feed can't go on or the server shuts down
+        async fn next(&mut self) -> Option<Vec<Arc<Trib>>> {
+            loop {
+                let announced = tokio::select! {
+                    announced = self.announcements.recv() => announced,
+                    _ = self.closing.wait_for(|closing| *closing) => return None,
+                };
+                match announced {
+                    Ok(who) => {
+                        let relevant = who == self.user
+                            || self
+                                .data
+                                .is_following(&self.user, &who)
+                                .await
+                                .unwrap_or(false);
+                        if !relevant {
+                            continue;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return None,
+                }
+
+                let home = self.data.home(&self.user).await.ok()?;
+                let fresh: Vec<Arc<Trib>> = home
+                    .iter()
+                    .filter(|t| !self.seen.contains(&(t.user.to_string(), t.clock)))
+                    .cloned()
+                    .collect();
+                self.seen = home.iter().map(|t| (t.user.to_string(), t.clock)).collect();
+                if !fresh.is_empty() {
+                    return Some(fresh);
+                }
+            }
+        }
+    }
+
+    /// pushes the tribs the home timeline of a user gains, as they are posted,
+    /// over a websocket, one JSON trib per message
+    #[get("/ws")]
+    pub async fn live(
+        req: HttpRequest,
+        body: web::Payload,
+        data: web::Data<Srv>,
+        posts: web::Data<Posts>,
+        session: Session,
+        query: web::Query<Live>,
+    ) -> actix_web::Result<HttpResponse> {
+        let user = query.into_inner().user;
+        if !signed_in_as(&session, &user) {
+            return Ok(not_yours(&user));
+        }
+        let mut feed = match HomeFeed::start(data, &posts, user).await {
+            Ok(feed) => feed,
+            Err(e) => return Ok(err_response(e)),
+        };
+        let (resp, mut session, mut msgs) = actix_ws::handle(&req, body)?;
+
+        actix_web::rt::spawn(async move {
+            loop {
+                let fresh = tokio::select! {
+                    fresh = feed.next() => match fresh {
+                        Some(fresh) => fresh,
+                        None => break,
                     },
-                    Err(e) => Bool {
-                        v: false,
-                        err: e.to_string(),
+                    msg = msgs.recv() => match msg {
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
                     },
                 };
-                build_resp(&x)
+
+                let mut closed = false;
+                for trib in fresh.iter() {
+                    let text = serde_json::to_string(&trib).unwrap();
+                    if session.text(text).await.is_err() {
+                        closed = true;
+                        break;
+                    }
+                }
+                if closed {
+                    break;
+                }
             }
-            Err(e) => err_response(Box::new(e)),
+            let _ = session.close(None).await;
+        });
+        Ok(resp)
+    }
+
+    /// streams the tribs the home timeline of a user gains as server-sent
+    /// events, one JSON trib per event, for clients without websockets
+    #[utoipa::path(
+        tag = "tribs",
+        params(Live),
+        responses(
+            (status = 200, description = "one `data:` event per trib, as JSON", body = String, content_type = "text/event-stream"),
+            (status = 403, description = "the browser is not signed in as the user"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[get("stream/home")]
+    pub async fn stream_home(
+        data: web::Data<Srv>,
+        posts: web::Data<Posts>,
+        session: Session,
+        query: web::Query<Live>,
+    ) -> HttpResponse {
+        let user = query.into_inner().user;
+        if !signed_in_as(&session, &user) {
+            return not_yours(&user);
         }
+        let mut feed = match HomeFeed::start(data, &posts, user).await {
+            Ok(feed) => feed,
+            Err(e) => return err_response(e),
+        };
+        let (tx, rx) = mpsc::channel::<Result<web::Bytes, Infallible>>(STREAM_BACKLOG);
+
+        actix_web::rt::spawn(async move {
+            // the client going away drops the receiver, which ends the feed
+            while let Some(fresh) = feed.next().await {
+                for trib in fresh.iter() {
+                    let event = format!("data: {}\n\n", serde_json::to_string(&trib).unwrap());
+                    if tx.send(Ok(web::Bytes::from(event))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .streaming(tokio_stream::wrappers::ReceiverStream::new(rx))
     }
 
+    /// makes a user like a trib
+    #[utoipa::path(
+        tag = "likes",
+        request_body(content = Like, content_type = "application/json"),
+        responses(
+            (status = 200, description = "liked", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user or the trib does not exist"),
+            (status = 409, description = "the user likes the trib already"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("like")]
+    pub async fn like(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<Like>(raw) {
+            Ok(l) if !signed_in_as(&session, &l.who) => {
+                err_response(TribblerError::NotSignedIn(l.who))
+            }
+            Ok(l) => match data.like(&l.who, &l.author, l.clock).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// makes a user take back a like
+    #[utoipa::path(
+        tag = "likes",
+        request_body(content = Like, content_type = "application/json"),
+        responses(
+            (status = 200, description = "like taken back", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user or the trib does not exist"),
+            (status = 409, description = "the user does not like the trib"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("unlike")]
+    pub async fn unlike(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<Like>(raw) {
+            Ok(l) if !signed_in_as(&session, &l.who) => {
+                err_response(TribblerError::NotSignedIn(l.who))
+            }
+            Ok(l) => match data.unlike(&l.who, &l.author, l.clock).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// lists the users who like a trib
+    #[utoipa::path(
+        tag = "likes",
+        request_body(content = TribRef, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the users who like the trib", body = UserList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 404, description = "the user or the trib does not exist"),
+        )
+    )]
+    #[post("likes")]
+    pub async fn likes(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<TribRef>(raw) {
+            Ok(t) => match data.likes(&t.author, t.clock).await {
+                Ok(v) => build_resp(&UserList {
+                    users: v,
+                    err: "".to_string(),
+                    following: vec![],
+                    followers: vec![],
+                    avatars: vec![],
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// lists the replies to a trib
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = TribRef, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the replies to the trib, oldest first", body = TribList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 404, description = "the user or the trib does not exist"),
+        )
+    )]
+    #[post("replies")]
+    pub async fn replies(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<TribRef>(raw) {
+            Ok(t) => match data.replies(&t.author, t.clock).await {
+                Ok(v) => build_resp(&TribList {
+                    likes: like_counts(&data, &v).await,
+                    next_cursor: None,
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// lists the tribs tagged with a hashtag
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = String, content_type = "application/json", description = "the hashtag, with or without the leading #, as a JSON string"),
+        responses(
+            (status = 200, description = "the recent tribs using the hashtag, oldest first", body = TribList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+        )
+    )]
+    #[post("tag")]
+    pub async fn tag(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        match data.tribs_by_tag(&arg.0).await {
+            Ok(v) => {
+                let ul = TribList {
+                    likes: like_counts(&data, &v).await,
+                    next_cursor: None,
+                    tribs: v,
+                    err: "".to_string(),
+                };
+                build_resp(&ul)
+            }
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// replaces the profile of a user
+    #[utoipa::path(
+        tag = "profiles",
+        request_body(content = SetProfile, content_type = "application/json"),
+        responses(
+            (status = 200, description = "profile replaced", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("set-profile")]
+    pub async fn set_profile(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<SetProfile>(raw) {
+            Ok(p) if !signed_in_as(&session, &p.who) => {
+                err_response(TribblerError::NotSignedIn(p.who))
+            }
+            Ok(p) => match data.set_profile(&p.who, &p.profile).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// shows the profile of a user
+    #[utoipa::path(
+        tag = "profiles",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the profile of the user", body = ProfileResp, content_type = "text/plain"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("get-profile")]
+    pub async fn get_profile(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        match data.get_profile(&arg.0).await {
+            Ok(profile) => build_resp(&ProfileResp {
+                profile,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// how many hashtags the trending route lists
+    const TRENDING_LIMIT: usize = 10;
+
+    /// lists the hashtags used the most lately
+    #[utoipa::path(
+        tag = "tribs",
+        responses(
+            (status = 200, description = "the most used hashtags of late, most used first", body = TrendList, content_type = "text/plain"),
+        )
+    )]
+    #[get("trending")]
+    pub async fn trending(data: web::Data<Srv>) -> impl Responder {
+        match data.trending(TRENDING_LIMIT).await {
+            Ok(trends) => build_resp(&TrendList {
+                trends,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// lists the notifications of a user
+    #[utoipa::path(
+        tag = "notifications",
+        request_body(content = Since, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the notifications of the user after since, oldest first", body = NotificationList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 403, description = "the browser is not signed in as the user"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("notifications")]
+    pub async fn notifications(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<Since>(raw) {
+            Ok(q) if !signed_in_as(&session, &q.user) => not_yours(&q.user),
+            Ok(q) => match data.notifications(&q.user, q.since).await {
+                Ok(notifications) => build_resp(&NotificationList {
+                    notifications,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// marks the notifications of a user read
+    #[utoipa::path(
+        tag = "notifications",
+        request_body(content = Upto, content_type = "application/json"),
+        responses(
+            (status = 200, description = "marked read", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("mark-read")]
+    pub async fn mark_read(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<Upto>(raw) {
+            Ok(q) if !signed_in_as(&session, &q.user) => {
+                err_response(TribblerError::NotSignedIn(q.user))
+            }
+            Ok(q) => match data.mark_read(&q.user, q.upto).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// counts the unread notifications of a user
+    #[utoipa::path(
+        tag = "notifications",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "how many notifications of the user are unread", body = Count, content_type = "text/plain"),
+            (status = 403, description = "the browser is not signed in as the user"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("unread-count")]
+    pub async fn unread_count(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        if !signed_in_as(&session, &arg.0) {
+            return not_yours(&arg.0);
+        }
+        match data.unread_count(&arg.0).await {
+            Ok(n) => build_resp(&Count {
+                n,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// creates a list for a user to group others in
+    #[utoipa::path(
+        tag = "lists",
+        request_body(content = ListName, content_type = "application/json"),
+        responses(
+            (status = 200, description = "list created", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 409, description = "the user has a list of that name already"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("create-list")]
+    pub async fn create_list(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<ListName>(raw) {
+            Ok(l) if !signed_in_as(&session, &l.who) => {
+                err_response(TribblerError::NotSignedIn(l.who))
+            }
+            Ok(l) => match data.create_list(&l.who, &l.name).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// adds a user to a list
+    #[utoipa::path(
+        tag = "lists",
+        request_body(content = ListMember, content_type = "application/json"),
+        responses(
+            (status = 200, description = "user added", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "a user or the list does not exist"),
+            (status = 409, description = "whom is on the list already"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("add-to-list")]
+    pub async fn add_to_list(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<ListMember>(raw) {
+            Ok(l) if !signed_in_as(&session, &l.who) => {
+                err_response(TribblerError::NotSignedIn(l.who))
+            }
+            Ok(l) => match data.add_to_list(&l.who, &l.name, &l.whom).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// lists the tribs of the users on a list
+    #[utoipa::path(
+        tag = "lists",
+        request_body(content = ListName, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the recent tribs of the users on the list, oldest first", body = TribList, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 404, description = "the user or the list does not exist"),
+        )
+    )]
+    #[post("list-timeline")]
+    pub async fn list_timeline(data: web::Data<Srv>, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<ListName>(raw) {
+            Ok(l) => match data.list_timeline(&l.who, &l.name).await {
+                Ok(v) => build_resp(&TribList {
+                    likes: like_counts(&data, &v).await,
+                    next_cursor: None,
+                    tribs: v,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// saves a draft for a user
+    #[utoipa::path(
+        tag = "drafts",
+        request_body(content = SaveDraft, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the id of the saved draft", body = Clock, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("save-draft")]
+    pub async fn save_draft(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<SaveDraft>(raw) {
+            Ok(d) if !signed_in_as(&session, &d.who) => {
+                err_response(TribblerError::NotSignedIn(d.who))
+            }
+            Ok(d) => match data.save_draft(&d.who, &d.message).await {
+                Ok(n) => build_resp(&Clock {
+                    n,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// lists the drafts of a user
+    #[utoipa::path(
+        tag = "drafts",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "the drafts of the user", body = DraftList, content_type = "text/plain"),
+            (status = 403, description = "the browser is not signed in as the user"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("list-drafts")]
+    pub async fn list_drafts(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        if !signed_in_as(&session, &arg.0) {
+            return not_yours(&arg.0);
+        }
+        match data.list_drafts(&arg.0).await {
+            Ok(drafts) => build_resp(&DraftList {
+                drafts,
+                err: "".to_string(),
+            }),
+            Err(e) => err_response(e),
+        }
+    }
+
+    /// deletes a draft of a user
+    #[utoipa::path(
+        tag = "drafts",
+        request_body(content = DraftId, content_type = "application/json"),
+        responses(
+            (status = 200, description = "draft deleted", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user or the draft does not exist"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("delete-draft")]
+    pub async fn delete_draft(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<DraftId>(raw) {
+            Ok(d) if !signed_in_as(&session, &d.who) => {
+                err_response(TribblerError::NotSignedIn(d.who))
+            }
+            Ok(d) => match data.delete_draft(&d.who, d.id).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// schedules a post for later
+    #[utoipa::path(
+        tag = "tribs",
+        request_body(content = SchedulePost, content_type = "application/json"),
+        responses(
+            (status = 200, description = "post scheduled", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the browser is not signed in as the user acted for"),
+            (status = 404, description = "the user does not exist"),
+            (status = 503, description = "the cluster does not take writes, or is degraded"),
+        )
+    )]
+    #[post("schedule-post")]
+    pub async fn schedule_post(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<SchedulePost>(raw) {
+            Ok(p) if !signed_in_as(&session, &p.who) => {
+                err_response(TribblerError::NotSignedIn(p.who))
+            }
+            Ok(p) => match data.schedule_post(&p.who, &p.message, p.publish_at).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// exports everything a user put into the service as one JSON document,
+    /// streamed a trib and a like at a time
+    #[utoipa::path(
+        tag = "users",
+        request_body(content = String, content_type = "application/json", description = "the user name, as a JSON string"),
+        responses(
+            (status = 200, description = "everything the user put into the service", body = UserExport, content_type = "application/json"),
+            (status = 403, description = "the browser is not signed in as the user"),
+            (status = 404, description = "the user does not exist"),
+        )
+    )]
+    #[post("export-user")]
+    pub async fn export_user(data: web::Data<Srv>, session: Session, arg: Arg) -> impl Responder {
+        let user = &arg.0;
+        if !signed_in_as(&session, user) {
+            return not_yours(user);
+        }
+        let export = match data.export_user(user).await {
+            Ok(export) => export,
+            Err(e) => return err_response(e),
+        };
+        // what comes before the tribs is serialized up front, so that failing
+        // to is still answered with an error status rather than a cut short
+        // document
+        let head = match (
+            json_piece("{\"user\":", &export.user),
+            json_piece(",\"profile\":", &export.profile),
+        ) {
+            (Ok(user), Ok(profile)) => [user, profile],
+            (Err(e), _) | (_, Err(e)) => return err_response(e),
+        };
+        let body = head
+            .into_iter()
+            .map(Ok)
+            .chain(json_list("tribs", export.tribs))
+            .chain(iter::once(export.following).map(|f| json_piece(",\"following\":", &f)))
+            .chain(json_list("likes", export.likes))
+            .chain(iter::once(Ok(web::Bytes::from_static(b"}"))))
+            .map(|piece| piece.map_err(actix_web::error::ErrorInternalServerError));
+        HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.json\"", user),
+            ))
+            .streaming(tokio_stream::iter(body))
+    }
+
+    // a value serialized as JSON, after `prefix`
+    fn json_piece(prefix: &str, value: &impl Serialize) -> TribResult<web::Bytes> {
+        let mut piece = prefix.as_bytes().to_vec();
+        serde_json::to_writer(&mut piece, value)?;
+        Ok(piece.into())
+    }
+
+    // the field `name` of a JSON object holding `items` as an array, each
+    // item serialized only once the piece before it is taken
+    fn json_list<T: Serialize>(
+        name: &str,
+        items: Vec<T>,
+    ) -> impl Iterator<Item = TribResult<web::Bytes>> {
+        let open = format!(",\"{}\":[", name);
+        iter::once(Ok(web::Bytes::from(open)))
+            .chain(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, item)| json_piece(if i == 0 { "" } else { "," }, &item)),
+            )
+            .chain(iter::once(Ok(web::Bytes::from_static(b"]"))))
+    }
+
+    /// deletes a trib on behalf of a moderator
+    #[utoipa::path(
+        tag = "admin",
+        request_body(content = AdminTrib, content_type = "application/json"),
+        responses(
+            (status = 200, description = "trib deleted", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the admin token is missing or wrong"),
+            (status = 404, description = "the user or the trib does not exist"),
+        )
+    )]
+    #[post("admin/delete-trib")]
+    pub async fn admin_delete_trib(
+        data: web::Data<Srv>,
+        admin: web::Data<AdminToken>,
+        arg: Arg,
+    ) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<AdminTrib>(raw) {
+            Ok(a) if !admin.allows(&a.token) => unauthorized(),
+            Ok(a) => match data.admin_delete_trib(&a.author, a.clock).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// bans a user on behalf of a moderator
+    #[utoipa::path(
+        tag = "admin",
+        request_body(content = AdminUser, content_type = "application/json"),
+        responses(
+            (status = 200, description = "user banned", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the admin token is missing or wrong"),
+            (status = 404, description = "the user does not exist"),
+            (status = 409, description = "the user is banned already"),
+        )
+    )]
+    #[post("admin/ban")]
+    pub async fn admin_ban_user(
+        data: web::Data<Srv>,
+        admin: web::Data<AdminToken>,
+        arg: Arg,
+    ) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<AdminUser>(raw) {
+            Ok(a) if !admin.allows(&a.token) => unauthorized(),
+            Ok(a) => match data.admin_ban_user(&a.user).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// takes back a ban on behalf of a moderator
+    #[utoipa::path(
+        tag = "admin",
+        request_body(content = AdminUser, content_type = "application/json"),
+        responses(
+            (status = 200, description = "ban taken back", body = Bool, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed or invalid"),
+            (status = 401, description = "the admin token is missing or wrong"),
+            (status = 404, description = "the user does not exist"),
+            (status = 409, description = "the user is not banned"),
+        )
+    )]
+    #[post("admin/unban")]
+    pub async fn admin_unban(
+        data: web::Data<Srv>,
+        admin: web::Data<AdminToken>,
+        arg: Arg,
+    ) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<AdminUser>(raw) {
+            Ok(a) if !admin.allows(&a.token) => unauthorized(),
+            Ok(a) => match data.admin_unban(&a.user).await {
+                Ok(_) => build_resp(&Bool {
+                    v: true,
+                    err: "".to_string(),
+                }),
+                Err(e) => err_response(e),
+            },
+            Err(e) => bad_request(e),
+        }
+    }
+
+    /// reports the keeper's view of the cluster and probes every backend on
+    /// behalf of an operator
+    #[utoipa::path(
+        tag = "admin",
+        request_body(content = AdminOnly, content_type = "application/json"),
+        responses(
+            (status = 200, description = "the cluster status", body = ClusterReport, content_type = "text/plain"),
+            (status = 400, description = "the argument is malformed"),
+            (status = 401, description = "the admin token is missing or wrong"),
+        )
+    )]
+    #[post("admin/cluster")]
+    pub async fn admin_cluster(
+        cluster: web::Data<Cluster>,
+        admin: web::Data<AdminToken>,
+        arg: Arg,
+    ) -> impl Responder {
+        let raw = &arg.0;
+        match serde_json::from_str::<AdminOnly>(raw) {
+            Ok(a) if !admin.allows(&a.token) => unauthorized(),
+            Ok(_) => {
+                let (backs, keepers) = cluster.members();
+                build_resp(&ClusterReport {
+                    err: "".to_string(),
+                    status: lab2::cluster_status(&backs, &keepers).await,
+                })
+            }
+            Err(e) => bad_request(e),
+        }
+    }
+
+    use lab::lab2::{self, ClusterStatus};
     use serde::{Deserialize, Serialize};
-    use tribbler::trib::Trib;
+    use tribbler::config::{Config, Limits};
+    use tribbler::err::{TribResult, TribblerError};
+    use tribbler::trib::{Attachment, Draft, Notification, Profile, Trend, Trib, UserExport};
+    use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct UserList {
         err: String,
         users: Vec<String>,
+        /// how many users each user in `users` follows, only in user listings
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        following: Vec<usize>,
+        /// how many followers each user in `users` has, only in user listings
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        followers: Vec<usize>,
+        /// the avatar of each user in `users`, only in user listings
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        avatars: Vec<String>,
     }
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct TribList {
         err: String,
         tribs: Vec<Arc<Trib>>,
+        /// the number of likes of each trib in `tribs`
+        likes: Vec<usize>,
+        /// what to pass as `before` to get the page of older tribs; null when
+        /// there are none
+        next_cursor: Option<u64>,
     }
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct Bool {
         err: String,
         v: bool,
     }
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct Clock {
         err: String,
         n: u64,
     }
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct LastActive {
+        err: String,
+        /// seconds since the unix epoch, null when the user never acted
+        at: Option<u64>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct SessionInfo {
+        err: String,
+        /// the user the browser signed in as, empty when signed out
+        user: String,
+        /// the CSRF token the calls of the signed in browser carry, empty
+        /// when signed out
+        csrf: String,
+        /// whether single sign-on is on
+        sso: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, IntoParams)]
+    pub struct Page {
+        /// only list tribs older than this, the `next_cursor` of the page
+        /// before; without `before` or `limit` the most recent tribs are listed
+        before: Option<u64>,
+        /// list at most this many tribs, and never more than the server fetches
+        limit: Option<usize>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, IntoParams)]
+    pub struct SsoCallback {
+        code: Option<String>,
+        state: Option<String>,
+        error: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct Credentials {
+        user: String,
+        password: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, IntoParams)]
+    pub struct Live {
+        user: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct Count {
+        err: String,
+        n: usize,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct WhoWhom {
         who: String,
         whom: String,
     }
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
     struct Post {
         who: String,
         message: String,
         clock: u64,
+        #[serde(default)]
+        attachments: Vec<Attachment>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct Like {
+        who: String,
+        author: String,
+        clock: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    #[schema(as = TribKey)]
+    struct TribRef {
+        author: String,
+        clock: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct SetProfile {
+        who: String,
+        #[serde(flatten)]
+        profile: Profile,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct ProfileResp {
+        err: String,
+        profile: Profile,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct TrendList {
+        err: String,
+        trends: Vec<Trend>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct Since {
+        user: String,
+        #[serde(default)]
+        since: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct Upto {
+        user: String,
+        upto: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct ListName {
+        who: String,
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct ListMember {
+        who: String,
+        name: String,
+        whom: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct SaveDraft {
+        who: String,
+        message: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct DraftId {
+        who: String,
+        id: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct SchedulePost {
+        who: String,
+        message: String,
+        publish_at: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct SignedIn {
+        err: String,
+        v: bool,
+        /// the CSRF token the calls of the browser have to carry
+        csrf: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct AdminTrib {
+        token: String,
+        author: String,
+        clock: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct AdminUser {
+        token: String,
+        user: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct AdminOnly {
+        token: String,
+    }
+
+    #[derive(Serialize, Debug, Clone, ToSchema)]
+    struct ClusterReport {
+        err: String,
+        #[serde(flatten)]
+        status: ClusterStatus,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct DraftList {
+        err: String,
+        drafts: Vec<Draft>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+    struct NotificationList {
+        err: String,
+        notifications: Vec<Notification>,
     }
 }
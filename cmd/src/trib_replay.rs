@@ -0,0 +1,66 @@
+//! function which serves back the RPCs recorded by `trib-front --record`,
+//! each recorded backend at the address it was recorded at, so a front-end
+//! pointed at them sees what the original cluster answered
+use std::collections::BTreeSet;
+
+use clap::Parser;
+use lab::lab1::record::{read_recording, ReplayStorage};
+use lab::lab1::serve_back;
+use log::{info, LevelFilter};
+use tokio::task::JoinSet;
+use tribbler::{
+    config::BackConfig,
+    err::{TribResult, TribblerError},
+};
+
+#[derive(Parser, Debug)]
+#[clap(name = "trib-replay")]
+struct Options {
+    /// the recording to serve back
+    recording: String,
+
+    /// a recorded backend to serve back, by the address it was recorded at;
+    /// repeat for more. All of them by default
+    #[clap(short, long = "back")]
+    backs: Vec<String>,
+
+    #[clap(short, long, default_value = "INFO")]
+    log_level: LevelFilter,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let options = Options::parse();
+    env_logger::builder()
+        .default_format()
+        .filter_level(options.log_level)
+        .init();
+    let exchanges = read_recording(&options.recording)?;
+    let recorded: BTreeSet<&str> = exchanges.iter().map(|e| e.back.as_str()).collect();
+    let backs: Vec<String> = match options.backs.is_empty() {
+        true => recorded.iter().map(|back| back.to_string()).collect(),
+        false => options.backs.clone(),
+    };
+    if let Some(back) = backs.iter().find(|back| !recorded.contains(back.as_str())) {
+        return Err(TribblerError::Unknown(format!(
+            "no calls to {} were recorded in {}",
+            back, options.recording
+        )));
+    }
+
+    let mut servers = JoinSet::new();
+    for back in backs {
+        let calls = exchanges.iter().filter(|e| e.back == back).count();
+        info!("serving back {} recorded calls to {}", calls, back);
+        servers.spawn(serve_back(BackConfig {
+            storage: Box::new(ReplayStorage::new(&exchanges, &back)),
+            addr: back,
+            ready: None,
+            shutdown: None,
+        }));
+    }
+    while let Some(served) = servers.join_next().await {
+        served.map_err(|e| TribblerError::Unknown(e.to_string()))??;
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use clap::Parser;
+use lab::lab2::bin_replicas;
+use std::process;
+use tribbler::{
+    config::{Config, DEFAULT_CONFIG_LOCATION},
+    err::TribResult,
+};
+
+// how many made up bins the data movement is estimated over
+const ESTIMATE_BINS: usize = 10_000;
+
+/// prints the backends every one of a sample of users is kept on, the way the
+/// bin clients of the config place them, and which bins move where when the
+/// backends given with --remove go down.
+#[derive(Parser, Debug)]
+#[clap(name = "trib-ring")]
+struct Options {
+    #[clap(short, long, default_value = DEFAULT_CONFIG_LOCATION)]
+    config: String,
+    /// the users to place. Without any, --sample made up ones are
+    users: Vec<String>,
+    /// how many made up users to place when none are given
+    #[clap(short, long, default_value = "20")]
+    sample: usize,
+    /// a backend to take out of the ring. Specify this flag multiple times to
+    /// take out more than one
+    #[clap(short, long)]
+    remove: Vec<String>,
+}
+
+fn main() -> TribResult<()> {
+    let args = Options::parse();
+    let cfg = Config::read(Some(&args.config))?;
+    let backs = &cfg.backs;
+    if backs.is_empty() {
+        eprintln!("no backends in {}", args.config);
+        process::exit(1)
+    }
+
+    let mut eligible = vec![true; backs.len()];
+    for back in args.remove.iter() {
+        match backs.iter().position(|b| b == back) {
+            Some(i) => eligible[i] = false,
+            None => {
+                eprintln!("{} is not a backend of {}", back, args.config);
+                process::exit(1)
+            }
+        }
+    }
+
+    let users = match args.users.is_empty() {
+        true => (0..args.sample).map(|i| format!("user{}", i)).collect(),
+        false => args.users.clone(),
+    };
+    let all = vec![true; backs.len()];
+    let names = |replicas: &[usize]| -> String {
+        let names: Vec<&str> = replicas.iter().map(|i| backs[*i].as_str()).collect();
+        names.join(", ")
+    };
+    println!(
+        "{} backends, {} replicas a bin, {} slots a backend",
+        backs.len(),
+        cfg.placement.replication_factor,
+        cfg.placement.virtual_nodes
+    );
+    let width = users.iter().map(|u| u.len()).max().unwrap_or(0);
+    for user in users.iter() {
+        let before = bin_replicas(user, backs, &all, cfg.placement);
+        if args.remove.is_empty() {
+            println!("{:width$}  {}", user, names(&before), width = width);
+            continue;
+        }
+        let after = bin_replicas(user, backs, &eligible, cfg.placement);
+        let mark = if before == after { " " } else { "*" };
+        println!(
+            "{} {:width$}  {}  ->  {}",
+            mark,
+            user,
+            names(&before),
+            names(&after),
+            width = width
+        );
+    }
+    if args.remove.is_empty() {
+        return Ok(());
+    }
+
+    // every bin which gets a replica it did not have is copied there
+    let mut moved = 0;
+    let mut copies = vec![0; backs.len()];
+    for i in 0..ESTIMATE_BINS {
+        let name = format!("bin{}", i);
+        let before = bin_replicas(&name, backs, &all, cfg.placement);
+        let after = bin_replicas(&name, backs, &eligible, cfg.placement);
+        if before != after {
+            moved += 1;
+        }
+        for back in after.iter().filter(|b| !before.contains(b)) {
+            copies[*back] += 1;
+        }
+    }
+    let share = |n: usize| 100.0 * n as f64 / ESTIMATE_BINS as f64;
+    println!();
+    println!(
+        "removing {}: {:.1}% of the bins change backends",
+        args.remove.join(", "),
+        share(moved)
+    );
+    if eligible.iter().all(|e| !e) {
+        println!("no backend is left to keep them");
+        return Ok(());
+    }
+    for (back, n) in backs.iter().zip(copies) {
+        if n > 0 {
+            println!("  {} takes a copy of {:.1}% of the bins", back, share(n));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use clap::Parser;
+use lab::lab1::new_client;
+use lab::lab2::{cluster_status, BackendReport};
+use tribbler::{addr, config::Config, err::TribResult, storage::Pattern};
+
+/// prints the state of a cluster in one table: whether each backend answers a
+/// probe from here and at which clock, whether the keeper finds it alive and
+/// has it in the write path, how far behind the cluster clock it was, and how
+/// many keys and lists it keeps. The backends have no RPC which counts their
+/// keys, so they are counted by listing them, which takes a while on a
+/// backend holding a lot
+#[derive(Parser, Debug)]
+#[clap(name = "trib-status")]
+struct Options {
+    /// the config of the cluster
+    config: String,
+    /// leave out the key counts
+    #[clap(long)]
+    no_count: bool,
+}
+
+#[tokio::main]
+async fn main() -> TribResult<()> {
+    let args = Options::parse();
+    let cfg = Config::read(Some(&args.config))?;
+    let status = cluster_status(&cfg.backs, &cfg.keepers).await;
+
+    match &status.keeper {
+        Some(keeper) => println!(
+            "keeper {}: view epoch {}, clock {}, clocks {}",
+            keeper,
+            status.epoch,
+            status.clock,
+            if status.consistent {
+                "converged"
+            } else {
+                "not converged yet"
+            }
+        ),
+        None => println!("no keeper answered; the keeper columns are blank"),
+    }
+    let width = cfg.backs.iter().map(|b| b.len()).max().unwrap_or(0).max(7);
+    println!(
+        "{:width$}  {:>5}  {:>5}  {:>5}  {:>8}  {:>6}  {:>5}  {:>9}  {:>5}  {:>7}  {:>6}",
+        "BACKEND",
+        "PROBE",
+        "ALIVE",
+        "WRITE",
+        "CLOCK",
+        "LAG",
+        "FAILS",
+        "HOLD-DOWN",
+        "SLOTS",
+        "KEYS",
+        "LISTS",
+        width = width
+    );
+    let keeper = status.keeper.is_some();
+    for back in status.backends.iter() {
+        let (keys, lists) = match (args.no_count, back.clock) {
+            (false, Some(_)) => match count(&back.addr).await {
+                Ok((keys, lists)) => (keys.to_string(), lists.to_string()),
+                Err(_) => ("?".to_string(), "?".to_string()),
+            },
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        println!(
+            "{:width$}  {:>5}  {:>5}  {:>5}  {:>8}  {:>6}  {:>5}  {:>9}  {:>5}  {:>7}  {:>6}",
+            back.addr,
+            if back.clock.is_some() { "up" } else { "down" },
+            keeper_says(keeper, back, |b| yes(b.alive)),
+            keeper_says(keeper, back, |b| yes(b.eligible)),
+            back.clock.map_or("-".to_string(), |c| c.to_string()),
+            keeper_says(keeper, back, |b| b.lag.to_string()),
+            keeper_says(keeper, back, |b| b.failures.to_string()),
+            keeper_says(keeper, back, |b| format!("{}ms", b.hold_down_ms)),
+            back.slots.len(),
+            keys,
+            lists,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+// a column of what the keeper makes of a backend, blank without a keeper
+fn keeper_says(
+    keeper: bool,
+    back: &BackendReport,
+    column: impl Fn(&BackendReport) -> String,
+) -> String {
+    match keeper {
+        true => column(back),
+        false => "".to_string(),
+    }
+}
+
+fn yes(b: bool) -> String {
+    match b {
+        true => "yes".to_string(),
+        false => "no".to_string(),
+    }
+}
+
+// counts the keys and lists a backend keeps, over every bin
+async fn count(back: &str) -> TribResult<(usize, usize)> {
+    let client = new_client(&addr::uri(back)).await?;
+    let keys = client.keys(&Pattern::default()).await?.0.len();
+    let lists = client.list_keys(&Pattern::default()).await?.0.len();
+    Ok((keys, lists))
+}
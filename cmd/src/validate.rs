@@ -0,0 +1,150 @@
+//! Checks on the arguments of API calls which the front-end makes itself, so
+//! that plainly invalid input is turned away without a round trip to the
+//! service, with the fields at fault named.
+use serde_json::Value;
+use std::fmt;
+use tribbler::trib::is_valid_username;
+
+// the fields of an argument which name a user
+const USER_FIELDS: &[&str] = &["user", "who", "whom", "author"];
+
+// the fields of an argument holding the text of a trib
+const MESSAGE_FIELDS: &[&str] = &["message"];
+
+/// What is wrong with a field of an argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// the field at fault, `body` for the body as a whole
+    pub field: String,
+    pub problem: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.problem)
+    }
+}
+
+fn field_error(field: &str, problem: String) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        problem,
+    }
+}
+
+/// Checks the body of a call, sent with `content_type`, the way the API takes
+/// it: a JSON body, or a form whose only key is the argument. The argument has
+/// to be UTF-8, its user fields valid usernames, and its messages no longer
+/// than `max_trib_len` bytes. What can't be read as an argument is left to the
+/// call to turn away.
+pub fn check_body(
+    content_type: Option<&str>,
+    body: &[u8],
+    max_trib_len: usize,
+) -> Result<(), Vec<FieldError>> {
+    let body = std::str::from_utf8(body)
+        .map_err(|_| vec![field_error("body", "not UTF-8".to_string())])?;
+    let json = content_type.is_some_and(|ct| ct.starts_with("application/json"));
+    let arg = match json {
+        true => body.to_string(),
+        false => match serde_urlencoded::from_str::<Vec<(String, String)>>(body) {
+            Ok(form) => match form.into_iter().next() {
+                Some((arg, _)) => arg,
+                None => return Ok(()),
+            },
+            Err(_) => return Ok(()),
+        },
+    };
+    check_arg(&arg, max_trib_len)
+}
+
+/// Checks the fields of an argument given as a JSON object; a bare user name
+/// is left to the call, as not every call takes one.
+pub fn check_arg(arg: &str, max_trib_len: usize) -> Result<(), Vec<FieldError>> {
+    let fields = match serde_json::from_str::<Value>(arg) {
+        Ok(Value::Object(fields)) => fields,
+        _ => return Ok(()),
+    };
+    let mut errors = vec![];
+    for (field, value) in fields.iter() {
+        if USER_FIELDS.contains(&field.as_str()) {
+            match value.as_str() {
+                Some(name) if is_valid_username(name) => {}
+                Some(name) => errors.push(field_error(
+                    field,
+                    format!("{:?} is not a valid username", name),
+                )),
+                None => errors.push(field_error(field, "not a string".to_string())),
+            }
+        }
+        if MESSAGE_FIELDS.contains(&field.as_str()) {
+            match value.as_str() {
+                Some(message) if message.len() <= max_trib_len => {}
+                Some(_) => errors.push(field_error(
+                    field,
+                    format!("longer than {} bytes", max_trib_len),
+                )),
+                None => errors.push(field_error(field, "not a string".to_string())),
+            }
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_arg, check_body, FieldError};
+
+    fn error(field: &str, problem: &str) -> FieldError {
+        FieldError {
+            field: field.to_string(),
+            problem: problem.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_check_arg() {
+        assert_eq!(Ok(()), check_arg(r#"{"who":"alice","whom":"bob"}"#, 140));
+        assert_eq!(Ok(()), check_arg(r#"{"who":"alice","message":"hi"}"#, 140));
+        // bare names, and what is no JSON, are the call's to judge
+        assert_eq!(Ok(()), check_arg("Alice", 140));
+        assert_eq!(Ok(()), check_arg(r#"{"who":"alice""#, 140));
+
+        assert_eq!(
+            Err(vec![
+                error("message", "longer than 3 bytes"),
+                error("who", "\"Alice\" is not a valid username"),
+            ]),
+            check_arg(r#"{"who":"Alice","message":"four"}"#, 3)
+        );
+        assert_eq!(
+            Err(vec![error("author", "not a string")]),
+            check_arg(r#"{"author":7,"clock":1}"#, 140)
+        );
+    }
+
+    #[test]
+    fn test_check_body() {
+        let json = Some("application/json");
+        let form = Some("application/x-www-form-urlencoded");
+        assert_eq!(Ok(()), check_body(json, br#"{"who":"alice"}"#, 140));
+        assert_eq!(Ok(()), check_body(json, br#""alice""#, 140));
+        assert_eq!(
+            Err(vec![error("body", "not UTF-8")]),
+            check_body(json, b"\xff\xfe", 140)
+        );
+        assert_eq!(
+            Err(vec![error("whom", "\"\" is not a valid username")]),
+            check_body(
+                form,
+                b"%7B%22who%22%3A%22alice%22%2C%22whom%22%3A%22%22%7D=",
+                140
+            )
+        );
+        assert_eq!(Ok(()), check_body(form, b"alice=", 140));
+        assert_eq!(Ok(()), check_body(form, b"", 140));
+    }
+}
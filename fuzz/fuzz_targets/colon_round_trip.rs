@@ -0,0 +1,12 @@
+#![no_main]
+//! Escaping any string and unescaping it gives the string back, and
+//! unescaping whatever is stored, escaped or not, never panics.
+use libfuzzer_sys::fuzz_target;
+use tribbler::colon::{escape, unescape};
+
+fuzz_target!(|s: &str| {
+    let escaped = escape(s);
+    assert!(!escaped.contains(':'));
+    assert_eq!(s, unescape(escaped));
+    let _ = unescape(s);
+});
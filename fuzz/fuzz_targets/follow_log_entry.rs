@@ -0,0 +1,12 @@
+#![no_main]
+//! Reading any follow log entry either fails with an error or gives an entry
+//! which reads back the same once written out.
+use libfuzzer_sys::fuzz_target;
+use tribbler::trib::FollowLogEntry;
+
+fuzz_target!(|entry: &str| {
+    if let Ok(parsed) = FollowLogEntry::parse(entry) {
+        let written = parsed.to_string();
+        assert_eq!(parsed, FollowLogEntry::parse(&written).unwrap());
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+//! Reading any stored trib, or reference to one, either fails with an error
+//! or gives one which is written out and read back the same, and which
+//! sorts among other tribs without panicking.
+use std::cmp::Ordering;
+
+use libfuzzer_sys::fuzz_target;
+use tribbler::trib::{trib_order, Trib, TribRef};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(trib) = serde_json::from_slice::<Trib>(data) {
+        let written = serde_json::to_string(&trib).unwrap();
+        let read: Trib = serde_json::from_str(&written).unwrap();
+        assert_eq!(written, serde_json::to_string(&read).unwrap());
+        assert_eq!(Ordering::Equal, trib_order(&trib, &read));
+    }
+    if let Ok(trib_ref) = serde_json::from_slice::<TribRef>(data) {
+        let written = serde_json::to_string(&trib_ref).unwrap();
+        assert_eq!(trib_ref, serde_json::from_str(&written).unwrap());
+    }
+});
@@ -0,0 +1,231 @@
+//! Microbenchmarks of the paths every call of the front-end goes through,
+//! over synthetic data held in memory, so that a change slowing them down
+//! shows up as a number. Run them with `cargo bench -p lab`.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use lab::lab2;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use tokio::runtime::Runtime;
+use tribbler::{
+    colon,
+    err::TribResult,
+    storage::{BinStorage, KeyList, KeyString, KeyValue, MemStorage, Pattern, Storage},
+    trib::{trib_order, FollowLogEntry, Server, Trib, MAX_TRIB_FETCH},
+};
+
+// bins kept apart in memory, the way the backends would keep them
+#[derive(Default, Clone)]
+struct MemBins(Arc<Mutex<HashMap<String, Arc<MemStorage>>>>);
+
+#[async_trait]
+impl BinStorage for MemBins {
+    async fn bin(&self, name: &str) -> TribResult<Box<dyn Storage>> {
+        let mut bins = self.0.lock().unwrap();
+        Ok(Box::new(bins.entry(name.to_string()).or_default().clone()))
+    }
+}
+
+fn kv(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    }
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn mem_storage(c: &mut Criterion) {
+    let rt = runtime();
+    let storage = MemStorage::new();
+    rt.block_on(async {
+        for i in 0..1000 {
+            storage
+                .set(&kv(&format!("key{}", i), "value"))
+                .await
+                .unwrap();
+            storage
+                .list_append(&kv("list", &i.to_string()))
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut group = c.benchmark_group("mem_storage");
+    group.bench_function("get", |b| {
+        b.to_async(&rt)
+            .iter(|| async { storage.get(black_box("key500")).await.unwrap() })
+    });
+    group.bench_function("set", |b| {
+        b.to_async(&rt).iter(|| async {
+            storage
+                .set(black_box(&kv("key500", "value")))
+                .await
+                .unwrap()
+        })
+    });
+    group.bench_function("keys", |b| {
+        let p = Pattern {
+            prefix: "key5".to_string(),
+            suffix: "".to_string(),
+        };
+        b.to_async(&rt)
+            .iter(|| async { storage.keys(black_box(&p)).await.unwrap() })
+    });
+    group.bench_function("list_get", |b| {
+        b.to_async(&rt)
+            .iter(|| async { storage.list_get(black_box("list")).await.unwrap() })
+    });
+    group.bench_function("list_append_remove", |b| {
+        b.to_async(&rt).iter(|| async {
+            storage.list_append(&kv("scratch", "value")).await.unwrap();
+            storage.list_remove(&kv("scratch", "value")).await.unwrap()
+        })
+    });
+    group.bench_function("clock", |b| {
+        b.to_async(&rt)
+            .iter(|| async { storage.clock(black_box(0)).await.unwrap() })
+    });
+    group.finish();
+}
+
+fn colon_escape(c: &mut Criterion) {
+    let plain = "alice".repeat(20);
+    let colons = "alice::follow::bob|".repeat(5);
+    let mut group = c.benchmark_group("colon");
+    group.bench_function("escape_plain", |b| {
+        b.iter(|| colon::escape(black_box(plain.as_str())))
+    });
+    group.bench_function("escape_colons", |b| {
+        b.iter(|| colon::escape(black_box(colons.as_str())))
+    });
+    let escaped = colon::escape(colons.as_str());
+    group.bench_function("unescape", |b| {
+        b.iter(|| colon::unescape(black_box(escaped.as_str())))
+    });
+    group.finish();
+}
+
+// tribs posted by a few users over a while, in no particular order
+fn tribs(n: usize) -> Vec<Arc<Trib>> {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut tribs: Vec<Arc<Trib>> = (0..n)
+        .map(|i| {
+            Arc::new(Trib {
+                user: format!("user{}", rng.gen_range(0..20)),
+                message: format!("trib {}", i),
+                time: rng.gen_range(0..1000),
+                clock: rng.gen_range(0..(n as u64 / 2)),
+                revision: 0,
+                retrib_of: None,
+                reply_to: None,
+                attachments: vec![],
+            })
+        })
+        .collect();
+    tribs.shuffle(&mut rng);
+    tribs
+}
+
+fn sort_tribs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_tribs");
+    for n in [MAX_TRIB_FETCH, 20 * MAX_TRIB_FETCH] {
+        let unsorted = tribs(n);
+        group.bench_function(n.to_string(), |b| {
+            b.iter_batched(
+                || unsorted.clone(),
+                |mut tribs| tribs.sort_by(|a, b| trib_order(a, b)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// a front-end over bins in memory, with `reader` signed up and following
+// `authors` users who posted `per_author` tribs each
+async fn front_with_home(
+    authors: usize,
+    per_author: usize,
+) -> TribResult<Box<dyn Server + Send + Sync>> {
+    let front = lab2::new_front(Box::<MemBins>::default()).await?;
+    front.sign_up("reader").await?;
+    for a in 0..authors {
+        let author = format!("author{}", a);
+        front.sign_up(&author).await?;
+        front.follow("reader", &author).await?;
+    }
+    for i in 0..per_author {
+        for a in 0..authors {
+            let author = format!("author{}", a);
+            front.post(&author, &format!("trib {}", i), 0).await?;
+        }
+    }
+    Ok(front)
+}
+
+fn follow_log_replay(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("follow_log_replay");
+    for entries in [100, 1000] {
+        // a log with follows and unfollows of the same few users, written
+        // straight to the bin as by a front-end from before followee sets
+        let bins = MemBins::default();
+        let front = rt.block_on(async {
+            let front = lab2::new_front(Box::new(bins.clone())).await.unwrap();
+            front.sign_up("reader").await.unwrap();
+            let reader = bins.bin("reader").await.unwrap();
+            let mut rng = StdRng::seed_from_u64(2);
+            for clock in 0..entries {
+                let whom = format!("user{}", rng.gen_range(0..50));
+                let entry = match rng.gen_bool(0.7) {
+                    true => FollowLogEntry::Follow { clock, whom },
+                    false => FollowLogEntry::Unfollow { clock, whom },
+                };
+                reader
+                    .list_append(&kv("log", &entry.to_string()))
+                    .await
+                    .unwrap();
+            }
+            front
+        });
+        group.bench_function(entries.to_string(), |b| {
+            b.to_async(&rt)
+                .iter(|| async { front.following(black_box("reader")).await.unwrap() })
+        });
+    }
+    group.finish();
+}
+
+fn home(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("home");
+    // at most the fetch limit, so that reading it leaves the home as it was
+    for (authors, per_author) in [(10, 10), (50, 2)] {
+        let front = rt.block_on(front_with_home(authors, per_author)).unwrap();
+        group.bench_function(format!("{}x{}", authors, per_author), |b| {
+            b.to_async(&rt)
+                .iter(|| async { front.home(black_box("reader")).await.unwrap() })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    mem_storage,
+    colon_escape,
+    sort_tribs,
+    follow_log_replay,
+    home
+);
+criterion_main!(benches);
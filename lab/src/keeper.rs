@@ -0,0 +1,316 @@
+// Add your message and service definitions below this line
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchRequest {}
+/// The keeper's view of the cluster, pushed to subscribed bin clients
+/// whenever it changes.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterView {
+    /// bumped every time the set of live backends changes
+    #[prost(uint64, tag = "1")]
+    pub epoch: u64,
+    /// every backend known to the keeper, in config order
+    #[prost(string, repeated, tag = "2")]
+    pub backs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the backends currently in the write path
+    #[prost(string, repeated, tag = "3")]
+    pub live: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// ring\[i\] is the index in backs of the backend serving hash slot i, of
+    /// which every backend owns as many as the virtual nodes of the placement
+    #[prost(uint32, repeated, tag = "4")]
+    pub ring: ::prost::alloc::vec::Vec<u32>,
+    /// true once every live backend has been verified to report a clock at
+    /// least as large as the max clock observed by the keeper
+    #[prost(bool, tag = "5")]
+    pub consistent: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatusRequest {}
+/// The keeper's view of a single backend.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BackendStatus {
+    #[prost(string, tag = "1")]
+    pub addr: ::prost::alloc::string::String,
+    /// whether the backend answered its last probe
+    #[prost(bool, tag = "2")]
+    pub alive: bool,
+    /// whether the backend is in the write path
+    #[prost(bool, tag = "3")]
+    pub eligible: bool,
+    /// the clock the backend reported on its last successful probe
+    #[prost(uint64, tag = "4")]
+    pub clock: u64,
+    /// how far the backend clock was behind the cluster clock when it was
+    /// last probed
+    #[prost(uint64, tag = "5")]
+    pub lag: u64,
+    /// the hold-down applied to the backend if it flaps
+    #[prost(uint64, tag = "6")]
+    pub hold_down_ms: u64,
+    /// probes failed in a row
+    #[prost(uint32, tag = "7")]
+    pub failures: u32,
+}
+/// Per-backend health of the cluster as seen by one keeper.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeeperStatus {
+    #[prost(uint64, tag = "1")]
+    pub clock: u64,
+    #[prost(uint64, tag = "2")]
+    pub epoch: u64,
+    #[prost(bool, tag = "3")]
+    pub consistent: bool,
+    #[prost(message, repeated, tag = "4")]
+    pub backends: ::prost::alloc::vec::Vec<BackendStatus>,
+}
+#[doc = r" Generated client implementations."]
+pub mod keeper_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[derive(Debug, Clone)]
+    pub struct KeeperClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl KeeperClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> KeeperClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> KeeperClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
+        {
+            KeeperClient::new(InterceptedService::new(inner, interceptor))
+        }
+        #[doc = r" Compress requests with `gzip`."]
+        #[doc = r""]
+        #[doc = r" This requires the server to support it otherwise it might respond with an"]
+        #[doc = r" error."]
+        pub fn send_gzip(mut self) -> Self {
+            self.inner = self.inner.send_gzip();
+            self
+        }
+        #[doc = r" Enable decompressing responses with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.inner = self.inner.accept_gzip();
+            self
+        }
+        pub async fn watch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WatchRequest>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::ClusterView>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/keeper.Keeper/Watch");
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+        pub async fn status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StatusRequest>,
+        ) -> Result<tonic::Response<super::KeeperStatus>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/keeper.Keeper/Status");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod keeper_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with KeeperServer."]
+    #[async_trait]
+    pub trait Keeper: Send + Sync + 'static {
+        #[doc = "Server streaming response type for the Watch method."]
+        type WatchStream: futures_core::Stream<Item = Result<super::ClusterView, tonic::Status>>
+            + Send
+            + 'static;
+        async fn watch(
+            &self,
+            request: tonic::Request<super::WatchRequest>,
+        ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status>;
+        async fn status(
+            &self,
+            request: tonic::Request<super::StatusRequest>,
+        ) -> Result<tonic::Response<super::KeeperStatus>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct KeeperServer<T: Keeper> {
+        inner: _Inner<T>,
+        accept_compression_encodings: (),
+        send_compression_encodings: (),
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: Keeper> KeeperServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for KeeperServer<T>
+    where
+        T: Keeper,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/keeper.Keeper/Watch" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchSvc<T: Keeper>(pub Arc<T>);
+                    impl<T: Keeper> tonic::server::ServerStreamingService<super::WatchRequest> for WatchSvc<T> {
+                        type Response = super::ClusterView;
+                        type ResponseStream = T::WatchStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).watch(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = WatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/keeper.Keeper/Status" => {
+                    #[allow(non_camel_case_types)]
+                    struct StatusSvc<T: Keeper>(pub Arc<T>);
+                    impl<T: Keeper> tonic::server::UnaryService<super::StatusRequest> for StatusSvc<T> {
+                        type Response = super::KeeperStatus;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StatusRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).status(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = StatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Keeper> Clone for KeeperServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: Keeper> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Keeper> tonic::transport::NamedService for KeeperServer<T> {
+        const NAME: &'static str = "keeper.Keeper";
+    }
+}
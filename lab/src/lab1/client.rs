@@ -1,13 +1,83 @@
 // use path::item
+use crate::lab1::mem;
 use async_trait::async_trait;
+use tokio::net::UnixStream;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint, Uri};
+use tower::service_fn;
 use tribbler::{
     self,
-    err::TribResult,
+    addr::Transport,
+    err::{TribResult, TribblerError},
     rpc,
     rpc::trib_storage_client::TribStorageClient,
     storage::{KeyList, KeyString, KeyValue, List, Pattern, Storage}, // to implement the RPCs
 };
 
+/// The header, and the gRPC metadata key, a request ID travels under.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    /// The ID of the front-end request being served, which every RPC made on
+    /// its behalf carries to the backends, so their logs can be tied back to
+    /// it. Set it with `REQUEST_ID.scope(id, future)`.
+    pub static REQUEST_ID: String;
+}
+
+/// Opens a channel to the server at `uri`, going by its scheme (see
+/// [Transport]): over TLS for `https://`, trusting the roots of the system or
+/// those of `SSL_CERT_FILE`, over a unix domain socket for `unix://`, over
+/// in-memory pipes to a server in the process for `mem://`, and over plain
+/// HTTP/2 otherwise.
+pub async fn channel(uri: &str) -> TribResult<Channel> {
+    let transport = Transport::parse(uri)?;
+    let channel = match transport {
+        Transport::Tcp(_) => endpoint(transport.uri())?.connect().await?,
+        Transport::Tls(_) => {
+            endpoint(transport.uri())?
+                .tls_config(ClientTlsConfig::new())?
+                .connect()
+                .await?
+        }
+        Transport::Unix(path) => {
+            // the connector dials the socket, but the endpoint still needs a
+            // URI of its own
+            Endpoint::from_static("http://localhost")
+                .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(path.clone())))
+                .await?
+        }
+        Transport::Mem(name) => {
+            Endpoint::from_static("http://localhost")
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    let name = name.clone();
+                    async move { mem::connect(&name).await }
+                }))
+                .await?
+        }
+    };
+    Ok(channel)
+}
+
+// the endpoint of a server to connect to at `uri`
+fn endpoint(uri: String) -> TribResult<Endpoint> {
+    Endpoint::from_shared(uri.clone())
+        .map_err(|e| TribblerError::RpcError(format!("bad address {}: {}", uri, e)))
+}
+
+// wraps an RPC message, tagging it with the ID of the request it is made for,
+// if any
+fn with_request_id<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    let id = REQUEST_ID
+        .try_with(|id| id.parse::<MetadataValue<Ascii>>())
+        .ok()
+        .and_then(Result::ok);
+    if let Some(id) = id {
+        request.metadata_mut().insert(REQUEST_ID_HEADER, id);
+    }
+    request
+}
+
 // declare a new struct and add fileds to it (addr)
 pub struct StorageClient {
     pub addr: String, // note that str and String are distinct types => let _ = StorageClient { addr: addr.to_string() };
@@ -19,11 +89,11 @@ impl KeyString for StorageClient {
     // add method implementations to match the tribbler::storage::Storage trait
     async fn get(&self, key: &str) -> TribResult<Option<String>> {
         // acceptable but not efficient since we open a connection for each RPC call
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?; // wait until we need to perform the first RPC function call
+        let mut client = TribStorageClient::new(channel(&self.addr).await?); // wait until we need to perform the first RPC function call
         let r = client
-            .get(rpc::Key {
+            .get(with_request_id(rpc::Key {
                 key: key.to_string(),
-            })
+            }))
             .await?; // "?" replaces the common syntax for error handling
                      // https://web.mit.edu/rust-lang_v1.25/arch/amd64_ubuntu1404/share/doc/rust/html/reference/expressions/operator-expr.html
 
@@ -38,97 +108,113 @@ impl KeyString for StorageClient {
     // This kv passed by the user should be the KeyValue struct of the storage because the user should use the storage as if he has it.
     async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
         // modify key value
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .set(rpc::KeyValue {
+            .set(with_request_id(rpc::KeyValue {
                 key: kv.key.clone(),
                 value: kv.value.clone(),
-            })
+            }))
             .await?;
-        match r.into_inner().value {
-            value => Ok(value),
-        }
+        let value = r.into_inner().value;
+        Ok(value)
     }
 
     async fn keys(&self, p: &Pattern) -> TribResult<List> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .keys(rpc::Pattern {
+            .keys(with_request_id(rpc::Pattern {
                 prefix: p.prefix.clone(),
                 suffix: p.suffix.clone(),
-            })
+            }))
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
+        let r = client
+            .incr(with_request_id(rpc::Incr {
+                key: key.to_string(),
+                delta,
+            }))
+            .await?;
+        Ok(r.into_inner().value)
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
+        let r = client
+            .compare_and_swap(with_request_id(rpc::Swap {
+                key: key.to_string(),
+                expected: expected.to_string(),
+                value: value.to_string(),
+            }))
+            .await?;
+        Ok(r.into_inner().value)
     }
 }
 
 #[async_trait]
 impl KeyList for StorageClient {
     async fn list_get(&self, key: &str) -> TribResult<List> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .list_get(rpc::Key {
+            .list_get(with_request_id(rpc::Key {
                 key: key.to_string(),
-            })
+            }))
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
     }
 
     async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .list_append(rpc::KeyValue {
+            .list_append(with_request_id(rpc::KeyValue {
                 key: kv.key.clone(),
                 value: kv.value.clone(),
-            })
+            }))
             .await?;
-        match r.into_inner().value {
-            value => Ok(value),
-        }
+        let value = r.into_inner().value;
+        Ok(value)
     }
 
     async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .list_remove(rpc::KeyValue {
+            .list_remove(with_request_id(rpc::KeyValue {
                 key: kv.key.clone(),
                 value: kv.value.clone(),
-            })
+            }))
             .await?;
-        match r.into_inner().removed {
-            removed => Ok(removed),
-        }
+        let removed = r.into_inner().removed;
+        Ok(removed)
     }
 
     async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .list_keys(rpc::Pattern {
+            .list_keys(with_request_id(rpc::Pattern {
                 prefix: p.prefix.clone(),
                 suffix: p.suffix.clone(),
-            })
+            }))
             .await?;
-        match r.into_inner().list {
-            list => Ok(List(list)),
-        }
+        let list = r.into_inner().list;
+        Ok(List(list))
     }
 }
 
 #[async_trait]
 impl Storage for StorageClient {
     async fn clock(&self, at_least: u64) -> TribResult<u64> {
-        let mut client = TribStorageClient::connect(self.addr.clone()).await?;
+        let mut client = TribStorageClient::new(channel(&self.addr).await?);
         let r = client
-            .clock(rpc::Clock {
+            .clock(with_request_id(rpc::Clock {
                 timestamp: at_least,
-            })
+            }))
             .await?;
-        match r.into_inner().timestamp {
-            timestamp => Ok(timestamp),
-        }
+        let timestamp = r.into_inner().timestamp;
+        Ok(timestamp)
     }
 }
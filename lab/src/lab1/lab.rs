@@ -1,67 +1,224 @@
-use crate::lab1::client::StorageClient;
+use crate::lab1::client::{StorageClient, REQUEST_ID_HEADER};
+use crate::lab1::mem::{self, MemListener};
+use crate::lab1::record;
 use crate::lab1::server::StorageServer;
+use log::debug;
 use std::boxed::Box;
 use std::net::ToSocketAddrs;
-use tonic::transport::Server;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, UnixListener, UnixStream};
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::service::Interceptor;
+use tonic::transport::server::Connected;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Status};
 use tribbler::err::TribblerError;
 use tribbler::{
     self,
+    addr::Transport,
     err::TribResult,
     rpc::trib_storage_server::TribStorageServer,
     {config::BackConfig, storage::Storage},
 };
 
+// logs the ID of the front-end request an RPC is made for, when it carries one
+#[derive(Clone, Copy)]
+struct LogRequestId;
+
+impl Interceptor for LogRequestId {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(Ok(id)) = request
+            .metadata()
+            .get(REQUEST_ID_HEADER)
+            .map(|id| id.to_str())
+        {
+            debug!("serving an RPC for request {}", id);
+        }
+        Ok(request)
+    }
+}
+
 /// an async function which blocks indefinitely (unlimited time) until interrupted serving on the host and port specified in the [BackConfig] parameter.
+/// The address may also be a `unix://` socket, or a `mem://` server in the
+/// process (see [mem]); see [serve_back_with_tls] for
+/// serving an `https://` one.
 pub async fn serve_back(config: BackConfig) -> TribResult<()> {
+    serve_back_with_tls(config, None).await
+}
+
+/// Like [serve_back], but serves an `https://` address over TLS, with the
+/// certificate and key in `identity`, without which such an address can't be
+/// served. The identity is left unused on an address of another scheme.
+pub async fn serve_back_with_tls(config: BackConfig, identity: Option<Identity>) -> TribResult<()> {
     // creates an instance of a back-end server based on configuration
     let storage_server = StorageServer {
         storage: config.storage,
     };
 
-    match config.addr.clone().to_socket_addrs() {
-        Ok(iterator) => match iterator.last() {
-            Some(socket_addr) => {
-                match config.shutdown {
-                    Some(mut s) => {
-                        let _ = match config.ready {
-                            Some(unwrapped_ready) => unwrapped_ready.send(true), // The server is ready if it reaches this line.
-                            None => Ok(()),
-                        };
-                        Server::builder()
-                            .add_service(TribStorageServer::new(storage_server))
-                            .serve_with_shutdown(socket_addr, async {
-                                s.recv().await;
-                            }) // block until there is an error, or a shutdown message is received
-                            .await?
-                    }
-                    None => {
-                        let _ = match config.ready {
-                            Some(unwrapped_ready) => unwrapped_ready.send(true),
-                            None => Ok(()),
-                        };
-                        Server::builder()
-                            .add_service(TribStorageServer::new(storage_server))
-                            .serve(socket_addr)
-                            .await?
-                    }
-                }
+    // bind before signaling so clients never race the listener
+    let listener = match bind(&config.addr, identity).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            if let Some(unwrapped_ready) = config.ready {
+                let _ = unwrapped_ready.send(false);
             }
+            return Err(e);
+        }
+    };
+    let _ = match config.ready {
+        Some(unwrapped_ready) => unwrapped_ready.send(true), // The server is ready if it reaches this line.
+        None => Ok(()),
+    };
+    match listener {
+        Listener::Tcp(listener, server) => {
+            // disable Nagle like tonic does for the listeners it binds itself
+            let incoming = TcpListenerStream::new(listener).map(|conn| {
+                conn.inspect(|stream| {
+                    let _ = stream.set_nodelay(true);
+                })
+            });
+            serve(server, storage_server, incoming, config.shutdown).await
+        }
+        Listener::Unix(listener) => {
+            let incoming = UnixListenerStream::new(listener).map(|conn| conn.map(UnixConnection));
+            serve(Server::builder(), storage_server, incoming, config.shutdown).await
+        }
+        Listener::Mem(listener) => {
+            serve(Server::builder(), storage_server, listener, config.shutdown).await
+        }
+    }
+}
+
+// a listener a backend serves on, with the server a TCP one is served by
+enum Listener {
+    Tcp(TcpListener, Server),
+    Unix(UnixListener),
+    Mem(MemListener),
+}
+
+// listens on `addr`, with a server which takes on TLS for an `https://`
+// address
+async fn bind(addr: &str, identity: Option<Identity>) -> TribResult<Listener> {
+    let (host_port, tls) = match Transport::parse(addr)? {
+        Transport::Unix(path) => return Ok(Listener::Unix(bind_unix(&path)?)),
+        Transport::Mem(name) => return Ok(Listener::Mem(mem::listen(&name)?)),
+        Transport::Tcp(host_port) => (host_port, None),
+        Transport::Tls(host_port) => match identity {
+            Some(identity) => (host_port, Some(ServerTlsConfig::new().identity(identity))),
             None => {
-                return Err(Box::new(TribblerError::Unknown(
-                    "Cannot parse address".to_string(),
+                return Err(TribblerError::Unknown(format!(
+                    "{} is served over TLS, which needs a certificate and key",
+                    addr
                 )))
             }
         },
-        Err(e) => return Err(Box::new(e)),
     };
+    let socket_addr = match host_port.to_socket_addrs()?.last() {
+        Some(socket_addr) => socket_addr,
+        None => return Err(TribblerError::Unknown("Cannot parse address".to_string())),
+    };
+    let server = match tls {
+        Some(tls) => Server::builder().tls_config(tls)?,
+        None => Server::builder(),
+    };
+    Ok(Listener::Tcp(TcpListener::bind(socket_addr).await?, server))
+}
+
+// binds the unix domain socket at `path`, taking the place of the socket a
+// backend which went away left there
+fn bind_unix(path: &Path) -> std::io::Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    UnixListener::bind(path)
+}
+
+// serves the storage on the connections `incoming`, until there is an error,
+// or a shutdown message is received
+async fn serve<I, IO, IE>(
+    mut server: Server,
+    storage_server: StorageServer,
+    incoming: I,
+    shutdown: Option<tokio::sync::mpsc::Receiver<()>>,
+) -> TribResult<()>
+where
+    I: Stream<Item = Result<IO, IE>>,
+    IO: AsyncRead + AsyncWrite + Connected + Unpin + Send + 'static,
+    IO::ConnectInfo: Clone + Send + Sync + 'static,
+    IE: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let router = server.add_service(TribStorageServer::with_interceptor(
+        storage_server,
+        LogRequestId,
+    ));
+    match shutdown {
+        Some(mut s) => {
+            router
+                .serve_with_incoming_shutdown(incoming, async {
+                    s.recv().await;
+                })
+                .await?
+        }
+        None => router.serve_with_incoming(incoming).await?,
+    }
     Ok(())
 }
 
+// a connection on a unix domain socket, which tonic serves once told it has
+// no peer address to speak of
+struct UnixConnection(UnixStream);
+
+impl Connected for UnixConnection {
+    type ConnectInfo = ();
+
+    fn connect_info(&self) -> Self::ConnectInfo {}
+}
+
+impl AsyncRead for UnixConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
 /// This function should create a new client which implements the [Storage] trait.
 /// It should communicate with the backend that is started in the [serve_back] function.
+/// Its calls are recorded once [record::record_to] is called.
 pub async fn new_client(addr: &str) -> TribResult<Box<dyn Storage>> {
-    Ok(Box::new(StorageClient {
+    let client = Box::new(StorageClient {
         // wrap a new client obeject with Ok(Box::new()) for the type constraint
         addr: addr.to_string(), // &str and String are distinct types in Rust.
-    }))
+    });
+    Ok(match record::recorder() {
+        Some(recorder) => recorder.wrap(addr, client),
+        None => client,
+    })
 }
@@ -0,0 +1,127 @@
+//! Servers in the same process, reached at `mem://<name>` addresses over
+//! in-memory pipes rather than the network, so that a whole cluster can be
+//! run in one process, on a clock of its own (see [crate::sim]).
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::{LazyLock, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tonic::transport::server::Connected;
+use tribbler::addr::MEM;
+use tribbler::err::{TribResult, TribblerError};
+
+// the bytes a pipe buffers each way before its writer waits on the reader
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+// the servers listening in the process, by name, with the sender the server
+// end of every connection made to one is handed over on
+static LISTENERS: LazyLock<Mutex<HashMap<String, mpsc::UnboundedSender<DuplexStream>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Listens as the server `name`, taking the name over from a listener which
+/// went away; it is an error for two listeners to be up under one name.
+pub fn listen(name: &str) -> TribResult<MemListener> {
+    let mut listeners = LISTENERS.lock().unwrap();
+    if listeners
+        .get(name)
+        .is_some_and(|sender| !sender.is_closed())
+    {
+        return Err(TribblerError::Unknown(format!(
+            "{}{} is already being served",
+            MEM, name
+        )));
+    }
+    let (sender, incoming) = mpsc::unbounded_channel();
+    listeners.insert(name.to_string(), sender);
+    Ok(MemListener {
+        name: name.to_string(),
+        incoming,
+    })
+}
+
+/// Dials the server `name` in the process, which is refused as a TCP
+/// connection would be when nothing listens under the name.
+pub async fn connect(name: &str) -> io::Result<DuplexStream> {
+    let sender = LISTENERS.lock().unwrap().get(name).cloned();
+    let (client, server) = tokio::io::duplex(PIPE_CAPACITY);
+    match sender {
+        Some(sender) if sender.send(server).is_ok() => Ok(client),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("nothing is listening at {}{}", MEM, name),
+        )),
+    }
+}
+
+/// The connections made to a server in the process, as a stream of them to
+/// serve. The name is free again once the listener is dropped.
+pub struct MemListener {
+    name: String,
+    incoming: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+impl Stream for MemListener {
+    type Item = io::Result<MemConnection>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming
+            .poll_recv(cx)
+            .map(|conn| conn.map(|conn| Ok(MemConnection(conn))))
+    }
+}
+
+impl Drop for MemListener {
+    fn drop(&mut self) {
+        // refuse the connections still to come before giving up the name,
+        // unless another listener took it over already
+        self.incoming.close();
+        let mut listeners = LISTENERS.lock().unwrap();
+        if listeners
+            .get(&self.name)
+            .is_some_and(|sender| sender.is_closed())
+        {
+            listeners.remove(&self.name);
+        }
+    }
+}
+
+/// The server end of a connection made in the process, which tonic serves
+/// once told it has no peer address to speak of.
+pub struct MemConnection(DuplexStream);
+
+impl Connected for MemConnection {
+    type ConnectInfo = ();
+
+    fn connect_info(&self) -> Self::ConnectInfo {}
+}
+
+impl AsyncRead for MemConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MemConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
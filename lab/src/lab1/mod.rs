@@ -98,7 +98,7 @@
 //!
 //! First is the key-string pair part, which is its own interface.
 //!
-//! ```rust
+//! ```rust,ignore
 //! #[async_trait] // important annotation!
 //! /// Key-value pair interfaces
 //! /// Default value for all keys is empty string
@@ -122,7 +122,7 @@
 //! The second part is the key-list pair interface that handles list-valued
 //! key-value pairs.
 //!
-//! ```rust
+//! ```rust,ignore
 //! #[async_trait]
 //! /// Key-list interfaces
 //! pub trait KeyList {
@@ -145,7 +145,7 @@
 //! The `Storage` interface glues these two interfaces together, and also
 //! includes an auto-incrementing clock feature.
 //!
-//! ```rust
+//! ```rust,ignore
 //! #[async_trait]
 //! pub trait Storage: KeyString + KeyList {
 //!     /// Returns an auto-incrementing clock. The returned value of each call will
@@ -177,7 +177,7 @@
 //! are implicitly wrapped by a [std::future::Future]. Calling `.await` on the
 //! future will retrieve its value.
 //!
-//! ```rust
+//! ```rust,ignore
 //! async fn do_io() -> bool {
 //!     println!("look ma! I'm doin' I/O!");
 //!     true
@@ -212,7 +212,7 @@
 //!
 //! ### Server-side
 //!
-//! ```
+//! ```text
 //! pub async fn serve_back(config: BackConfig) -> TribResult<()>
 //! ```
 //!
@@ -229,9 +229,9 @@
 //!   sending `false`). `ready` might be `None`, which means the caller does not
 //!   care about when the server is ready.
 //! - `shutdown` is another type of channel for receiving a shutdown
-//! notification. when a message is received on this channel, the server should
-//! shut down. **Hint**: take a look at
-//! [serve_with_shutdown](tonic::transport::server::Router)
+//!   notification. when a message is received on this channel, the server should
+//!   shut down. **Hint**: take a look at
+//!   [serve_with_shutdown](tonic::transport::server::Router)
 //!
 //! This function should block indefinitely unless there is errors or the server
 //! is sent a shutdown signal. It is `async`, you should be able to call
@@ -244,7 +244,7 @@
 //!
 //! ### Client-side
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn new_client(addr: &str) -> TribResult<Box<dyn Storage>>
 //! ```
 //!
@@ -271,7 +271,7 @@
 //! - **protobuf** (or protocol buffers) is an RPC message and service interface
 //!   language (`.proto` files) and a wire serialization format.
 //! - **tonic** is a gRPC server and client implementation which has protobuf
-//! compilation support baked in.
+//!   compilation support baked in.
 //!
 //! Tonic is a gRPC server and client implementation written and designed in
 //! rust. gRPC implementations use a protobuf (`.proto`) file to define message
@@ -288,7 +288,7 @@
 //! the module you wish to test using and annotate it with `#[cfg(test)]`. To
 //! run the tests, you can then simply run `cargo test`. Example
 //!
-//! ```rust
+//! ```rust,ignore
 //! // your code here
 //! // ...
 //!
@@ -328,7 +328,7 @@
 //! First, create a `client.rs` file under the [crate::lab1] module, declare a
 //! new struct called `StorageClient`, and make it visible to your lab 1 module:
 //!
-//! ```rust
+//! ```rust,ignore
 //! // ... in lab/src/lab1/client.rs
 //! pub struct StorageClient;
 //! // ... in lab/src/lab1/mod.rs
@@ -339,7 +339,7 @@
 //! matches the [tribbler::storage::Storage] trait. For example, for the `get()`
 //! function:
 //!
-//! ```rust
+//! ```rust,ignore
 //! impl KeyString for StorageClient {
 //!     fn get(&self, key: &str) -> TribResult<Option<String>> {
 //!         todo!()
@@ -355,7 +355,7 @@
 //! type with `Ok(Box::new())` to satisfy the type constraints. Now the
 //! [new_client] function should look something like this:
 //!
-//! ```rust
+//! ```rust,ignore
 //! use crate::lab1::client::StorageClient;
 //! pub async fn new_client(addr: &str) -> TribResult<Box<dyn Storage>> {
 //!     Ok(Box::new(StorageClient { }))
@@ -370,7 +370,7 @@
 //! here](https://doc.rust-lang.org/book/ch08-02-strings.html). To convert from
 //! `&str` to [String], call the `to_string()` method.
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub struct StorageClient {
 //!     pub addr: String,
 //! }
@@ -381,7 +381,7 @@
 //!
 //! To implement the RPCs, we need to
 //!
-//! ```rust
+//! ```rust,ignore
 //! use tribbler::rpc;
 //! ```
 //!
@@ -400,7 +400,7 @@
 //! something like this (though you shouldn't be connecting a new `client` on
 //! every RPC):
 //!
-//! ```rust
+//! ```rust,ignore
 //! use tribbler::rpc::trib_storage_client::TribStorageClient;
 //! #[async_trait] // VERY IMPORTANT !!
 //! impl KeyString for ClientStorage {
@@ -457,7 +457,7 @@
 //! implementing the storage server, You might encounter examples of
 //! implementation that look like:
 //!
-//! ```rust
+//! ```rust,ignore
 //! // StorageServer is a type you can define in your lab
 //! impl KeyString for StorageServer {
 //!     fn set<'life0, 'async_trait>(
@@ -488,7 +488,7 @@
 //! Using the [async_trait::async_trait] above your `impl` results in methods
 //! that can look like:
 //!
-//! ```
+//! ```text
 //! #[async_trait::async_trait]
 //! impl KeyString for StorageServer {
 //!     async fn set(&self, request: tonic::Request<rpc::KeyValue>,
@@ -507,7 +507,7 @@
 //!
 //! First make sure your code compiles, then run the server.
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin kv-server
 //! ```
 //!
@@ -516,7 +516,7 @@
 //!
 //! Now you can play with your server via the `kv-client` program. For example:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin kv-client
 //! > set foo value
 //! Ok(true)
@@ -566,7 +566,10 @@
 
 pub mod client; // make StorageClient visible in the lab 1 module
 pub mod lab;
+pub mod mem;
+pub mod record;
 pub mod server; // make StorageServer visible in the lab 1 module
 
+pub use crate::lab1::client::{REQUEST_ID, REQUEST_ID_HEADER};
 pub use crate::lab1::lab::new_client;
-pub use crate::lab1::lab::serve_back;
+pub use crate::lab1::lab::{serve_back, serve_back_with_tls};
@@ -0,0 +1,512 @@
+//! Recording the RPCs made to the backends, and replaying them, so that what
+//! a front-end saw of a cluster can be served back to it later without the
+//! cluster.
+//!
+//! Once [record_to] is called, every client [crate::lab1::new_client] makes
+//! writes each call it makes, and what came back for it, to the recording
+//! file as a line of JSON. A [ReplayStorage] serves a backend's calls back
+//! from such a file, answering each call with what was recorded for the
+//! same call, in the order it was recorded.
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tribbler::err::{TribResult, TribblerError};
+use tribbler::storage::{KeyList, KeyString, KeyValue, List, Pattern, Storage};
+
+// the recorder every new client records to, once one is set up
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+
+/// A call made to a backend, with its arguments.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    Get {
+        key: String,
+    },
+    Set {
+        key: String,
+        value: String,
+    },
+    Keys {
+        prefix: String,
+        suffix: String,
+    },
+    Incr {
+        key: String,
+        delta: i64,
+    },
+    CompareAndSwap {
+        key: String,
+        expected: String,
+        value: String,
+    },
+    ListGet {
+        key: String,
+    },
+    ListAppend {
+        key: String,
+        value: String,
+    },
+    ListRemove {
+        key: String,
+        value: String,
+    },
+    ListKeys {
+        prefix: String,
+        suffix: String,
+    },
+    Clock {
+        at_least: u64,
+    },
+}
+
+/// What came back for a [Request], of the type its call returns, or the
+/// error it failed with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Value(Option<String>),
+    Done(bool),
+    List(Vec<String>),
+    Removed(u32),
+    Count(i64),
+    Clock(u64),
+    Error(String),
+}
+
+/// A call made to the backend `back`, and what came back for it: a line of
+/// a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    pub back: String,
+    pub request: Request,
+    pub response: Response,
+}
+
+/// Writes the calls of the clients it wraps to a recording file.
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    /// Records to the file at `path`, which is created or emptied.
+    pub fn create(path: &str) -> TribResult<Recorder> {
+        let file = File::create(path)
+            .map_err(|e| TribblerError::Unknown(format!("cannot record to {}: {}", path, e)))?;
+        Ok(Recorder {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Wraps `storage`, the client of the backend `back`, so that the calls
+    /// made through it are recorded.
+    pub fn wrap(&self, back: &str, storage: Box<dyn Storage>) -> Box<dyn Storage> {
+        Box::new(RecordingStorage {
+            back: back.to_string(),
+            storage,
+            recorder: self.clone(),
+        })
+    }
+
+    // writes a line of the recording; a line which can't be written is
+    // reported and left out, rather than failing the call it records
+    fn record(&self, exchange: &Exchange) {
+        let mut line = match serde_json::to_string(exchange) {
+            Ok(line) => line,
+            Err(e) => return warn!("cannot record a call to {}: {}", exchange.back, e),
+        };
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            warn!("cannot record a call to {}: {}", exchange.back, e);
+        }
+    }
+}
+
+/// Records the calls of every client [crate::lab1::new_client] makes from
+/// now on to the file at `path`. Only one recording is made by a process.
+pub fn record_to(path: &str) -> TribResult<()> {
+    let recorder = Recorder::create(path)?;
+    RECORDER
+        .set(recorder)
+        .map_err(|_| TribblerError::Unknown("the calls are already being recorded".to_string()))
+}
+
+/// The recorder set up by [record_to], if any.
+pub fn recorder() -> Option<&'static Recorder> {
+    RECORDER.get()
+}
+
+/// Reads the exchanges of the recording at `path`, in the order they were
+/// recorded.
+pub fn read_recording(path: &str) -> TribResult<Vec<Exchange>> {
+    let file = File::open(path)
+        .map_err(|e| TribblerError::Unknown(format!("cannot read {}: {}", path, e)))?;
+    let mut exchanges = vec![];
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let exchange = serde_json::from_str(&line).map_err(|e| {
+            TribblerError::Unknown(format!("line {} of {} is corrupt: {}", n + 1, path, e))
+        })?;
+        exchanges.push(exchange);
+    }
+    Ok(exchanges)
+}
+
+// a client of `back` whose calls are recorded
+struct RecordingStorage {
+    back: String,
+    storage: Box<dyn Storage>,
+    recorder: Recorder,
+}
+
+impl RecordingStorage {
+    // records the call `request`, which came back with `result`, handing the
+    // result on
+    fn record<T>(
+        &self,
+        request: Request,
+        result: TribResult<T>,
+        response: impl FnOnce(&T) -> Response,
+    ) -> TribResult<T> {
+        let recorded = match &result {
+            Ok(value) => response(value),
+            Err(e) => Response::Error(e.to_string()),
+        };
+        self.recorder.record(&Exchange {
+            back: self.back.clone(),
+            request,
+            response: recorded,
+        });
+        result
+    }
+}
+
+#[async_trait]
+impl KeyString for RecordingStorage {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        let request = Request::Get {
+            key: key.to_string(),
+        };
+        let result = self.storage.get(key).await;
+        self.record(request, result, |v| Response::Value(v.clone()))
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        let request = Request::Set {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        };
+        let result = self.storage.set(kv).await;
+        self.record(request, result, |v| Response::Done(*v))
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<List> {
+        let request = Request::Keys {
+            prefix: p.prefix.clone(),
+            suffix: p.suffix.clone(),
+        };
+        let result = self.storage.keys(p).await;
+        self.record(request, result, |v| Response::List(v.0.clone()))
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        let request = Request::Incr {
+            key: key.to_string(),
+            delta,
+        };
+        let result = self.storage.incr(key, delta).await;
+        self.record(request, result, |v| Response::Count(*v))
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let request = Request::CompareAndSwap {
+            key: key.to_string(),
+            expected: expected.to_string(),
+            value: value.to_string(),
+        };
+        let result = self.storage.compare_and_swap(key, expected, value).await;
+        self.record(request, result, |v| Response::Value(Some(v.clone())))
+    }
+}
+
+#[async_trait]
+impl KeyList for RecordingStorage {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        let request = Request::ListGet {
+            key: key.to_string(),
+        };
+        let result = self.storage.list_get(key).await;
+        self.record(request, result, |v| Response::List(v.0.clone()))
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        let request = Request::ListAppend {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        };
+        let result = self.storage.list_append(kv).await;
+        self.record(request, result, |v| Response::Done(*v))
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        let request = Request::ListRemove {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        };
+        let result = self.storage.list_remove(kv).await;
+        self.record(request, result, |v| Response::Removed(*v))
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        let request = Request::ListKeys {
+            prefix: p.prefix.clone(),
+            suffix: p.suffix.clone(),
+        };
+        let result = self.storage.list_keys(p).await;
+        self.record(request, result, |v| Response::List(v.0.clone()))
+    }
+}
+
+#[async_trait]
+impl Storage for RecordingStorage {
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        let request = Request::Clock { at_least };
+        let result = self.storage.clock(at_least).await;
+        self.record(request, result, |v| Response::Clock(*v))
+    }
+}
+
+// the responses recorded for a request, the next one to give first, and the
+// last one given, given again once they run out
+#[derive(Default)]
+struct Replies {
+    next: VecDeque<Response>,
+    last: Option<Response>,
+}
+
+/// A [Storage] serving back the calls recorded for a backend: each call is
+/// answered with the responses recorded for the same call, in the order they
+/// were recorded, the last of them again once they run out. A call never
+/// recorded fails.
+pub struct ReplayStorage {
+    replies: Mutex<HashMap<Request, Replies>>,
+}
+
+impl ReplayStorage {
+    /// Serves back the exchanges of `exchanges` recorded for the backend
+    /// `back`.
+    pub fn new(exchanges: &[Exchange], back: &str) -> ReplayStorage {
+        let mut replies = HashMap::<Request, Replies>::new();
+        for exchange in exchanges.iter().filter(|e| e.back == back) {
+            replies
+                .entry(exchange.request.clone())
+                .or_default()
+                .next
+                .push_back(exchange.response.clone());
+        }
+        ReplayStorage {
+            replies: Mutex::new(replies),
+        }
+    }
+
+    // the next response recorded for `request`
+    fn reply(&self, request: Request) -> TribResult<Response> {
+        let mut replies = self.replies.lock().unwrap();
+        let replies = match replies.get_mut(&request) {
+            Some(replies) => replies,
+            None => {
+                return Err(TribblerError::RpcError(format!(
+                    "{:?} was not recorded",
+                    request
+                )))
+            }
+        };
+        if let Some(next) = replies.next.pop_front() {
+            replies.last = Some(next);
+        }
+        match replies.last.clone() {
+            Some(Response::Error(e)) => Err(TribblerError::RpcError(e)),
+            Some(response) => Ok(response),
+            None => Err(TribblerError::RpcError(format!(
+                "{:?} was not recorded",
+                request
+            ))),
+        }
+    }
+}
+
+// the recorded response `response`, which is not of the type the call
+// returns
+fn mismatched<T>(response: Response) -> TribResult<T> {
+    Err(TribblerError::RpcError(format!(
+        "{:?} was recorded for a call which can't return it",
+        response
+    )))
+}
+
+#[async_trait]
+impl KeyString for ReplayStorage {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        match self.reply(Request::Get {
+            key: key.to_string(),
+        })? {
+            Response::Value(value) => Ok(value),
+            response => mismatched(response),
+        }
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        match self.reply(Request::Set {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        })? {
+            Response::Done(done) => Ok(done),
+            response => mismatched(response),
+        }
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<List> {
+        match self.reply(Request::Keys {
+            prefix: p.prefix.clone(),
+            suffix: p.suffix.clone(),
+        })? {
+            Response::List(list) => Ok(List(list)),
+            response => mismatched(response),
+        }
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        match self.reply(Request::Incr {
+            key: key.to_string(),
+            delta,
+        })? {
+            Response::Count(count) => Ok(count),
+            response => mismatched(response),
+        }
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        match self.reply(Request::CompareAndSwap {
+            key: key.to_string(),
+            expected: expected.to_string(),
+            value: value.to_string(),
+        })? {
+            Response::Value(held) => Ok(held.unwrap_or_default()),
+            response => mismatched(response),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyList for ReplayStorage {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        match self.reply(Request::ListGet {
+            key: key.to_string(),
+        })? {
+            Response::List(list) => Ok(List(list)),
+            response => mismatched(response),
+        }
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        match self.reply(Request::ListAppend {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        })? {
+            Response::Done(done) => Ok(done),
+            response => mismatched(response),
+        }
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        match self.reply(Request::ListRemove {
+            key: kv.key.clone(),
+            value: kv.value.clone(),
+        })? {
+            Response::Removed(removed) => Ok(removed),
+            response => mismatched(response),
+        }
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        match self.reply(Request::ListKeys {
+            prefix: p.prefix.clone(),
+            suffix: p.suffix.clone(),
+        })? {
+            Response::List(list) => Ok(List(list)),
+            response => mismatched(response),
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for ReplayStorage {
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        match self.reply(Request::Clock { at_least })? {
+            Response::Clock(clock) => Ok(clock),
+            response => mismatched(response),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_recording, Exchange, Recorder, ReplayStorage, Request, Response};
+    use tribbler::err::TribResult;
+    use tribbler::storage::{KeyList, KeyString, KeyValue, MemStorage, Storage};
+
+    #[tokio::test]
+    async fn recorded_calls_are_replayed() -> TribResult<()> {
+        let path =
+            std::env::temp_dir().join(format!("trib-record-{}.jsonl", rand::random::<u64>()));
+        let path = path.to_str().unwrap();
+        let recorder = Recorder::create(path)?;
+        let storage = recorder.wrap("back-0", Box::new(MemStorage::new()));
+        storage.set(&KeyValue::new("k", "v")).await?;
+        storage.list_append(&KeyValue::new("l", "a")).await?;
+        let first = storage.get("k").await?;
+        storage.set(&KeyValue::new("k", "w")).await?;
+        let second = storage.get("k").await?;
+        let list = storage.list_get("l").await?;
+        let clock = storage.clock(7).await?;
+        assert!(storage.incr("k", 1).await.is_err());
+
+        let exchanges = read_recording(path)?;
+        std::fs::remove_file(path)?;
+        assert_eq!(8, exchanges.len());
+        assert_eq!(
+            Exchange {
+                back: "back-0".to_string(),
+                request: Request::Get {
+                    key: "k".to_string()
+                },
+                response: Response::Value(Some("v".to_string())),
+            },
+            exchanges[2]
+        );
+
+        let replay = ReplayStorage::new(&exchanges, "back-0");
+        assert_eq!(first, replay.get("k").await?);
+        assert_eq!(second, replay.get("k").await?);
+        assert_eq!(second, replay.get("k").await?);
+        assert_eq!(list.0, replay.list_get("l").await?.0);
+        assert_eq!(clock, replay.clock(7).await?);
+        assert!(replay.incr("k", 1).await.is_err());
+        assert!(replay.get("never").await.is_err());
+        assert!(ReplayStorage::new(&exchanges, "back-1")
+            .get("k")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -23,7 +23,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             Ok(None) => Ok(Response::new(rpc::Value {
                 value: "".to_string(),
             })),
-            Err(e) => Err(tonic::Status::unknown("fail to get")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -41,7 +41,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             .await;
         match output {
             Ok(t) => Ok(Response::new(rpc::Bool { value: t })),
-            Err(e) => Err(tonic::Status::unknown("fail to set")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -59,7 +59,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             .await;
         match output {
             Ok(List(t)) => Ok(Response::new(rpc::StringList { list: t })),
-            Err(e) => Err(tonic::Status::unknown("fail keys")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -71,7 +71,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
         let output = self.storage.list_get(k.key.as_str()).await;
         match output {
             Ok(List(t)) => Ok(Response::new(rpc::StringList { list: t })),
-            Err(e) => Err(tonic::Status::unknown("fail keys")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -89,7 +89,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             .await;
         match output {
             Ok(t) => Ok(Response::new(rpc::Bool { value: t })),
-            Err(e) => Err(tonic::Status::unknown("fail list_append")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -107,7 +107,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             .await;
         match output {
             Ok(t) => Ok(Response::new(rpc::ListRemoveResponse { removed: t })),
-            Err(e) => Err(tonic::Status::unknown("fail list_remove")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -125,7 +125,7 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
             .await;
         match output {
             Ok(List(t)) => Ok(Response::new(rpc::StringList { list: t })),
-            Err(e) => Err(tonic::Status::unknown("fail list_keys")),
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -137,7 +137,34 @@ impl rpc::trib_storage_server::TribStorage for StorageServer {
         let output = self.storage.clock(t.timestamp).await;
         match output {
             Ok(t) => Ok(Response::new(rpc::Clock { timestamp: t })),
-            Err(e) => Err(tonic::Status::unknown("fail clock")),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn incr(
+        &self,
+        request: tonic::Request<rpc::Incr>,
+    ) -> Result<tonic::Response<rpc::Counter>, tonic::Status> {
+        let i = request.into_inner();
+        let output = self.storage.incr(&i.key, i.delta).await;
+        match output {
+            Ok(t) => Ok(Response::new(rpc::Counter { value: t })),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn compare_and_swap(
+        &self,
+        request: tonic::Request<rpc::Swap>,
+    ) -> Result<tonic::Response<rpc::Value>, tonic::Status> {
+        let s = request.into_inner();
+        let output = self
+            .storage
+            .compare_and_swap(&s.key, &s.expected, &s.value)
+            .await;
+        match output {
+            Ok(held) => Ok(Response::new(rpc::Value { value: held })),
+            Err(e) => Err(e.into()),
         }
     }
 }
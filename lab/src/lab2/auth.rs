@@ -0,0 +1,240 @@
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tribbler::{
+    err::{ServerResult, TribblerError},
+    storage::{BinStorage, KeyValue},
+    trib::is_valid_username,
+};
+
+// the key of a user's bin holding the hash of the user's password
+const PASSWORD_KEY: &str = "password";
+
+// the bin mapping external identities to the users they sign in as; no
+// username can take this name
+const IDENTITY_BIN: &str = "@identities";
+
+/// Keeps the passwords users sign in with, and the external identities they
+/// may sign in as instead, next to the [tribbler::trib::Server] rather than in
+/// it, so the service itself knows nothing of them. A password is only ever
+/// kept as its argon2 hash.
+#[async_trait]
+pub trait Auth: Send + Sync {
+    /// Sets the password of a user, replacing the one the user had.
+    async fn set_password(&self, user: &str, password: &str) -> ServerResult<()>;
+
+    /// Sets the password of a user who has none yet, as one signing up, and
+    /// returns the hash it keeps; returns none, leaving the password as it
+    /// is, when the user has one already.
+    async fn claim_password(&self, user: &str, password: &str) -> ServerResult<Option<String>>;
+
+    /// Takes back the password [Auth::claim_password] kept as `hashed`, as
+    /// when the sign-up it was claimed for failed. A password set since is
+    /// left as it is.
+    async fn release_password(&self, user: &str, hashed: &str) -> ServerResult<()>;
+
+    /// Checks a password against the one the user set. A user who never set
+    /// a password, such as one who signs in through single sign-on only, has
+    /// none to check against, so no password passes; checking takes as long
+    /// either way, so it doesn't tell which users have a password.
+    async fn check_password(&self, user: &str, password: &str) -> ServerResult<bool>;
+
+    /// Names the user an external identity, such as a single sign-on
+    /// subject, signs in as, if it was linked to one.
+    async fn linked_user(&self, identity: &str) -> ServerResult<Option<String>>;
+
+    /// Links an external identity to a user, who it signs in as from then on.
+    async fn link_identity(&self, identity: &str, user: &str) -> ServerResult<()>;
+}
+
+/// Keeps the password hash of every user in the user's own bin, where a
+/// rename carries it along with the rest of the user's data.
+pub struct BinAuth {
+    pub bin_storage: Box<dyn BinStorage>,
+}
+
+#[async_trait]
+impl Auth for BinAuth {
+    async fn set_password(&self, user: &str, password: &str) -> ServerResult<()> {
+        check_credentials(user, password)?;
+        let hashed = hash(password).await?;
+        let user_bin = self.bin_storage.bin(user).await?;
+        user_bin
+            .set(&KeyValue {
+                key: PASSWORD_KEY.to_string(),
+                value: hashed,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_password(&self, user: &str, password: &str) -> ServerResult<Option<String>> {
+        check_credentials(user, password)?;
+        let hashed = hash(password).await?;
+        let user_bin = self.bin_storage.bin(user).await?;
+        // an emptied key is how a bin forgets, and an unset one holds ""
+        let held = user_bin.compare_and_swap(PASSWORD_KEY, "", &hashed).await?;
+        Ok(held.is_empty().then_some(hashed))
+    }
+
+    async fn release_password(&self, user: &str, hashed: &str) -> ServerResult<()> {
+        let user_bin = self.bin_storage.bin(user).await?;
+        user_bin.compare_and_swap(PASSWORD_KEY, hashed, "").await?;
+        Ok(())
+    }
+
+    async fn check_password(&self, user: &str, password: &str) -> ServerResult<bool> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        let user_bin = self.bin_storage.bin(user).await?;
+        match user_bin.get(PASSWORD_KEY).await? {
+            // an emptied key is how a bin forgets
+            Some(hashed) if !hashed.is_empty() => verify(password, hashed).await,
+            _ => refuse(password).await,
+        }
+    }
+
+    async fn linked_user(&self, identity: &str) -> ServerResult<Option<String>> {
+        let identity_bin = self.bin_storage.bin(IDENTITY_BIN).await?;
+        Ok(identity_bin
+            .get(identity)
+            .await?
+            .filter(|user| !user.is_empty()))
+    }
+
+    async fn link_identity(&self, identity: &str, user: &str) -> ServerResult<()> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        let identity_bin = self.bin_storage.bin(IDENTITY_BIN).await?;
+        identity_bin
+            .set(&KeyValue {
+                key: identity.to_string(),
+                value: user.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Keeps the password hashes in memory, for front-ends serving the reference
+/// server, which has no bins to keep them in.
+#[derive(Default)]
+pub struct MemAuth {
+    hashes: Mutex<HashMap<String, String>>,
+    identities: Mutex<HashMap<String, String>>,
+}
+
+#[async_trait]
+impl Auth for MemAuth {
+    async fn set_password(&self, user: &str, password: &str) -> ServerResult<()> {
+        check_credentials(user, password)?;
+        let hashed = hash(password).await?;
+        self.hashes.lock().unwrap().insert(user.to_string(), hashed);
+        Ok(())
+    }
+
+    async fn claim_password(&self, user: &str, password: &str) -> ServerResult<Option<String>> {
+        check_credentials(user, password)?;
+        let hashed = hash(password).await?;
+        let mut hashes = self.hashes.lock().unwrap();
+        if hashes.contains_key(user) {
+            return Ok(None);
+        }
+        hashes.insert(user.to_string(), hashed.clone());
+        Ok(Some(hashed))
+    }
+
+    async fn release_password(&self, user: &str, hashed: &str) -> ServerResult<()> {
+        let mut hashes = self.hashes.lock().unwrap();
+        if hashes.get(user).map(String::as_str) == Some(hashed) {
+            hashes.remove(user);
+        }
+        Ok(())
+    }
+
+    async fn check_password(&self, user: &str, password: &str) -> ServerResult<bool> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        let hashed = self.hashes.lock().unwrap().get(user).cloned();
+        match hashed {
+            Some(hashed) => verify(password, hashed).await,
+            None => refuse(password).await,
+        }
+    }
+
+    async fn linked_user(&self, identity: &str) -> ServerResult<Option<String>> {
+        Ok(self.identities.lock().unwrap().get(identity).cloned())
+    }
+
+    async fn link_identity(&self, identity: &str, user: &str) -> ServerResult<()> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        self.identities
+            .lock()
+            .unwrap()
+            .insert(identity.to_string(), user.to_string());
+        Ok(())
+    }
+}
+
+fn check_credentials(user: &str, password: &str) -> ServerResult<()> {
+    if !is_valid_username(user) {
+        return Err(TribblerError::InvalidUsername(user.to_string()));
+    }
+    if password.is_empty() {
+        return Err(TribblerError::InvalidPassword);
+    }
+    Ok(())
+}
+
+// hashes a password under a fresh salt; argon2 is slow on purpose, so the
+// work is kept off the async workers
+async fn hash(password: &str) -> ServerResult<String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hashed| hashed.to_string())
+            .map_err(|e| TribblerError::Unknown(e.to_string()))
+    })
+    .await
+    .map_err(|e| TribblerError::Unknown(e.to_string()))?
+}
+
+// refuses a password of a user who has none, after checking it against a
+// hash of another, so that it takes as long as refusing a wrong one
+async fn refuse(password: &str) -> ServerResult<bool> {
+    static DECOY: OnceLock<String> = OnceLock::new();
+    let decoy = match DECOY.get() {
+        Some(decoy) => decoy.clone(),
+        None => {
+            let decoy = hash("a password nobody has").await?;
+            DECOY.get_or_init(|| decoy).clone()
+        }
+    };
+    verify(password, decoy).await?;
+    Ok(false)
+}
+
+// checks a password against a hash made by hash
+async fn verify(password: &str, hashed: String) -> ServerResult<bool> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let hashed =
+            PasswordHash::new(&hashed).map_err(|e| TribblerError::Unknown(e.to_string()))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &hashed)
+            .is_ok())
+    })
+    .await
+    .map_err(|e| TribblerError::Unknown(e.to_string()))?
+}
@@ -0,0 +1,275 @@
+use crate::lab1::lab::new_client;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tribbler::{
+    addr,
+    config::BackupConfig,
+    err::TribResult,
+    storage::{KeyValue, Pattern, Storage},
+};
+
+/// Everything stored on a single backend at the time of the snapshot.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Snapshot {
+    pub backend: String,
+    pub taken_at: u64,
+    pub clock: u64,
+    pub kv: BTreeMap<String, String>,
+    pub lists: BTreeMap<String, Vec<String>>,
+}
+
+// one line of the backup log
+#[derive(Serialize, Debug)]
+struct BackupRecord<'a> {
+    backend: &'a str,
+    taken_at: u64,
+    ok: bool,
+    detail: String,
+}
+
+/// Returns the first scheduled time (in seconds since the unix epoch)
+/// strictly after `now`.
+pub fn next_run(now: u64, cfg: &BackupConfig) -> u64 {
+    let interval = cfg.interval_secs.max(1);
+    let offset = cfg.offset_secs % interval;
+    // how far `now` is past the last scheduled time
+    let phase = (now % interval + interval - offset) % interval;
+    now + (interval - phase)
+}
+
+/// Takes a snapshot of every backend on the configured schedule, forever.
+/// Every run snapshots the backends `backs` holds at the time.
+pub async fn run_schedule(backs: watch::Receiver<Vec<String>>, cfg: BackupConfig) {
+    loop {
+        let now = unix_now();
+        let next = next_run(now, &cfg);
+        tokio::time::sleep(Duration::from_secs(next - now)).await;
+        let backs = backs.borrow().clone();
+        backup_all(&backs, &cfg, next).await;
+    }
+}
+
+/// Snapshots every backend into `cfg.dir` and records the outcome of each
+/// snapshot in the backup log of that directory.
+pub async fn backup_all(backs: &[String], cfg: &BackupConfig, taken_at: u64) {
+    for back in backs {
+        let (ok, detail) = match backup_one(back, cfg, taken_at).await {
+            Ok(path) => {
+                info!("backed up {} to {}", back, path.display());
+                (true, path.display().to_string())
+            }
+            Err(e) => {
+                warn!("failed to back up {}: {}", back, e);
+                (false, e.to_string())
+            }
+        };
+        let record = BackupRecord {
+            backend: back,
+            taken_at,
+            ok,
+            detail,
+        };
+        if let Err(e) = append_log(Path::new(&cfg.dir), &record) {
+            warn!("failed to record the backup of {}: {}", back, e);
+        }
+    }
+}
+
+async fn backup_one(back: &str, cfg: &BackupConfig, taken_at: u64) -> TribResult<PathBuf> {
+    let client = new_client(&addr::uri(back)).await?;
+    let snapshot = dump(back, &*client, taken_at).await?;
+    fs::create_dir_all(&cfg.dir)?;
+    let name = format!("{}-{}.json", file_stem(back), taken_at);
+    let path = Path::new(&cfg.dir).join(name);
+    fs::write(&path, serde_json::to_vec_pretty(&snapshot)?)?;
+    Ok(path)
+}
+
+/// Reads every key-value pair and every list stored on a backend.
+pub async fn dump(back: &str, storage: &dyn Storage, taken_at: u64) -> TribResult<Snapshot> {
+    let everything = Pattern {
+        prefix: "".to_string(),
+        suffix: "".to_string(),
+    };
+    let mut snapshot = Snapshot {
+        backend: back.to_string(),
+        taken_at,
+        clock: storage.clock(0).await?,
+        ..Snapshot::default()
+    };
+    for key in storage.keys(&everything).await?.0 {
+        if let Some(value) = storage.get(&key).await? {
+            snapshot.kv.insert(key, value);
+        }
+    }
+    for key in storage.list_keys(&everything).await?.0 {
+        let list = storage.list_get(&key).await?.0;
+        snapshot.lists.insert(key, list);
+    }
+    Ok(snapshot)
+}
+
+/// Loads the newest snapshot of the backend `back` found in `dir` into
+/// `storage`, returning where it was read from, or none when `dir` holds no
+/// snapshot of the backend. The keys and lists of the snapshot replace those
+/// `storage` already holds, and the clock of `storage` is moved up to the
+/// clock of the snapshot.
+pub async fn restore(dir: &str, back: &str, storage: &dyn Storage) -> TribResult<Option<PathBuf>> {
+    let prefix = format!("{}-", file_stem(back));
+    let mut newest: Option<(u64, PathBuf)> = None;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let taken_at = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|rest| rest.strip_suffix(".json"))
+            .and_then(|taken_at| taken_at.parse::<u64>().ok());
+        if let Some(taken_at) = taken_at {
+            if newest.as_ref().is_none_or(|(newest, _)| taken_at > *newest) {
+                newest = Some((taken_at, path));
+            }
+        }
+    }
+    let path = match newest {
+        Some((_, path)) => path,
+        None => return Ok(None),
+    };
+    let snapshot: Snapshot = serde_json::from_slice(&fs::read(&path)?)?;
+    for (key, value) in snapshot.kv {
+        storage.set(&KeyValue { key, value }).await?;
+    }
+    for (key, list) in snapshot.lists {
+        // appending onto what is already there would repeat it
+        let mut held = storage.list_get(&key).await?.0;
+        held.sort();
+        held.dedup();
+        for value in held {
+            storage
+                .list_remove(&KeyValue {
+                    key: key.clone(),
+                    value,
+                })
+                .await?;
+        }
+        for value in list {
+            storage
+                .list_append(&KeyValue {
+                    key: key.clone(),
+                    value,
+                })
+                .await?;
+        }
+    }
+    storage.clock(snapshot.clock).await?;
+    Ok(Some(path))
+}
+
+// the name the snapshots of a backend start with
+fn file_stem(back: &str) -> String {
+    back.replace([':', '/'], "_")
+}
+
+fn append_log(dir: &Path, record: &BackupRecord) -> TribResult<()> {
+    fs::create_dir_all(dir)?;
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("backup.log"))?;
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    log.write_all(&line)?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dump, next_run, restore};
+    use tribbler::config::BackupConfig;
+    use tribbler::storage::{KeyList, KeyString, KeyValue, MemStorage, Storage};
+
+    fn cfg(interval_secs: u64, offset_secs: u64) -> BackupConfig {
+        BackupConfig {
+            dir: "".to_string(),
+            interval_secs,
+            offset_secs,
+        }
+    }
+
+    #[test]
+    fn next_run_follows_the_schedule() {
+        assert_eq!(60, next_run(0, &cfg(60, 0)));
+        assert_eq!(120, next_run(60, &cfg(60, 0)));
+        assert_eq!(120, next_run(61, &cfg(60, 0)));
+        // daily at 02:00
+        assert_eq!(7_200, next_run(0, &cfg(86_400, 7_200)));
+        assert_eq!(93_600, next_run(7_200, &cfg(86_400, 7_200)));
+    }
+
+    #[tokio::test]
+    async fn dump_reads_kv_and_lists() {
+        let storage = MemStorage::new();
+        let kv = |k: &str, v: &str| KeyValue {
+            key: k.to_string(),
+            value: v.to_string(),
+        };
+        storage.set(&kv("a", "1")).await.unwrap();
+        storage.list_append(&kv("l", "x")).await.unwrap();
+        storage.list_append(&kv("l", "y")).await.unwrap();
+
+        let snapshot = dump("back", &storage, 42).await.unwrap();
+        assert_eq!(Some(&"1".to_string()), snapshot.kv.get("a"));
+        assert_eq!(
+            Some(&vec!["x".to_string(), "y".to_string()]),
+            snapshot.lists.get("l")
+        );
+        assert_eq!(42, snapshot.taken_at);
+    }
+
+    #[tokio::test]
+    async fn restore_loads_the_newest_snapshot() {
+        let dir = std::env::temp_dir().join(format!("trib-restore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, value: &str, clock: u64| {
+            let snapshot = format!(
+                r#"{{"backend":"localhost:3000","taken_at":0,"clock":{},"kv":{{"a":"{}"}},"lists":{{"l":["x","y"]}}}}"#,
+                clock, value
+            );
+            std::fs::write(dir.join(name), snapshot).unwrap();
+        };
+        write("localhost_3000-60.json", "old", 5);
+        write("localhost_3000-120.json", "new", 9);
+        write("localhost_3001-180.json", "other", 12);
+
+        let storage = MemStorage::new();
+        storage.list_append(&KeyValue::new("l", "x")).await.unwrap();
+        let dir_name = dir.to_str().unwrap();
+        let path = restore(dir_name, "localhost:3000", &storage).await.unwrap();
+        assert_eq!(Some(dir.join("localhost_3000-120.json")), path);
+        assert_eq!(Some("new".to_string()), storage.get("a").await.unwrap());
+        assert_eq!(vec!["x", "y"], storage.list_get("l").await.unwrap().0);
+        assert!(storage.clock(0).await.unwrap() >= 9);
+        restore(dir_name, "localhost:3000", &storage).await.unwrap();
+        assert_eq!(vec!["x", "y"], storage.list_get("l").await.unwrap().0);
+
+        let empty = MemStorage::new();
+        assert_eq!(
+            None,
+            restore(dir_name, "localhost:3002", &empty).await.unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,220 @@
 use super::bin_user_client::BinUserClient;
+use super::keeper::{layout, replicas, ring};
+use super::replicated_client::ReplicatedClient;
+use crate::keeper::{keeper_client::KeeperClient, ClusterView, WatchRequest};
+use crate::lab1::client::channel;
 use crate::lab1::lab::new_client;
 use async_trait::async_trait;
+use log::{debug, info};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+use tokio::sync::watch;
 use tribbler::{
     self,
+    addr,
     colon::escape,
-    err::TribResult,
+    config::{Config, Placement},
+    err::{Context, TribResult},
     storage::{BinStorage, Storage}, // to implement the RPCs
 };
 
+/// The latest cluster view pushed by a keeper, shared between the bin client
+/// and the front-end. `None` until the first view arrives.
+pub type SharedView = Arc<RwLock<Option<ClusterView>>>;
+
 // declare a new struct and add fileds to it (addr)
 pub struct BinStorageClient {
-    pub backs: Vec<String>, // store the storage clients
+    pub backs: Arc<RwLock<Vec<String>>>, // store the storage clients
+    pub view: SharedView,                // the latest cluster view pushed by a keeper, if any
+    pub placement: Placement,            // how many backends a bin is kept on, and over which slots
+}
+
+impl BinStorageClient {
+    pub fn new(backs: Vec<String>) -> BinStorageClient {
+        BinStorageClient::with_placement(backs, Placement::default())
+    }
+
+    /// Like [BinStorageClient::new], but keeps the bins the way `placement`
+    /// says, which has to be the way of the keepers of the cluster.
+    pub fn with_placement(backs: Vec<String>, placement: Placement) -> BinStorageClient {
+        BinStorageClient {
+            backs: Arc::new(RwLock::new(backs)),
+            view: Arc::new(RwLock::new(None)),
+            placement,
+        }
+    }
+
+    /// Takes on the backends of whichever config `config` holds, for the bins
+    /// routed without a cluster view, until the client is dropped.
+    pub fn follow_backs(&self, mut config: watch::Receiver<Config>) {
+        let backs = Arc::downgrade(&self.backs);
+        tokio::spawn(async move {
+            while config.changed().await.is_ok() {
+                let shared = match backs.upgrade() {
+                    Some(shared) => shared,
+                    None => return, // the bin client was dropped
+                };
+                *shared.write().unwrap() = http_backs(&config.borrow_and_update().backs);
+            }
+        });
+    }
+
+    /// Subscribes to the cluster view of the given keepers in the background,
+    /// so bins are routed around backends the keeper considers dead. The
+    /// subscription ends when the client is dropped.
+    pub fn watch(&self, keepers: Vec<String>) {
+        if keepers.is_empty() {
+            return;
+        }
+        let (_, config) = watch::channel(Config {
+            keepers,
+            ..Config::default()
+        });
+        self.follow(config);
+    }
+
+    /// Like [BinStorageClient::watch], but subscribes to the keepers of
+    /// whichever config `config` holds at the time, so that a reloaded config
+    /// moves the subscription over to its keepers.
+    pub fn follow(&self, config: watch::Receiver<Config>) {
+        let view = Arc::downgrade(&self.view);
+        tokio::spawn(watch_keepers(config, view));
+    }
+
+    // the addresses of the backends keeping the bins of the given hash value,
+    // the one serving its slot first: the keeper's view names the backends
+    // once it arrives, which may since have changed from the ones the client
+    // was made with, along with those which are up
+    fn route(&self, hash_value: usize) -> (Vec<String>, usize) {
+        let count = self.placement.replication_factor;
+        if let Some(view) = &*self.view.read().unwrap() {
+            let backend_num = view.backs.len();
+            if backend_num > 0 {
+                let ring = match !view.ring.is_empty() && view.ring.len() % backend_num == 0 {
+                    true => view.ring.clone(),
+                    false => layout(&view.backs, self.placement.virtual_nodes),
+                };
+                let addrs = replicas(&ring, hash_value % ring.len(), count)
+                    .into_iter()
+                    .map(|i| addr::uri(&view.backs[i]))
+                    .collect();
+                return (addrs, count.min(backend_num));
+            }
+        }
+        let backs = self.backs.read().unwrap();
+        let ring = layout(&backs, self.placement.virtual_nodes);
+        let addrs = replicas(&ring, hash_value % ring.len(), count)
+            .into_iter()
+            .map(|i| backs[i].clone())
+            .collect();
+        (addrs, count.min(backs.len()))
+    }
+}
+
+/// The hash value every bin client places the bin `name` by.
+pub fn bin_hash(name: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(name.as_bytes());
+    hasher.finish() as usize
+}
+
+/// The backends keeping the bin `name`, as indices into `backs`, the one
+/// serving it first, when `eligible` flags the backends the keeper has in the
+/// write path. This is where a bin client routes the bin once the keeper
+/// published its view; empty when no backend is eligible.
+pub fn bin_replicas(
+    name: &str,
+    backs: &[String],
+    eligible: &[bool],
+    placement: Placement,
+) -> Vec<usize> {
+    let ring = ring(eligible, &layout(backs, placement.virtual_nodes));
+    if ring.is_empty() {
+        return vec![];
+    }
+    replicas(
+        &ring,
+        bin_hash(name) % ring.len(),
+        placement.replication_factor,
+    )
+}
+
+/// The URIs the backends `backs` are dialed with, as [addr::uri] has them.
+pub fn http_backs(backs: &[String]) -> Vec<String> {
+    backs.iter().map(|back| addr::uri(back)).collect()
+}
+
+// follows the view stream of the first reachable keeper of the config, moving
+// on to the next keeper whenever the stream breaks, and starting over from the
+// first whenever the config is reloaded, until the bin client goes away
+async fn watch_keepers(
+    mut config: watch::Receiver<Config>,
+    view: Weak<RwLock<Option<ClusterView>>>,
+) {
+    loop {
+        let keepers = config.borrow_and_update().keepers.clone();
+        let mut reloaded = false;
+        for keeper in keepers.iter() {
+            let mut client = match channel(&addr::uri(keeper)).await {
+                Ok(channel) => KeeperClient::new(channel),
+                Err(e) => {
+                    debug!("failed to connect to keeper {}: {}", keeper, e);
+                    continue;
+                }
+            };
+            let mut stream = match client.watch(WatchRequest {}).await {
+                Ok(response) => response.into_inner(),
+                Err(e) => {
+                    debug!("failed to watch keeper {}: {}", keeper, e);
+                    continue;
+                }
+            };
+            loop {
+                let update = tokio::select! {
+                    update = stream.message() => update,
+                    _ = reload(&mut config) => {
+                        reloaded = true;
+                        break;
+                    }
+                };
+                let update = match update {
+                    Ok(Some(update)) => update,
+                    _ => break,
+                };
+                let shared = match view.upgrade() {
+                    Some(shared) => shared,
+                    None => return, // the bin client was dropped
+                };
+                info!("cluster view epoch {} from keeper {}", update.epoch, keeper);
+                *shared.write().unwrap() = Some(update);
+            }
+            if reloaded {
+                break;
+            }
+        }
+        if view.strong_count() == 0 {
+            return;
+        }
+        if !reloaded {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+// resolves when the config is reloaded with other keepers, and never once no
+// more reloads can come
+async fn reload(config: &mut watch::Receiver<Config>) {
+    let keepers = config.borrow().keepers.clone();
+    loop {
+        if config.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        if config.borrow().keepers != keepers {
+            return;
+        }
+    }
 }
 
 // We escape the name because BinStorage will be tested separately, and invalid usernames that include ":" may be sent.
@@ -21,21 +223,76 @@ pub struct BinStorageClient {
 impl BinStorage for BinStorageClient {
     async fn bin(&self, name: &str) -> TribResult<Box<dyn Storage>> {
         // get the hash value
-        let mut hasher = DefaultHasher::new();
-        hasher.write(name.as_bytes());
-        let hash_value = hasher.finish() as usize;
-
-        // make the hash value in the range
-        let backend_num = self.backs.len();
-        let backend_id = hash_value % backend_num;
-        let addr = &self.backs[backend_id];
-        let storage = new_client(addr).await?;
-
-        // wrap the storage client as a bin storage client
-        let user_storage = BinUserClient {
-            name: escape(name),
-            bin_storage: storage,
+        let hash_value = bin_hash(name);
+
+        // make the hash value in the range, skipping the backends which are down
+        let (addrs, wanted) = self.route(hash_value);
+        let mut replicas: Vec<Box<dyn Storage>> = Vec::with_capacity(addrs.len());
+        for addr in addrs.iter() {
+            // wrap the storage client as a bin storage client
+            replicas.push(Box::new(BinUserClient {
+                name: escape(name),
+                bin_storage: new_client(addr)
+                    .await
+                    .with_context(|| format!("bin {} on {}", name, addr))?,
+                addr: addr.clone(),
+            }));
+        }
+        if wanted == 1 && replicas.len() == 1 {
+            return Ok(replicas.pop().unwrap());
+        }
+        Ok(Box::new(ReplicatedClient {
+            replicas,
+            consistency: self.placement.consistency,
+            wanted,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bin_hash, bin_replicas, BinStorageClient};
+    use tribbler::config::Placement;
+
+    #[test]
+    fn bin_replicas_route_like_the_client() {
+        let backs: Vec<String> = (0..5).map(|i| format!("localhost:{}", 3000 + i)).collect();
+        let placement = Placement {
+            replication_factor: 3,
+            virtual_nodes: 4,
+            ..Placement::default()
+        };
+        let client = BinStorageClient::with_placement(backs.clone(), placement);
+        for name in ["alice", "bob", "h8liu", "z"] {
+            let placed = bin_replicas(name, &backs, &[true; 5], placement);
+            let (routed, wanted) = client.route(bin_hash(name));
+            assert_eq!(3, wanted);
+            let routed: Vec<usize> = routed
+                .iter()
+                .map(|addr| backs.iter().position(|b| b == addr).unwrap())
+                .collect();
+            assert_eq!(routed, placed);
+        }
+    }
+
+    #[test]
+    fn only_the_bins_of_a_removed_backend_move() {
+        let backs: Vec<String> = (0..4).map(|i| format!("localhost:{}", 3000 + i)).collect();
+        let placement = Placement {
+            replication_factor: 1,
+            virtual_nodes: 8,
+            ..Placement::default()
         };
-        return Ok(Box::new(user_storage));
+        let without_2 = [true, true, false, true];
+        for i in 0..100 {
+            let name = format!("user{}", i);
+            let before = bin_replicas(&name, &backs, &[true; 4], placement);
+            let after = bin_replicas(&name, &backs, &without_2, placement);
+            assert!(!after.contains(&2));
+            if before != vec![2] {
+                assert_eq!(before, after);
+            }
+        }
+        assert!(bin_replicas("alice", &backs, &[false; 4], placement).is_empty());
     }
 }
@@ -1,12 +1,36 @@
 use async_trait::async_trait;
+use metrics::{counter, histogram};
+use std::future::Future;
+use std::time::Instant;
 use tribbler::{
     colon::escape,
-    err::TribResult,
+    err::{Context, TribResult},
     storage::{KeyList, KeyString, KeyValue, List, Pattern, Storage},
 };
 pub struct BinUserClient {
     pub name: String,                  // store the name of the client
     pub bin_storage: Box<dyn Storage>, // store the storage
+    pub addr: String,                  // the backend the bin is kept on, for errors to name
+}
+
+impl BinUserClient {
+    // times a call to the backend and counts it if it fails, by operation, for
+    // whoever collects the metrics (trib-front serves them at /metrics); a
+    // failed call says which bin, backend, and operation it was
+    async fn timed<T>(
+        &self,
+        op: &'static str,
+        call: impl Future<Output = TribResult<T>>,
+    ) -> TribResult<T> {
+        let start = Instant::now();
+        let result = call.await;
+        histogram!("tribbler_backend_rpc_duration_seconds", "op" => op)
+            .record(start.elapsed().as_secs_f64());
+        if result.is_err() {
+            counter!("tribbler_backend_rpc_errors_total", "op" => op).increment(1);
+        }
+        result.with_context(|| format!("{} in bin {} on {}", op, self.name, self.addr))
+    }
 }
 
 // We escape the name because BinStorage will be tested separately, and invalid keys that include ":" may be sent.
@@ -14,31 +38,35 @@ pub struct BinUserClient {
 #[async_trait]
 impl KeyString for BinUserClient {
     async fn get(&self, key: &str) -> TribResult<Option<String>> {
-        let prefix_key = (&self.name).to_string() + "::" + &escape(key);
-        return self.bin_storage.get(&prefix_key).await;
+        let prefix_key = self.name.to_string() + "::" + &escape(key);
+        return self.timed("get", self.bin_storage.get(&prefix_key)).await;
     }
 
     async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
-        let prefix_key = (&self.name).to_string() + "::" + &escape(&kv.key);
+        let prefix_key = self.name.to_string() + "::" + &escape(&kv.key);
         println!("{}", prefix_key);
         return self
-            .bin_storage
-            .set(&KeyValue {
-                key: prefix_key,
-                value: (&kv.value).to_string(),
-            })
+            .timed(
+                "set",
+                self.bin_storage.set(&KeyValue {
+                    key: prefix_key,
+                    value: kv.value.to_string(),
+                }),
+            )
             .await;
     }
 
     async fn keys(&self, p: &Pattern) -> TribResult<List> {
-        let prefix_prefix = (&self.name).to_string() + "::" + &p.prefix;
+        let prefix_prefix = self.name.to_string() + "::" + &p.prefix;
 
         let output_list = self
-            .bin_storage
-            .keys(&Pattern {
-                prefix: prefix_prefix,
-                suffix: (&p.suffix).to_string(),
-            })
+            .timed(
+                "keys",
+                self.bin_storage.keys(&Pattern {
+                    prefix: prefix_prefix,
+                    suffix: p.suffix.to_string(),
+                }),
+            )
             .await;
 
         match output_list {
@@ -47,7 +75,7 @@ impl KeyString for BinUserClient {
                 for key in output.0 {
                     println!("{}", key);
                     let pass_length = self.name.len() + 2;
-                    output_vec.push((&key[pass_length..]).to_string());
+                    output_vec.push(key[pass_length..].to_string());
                 }
                 return Ok(List(output_vec));
             }
@@ -56,45 +84,71 @@ impl KeyString for BinUserClient {
             }
         }
     }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        let prefix_key = self.name.to_string() + "::" + &escape(key);
+        return self
+            .timed("incr", self.bin_storage.incr(&prefix_key, delta))
+            .await;
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let prefix_key = self.name.to_string() + "::" + &escape(key);
+        return self
+            .timed(
+                "compare_and_swap",
+                self.bin_storage
+                    .compare_and_swap(&prefix_key, expected, value),
+            )
+            .await;
+    }
 }
 
 #[async_trait]
 impl KeyList for BinUserClient {
     async fn list_get(&self, key: &str) -> TribResult<List> {
-        let prefix_key = (&self.name).to_string() + "::" + &escape(key);
-        return self.bin_storage.list_get(&prefix_key).await;
+        let prefix_key = self.name.to_string() + "::" + &escape(key);
+        return self
+            .timed("list_get", self.bin_storage.list_get(&prefix_key))
+            .await;
     }
 
     async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
-        let prefix_key = (&self.name).to_string() + "::" + &escape(&kv.key);
+        let prefix_key = self.name.to_string() + "::" + &escape(&kv.key);
         return self
-            .bin_storage
-            .list_append(&KeyValue {
-                key: prefix_key,
-                value: (&kv.value).to_string(),
-            })
+            .timed(
+                "list_append",
+                self.bin_storage.list_append(&KeyValue {
+                    key: prefix_key,
+                    value: kv.value.to_string(),
+                }),
+            )
             .await;
     }
 
     async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
-        let prefix_key = (&self.name).to_string() + "::" + &escape(&kv.key);
+        let prefix_key = self.name.to_string() + "::" + &escape(&kv.key);
         return self
-            .bin_storage
-            .list_remove(&KeyValue {
-                key: prefix_key,
-                value: (&kv.value).to_string(),
-            })
+            .timed(
+                "list_remove",
+                self.bin_storage.list_remove(&KeyValue {
+                    key: prefix_key,
+                    value: kv.value.to_string(),
+                }),
+            )
             .await;
     }
 
     async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
-        let prefix_prefix = (&self.name).to_string() + "::" + &p.prefix;
+        let prefix_prefix = self.name.to_string() + "::" + &p.prefix;
         let output_list = self
-            .bin_storage
-            .list_keys(&Pattern {
-                prefix: prefix_prefix,
-                suffix: (&p.suffix).to_string(),
-            })
+            .timed(
+                "list_keys",
+                self.bin_storage.list_keys(&Pattern {
+                    prefix: prefix_prefix,
+                    suffix: p.suffix.to_string(),
+                }),
+            )
             .await;
 
         match output_list {
@@ -102,7 +156,7 @@ impl KeyList for BinUserClient {
                 let mut output_vec = Vec::<String>::new();
                 for key in output.0 {
                     let pass_length = self.name.len() + 2;
-                    output_vec.push((&key[pass_length..]).to_string());
+                    output_vec.push(key[pass_length..].to_string());
                 }
                 return Ok(List(output_vec));
             }
@@ -123,6 +177,6 @@ impl KeyList for BinUserClient {
 #[async_trait]
 impl Storage for BinUserClient {
     async fn clock(&self, at_least: u64) -> TribResult<u64> {
-        return self.bin_storage.clock(at_least).await;
+        return self.timed("clock", self.bin_storage.clock(at_least)).await;
     }
 }
@@ -0,0 +1,169 @@
+//! Finding the backends of a cluster through DNS: a `backs` entry of the
+//! form `dns+srv://<name>` stands for every target of the SRV records of
+//! `<name>`, so that a cluster whose backends come and go is described by
+//! its DNS zone rather than by a list kept in every config.
+use hickory_resolver::TokioAsyncResolver;
+use log::{info, warn};
+use std::time::Duration;
+use tokio::sync::watch;
+use tribbler::{
+    config::Config,
+    err::{TribResult, TribblerError},
+};
+
+/// the scheme of a `backs` entry naming SRV records
+pub const DNS_SRV: &str = "dns+srv://";
+
+/// how often the backends found through DNS are looked up again
+pub const RESOLVE_EVERY: Duration = Duration::from_secs(30);
+
+/// Returns true when some of `backs` are to be found through DNS.
+pub fn needs_discovery(backs: &[String]) -> bool {
+    backs.iter().any(|back| srv_name(back).is_some())
+}
+
+/// Resolves `backs` into the addresses of the backends: entries naming SRV
+/// records become the `host:port` of every target, while plain addresses are
+/// kept. The addresses come out sorted, and each once, so that every client
+/// resolving the same records lays the hash slots out alike.
+pub async fn resolve_backs(backs: &[String]) -> TribResult<Vec<String>> {
+    if !needs_discovery(backs) {
+        return Ok(backs.to_vec());
+    }
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()
+        .map_err(|e| TribblerError::Unknown(format!("cannot read the DNS config: {}", e)))?;
+    let mut addrs = vec![];
+    for back in backs {
+        let name = match srv_name(back) {
+            Some(name) => name,
+            None => {
+                addrs.push(back.clone());
+                continue;
+            }
+        };
+        let records = resolver
+            .srv_lookup(name)
+            .await
+            .map_err(|e| TribblerError::Unknown(format!("cannot look up {}: {}", name, e)))?;
+        let targets: Vec<String> = records
+            .iter()
+            .map(|srv| target(&srv.target().to_utf8(), srv.port()))
+            .collect();
+        if targets.is_empty() {
+            return Err(TribblerError::Unknown(format!(
+                "{} has no SRV records",
+                name
+            )));
+        }
+        addrs.extend(targets);
+    }
+    addrs.sort();
+    addrs.dedup();
+    Ok(addrs)
+}
+
+/// Hands out the configs `config` holds with their backends resolved by
+/// [resolve_backs], resolving them again every [RESOLVE_EVERY] and whenever
+/// the config changes. The backends are resolved once before this returns,
+/// which fails when they can't be; a later lookup which fails is reported,
+/// and the backends last found kept. A config naming no SRV records is
+/// handed out as it is.
+pub async fn discover(mut config: watch::Receiver<Config>) -> TribResult<watch::Receiver<Config>> {
+    let named = config.borrow_and_update().clone();
+    if !needs_discovery(&named.backs) {
+        return Ok(config);
+    }
+    let resolved = Config {
+        backs: resolve_backs(&named.backs).await?,
+        ..named.clone()
+    };
+    info!("found backends {:?} for {:?}", resolved.backs, named.backs);
+    let (tx, rx) = watch::channel(resolved);
+    tokio::spawn(async move {
+        let mut named = named;
+        // whether the config can change still
+        let mut following = true;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(RESOLVE_EVERY) => {}
+                changed = config.changed(), if following => match changed {
+                    Ok(()) => named = config.borrow_and_update().clone(),
+                    Err(_) => {
+                        following = false;
+                        continue;
+                    }
+                },
+            }
+            if tx.is_closed() {
+                return; // nothing follows the backends anymore
+            }
+            let backs = match resolve_backs(&named.backs).await {
+                Ok(backs) => backs,
+                Err(e) => {
+                    warn!(
+                        "keeping the backends, as {:?} failed to resolve: {}",
+                        named.backs, e
+                    );
+                    continue;
+                }
+            };
+            tx.send_if_modified(|current| {
+                let update = Config {
+                    backs,
+                    ..named.clone()
+                };
+                if current.backs == update.backs && current.keepers == update.keepers {
+                    return false;
+                }
+                info!("found backends {:?} for {:?}", update.backs, named.backs);
+                *current = update;
+                true
+            });
+        }
+    });
+    Ok(rx)
+}
+
+// the name of the SRV records a `backs` entry stands for, if it names any
+fn srv_name(back: &str) -> Option<&str> {
+    back.strip_prefix(DNS_SRV)
+        .map(|name| name.trim_end_matches('/'))
+        .filter(|name| !name.is_empty())
+}
+
+// the address of an SRV target, without the trailing dot of its name
+fn target(name: &str, port: u16) -> String {
+    format!("{}:{}", name.trim_end_matches('.'), port)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{needs_discovery, resolve_backs, srv_name, target};
+
+    #[test]
+    fn test_srv_name() {
+        assert_eq!(
+            Some("backends.trib.local"),
+            srv_name("dns+srv://backends.trib.local")
+        );
+        assert_eq!(
+            Some("_trib._tcp.local"),
+            srv_name("dns+srv://_trib._tcp.local/")
+        );
+        assert_eq!(None, srv_name("dns+srv://"));
+        assert_eq!(None, srv_name("localhost:3000"));
+        assert!(needs_discovery(&[
+            "localhost:3000".to_string(),
+            "dns+srv://b.local".to_string()
+        ]));
+        assert!(!needs_discovery(&["localhost:3000".to_string()]));
+        assert_eq!("back-1.trib.local:3000", target("back-1.trib.local.", 3000));
+    }
+
+    #[tokio::test]
+    async fn test_plain_backs_kept() {
+        let backs = vec!["localhost:3001".to_string(), "localhost:3000".to_string()];
+        // nothing to look up, so the order is the config's
+        assert_eq!(backs, resolve_backs(&backs).await.unwrap());
+    }
+}
@@ -1,38 +1,911 @@
+use super::bin_client::SharedView;
 use async_trait::async_trait;
+use log::warn;
 use serde_json;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::string::String;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
 use tribbler::{
     self,
-    err::{TribResult, TribblerError},
+    config::Limits,
+    err::{ServerResult, TribblerError},
     storage::{BinStorage, KeyValue, Pattern},
+    time::{SystemClock, TimeSource},
     trib::{
-        is_valid_username, Server, Trib, MAX_FOLLOWING, MAX_TRIB_FETCH, MAX_TRIB_LEN, MIN_LIST_USER,
+        first_trending_slot, hashtags, is_valid_username, mentioned_users, normalize_tag,
+        top_trends, trending_slot, trib_order, validate_attachments, Attachment, Draft,
+        FollowLogEntry, FolloweeSet, Notification, NotificationKind, Profile, ScheduledPost,
+        Server, Trend, Trib, TribRef, UserExport, MAX_DRAFTS,
     },
 };
 
+/// How long a front-end trusts that a user it has seen signed up still is,
+/// before asking the general bin again.
+pub const SIGNUP_TTL: Duration = Duration::from_secs(30);
+
+// the most users a SignupCache remembers
+const SIGNUP_CACHE_CAPACITY: usize = 10_000;
+
+// the bin counting the uses of hashtags per trending slot, as "slot_tag"
+// counters; no username or hashtag can take this name
+const TRENDING_BIN: &str = "@trending";
+
+// the bin indexing scheduled posts, as "publish_at::user" entries of its
+// "due" list, so the keeper finds them without visiting every bin
+const SCHEDULED_BIN: &str = "@scheduled";
+
 pub struct FrontendServer {
     pub bin_storage: Box<dyn BinStorage>,
+    pub view: Option<SharedView>, // the keeper's view of the cluster, used to gate writes
+    pub signups: SignupCache, // users verified to have signed up, saves a trip to the general bin
+    pub limits: Limits,       // the limits enforced on users
+    pub time: Arc<dyn TimeSource>, // what tribs, activity, and signups are timed by
+}
+
+/// The users a front-end verified to have signed up, each trusted for a
+/// while. Only positive answers are cached, so a new user is seen right away.
+pub struct SignupCache {
+    ttl: Duration,
+    verified: Mutex<HashMap<String, Instant>>,
+    time: Arc<dyn TimeSource>,
+}
+
+impl SignupCache {
+    pub fn new(ttl: Duration) -> SignupCache {
+        SignupCache::with_time(ttl, Arc::new(SystemClock))
+    }
+
+    /// Like [SignupCache::new], but the entries age by `time`.
+    pub fn with_time(ttl: Duration, time: Arc<dyn TimeSource>) -> SignupCache {
+        SignupCache {
+            ttl,
+            verified: Mutex::new(HashMap::new()),
+            time,
+        }
+    }
+
+    fn contains(&self, user: &str) -> ServerResult<bool> {
+        let verified = self.verified()?;
+        let now = self.time.instant();
+        Ok(match verified.get(user) {
+            Some(at) => now - *at < self.ttl,
+            None => false,
+        })
+    }
+
+    fn insert(&self, user: &str) -> ServerResult<()> {
+        let mut verified = self.verified()?;
+        let now = self.time.instant();
+        if verified.len() >= SIGNUP_CACHE_CAPACITY {
+            let ttl = self.ttl;
+            verified.retain(|_, at| now - *at < ttl);
+            if verified.len() >= SIGNUP_CACHE_CAPACITY {
+                verified.clear();
+            }
+        }
+        verified.insert(user.to_string(), now);
+        Ok(())
+    }
+
+    fn remove(&self, user: &str) -> ServerResult<()> {
+        self.verified()?.remove(user);
+        Ok(())
+    }
+
+    // the users verified so far, unless a thread panicked holding them
+    fn verified(&self) -> ServerResult<MutexGuard<'_, HashMap<String, Instant>>> {
+        self.verified
+            .lock()
+            .map_err(|e| TribblerError::CacheFailure(format!("signup cache: {}", e)))
+    }
+}
+
+impl FrontendServer {
+    // Stores a new trib in the bin of who and returns it. The caller
+    // validates the trib.
+    async fn publish(
+        &self,
+        who: &str,
+        message: &str,
+        clock: u64,
+        retrib_of: Option<TribRef>,
+        reply_to: Option<TribRef>,
+        attachments: Vec<Attachment>,
+    ) -> ServerResult<Trib> {
+        // use the user bin to store his trib
+        let who_bin = self.bin_storage.bin(who).await?;
+        let storage_clock = who_bin.clock(clock).await?; // get the clock from the storage
+
+        // create the trib
+        let trib = Trib {
+            user: who.to_string(),
+            message: message.to_string(),
+            time: self.time.now()?,
+            clock: storage_clock,
+            revision: 0,
+            retrib_of,
+            reply_to,
+            attachments,
+        };
+        self.store_trib(&trib).await?;
+        Ok(trib)
+    }
+
+    // Stores a trib, clock and all, as posted by its user: on their trib
+    // list, and by reference on the home timelines it fans out to and in the
+    // indexes of its mentions and hashtags.
+    async fn store_trib(&self, trib: &Trib) -> ServerResult<()> {
+        let who = trib.user.as_str();
+        let message = trib.message.as_str();
+        let who_bin = self.bin_storage.bin(who).await?;
+
+        // store as the user's posted trib
+        let trib_string = serde_json::to_string(&trib)?;
+        who_bin
+            .list_append(&KeyValue {
+                key: "tribs".to_string(),
+                value: trib_string.to_string(),
+            })
+            .await?;
+
+        // push it to the home timelines of the user and the followers
+        let trib_ref = serde_json::to_string(&TribRef {
+            user: who.to_string(),
+            clock: trib.clock,
+        })?;
+        self.fan_out(who, Some(&trib_ref), None).await?;
+
+        // index it in the bins of the mentioned users and of its hashtags
+        for user in mentioned_users(message) {
+            // only signed up users get to know about their mentions
+            if !self.is_signed_up(&user).await? {
+                continue;
+            }
+            let user_bin = self.bin_storage.bin(&user).await?;
+            user_bin
+                .list_append(&KeyValue {
+                    key: "mentions".to_string(),
+                    value: trib_ref.clone(),
+                })
+                .await?;
+            let mention = TribRef {
+                user: who.to_string(),
+                clock: trib.clock,
+            };
+            self.notify(&user, NotificationKind::Mention, who, Some(mention))
+                .await?;
+        }
+        let trending_bin = self.bin_storage.bin(TRENDING_BIN).await?;
+        let slot = trending_slot(trib.time);
+        for tag in hashtags(message) {
+            let tag_bin = self.bin_storage.bin(&("#".to_owned() + &tag)).await?;
+            tag_bin
+                .list_append(&KeyValue {
+                    key: "tribs".to_string(),
+                    value: trib_ref.clone(),
+                })
+                .await?;
+            trending_bin
+                .incr(&(slot.to_string() + "_" + &tag), 1)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Lets user know that from did something involving them, unless from is
+    // the user themself. The notifications are kept as JSON in the
+    // "notifications" list of the user, clocked by the bin of the user, and
+    // counted by its "unread" counter until marked read.
+    async fn notify(
+        &self,
+        user: &str,
+        kind: NotificationKind,
+        from: &str,
+        trib: Option<TribRef>,
+    ) -> ServerResult<()> {
+        if user == from {
+            return Ok(());
+        }
+        let user_bin = self.bin_storage.bin(user).await?;
+        let notification = Notification {
+            kind,
+            from: from.to_string(),
+            trib,
+            clock: user_bin.clock(1).await?,
+            read: false,
+        };
+        user_bin
+            .list_append(&KeyValue {
+                key: "notifications".to_string(),
+                value: serde_json::to_string(&notification)?,
+            })
+            .await?;
+        user_bin.incr("unread", 1).await?;
+        Ok(())
+    }
+
+    // Looks up the tribs referred to by the serialized trib references. Tribs
+    // which no longer exist are skipped.
+    async fn resolve(&self, ref_strings: Vec<String>) -> ServerResult<Vec<Arc<Trib>>> {
+        // group the references by user so every trib list is read once
+        let mut wanted = HashMap::<String, HashSet<u64>>::new();
+        let redirects = self.redirects().await?;
+        for ref_string in ref_strings {
+            let trib_ref: TribRef = serde_json::from_str(&ref_string)?;
+            wanted
+                .entry(redirected(&redirects, &trib_ref.user))
+                .or_default()
+                .insert(trib_ref.clock);
+        }
+        // the tribs referred to are mostly recent ones, so the archive is only
+        // read for those missing from "tribs"
+        let mut tribs = Vec::<Trib>::new();
+        for (user, mut clocks) in wanted {
+            let user_bin = self.bin_storage.bin(&user).await?;
+            for key in ["tribs", "archived_tribs"] {
+                if clocks.is_empty() {
+                    break;
+                }
+                let mut found = HashSet::new();
+                for trib_string in user_bin.list_get(key).await?.0 {
+                    let trib: Trib = serde_json::from_str(&trib_string)?;
+                    if clocks.contains(&trib.clock) {
+                        found.insert(trib.clock);
+                        tribs.push(trib);
+                    }
+                }
+                clocks.retain(|clock| !found.contains(clock));
+            }
+        }
+        Ok(latest_revisions(tribs))
+    }
+
+    // Checks that who and author signed up and that author posted a trib at
+    // clock, and returns that trib.
+    async fn check_trib(&self, who: &str, author: &str, clock: u64) -> ServerResult<Trib> {
+        for user in [who, author] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
+        }
+
+        // check if both users have signed up
+        for user in [who, author] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
+            }
+        }
+
+        match self.find_trib(author, clock).await? {
+            Some((_, _, trib)) => Ok(trib),
+            None => Err(TribblerError::TribNotFound(author.to_string(), clock)),
+        }
+    }
+
+    // Reads every trib the user stored, in "archived_tribs" and in "tribs",
+    // along with the list holding it and the string it is stored as. A trib
+    // being archived or edited can show up more than once.
+    async fn stored_tribs(&self, user: &str) -> ServerResult<Vec<(&'static str, String, Trib)>> {
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mut stored = vec![];
+        for key in ["archived_tribs", "tribs"] {
+            for trib_string in user_bin.list_get(key).await?.0 {
+                let trib: Trib = serde_json::from_str(&trib_string)?;
+                stored.push((key, trib_string, trib));
+            }
+        }
+        Ok(stored)
+    }
+
+    // Finds the last revision of the trib the user posted at clock, wherever
+    // tribs() left it.
+    async fn find_trib(
+        &self,
+        user: &str,
+        clock: u64,
+    ) -> ServerResult<Option<(&'static str, String, Trib)>> {
+        Ok(self
+            .stored_tribs(user)
+            .await?
+            .into_iter()
+            .filter(|(_, _, trib)| trib.clock == clock)
+            .max_by_key(|(_, _, trib)| trib.revision))
+    }
+
+    // Reads every trib the user posted, including the ones tribs() archived,
+    // sorted by priority.
+    async fn all_tribs(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let stored = self.stored_tribs(user).await?;
+        Ok(latest_revisions(
+            stored.into_iter().map(|(_, _, trib)| trib),
+        ))
+    }
+
+    // Puts who on the followers list of whom that posts fan out to, and the
+    // tribs of whom on the home timeline of who, when who follows whom, and
+    // takes them off otherwise. What is there already is left as it is, so
+    // doing it over changes nothing. The home timeline holds references to
+    // the tribs, resolved on read.
+    async fn settle_follow(&self, who: &str, whom: &str, following: bool) -> ServerResult<()> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        let whom_bin = self.bin_storage.bin(whom).await?;
+        let home = who_bin.list_get("home").await?.0;
+        // who may have followed whom under a name they had before a rename
+        let redirects = self.redirects().await?;
+        let old_names = old_names(&redirects, who);
+
+        if following {
+            if !self.followers(whom).await?.iter().any(|user| user == who) {
+                whom_bin
+                    .list_append(&KeyValue {
+                        key: "followers".to_string(),
+                        value: who.to_string(),
+                    })
+                    .await?;
+            }
+            self.count_follow(who, whom, &old_names, true).await?;
+            for trib in self.tribs(whom).await? {
+                let trib_ref = TribRef {
+                    user: trib.user.to_string(),
+                    clock: trib.clock,
+                };
+                let ref_string = serde_json::to_string(&trib_ref)?;
+                if !home.contains(&ref_string) {
+                    who_bin
+                        .list_append(&KeyValue {
+                            key: "home".to_string(),
+                            value: ref_string,
+                        })
+                        .await?;
+                }
+            }
+            self.trim_home(who).await?;
+        } else {
+            for name in old_names.iter().map(String::as_str).chain([who]) {
+                whom_bin
+                    .list_remove(&KeyValue {
+                        key: "followers".to_string(),
+                        value: name.to_string(),
+                    })
+                    .await?;
+            }
+            self.count_follow(who, whom, &old_names, false).await?;
+            for ref_string in home {
+                let trib_ref: TribRef = serde_json::from_str(&ref_string)?;
+                if redirected(&redirects, &trib_ref.user) == whom {
+                    who_bin
+                        .list_remove(&KeyValue {
+                            key: "home".to_string(),
+                            value: ref_string,
+                        })
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Counts the follow of whom by who in the following count of who and the
+    // followers count of whom when who follows whom, and takes it off the
+    // counts otherwise. Swapping a marker in the bin of whom first makes sure
+    // the counts move once, however many settle the follow at the same time.
+    // The marker stays under the name who followed under, which may be one of
+    // their old names.
+    async fn count_follow(
+        &self,
+        who: &str,
+        whom: &str,
+        old_names: &[String],
+        following: bool,
+    ) -> ServerResult<()> {
+        let whom_bin = self.bin_storage.bin(whom).await?;
+        let marker = |name: &str| "counted_".to_owned() + name;
+        let mut delta = 0;
+        if following {
+            for name in old_names {
+                if whom_bin.get(&marker(name)).await?.as_deref() == Some("1") {
+                    return Ok(());
+                }
+            }
+            if whom_bin
+                .compare_and_swap(&marker(who), "", "1")
+                .await?
+                .is_empty()
+            {
+                delta = 1;
+            }
+        } else {
+            for name in old_names.iter().map(String::as_str).chain([who]) {
+                if whom_bin.compare_and_swap(&marker(name), "1", "").await? == "1" {
+                    delta -= 1;
+                }
+            }
+        }
+        if delta == 0 {
+            return Ok(());
+        }
+        let who_bin = self.bin_storage.bin(who).await?;
+        who_bin.incr("following_count", delta).await?;
+        whom_bin.incr("followers_count", delta).await?;
+        Ok(())
+    }
+
+    // Drops the references to the oldest tribs off the home timeline of the
+    // user, past the last max_trib_fetch of them, which is all home shows.
+    async fn trim_home(&self, user: &str) -> ServerResult<()> {
+        let user_bin = self.bin_storage.bin(user).await?;
+        let home = user_bin.list_get("home").await?.0;
+        if home.len() <= self.limits.max_trib_fetch {
+            return Ok(());
+        }
+        let mut refs = Vec::<(u64, String, String)>::new();
+        for ref_string in home {
+            let trib_ref: TribRef = serde_json::from_str(&ref_string)?;
+            refs.push((trib_ref.clock, trib_ref.user, ref_string));
+        }
+        refs.sort();
+        let old_num = refs.len() - self.limits.max_trib_fetch;
+        for (_, _, ref_string) in refs.into_iter().take(old_num) {
+            user_bin
+                .list_remove(&KeyValue {
+                    key: "home".to_string(),
+                    value: ref_string,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Merges the tribs the members posted with a clock less than
+    // before_clock into one timeline, keeping at most limit (and never more
+    // than the fetch limit) of the most recent ones, sorted by priority.
+    async fn merged_page(
+        &self,
+        members: &[String],
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>> {
+        // every trib on the page is among the last `limit` of its author
+        let mut merged = Vec::<Arc<Trib>>::new();
+        for member in members {
+            let mut member_tribs = self.tribs_page(member, before_clock, limit).await?;
+            merged.append(&mut member_tribs);
+        }
+
+        // sort the tribbles based on the priority
+        merged.sort_by(sort_trib);
+        let start = merged
+            .len()
+            .saturating_sub(limit.min(self.limits.max_trib_fetch));
+        merged.drain(..start);
+        Ok(merged)
+    }
+
+    // Reads the members of the list name of who, under their current
+    // names. Each list is kept as a "list_<name>" list in the bin of who,
+    // and its name in the "lists" list.
+    async fn list_members(&self, who: &str, name: &str) -> ServerResult<Vec<String>> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        if !who_bin.list_get("lists").await?.0.iter().any(|l| l == name) {
+            return Err(TribblerError::NoSuchList(who.to_string(), name.to_string()));
+        }
+        let redirects = self.redirects().await?;
+        let mut members = Vec::<String>::new();
+        for member in who_bin.list_get(&("list_".to_owned() + name)).await?.0 {
+            let member = redirected(&redirects, &member);
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+        Ok(members)
+    }
+
+    // Reads the drafts of who along with how each is stored. The drafts are
+    // kept as JSON in the "drafts" list of who, identified by a clock of
+    // the bin of who.
+    async fn stored_drafts(&self, who: &str) -> ServerResult<Vec<(String, Draft)>> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        let mut drafts = Vec::<(String, Draft)>::new();
+        for draft_string in who_bin.list_get("drafts").await?.0 {
+            let draft: Draft = serde_json::from_str(&draft_string)?;
+            drafts.push((draft_string, draft));
+        }
+        Ok(drafts)
+    }
+
+    // Records that who acted just now, for last_active. Called once the call
+    // went through, so a refused one leaves no trace.
+    async fn touch(&self, who: &str) -> ServerResult<()> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        let now = self.time.now()?;
+        who_bin
+            .set(&KeyValue {
+                key: "last_active".to_string(),
+                value: now.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Reads the followers list of who, under the followers' current names.
+    // The list can repeat a follower.
+    async fn followers(&self, who: &str) -> ServerResult<Vec<String>> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        let redirects = self.redirects().await?;
+        Ok(who_bin
+            .list_get("followers")
+            .await?
+            .0
+            .iter()
+            .map(|follower| redirected(&redirects, follower))
+            .collect())
+    }
+
+    // Appends the trib reference add to, and removes remove from, the home
+    // timelines of who and of everyone following who, trimming each one it
+    // added to.
+    async fn fan_out(
+        &self,
+        who: &str,
+        add: Option<&str>,
+        remove: Option<&str>,
+    ) -> ServerResult<()> {
+        let mut homes = self.followers(who).await?;
+        homes.push(who.to_string());
+        homes.sort();
+        homes.dedup();
+        for user in homes {
+            let user_bin = self.bin_storage.bin(&user).await?;
+            if let Some(ref_string) = add {
+                user_bin
+                    .list_append(&KeyValue {
+                        key: "home".to_string(),
+                        value: ref_string.to_string(),
+                    })
+                    .await?;
+                self.trim_home(&user).await?;
+            }
+            if let Some(ref_string) = remove {
+                user_bin
+                    .list_remove(&KeyValue {
+                        key: "home".to_string(),
+                        value: ref_string.to_string(),
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads the users kept in the list under key in the bin of the user,
+    // "blocked" or "muted".
+    async fn name_set(&self, user: &str, key: &str) -> ServerResult<HashSet<String>> {
+        let user_bin = self.bin_storage.bin(user).await?;
+        let names = user_bin.list_get(key).await?;
+        let redirects = self.redirects().await?;
+        Ok(names
+            .0
+            .iter()
+            .map(|name| redirected(&redirects, name))
+            .collect())
+    }
+
+    // Reads the users whose tribs stay off the home timeline of the user.
+    async fn hidden_set(&self, user: &str) -> ServerResult<HashSet<String>> {
+        let mut hidden = self.name_set(user, "blocked").await?;
+        hidden.extend(self.name_set(user, "muted").await?);
+        hidden.extend(self.banned().await?);
+        Ok(hidden)
+    }
+
+    // Reads the users banned by moderators, under their current names. They
+    // are kept in the "banned" list of the general bin.
+    async fn banned(&self) -> ServerResult<HashSet<String>> {
+        self.name_set("", "banned").await
+    }
+
+    // Refuses who when a moderator banned them.
+    async fn check_not_banned(&self, who: &str) -> ServerResult<()> {
+        if self.banned().await?.contains(who) {
+            return Err(TribblerError::Banned(who.to_string()));
+        }
+        Ok(())
+    }
+
+    // Checks if the user has signed up, asking the general bin only when the
+    // user isn't in the signup cache.
+    async fn is_signed_up(&self, user: &str) -> ServerResult<bool> {
+        if self.signups.contains(user)? {
+            return Ok(true);
+        }
+        let general_bin = self.bin_storage.bin("").await?; // get the general bin
+        let signup_string = "signup_".to_owned() + user;
+        let signed = general_bin.get(&signup_string).await?.is_some();
+        if signed {
+            self.signups.insert(user)?;
+        }
+        Ok(signed)
+    }
+
+    // Reads the redirects left behind by renames, from the old name to the
+    // current one. Each is kept as "old::new" in the general bin.
+    async fn redirects(&self) -> ServerResult<HashMap<String, String>> {
+        let general_bin = self.bin_storage.bin("").await?;
+        let mut redirects = HashMap::new();
+        for entry in general_bin.list_get("redirects").await?.0 {
+            if let Some((old, new)) = entry.split_once("::") {
+                redirects.insert(old.to_string(), new.to_string());
+            }
+        }
+        Ok(redirects)
+    }
+
+    // Reads the followee set of who from what is stored under "followees",
+    // with the followees renamed since under their current names. A user who
+    // followed before the set was kept has none stored yet, and starts from
+    // what their follow log adds up to.
+    async fn followee_set(
+        &self,
+        who: &str,
+        stored: &str,
+        redirects: &HashMap<String, String>,
+    ) -> ServerResult<FolloweeSet> {
+        let mut set = if stored.is_empty() {
+            let who_bin = self.bin_storage.bin(who).await?;
+            let log = who_bin.list_get("log").await?.0;
+            FolloweeSet {
+                version: 0,
+                followees: replay_log(redirects, read_log(who, &log), self.limits.max_following)
+                    .into_iter()
+                    .collect(),
+                pending: vec![],
+            }
+        } else {
+            serde_json::from_str::<FolloweeSet>(stored)?
+        };
+        set.followees = set
+            .followees
+            .iter()
+            .map(|followee| redirected(redirects, followee))
+            .collect();
+        set.followees.sort();
+        set.followees.dedup();
+        set.pending = set
+            .pending
+            .iter()
+            .map(|user| redirected(redirects, user))
+            .collect();
+        set.pending.sort();
+        set.pending.dedup();
+        Ok(set)
+    }
+
+    // Replaces the followee set of who with what change makes of it, starting
+    // over from the set another front-end swapped in meanwhile; change refuses
+    // by failing. The users the change followed or unfollowed are left
+    // pending in the set, and settled once it is in.
+    async fn swap_followees<F>(&self, who: &str, change: F) -> ServerResult<()>
+    where
+        F: Fn(&FolloweeSet) -> ServerResult<FolloweeSet>,
+    {
+        let redirects = self.redirects().await?;
+        let who_bin = self.bin_storage.bin(who).await?;
+        let mut stored = who_bin.get("followees").await?.unwrap_or_default();
+        loop {
+            let set = self.followee_set(who, &stored, &redirects).await?;
+            let changed = serde_json::to_string(&change(&set)?)?;
+            let held = who_bin
+                .compare_and_swap("followees", &stored, &changed)
+                .await?;
+            if held == stored {
+                return self.settle_followees(who, changed, &redirects).await;
+            }
+            stored = held;
+        }
+    }
+
+    // Brings the followers lists and the home timeline of who in line with
+    // the followee set stored, for the users pending in it, then clears them
+    // off. A change which failed partway leaves its users pending, and the
+    // next change settles them along with its own. When the set was swapped
+    // meanwhile, the users it follows or unfollows unlike the set settled
+    // against are settled over, as what was done for them is out of date.
+    async fn settle_followees(
+        &self,
+        who: &str,
+        mut stored: String,
+        redirects: &HashMap<String, String>,
+    ) -> ServerResult<()> {
+        let who_bin = self.bin_storage.bin(who).await?;
+        // whether who follows each user settled so far, as settled
+        let mut settled = HashMap::<String, bool>::new();
+        loop {
+            let set = self.followee_set(who, &stored, redirects).await?;
+            let mut unsettled: Vec<String> = settled.keys().cloned().collect();
+            unsettled.extend(set.pending.iter().cloned());
+            for whom in unsettled {
+                let following = set.followees.contains(&whom);
+                if settled.get(&whom) != Some(&following) {
+                    self.settle_follow(who, &whom, following).await?;
+                    settled.insert(whom, following);
+                }
+            }
+            // swapping the set for itself checks it is still the one settled
+            let cleared = match set.pending.is_empty() {
+                true => stored.clone(),
+                false => serde_json::to_string(&FolloweeSet {
+                    pending: vec![],
+                    ..set
+                })?,
+            };
+            let held = who_bin
+                .compare_and_swap("followees", &stored, &cleared)
+                .await?;
+            if held == stored {
+                return Ok(());
+            }
+            stored = held;
+        }
+    }
+
+    // Moves everything in the bin of old to the bin of new. The tribs are
+    // handed over to new, and the clock of new catches up with them so that
+    // later posts still sort after them.
+    async fn rename_bin(&self, old: &str, new: &str) -> ServerResult<()> {
+        let old_bin = self.bin_storage.bin(old).await?;
+        let new_bin = self.bin_storage.bin(new).await?;
+        let everything = Pattern {
+            prefix: "".to_string(),
+            suffix: "".to_string(),
+        };
+        for key in old_bin.keys(&everything).await?.0 {
+            if let Some(value) = old_bin.get(&key).await? {
+                new_bin
+                    .set(&KeyValue {
+                        key: key.to_string(),
+                        value,
+                    })
+                    .await?;
+                old_bin
+                    .set(&KeyValue {
+                        key,
+                        value: "".to_string(),
+                    })
+                    .await?;
+            }
+        }
+
+        let mut max_clock = 0;
+        for key in old_bin.list_keys(&everything).await?.0 {
+            // the other lists refer to tribs by the name they were posted
+            // under, which the redirects resolve
+            let holds_tribs = key == "tribs" || key == "archived_tribs";
+            for value in old_bin.list_get(&key).await?.0 {
+                let moved = if holds_tribs {
+                    let mut trib: Trib = serde_json::from_str(&value)?;
+                    if trib.user == old {
+                        trib.user = new.to_string();
+                    }
+                    max_clock = max_clock.max(trib.clock);
+                    serde_json::to_string(&trib)?
+                } else {
+                    value.to_string()
+                };
+                new_bin
+                    .list_append(&KeyValue {
+                        key: key.to_string(),
+                        value: moved,
+                    })
+                    .await?;
+                old_bin
+                    .list_remove(&KeyValue {
+                        key: key.to_string(),
+                        value,
+                    })
+                    .await?;
+            }
+        }
+        new_bin.clock(max_clock).await?;
+        Ok(())
+    }
+
+    /// Posts the scheduled tribs due at `now`, in seconds since the epoch.
+    /// Each post is taken off the "scheduled" list of its user before it is
+    /// posted, so it is posted once even when several keepers run this.
+    pub async fn publish_due(&self, now: u64) -> ServerResult<()> {
+        let scheduled_bin = self.bin_storage.bin(SCHEDULED_BIN).await?;
+        let redirects = self.redirects().await?;
+        for entry in scheduled_bin.list_get("due").await?.0 {
+            let parsed = entry
+                .split_once("::")
+                .and_then(|(publish_at, user)| Some((publish_at.parse::<u64>().ok()?, user)));
+            let (publish_at, user) = match parsed {
+                Some(parsed) => parsed,
+                None => {
+                    warn!("skipping a malformed entry of the due list: {}", entry);
+                    continue;
+                }
+            };
+            if publish_at > now {
+                continue;
+            }
+
+            let user = redirected(&redirects, user);
+            let user_bin = self.bin_storage.bin(&user).await?;
+            // the scheduled posts of banned users are dropped
+            let banned = self.banned().await?.contains(&user);
+            let mut due = Vec::<ScheduledPost>::new();
+            for post_string in user_bin.list_get("scheduled").await?.0 {
+                let post: ScheduledPost = serde_json::from_str(&post_string)?;
+                if post.publish_at > now {
+                    continue;
+                }
+                let claimed = user_bin
+                    .list_remove(&KeyValue {
+                        key: "scheduled".to_string(),
+                        value: post_string,
+                    })
+                    .await?;
+                if claimed > 0 && !banned {
+                    due.push(post);
+                }
+            }
+            due.sort_by_key(|post| (post.publish_at, post.id));
+            for post in due {
+                // a fresh clock, past everything the bin has seen so far
+                self.publish(&user, &post.message, 0, None, None, vec![])
+                    .await?;
+            }
+            scheduled_bin
+                .list_remove(&KeyValue {
+                    key: "due".to_string(),
+                    value: entry,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Refuses writes until the keeper reports that the backend clocks have
+    // converged. A front-end which doesn't follow a keeper always allows writes.
+    fn check_writable(&self) -> ServerResult<()> {
+        match &self.view {
+            Some(view) => {
+                let view = view
+                    .read()
+                    .map_err(|e| TribblerError::CacheFailure(format!("cluster view: {}", e)))?;
+                match &*view {
+                    Some(view) if view.consistent => Ok(()),
+                    _ => Err(TribblerError::ClusterNotReady),
+                }
+            }
+            None => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
 impl Server for FrontendServer {
-    async fn sign_up(&self, user: &str) -> TribResult<()> {
+    async fn sign_up(&self, user: &str) -> ServerResult<()> {
+        self.check_writable()?;
         // println!("sign_up input: {}", user);
         if !is_valid_username(user) {
             // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+            return Err(TribblerError::InvalidUsername(user.to_string()));
         }
 
         // use the general bin to check if the user has signed up
         let general_bin = self.bin_storage.bin("").await?;
         let signup_string = "signup_".to_owned() + user;
         let signed = general_bin.get(&signup_string).await?;
+        // the old names of renamed users stay taken, follow logs still use them
+        let retired = self.redirects().await?.contains_key(user);
         match signed {
-            None => {
+            None if !retired => {
                 // The user hasn't signed up.
                 general_bin
                     .set(&KeyValue {
@@ -41,21 +914,35 @@ impl Server for FrontendServer {
                     })
                     .await?;
                 // Two sign_up operations may succeed (allowed in SPEC).
+                self.signups.insert(user)?;
+
+                // remember the first registered users so list_users can be served from the cache
+                let user_cache = general_bin.list_get("cache").await?;
+                if user_cache.0.len() < self.limits.min_list_user {
+                    general_bin
+                        .list_append(&KeyValue {
+                            key: "cache".to_string(),
+                            value: user.to_string(),
+                        })
+                        .await?;
+                }
             }
-            Some(_) => {
+            _ => {
                 // The user has already signed up.
-                return Err(Box::new(TribblerError::UsernameTaken(user.to_string())));
+                return Err(TribblerError::UsernameTaken(user.to_string()));
             }
         }
         return Ok(());
     }
 
-    async fn list_users(&self) -> TribResult<Vec<String>> {
+    async fn list_users(&self) -> ServerResult<Vec<String>> {
         // The cache is good enough if we remember to store unique elements in it.
         let general_bin = self.bin_storage.bin("").await?;
         let mut user_cache = general_bin.list_get("cache").await?;
-        if user_cache.0.len() >= MIN_LIST_USER {
+        if user_cache.0.len() >= self.limits.min_list_user {
             // println!("use cache!");
+            user_cache.0.sort();
+            user_cache.0.dedup();
             return Ok(user_cache.0);
         }
 
@@ -84,439 +971,2031 @@ impl Server for FrontendServer {
             user_vec.push(user.to_string()); // strip the "sign_up" prefix
         }
 
-        // sort + dedup to remove duplicates
-        user_vec.sort();
-        user_vec.dedup();
-
-        // get fewer than 20 users
-        let user_num = user_vec.len();
-        if user_num > MIN_LIST_USER {
-            user_vec = user_vec[..MIN_LIST_USER].to_vec();
+        // sort + dedup to remove duplicates
+        user_vec.sort();
+        user_vec.dedup();
+
+        // get fewer than 20 users
+        let user_num = user_vec.len();
+        if user_num > self.limits.min_list_user {
+            user_vec = user_vec[..self.limits.min_list_user].to_vec();
+        }
+        user_vec.sort(); // sort in alphabetical order
+
+        for user in user_vec {
+            general_bin
+                .list_append(&KeyValue {
+                    key: "cache".to_string(),
+                    value: user,
+                })
+                .await?;
+        }
+        user_cache = general_bin.list_get("cache").await?;
+        // println!("list_users output: {:?}", user_cache.0);
+        return Ok(user_cache.0);
+    }
+
+    async fn signed_up(&self, user: &str) -> ServerResult<bool> {
+        if !is_valid_username(user) {
+            return Ok(false);
+        }
+        self.is_signed_up(user).await
+    }
+
+    async fn post(&self, who: &str, post: &str, clock: u64) -> ServerResult<()> {
+        self.post_with_attachments(who, post, clock, &[]).await
+    }
+
+    async fn post_with_attachments(
+        &self,
+        who: &str,
+        post: &str,
+        clock: u64,
+        attachments: &[Attachment],
+    ) -> ServerResult<()> {
+        self.check_writable()?;
+        // println!("post input: {}", who);
+        // println!("post input: {}", post);
+        // println!("post input: {}", clock);
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if post.len() > self.limits.max_trib_len {
+            // The post is too long.
+            return Err(TribblerError::TribTooLong);
+        }
+        validate_attachments(attachments)?;
+
+        // check if the user has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        self.check_not_banned(who).await?;
+
+        self.publish(who, post, clock, None, None, attachments.to_vec())
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn clock(&self) -> ServerResult<u64> {
+        // the bins keep their clocks in sync, so any bin can tell
+        let general_bin = self.bin_storage.bin("").await?;
+        return general_bin.clock(0).await;
+    }
+
+    async fn tribs(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        // println!("tribs input: {}", user);
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // get the tribs, sorted by priority
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mut stored = Vec::<(String, Trib)>::new();
+        for trib_string in user_bin.list_get("tribs").await?.0 {
+            let trib: Trib = serde_json::from_str(&trib_string)?;
+            stored.push((trib_string, trib));
+        }
+        let mut trib_vec = latest_revisions(stored.iter().map(|(_, trib)| trib.clone()));
+
+        // an edit appends the new revision before it removes the old one, so
+        // an old revision it left behind is dropped here
+        let revisions: HashMap<u64, u32> = trib_vec
+            .iter()
+            .map(|trib| (trib.clock, trib.revision))
+            .collect();
+        for (trib_string, trib) in stored {
+            if revisions.get(&trib.clock) != Some(&trib.revision) {
+                user_bin
+                    .list_remove(&KeyValue {
+                        key: "tribs".to_string(),
+                        value: trib_string,
+                    })
+                    .await?;
+            }
+        }
+
+        // move older tribs out of the way, keeping them for tribs_page
+        let trib_num = trib_vec.len();
+        if trib_num > self.limits.max_trib_fetch {
+            let old_num = trib_num - self.limits.max_trib_fetch;
+
+            // The tribs with less clock values are older.
+            for old_trib in trib_vec.iter().take(old_num) {
+                let old_trib_string = serde_json::to_string(&old_trib)?;
+                user_bin
+                    .list_append(&KeyValue {
+                        key: "archived_tribs".to_string(),
+                        value: old_trib_string.clone(),
+                    })
+                    .await?;
+                user_bin
+                    .list_remove(&KeyValue {
+                        key: "tribs".to_string(),
+                        value: old_trib_string,
+                    })
+                    .await?;
+            }
+            trib_vec = trib_vec[old_num..].to_vec();
+        }
+        // println!("tribs output: {:?}", trib_vec);
+        return Ok(trib_vec);
+    }
+
+    async fn follow(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        // println!("follow input: {}", who);
+        // println!("follow input: {}", whom);
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if !is_valid_username(whom) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(whom.to_string()));
+        }
+
+        // check if who and whom have signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        if !self.is_signed_up(whom).await? {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+
+        // The follower cannot follow himself.
+        if who == whom {
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+
+        // nobody can follow a user who blocks them
+        if self.name_set(whom, "blocked").await?.contains(who) {
+            return Err(TribblerError::BlockedBy(who.to_string(), whom.to_string()));
+        }
+        self.check_not_banned(who).await?;
+
+        // swap whom into the followee set
+        let max_following = self.limits.max_following;
+        self.swap_followees(who, |set| {
+            if set.followees.iter().any(|followee| followee == whom) {
+                Err(TribblerError::AlreadyFollowing(
+                    who.to_string(),
+                    whom.to_string(),
+                ))
+            } else if set.followees.len() >= max_following {
+                Err(TribblerError::FollowingTooMany)
+            } else {
+                Ok(set.with(whom))
+            }
+        })
+        .await?;
+        self.notify(whom, NotificationKind::Follow, who, None)
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn unfollow(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        // println!("unfollow input: {}", who);
+        // println!("unfollow input: {}", whom);
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if !is_valid_username(whom) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(whom.to_string()));
+        }
+
+        // check if who and whom have signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        if !self.is_signed_up(whom).await? {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+
+        // The follower cannot unfollow himself.
+        if who == whom {
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+
+        // swap whom out of the followee set
+        self.swap_followees(who, |set| {
+            if set.followees.iter().any(|followee| followee == whom) {
+                Ok(set.without(whom))
+            } else {
+                Err(TribblerError::NotFollowing(
+                    who.to_string(),
+                    whom.to_string(),
+                ))
+            }
+        })
+        .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn is_following(&self, who: &str, whom: &str) -> ServerResult<bool> {
+        // println!("is_follow input: {}", who);
+        // println!("is_follow input: {}", whom);
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if !is_valid_username(whom) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(whom.to_string()));
+        }
+
+        // check if who and whom have signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        if !self.is_signed_up(whom).await? {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+
+        // The follower cannot follow/unfollow himself.
+        if who == whom {
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+
+        // check who's followees
+        let followee_vec = self.following(who).await?;
+        return Ok(followee_vec.contains(&whom.to_string()));
+    }
+
+    async fn following(&self, who: &str) -> ServerResult<Vec<String>> {
+        // println!("following input: {}", who);
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        // check the followee set, the followees may have been renamed since
+        let redirects = self.redirects().await?;
+        let who_bin = self.bin_storage.bin(who).await?;
+        let stored = who_bin.get("followees").await?.unwrap_or_default();
+        let followee_vec = self.followee_set(who, &stored, &redirects).await?.followees;
+        return Ok(followee_vec);
+    }
+
+    async fn mutuals(&self, user: &str) -> ServerResult<Vec<String>> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // the followee set decides whom user follows, the followers list who
+        // follows user
+        let followers: HashSet<String> = self.followers(user).await?.into_iter().collect();
+        let mutuals = self
+            .following(user)
+            .await?
+            .into_iter()
+            .filter(|followee| followers.contains(followee))
+            .collect();
+        return Ok(mutuals);
+    }
+
+    async fn last_active(&self, user: &str) -> ServerResult<Option<u64>> {
+        if !is_valid_username(user) {
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        let user_bin = self.bin_storage.bin(user).await?;
+        return Ok(user_bin
+            .get("last_active")
+            .await?
+            .and_then(|time| time.parse::<u64>().ok()));
+    }
+
+    async fn home(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        // println!("home input: {}", user);
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // the home timeline is precomputed by fanning out on post, and
+        // trimmed as it is
+        let user_bin = self.bin_storage.bin(user).await?;
+        let home = user_bin.list_get("home").await?;
+        let hidden = self.hidden_set(user).await?;
+        let redirects = self.redirects().await?;
+        let mut shown = Vec::<String>::new();
+        for ref_string in home.0 {
+            let trib_ref: TribRef = serde_json::from_str(&ref_string)?;
+            if !hidden.contains(&redirected(&redirects, &trib_ref.user)) {
+                shown.push(ref_string);
+            }
+        }
+
+        // the last revision of every trib, sorted by priority
+        let mut user_home = self.resolve(shown).await?;
+        let start = user_home.len().saturating_sub(self.limits.max_trib_fetch);
+        user_home.drain(..start);
+        return Ok(user_home);
+    }
+
+    async fn edit_trib(&self, who: &str, clock: u64, message: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if message.len() > self.limits.max_trib_len {
+            // The post is too long.
+            return Err(TribblerError::TribTooLong);
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        // find the trib posted at the clock, archived or not
+        let (key, old_string, old_trib) = match self.find_trib(who, clock).await? {
+            Some(found) => found,
+            None => return Err(TribblerError::TribNotFound(who.to_string(), clock)),
+        };
+
+        // store the new revision before dropping the old one, so the trib is
+        // never missing; it stays in the list the old one was in, and the
+        // home timelines, which refer to the trib, show it as they are
+        let new_trib = Trib {
+            message: message.to_string(),
+            revision: old_trib.revision + 1,
+            ..old_trib
+        };
+        let new_string = serde_json::to_string(&new_trib)?;
+        let who_bin = self.bin_storage.bin(who).await?;
+        who_bin
+            .list_append(&KeyValue {
+                key: key.to_string(),
+                value: new_string.to_string(),
+            })
+            .await?;
+        who_bin
+            .list_remove(&KeyValue {
+                key: key.to_string(),
+                value: old_string.to_string(),
+            })
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn like(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        self.check_trib(who, author, clock).await?;
+
+        // the likes of a trib are stored next to the trib in the author's bin
+        let author_bin = self.bin_storage.bin(author).await?;
+        let likes_key = "likes_".to_owned() + &clock.to_string();
+        let likes = author_bin.list_get(&likes_key).await?;
+        if likes.0.iter().any(|user| user == who) {
+            return Err(TribblerError::AlreadyLiked(
+                who.to_string(),
+                author.to_string(),
+                clock,
+            ));
+        }
+        author_bin
+            .list_append(&KeyValue {
+                key: likes_key,
+                value: who.to_string(),
+            })
+            .await?;
+        let liked = TribRef {
+            user: author.to_string(),
+            clock,
+        };
+        // and what a user likes is kept in their own bin, for export_user
+        let who_bin = self.bin_storage.bin(who).await?;
+        who_bin
+            .list_append(&KeyValue {
+                key: "liked".to_string(),
+                value: serde_json::to_string(&liked)?,
+            })
+            .await?;
+        self.notify(author, NotificationKind::Like, who, Some(liked))
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn unlike(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        self.check_trib(who, author, clock).await?;
+
+        let author_bin = self.bin_storage.bin(author).await?;
+        let removed = author_bin
+            .list_remove(&KeyValue {
+                key: "likes_".to_owned() + &clock.to_string(),
+                value: who.to_string(),
+            })
+            .await?;
+        if removed == 0 {
+            return Err(TribblerError::NotLiked(
+                who.to_string(),
+                author.to_string(),
+                clock,
+            ));
+        }
+        let redirects = self.redirects().await?;
+        let who_bin = self.bin_storage.bin(who).await?;
+        for liked_string in who_bin.list_get("liked").await?.0 {
+            let liked: TribRef = serde_json::from_str(&liked_string)?;
+            if redirected(&redirects, &liked.user) == author && liked.clock == clock {
+                who_bin
+                    .list_remove(&KeyValue {
+                        key: "liked".to_string(),
+                        value: liked_string,
+                    })
+                    .await?;
+            }
+        }
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn likes(&self, author: &str, clock: u64) -> ServerResult<Vec<String>> {
+        if !is_valid_username(author) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(author.to_string()));
+        }
+
+        // check if the author has signed up
+        if !self.is_signed_up(author).await? {
+            return Err(TribblerError::UserDoesNotExist(author.to_string()));
+        }
+
+        // concurrent likes may append the same user twice
+        let author_bin = self.bin_storage.bin(author).await?;
+        let likes = author_bin
+            .list_get(&("likes_".to_owned() + &clock.to_string()))
+            .await?
+            .0;
+        let redirects = self.redirects().await?;
+        let mut likes: Vec<String> = likes
+            .iter()
+            .map(|name| redirected(&redirects, name))
+            .collect();
+        likes.sort();
+        likes.dedup();
+        return Ok(likes);
+    }
+
+    async fn retrib(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        let original = self.check_trib(who, author, clock).await?;
+        self.check_not_banned(who).await?;
+        let retrib_of = original.retrib_of.clone().unwrap_or(TribRef {
+            user: original.user.clone(),
+            clock: original.clock,
+        });
+        // the retrib has to be ordered after the original trib
+        let after = original.clock.saturating_add(1);
+        self.publish(
+            who,
+            &original.message,
+            after,
+            Some(retrib_of),
+            None,
+            original.attachments.clone(),
+        )
+        .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn reply(
+        &self,
+        who: &str,
+        parent_author: &str,
+        parent_clock: u64,
+        message: &str,
+    ) -> ServerResult<()> {
+        self.check_writable()?;
+        if message.len() > self.limits.max_trib_len {
+            // The post is too long.
+            return Err(TribblerError::TribTooLong);
+        }
+        self.check_trib(who, parent_author, parent_clock).await?;
+        self.check_not_banned(who).await?;
+
+        // the reply has to be ordered after its parent
+        let parent = TribRef {
+            user: parent_author.to_string(),
+            clock: parent_clock,
+        };
+        let after = parent_clock.saturating_add(1);
+        let trib = self
+            .publish(who, message, after, None, Some(parent), vec![])
+            .await?;
+
+        // remember the reply next to the parent in the parent author's bin
+        let reply_ref = TribRef {
+            user: who.to_string(),
+            clock: trib.clock,
+        };
+        let parent_bin = self.bin_storage.bin(parent_author).await?;
+        parent_bin
+            .list_append(&KeyValue {
+                key: "replies_".to_owned() + &parent_clock.to_string(),
+                value: serde_json::to_string(&reply_ref)?,
+            })
+            .await?;
+        self.notify(parent_author, NotificationKind::Reply, who, Some(reply_ref))
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn replies(&self, author: &str, clock: u64) -> ServerResult<Vec<Arc<Trib>>> {
+        if !is_valid_username(author) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(author.to_string()));
+        }
+
+        // check if the author has signed up
+        if !self.is_signed_up(author).await? {
+            return Err(TribblerError::UserDoesNotExist(author.to_string()));
+        }
+
+        // resolve every reply reference against the tribs of the replier
+        let author_bin = self.bin_storage.bin(author).await?;
+        let reply_refs = author_bin
+            .list_get(&("replies_".to_owned() + &clock.to_string()))
+            .await?;
+        self.resolve(reply_refs.0).await
+    }
+
+    async fn tribs_by_tag(&self, tag: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() {
+            return Ok(vec![]);
+        }
+        let tag_bin = self.bin_storage.bin(&("#".to_owned() + &tag)).await?;
+        let tag_refs = tag_bin.list_get("tribs").await?;
+
+        // only the most recent references are needed
+        let start = tag_refs.0.len().saturating_sub(self.limits.max_trib_fetch);
+        let mut tribs = self.resolve(tag_refs.0[start..].to_vec()).await?;
+        let start = tribs.len().saturating_sub(self.limits.max_trib_fetch);
+        tribs.drain(..start);
+        return Ok(tribs);
+    }
+
+    async fn trending(&self, limit: usize) -> ServerResult<Vec<Trend>> {
+        let first = first_trending_slot(self.time.now()?);
+        let trending_bin = self.bin_storage.bin(TRENDING_BIN).await?;
+        let everything = Pattern {
+            prefix: "".to_string(),
+            suffix: "".to_string(),
+        };
+        let mut counts = HashMap::<String, u64>::new();
+        for key in trending_bin.keys(&everything).await?.0 {
+            let parsed = key
+                .split_once('_')
+                .and_then(|(slot, tag)| Some((slot.parse::<u64>().ok()?, tag)));
+            let (slot, tag) = match parsed {
+                Some(parsed) => parsed,
+                None => {
+                    warn!("skipping a malformed trending counter: {}", key);
+                    continue;
+                }
+            };
+            if slot < first {
+                // the slot slid out of the window, drop its counter
+                trending_bin
+                    .set(&KeyValue {
+                        key: key.to_string(),
+                        value: "".to_string(),
+                    })
+                    .await?;
+                continue;
+            }
+            if let Some(count) = trending_bin.get(&key).await? {
+                match count.parse::<u64>() {
+                    Ok(count) => *counts.entry(tag.to_string()).or_default() += count,
+                    Err(_) => warn!("skipping the trending counter {}: {:?}", key, count),
+                }
+            }
+        }
+        return Ok(top_trends(counts, limit));
+    }
+
+    async fn notifications(&self, user: &str, since: u64) -> ServerResult<Vec<Notification>> {
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mut notifications = Vec::<Notification>::new();
+        for notification_string in user_bin.list_get("notifications").await?.0 {
+            let notification: Notification = serde_json::from_str(&notification_string)?;
+            if notification.clock > since {
+                notifications.push(notification);
+            }
+        }
+        // concurrent notifications may have been appended out of order
+        notifications.sort_by_key(|n| n.clock);
+        let start = notifications
+            .len()
+            .saturating_sub(self.limits.max_trib_fetch);
+        notifications.drain(..start);
+
+        // the users involved may have been renamed since
+        let redirects = self.redirects().await?;
+        let read_upto = match user_bin.get("read_upto").await? {
+            Some(clock) => clock.parse::<u64>().unwrap_or(0),
+            None => 0,
+        };
+        for notification in notifications.iter_mut() {
+            notification.from = redirected(&redirects, &notification.from);
+            if let Some(trib) = notification.trib.as_mut() {
+                trib.user = redirected(&redirects, &trib.user);
+            }
+            notification.read = notification.clock <= read_upto;
+        }
+        return Ok(notifications);
+    }
+
+    async fn mark_read(&self, user: &str, upto: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // the read mark only moves forward
+        let user_bin = self.bin_storage.bin(user).await?;
+        let read_upto = match user_bin.get("read_upto").await? {
+            Some(clock) => clock.parse::<u64>().unwrap_or(0),
+            None => 0,
+        };
+        if upto > read_upto {
+            user_bin
+                .set(&KeyValue {
+                    key: "read_upto".to_string(),
+                    value: upto.to_string(),
+                })
+                .await?;
+
+            // take the newly read ones off the unread counter
+            let mut newly_read = 0;
+            for notification_string in user_bin.list_get("notifications").await?.0 {
+                let notification: Notification = serde_json::from_str(&notification_string)?;
+                if notification.clock > read_upto && notification.clock <= upto {
+                    newly_read += 1;
+                }
+            }
+            if newly_read > 0 {
+                user_bin.incr("unread", -newly_read).await?;
+            }
+        }
+        self.touch(user).await?;
+        return Ok(());
+    }
+
+    async fn unread_count(&self, user: &str) -> ServerResult<usize> {
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // racing mark_read calls may take the counter below zero
+        let user_bin = self.bin_storage.bin(user).await?;
+        let unread = match user_bin.get("unread").await? {
+            Some(count) => count.parse::<i64>().unwrap_or(0),
+            None => 0,
+        };
+        return Ok(unread.max(0) as usize);
+    }
+
+    async fn create_list(&self, who: &str, name: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if !is_valid_username(name) {
+            // list names follow the rules of usernames
+            return Err(TribblerError::InvalidListName(name.to_string()));
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        let who_bin = self.bin_storage.bin(who).await?;
+        if who_bin.list_get("lists").await?.0.iter().any(|l| l == name) {
+            return Err(TribblerError::ListExists(who.to_string(), name.to_string()));
+        }
+        who_bin
+            .list_append(&KeyValue {
+                key: "lists".to_string(),
+                value: name.to_string(),
+            })
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn add_to_list(&self, who: &str, name: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [who, whom] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
+        }
+
+        // check if who and whom have signed up
+        for user in [who, whom] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
+            }
+        }
+
+        let members = self.list_members(who, name).await?;
+        if members.iter().any(|m| m == whom) {
+            return Err(TribblerError::AlreadyInList(
+                whom.to_string(),
+                name.to_string(),
+            ));
+        }
+        if members.len() >= self.limits.max_following {
+            return Err(TribblerError::FollowingTooMany);
+        }
+        let who_bin = self.bin_storage.bin(who).await?;
+        who_bin
+            .list_append(&KeyValue {
+                key: "list_".to_owned() + name,
+                value: whom.to_string(),
+            })
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn list_timeline(&self, who: &str, name: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        // merge the list the way home_page merges the followees
+        let hidden = self.hidden_set(who).await?;
+        let members: Vec<String> = self
+            .list_members(who, name)
+            .await?
+            .into_iter()
+            .filter(|member| !hidden.contains(member))
+            .collect();
+        return self
+            .merged_page(&members, u64::MAX, self.limits.max_trib_fetch)
+            .await;
+    }
+
+    async fn save_draft(&self, who: &str, message: &str) -> ServerResult<u64> {
+        self.check_writable()?;
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if message.len() > self.limits.max_trib_len {
+            // The draft could never be posted.
+            return Err(TribblerError::TribTooLong);
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        if self.stored_drafts(who).await?.len() >= MAX_DRAFTS {
+            return Err(TribblerError::TooManyDrafts);
+        }
+        let who_bin = self.bin_storage.bin(who).await?;
+        let draft = Draft {
+            id: who_bin.clock(1).await?,
+            message: message.to_string(),
+        };
+        who_bin
+            .list_append(&KeyValue {
+                key: "drafts".to_string(),
+                value: serde_json::to_string(&draft)?,
+            })
+            .await?;
+        self.touch(who).await?;
+        return Ok(draft.id);
+    }
+
+    async fn list_drafts(&self, who: &str) -> ServerResult<Vec<Draft>> {
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        let mut drafts: Vec<Draft> = self
+            .stored_drafts(who)
+            .await?
+            .into_iter()
+            .map(|(_, draft)| draft)
+            .collect();
+        drafts.sort_by_key(|draft| draft.id);
+        return Ok(drafts);
+    }
+
+    async fn delete_draft(&self, who: &str, id: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        let who_bin = self.bin_storage.bin(who).await?;
+        for (draft_string, draft) in self.stored_drafts(who).await? {
+            if draft.id == id {
+                who_bin
+                    .list_remove(&KeyValue {
+                        key: "drafts".to_string(),
+                        value: draft_string,
+                    })
+                    .await?;
+                self.touch(who).await?;
+                return Ok(());
+            }
+        }
+        return Err(TribblerError::NoSuchDraft(who.to_string(), id));
+    }
+
+    async fn schedule_post(&self, who: &str, message: &str, publish_at: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(who) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(who.to_string()));
+        }
+        if message.len() > self.limits.max_trib_len {
+            // The post is too long.
+            return Err(TribblerError::TribTooLong);
+        }
+
+        // check if who has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+
+        // keep the post in the bin of who, then let the keeper know about it
+        let who_bin = self.bin_storage.bin(who).await?;
+        let post = ScheduledPost {
+            id: who_bin.clock(1).await?,
+            message: message.to_string(),
+            publish_at,
+        };
+        who_bin
+            .list_append(&KeyValue {
+                key: "scheduled".to_string(),
+                value: serde_json::to_string(&post)?,
+            })
+            .await?;
+        let scheduled_bin = self.bin_storage.bin(SCHEDULED_BIN).await?;
+        scheduled_bin
+            .list_append(&KeyValue {
+                key: "due".to_string(),
+                value: publish_at.to_string() + "::" + who,
+            })
+            .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
+
+    async fn admin_delete_trib(&self, author: &str, clock: u64) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(author) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(author.to_string()));
+        }
+
+        // check if the author has signed up
+        if !self.is_signed_up(author).await? {
+            return Err(TribblerError::UserDoesNotExist(author.to_string()));
+        }
+
+        // take every revision of the trib off the author's lists and off the
+        // home timelines it was fanned out to
+        let author_bin = self.bin_storage.bin(author).await?;
+        let mut found = false;
+        for (key, trib_string, trib) in self.stored_tribs(author).await? {
+            if trib.clock != clock {
+                continue;
+            }
+            found = true;
+            author_bin
+                .list_remove(&KeyValue {
+                    key: key.to_string(),
+                    value: trib_string.to_string(),
+                })
+                .await?;
+        }
+        if !found {
+            return Err(TribblerError::TribNotFound(author.to_string(), clock));
+        }
+        let trib_ref = serde_json::to_string(&TribRef {
+            user: author.to_string(),
+            clock,
+        })?;
+        self.fan_out(author, None, Some(&trib_ref)).await?;
+        return Ok(());
+    }
+
+    async fn admin_ban_user(&self, user: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        if self.banned().await?.contains(user) {
+            return Err(TribblerError::AlreadyBanned(user.to_string()));
+        }
+        let general_bin = self.bin_storage.bin("").await?;
+        general_bin
+            .list_append(&KeyValue {
+                key: "banned".to_string(),
+                value: user.to_string(),
+            })
+            .await?;
+        return Ok(());
+    }
+
+    async fn admin_unban(&self, user: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // concurrent bans may have appended user more than once, all of them
+        // go; so does a ban made under an old name of the user
+        let general_bin = self.bin_storage.bin("").await?;
+        let redirects = self.redirects().await?;
+        let mut removed = 0;
+        for name in general_bin.list_get("banned").await?.0 {
+            if redirected(&redirects, &name) == user {
+                removed += general_bin
+                    .list_remove(&KeyValue {
+                        key: "banned".to_string(),
+                        value: name,
+                    })
+                    .await?;
+            }
+        }
+        if removed == 0 {
+            return Err(TribblerError::NotBanned(user.to_string()));
+        }
+        return Ok(());
+    }
+
+    async fn export_user(&self, user: &str) -> ServerResult<UserExport> {
+        let profile = self.get_profile(user).await?;
+        let tribs = self
+            .all_tribs(user)
+            .await?
+            .iter()
+            .map(|trib| (**trib).clone())
+            .collect();
+        let mut following = self.following(user).await?;
+        following.sort();
+
+        let redirects = self.redirects().await?;
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mut likes = Vec::<TribRef>::new();
+        for liked_string in user_bin.list_get("liked").await?.0 {
+            let mut liked: TribRef = serde_json::from_str(&liked_string)?;
+            liked.user = redirected(&redirects, &liked.user);
+            if !likes.contains(&liked) {
+                likes.push(liked);
+            }
+        }
+        likes.sort_by(|a, b| a.user.cmp(&b.user).then(a.clock.cmp(&b.clock)));
+        return Ok(UserExport {
+            user: user.to_string(),
+            profile,
+            tribs,
+            following,
+            likes,
+        });
+    }
+
+    async fn import(&self, data: &[UserExport]) -> ServerResult<()> {
+        self.check_writable()?;
+        for export in data {
+            for trib in export.tribs.iter() {
+                if trib.message.len() > self.limits.max_trib_len {
+                    return Err(TribblerError::TribTooLong);
+                }
+                validate_attachments(&trib.attachments)?;
+            }
+        }
+        for export in data {
+            match self.sign_up(&export.user).await {
+                Ok(()) => self.set_profile(&export.user, &export.profile).await?,
+                // a user imported before is left as it is
+                Err(TribblerError::UsernameTaken(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        // the tribs keep their clocks, and the clock of every bin catches up
+        // with them so later posts still sort after them; the tribs imported
+        // before are skipped
+        for export in data {
+            let stored: HashSet<u64> = self
+                .stored_tribs(&export.user)
+                .await?
+                .into_iter()
+                .map(|(_, _, trib)| trib.clock)
+                .collect();
+            let mut max_clock = 0;
+            for trib in export.tribs.iter() {
+                max_clock = max_clock.max(trib.clock);
+                if stored.contains(&trib.clock) {
+                    continue;
+                }
+                let trib = Trib {
+                    user: export.user.to_string(),
+                    ..trib.clone()
+                };
+                self.store_trib(&trib).await?;
+            }
+            let user_bin = self.bin_storage.bin(&export.user).await?;
+            user_bin.clock(max_clock).await?;
+        }
+
+        // with every trib in, the replies are linked to their parents, unless
+        // they are already
+        for export in data {
+            for trib in export.tribs.iter() {
+                let parent = match &trib.reply_to {
+                    Some(parent) => parent,
+                    None => continue,
+                };
+                if self.find_trib(&parent.user, parent.clock).await?.is_none() {
+                    warn!(
+                        "importing the trib {} of {} without its missing parent {} of {}",
+                        trib.clock, export.user, parent.clock, parent.user
+                    );
+                    continue;
+                }
+                let reply_ref = serde_json::to_string(&TribRef {
+                    user: export.user.to_string(),
+                    clock: trib.clock,
+                })?;
+                let parent_bin = self.bin_storage.bin(&parent.user).await?;
+                let replies_key = "replies_".to_owned() + &parent.clock.to_string();
+                if parent_bin
+                    .list_get(&replies_key)
+                    .await?
+                    .0
+                    .contains(&reply_ref)
+                {
+                    continue;
+                }
+                parent_bin
+                    .list_append(&KeyValue {
+                        key: replies_key,
+                        value: reply_ref,
+                    })
+                    .await?;
+            }
+        }
+
+        for export in data {
+            for whom in export.following.iter() {
+                match self.follow(&export.user, whom).await {
+                    Ok(()) | Err(TribblerError::AlreadyFollowing(..)) => {}
+                    Err(TribblerError::UserDoesNotExist(_)) => {
+                        warn!(
+                            "skipping the follow of the missing {} by {}",
+                            whom, export.user
+                        )
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        for export in data {
+            for liked in export.likes.iter() {
+                match self.like(&export.user, &liked.user, liked.clock).await {
+                    Ok(()) | Err(TribblerError::AlreadyLiked(..)) => {}
+                    Err(TribblerError::UserDoesNotExist(_))
+                    | Err(TribblerError::TribNotFound(..)) => warn!(
+                        "skipping the like of the missing trib {} of {} by {}",
+                        liked.clock, liked.user, export.user
+                    ),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    async fn mentions(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        // only the most recent mentions are needed
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mention_refs = user_bin.list_get("mentions").await?;
+        let start = mention_refs
+            .0
+            .len()
+            .saturating_sub(self.limits.max_trib_fetch);
+        let mut tribs = self.resolve(mention_refs.0[start..].to_vec()).await?;
+        let start = tribs.len().saturating_sub(self.limits.max_trib_fetch);
+        tribs.drain(..start);
+        return Ok(tribs);
+    }
+
+    async fn tribs_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>> {
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+
+        let mut tribs = self.all_tribs(user).await?;
+        tribs.retain(|t| t.clock < before_clock);
+        let start = tribs
+            .len()
+            .saturating_sub(limit.min(self.limits.max_trib_fetch));
+        tribs.drain(..start);
+        return Ok(tribs);
+    }
+
+    async fn home_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>> {
+        let mut members = vec![user.to_string()];
+        let hidden = self.hidden_set(user).await?;
+        for followee in self.following(user).await? {
+            if !hidden.contains(&followee) {
+                members.push(followee);
+            }
+        }
+        return self.merged_page(&members, before_clock, limit).await;
+    }
+
+    async fn follow_counts(&self, user: &str) -> ServerResult<(usize, usize)> {
+        if !is_valid_username(user) {
+            // invalid user name
+            return Err(TribblerError::InvalidUsername(user.to_string()));
+        }
+
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
         }
-        user_vec.sort(); // sort in alphabetical order
 
-        for user in user_vec {
-            general_bin
-                .list_append(&KeyValue {
-                    key: "cache".to_string(),
-                    value: user,
-                })
-                .await?;
+        // the counters are kept up to date as follows are settled
+        let user_bin = self.bin_storage.bin(user).await?;
+        let mut counts = [0_usize; 2];
+        for (i, key) in ["following_count", "followers_count"].iter().enumerate() {
+            if let Some(value) = user_bin.get(key).await? {
+                match value.parse::<i64>() {
+                    Ok(count) => counts[i] = count.max(0) as usize,
+                    Err(_) => warn!("skipping the corrupt {} of {}: {:?}", key, user, value),
+                }
+            }
         }
-        user_cache = general_bin.list_get("cache").await?;
-        // println!("list_users output: {:?}", user_cache.0);
-        return Ok(user_cache.0);
+        return Ok((counts[0], counts[1]));
     }
 
-    async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
-        // println!("post input: {}", who);
-        // println!("post input: {}", post);
-        // println!("post input: {}", clock);
+    async fn set_profile(&self, who: &str, profile: &Profile) -> ServerResult<()> {
+        self.check_writable()?;
         if !is_valid_username(who) {
             // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(who.to_string())));
-        }
-        if post.len() > MAX_TRIB_LEN {
-            // The post is too long.
-            return Err(Box::new(TribblerError::TribTooLong));
+            return Err(TribblerError::InvalidUsername(who.to_string()));
         }
+        profile.validate()?;
 
-        // use the general bin to check if the user has signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let signup_string = "signup_".to_owned() + who;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+        // check if the user has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
         }
 
-        // use the user bin to store his trib
         let who_bin = self.bin_storage.bin(who).await?;
-        let storage_clock = who_bin.clock(clock).await?; // get the clock from the storage
-
-        // create the trib
-        let trib = Trib {
-            user: who.to_string(),
-            message: post.to_string(),
-            time: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)?
-                .as_secs(),
-            clock: storage_clock,
-        };
-
-        // store as the user's posted trib
-        let trib_string = serde_json::to_string(&trib)?;
         who_bin
-            .list_append(&KeyValue {
-                key: "tribs".to_string(),
-                value: trib_string,
+            .set(&KeyValue {
+                key: "profile".to_string(),
+                value: serde_json::to_string(profile)?,
             })
             .await?;
+        self.touch(who).await?;
         return Ok(());
     }
 
-    async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
-        // println!("tribs input: {}", user);
+    async fn get_profile(&self, user: &str) -> ServerResult<Profile> {
         if !is_valid_username(user) {
             // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+            return Err(TribblerError::InvalidUsername(user.to_string()));
         }
 
-        // use the general bin to check if the user has signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let signup_string = "signup_".to_owned() + user;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string())));
+        // check if the user has signed up
+        if !self.is_signed_up(user).await? {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
         }
 
-        // get the tribs
-        let mut trib_vec = Vec::<Arc<Trib>>::new();
         let user_bin = self.bin_storage.bin(user).await?;
-        let tribs = user_bin.list_get("tribs").await?;
-        for trib in tribs.0 {
-            let json_trib = serde_json::from_str(&trib)?;
-            trib_vec.push(json_trib);
-        }
-        trib_vec.sort_by(|a, b| sort_trib(a, b)); // sort the tribbles based on the priority
-
-        // garbage collect older tribs
-        let trib_num = trib_vec.len();
-        if trib_num > MAX_TRIB_FETCH {
-            let old_num = trib_num - MAX_TRIB_FETCH;
-
-            // The tribs with less clock values are older.
-            for i in 0..old_num {
-                let old_trib = &trib_vec[i];
-                let old_trib_string = serde_json::to_string(&old_trib)?;
-                user_bin
-                    .list_remove(&KeyValue {
-                        key: "tribs".to_string(),
-                        value: old_trib_string,
-                    })
-                    .await?;
-            }
-            trib_vec = trib_vec[old_num..].to_vec();
+        match user_bin.get("profile").await? {
+            Some(profile_string) => Ok(serde_json::from_str(&profile_string)?),
+            None => Ok(Profile::default()),
         }
-        // println!("tribs output: {:?}", trib_vec);
-        return Ok(trib_vec);
     }
 
-    async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
-        // println!("follow input: {}", who);
-        // println!("follow input: {}", whom);
-        if !is_valid_username(who) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(who.to_string())));
-        }
-        if !is_valid_username(whom) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(whom.to_string())));
+    async fn block(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [who, whom] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
         }
 
-        // use the general bin to check if who and whom have signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let mut signup_string = "signup_".to_owned() + who;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
-        }
-        signup_string = "signup_".to_owned() + whom;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+        // check if who and whom have signed up
+        for user in [who, whom] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
+            }
         }
 
-        // The follower cannot follow himself.
         if who == whom {
-            return Err(Box::new(TribblerError::Unknown(
-                "The follower cannot follow himself.".to_string(),
-            )));
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+        if self.name_set(who, "blocked").await?.contains(whom) {
+            return Err(TribblerError::AlreadyBlocked(
+                who.to_string(),
+                whom.to_string(),
+            ));
         }
 
-        // append the log entry
         let who_bin = self.bin_storage.bin(who).await?;
-        let storage_clock = who_bin.clock(0).await?;
-        let log_entry = storage_clock.to_string() + "::follow::" + whom;
         who_bin
             .list_append(&KeyValue {
-                key: "log".to_string(),
-                value: log_entry,
+                key: "blocked".to_string(),
+                value: whom.to_string(),
             })
             .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
 
-        // check the log entry
-        let mut followees = HashSet::new();
-        let log = who_bin.list_get("log").await?;
-        for log_entry in log.0 {
-            let res: Vec<String> = log_entry.split("::").map(|s| s.to_string()).collect();
-            let parsed_clock = (&res[0]).to_string(); // unique identifier
-            let parsed_follow_string = (&res[1]).to_string(); // follow or unfollow
-            let parsed_followee = (&res[2]).to_string(); // followee
+    async fn unblock(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [who, whom] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
+        }
 
-            if parsed_follow_string == "unfollow" {
-                if followees.contains(&parsed_followee) {
-                    followees.remove(&parsed_followee);
-                }
-            } else {
-                if parsed_followee == whom {
-                    if parsed_clock.to_string() == storage_clock.to_string() {
-                        // this operation
-                        if !followees.contains(&parsed_followee) && followees.len() < MAX_FOLLOWING
-                        {
-                            return Ok(()); // successfully follow whom
-                        } else if followees.contains(&parsed_followee) {
-                            return Err(Box::new(TribblerError::AlreadyFollowing(
-                                who.to_string(),
-                                whom.to_string(),
-                            )));
-                        } else {
-                            return Err(Box::new(TribblerError::FollowingTooMany));
-                        }
-                    } else {
-                        // other operations
-                        if !followees.contains(&parsed_followee) && followees.len() < MAX_FOLLOWING
-                        {
-                            followees.insert(parsed_followee);
-                        }
-                    }
-                } else {
-                    if !followees.contains(&parsed_followee) && followees.len() < MAX_FOLLOWING {
-                        followees.insert(parsed_followee);
-                    }
-                }
+        // check if who and whom have signed up
+        for user in [who, whom] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
             }
         }
+
+        // concurrent blocks may have appended whom more than once, all of
+        // them go
+        let who_bin = self.bin_storage.bin(who).await?;
+        let removed = who_bin
+            .list_remove(&KeyValue {
+                key: "blocked".to_string(),
+                value: whom.to_string(),
+            })
+            .await?;
+        if removed == 0 {
+            return Err(TribblerError::NotBlocked(who.to_string(), whom.to_string()));
+        }
+        self.touch(who).await?;
         return Ok(());
     }
 
-    async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
-        // println!("unfollow input: {}", who);
-        // println!("unfollow input: {}", whom);
-        if !is_valid_username(who) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(who.to_string())));
-        }
-        if !is_valid_username(whom) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(whom.to_string())));
+    async fn mute(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [who, whom] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
         }
 
-        // use the general bin to check if who and whom have signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let mut signup_string = "signup_".to_owned() + who;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
-        }
-        signup_string = "signup_".to_owned() + whom;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+        // check if who and whom have signed up
+        for user in [who, whom] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
+            }
         }
 
-        // The follower cannot unfollow himself.
         if who == whom {
-            return Err(Box::new(TribblerError::Unknown(
-                "The follower cannot follow himself.".to_string(),
-            )));
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+        if self.name_set(who, "muted").await?.contains(whom) {
+            return Err(TribblerError::AlreadyMuted(
+                who.to_string(),
+                whom.to_string(),
+            ));
         }
 
-        // append the log entry
         let who_bin = self.bin_storage.bin(who).await?;
-        let storage_clock = who_bin.clock(0).await?;
-        let log_entry = storage_clock.to_string() + "::unfollow::" + whom;
         who_bin
             .list_append(&KeyValue {
-                key: "log".to_string(),
-                value: log_entry,
+                key: "muted".to_string(),
+                value: whom.to_string(),
             })
             .await?;
+        self.touch(who).await?;
+        return Ok(());
+    }
 
-        // check the log entry
-        let mut followees = HashSet::new();
-        let log = who_bin.list_get("log").await?;
-        for log_entry in log.0 {
-            let res: Vec<String> = log_entry.split("::").map(|s| s.to_string()).collect();
-            let parsed_clock = (&res[0]).to_string(); // unique identifier
-            let parsed_follow_string = (&res[1]).to_string(); // follow or unfollow
-            let parsed_followee = (&res[2]).to_string(); // followee
+    async fn unmute(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [who, whom] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
+        }
 
-            if parsed_follow_string == "follow" {
-                if !followees.contains(&parsed_followee) && followees.len() < MAX_FOLLOWING {
-                    followees.insert(parsed_followee);
-                }
-            } else {
-                // unfollow
-                if parsed_followee == whom {
-                    if parsed_clock == storage_clock.to_string() {
-                        // this operation
-                        if followees.contains(&parsed_followee) {
-                            return Ok(());
-                        }
-                        return Err(Box::new(TribblerError::NotFollowing(
-                            who.to_string(),
-                            whom.to_string(),
-                        )));
-                    } else {
-                        // other operations
-                        if followees.contains(&parsed_followee) {
-                            followees.remove(&parsed_followee);
-                        }
-                    }
-                } else {
-                    if followees.contains(&parsed_followee) {
-                        followees.remove(&parsed_followee);
-                    }
-                }
+        // check if who and whom have signed up
+        for user in [who, whom] {
+            if !self.is_signed_up(user).await? {
+                return Err(TribblerError::UserDoesNotExist(user.to_string()));
             }
         }
+
+        // concurrent mutes may have appended whom more than once, all of
+        // them go
+        let who_bin = self.bin_storage.bin(who).await?;
+        let removed = who_bin
+            .list_remove(&KeyValue {
+                key: "muted".to_string(),
+                value: whom.to_string(),
+            })
+            .await?;
+        if removed == 0 {
+            return Err(TribblerError::NotMuted(who.to_string(), whom.to_string()));
+        }
+        self.touch(who).await?;
         return Ok(());
     }
 
-    async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
-        // println!("is_follow input: {}", who);
-        // println!("is_follow input: {}", whom);
-        if !is_valid_username(who) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(who.to_string())));
-        }
-        if !is_valid_username(whom) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(whom.to_string())));
+    async fn rename_user(&self, old: &str, new: &str) -> ServerResult<()> {
+        self.check_writable()?;
+        for user in [old, new] {
+            if !is_valid_username(user) {
+                // invalid user name
+                return Err(TribblerError::InvalidUsername(user.to_string()));
+            }
         }
 
-        // use the general bin to check if who and whom have signed up
+        // use the general bin to check that old has signed up and new is free
         let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let mut signup_string = "signup_".to_owned() + who;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+        let old_signup = "signup_".to_owned() + old;
+        if general_bin.get(&old_signup).await?.is_none() {
+            return Err(TribblerError::UserDoesNotExist(old.to_string()));
         }
-        signup_string = "signup_".to_owned() + whom;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+        // an old name is only free to the user it redirects to
+        let new_signup = "signup_".to_owned() + new;
+        let retired = match self.redirects().await?.get(new) {
+            Some(to) => to != old,
+            None => false,
+        };
+        if retired || general_bin.get(&new_signup).await?.is_some() {
+            return Err(TribblerError::UsernameTaken(new.to_string()));
         }
 
-        // The follower cannot follow/unfollow himself.
-        if who == whom {
-            return Err(Box::new(TribblerError::Unknown(
-                "The follower cannot follow himself.".to_string(),
-            )));
+        // claim the new name first, so nobody signs up as new meanwhile
+        general_bin
+            .set(&KeyValue {
+                key: new_signup,
+                value: "T".to_string(),
+            })
+            .await?;
+        self.rename_bin(old, new).await?;
+
+        // whatever redirected to old redirects to new now, so a redirect is
+        // never more than one hop, and new itself is no longer redirected
+        for entry in general_bin.list_get("redirects").await?.0 {
+            if let Some((from, to)) = entry.split_once("::") {
+                if to == old {
+                    if from != new {
+                        general_bin
+                            .list_append(&KeyValue {
+                                key: "redirects".to_string(),
+                                value: from.to_owned() + "::" + new,
+                            })
+                            .await?;
+                    }
+                    general_bin
+                        .list_remove(&KeyValue {
+                            key: "redirects".to_string(),
+                            value: entry,
+                        })
+                        .await?;
+                }
+            }
         }
+        general_bin
+            .list_append(&KeyValue {
+                key: "redirects".to_string(),
+                value: old.to_owned() + "::" + new,
+            })
+            .await?;
 
-        // check who's followees
-        let followee_vec = self.following(who).await?;
-        return Ok(followee_vec.contains(&whom.to_string()));
+        // give up the old name
+        self.signups.remove(old)?;
+        general_bin
+            .set(&KeyValue {
+                key: old_signup,
+                value: "".to_string(),
+            })
+            .await?;
+        let cached = general_bin
+            .list_remove(&KeyValue {
+                key: "cache".to_string(),
+                value: old.to_string(),
+            })
+            .await?;
+        if cached > 0 {
+            general_bin
+                .list_append(&KeyValue {
+                    key: "cache".to_string(),
+                    value: new.to_string(),
+                })
+                .await?;
+        }
+        return Ok(());
     }
 
-    async fn following(&self, who: &str) -> TribResult<Vec<String>> {
-        // println!("following input: {}", who);
+    async fn blocked(&self, who: &str) -> ServerResult<Vec<String>> {
         if !is_valid_username(who) {
             // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(who.to_string())));
+            return Err(TribblerError::InvalidUsername(who.to_string()));
         }
 
-        // use the general bin to check if who has signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let signup_string = "signup_".to_owned() + who;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+        // check if the user has signed up
+        if !self.is_signed_up(who).await? {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
         }
 
-        // check the log entry
-        let mut followees = HashSet::new();
-        let who_bin = self.bin_storage.bin(who).await?;
-        let log = who_bin.list_get("log").await?;
-        for log_entry in log.0 {
-            let res: Vec<String> = log_entry.split("::").map(|s| s.to_string()).collect();
-            let parsed_follow_string = (&res[1]).to_string(); // follow or unfollow
-            let parsed_followee = (&res[2]).to_string(); // followee
-
-            if parsed_follow_string == "follow" {
-                if !followees.contains(&parsed_followee) && followees.len() < MAX_FOLLOWING {
-                    followees.insert(parsed_followee);
-                }
-            } else {
-                if followees.contains(&parsed_followee) {
-                    followees.remove(&parsed_followee);
+        let mut blocked: Vec<String> = self.name_set(who, "blocked").await?.into_iter().collect();
+        blocked.sort();
+        return Ok(blocked);
+    }
+}
+
+// the current name of a user who may have been renamed
+fn redirected(redirects: &HashMap<String, String>, name: &str) -> String {
+    match redirects.get(name) {
+        Some(new) => new.to_string(),
+        None => name.to_string(),
+    }
+}
+
+// the names who had before being renamed to who, sorted
+fn old_names(redirects: &HashMap<String, String>, who: &str) -> Vec<String> {
+    let mut names: Vec<String> = redirects
+        .iter()
+        .filter(|(_, new)| new.as_str() == who)
+        .map(|(old, _)| old.to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+// The entries of the follow log of who, in log order. A corrupt entry is
+// reported and skipped, so that one bad write doesn't lose who everyone they
+// follow.
+fn read_log(who: &str, log: &[String]) -> Vec<FollowLogEntry> {
+    log.iter()
+        .filter_map(|entry| match FollowLogEntry::parse(entry) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("skipping an entry of the follow log of {}: {}", who, e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Starts a follow log from its latest snapshot: the followees it holds, and
+// the entries past its clock, in log order. Without a snapshot that is an
+// empty set and the whole log.
+fn from_snapshot(
+    redirects: &HashMap<String, String>,
+    log: Vec<FollowLogEntry>,
+) -> (HashSet<String>, Vec<FollowLogEntry>) {
+    let mut high_water = None;
+    let mut followees = HashSet::new();
+    for log_entry in log.iter() {
+        if let FollowLogEntry::Snapshot {
+            clock,
+            followees: names,
+        } = log_entry
+        {
+            if high_water.is_none_or(|high| *clock >= high) {
+                high_water = Some(*clock);
+                followees = names
+                    .iter()
+                    .map(|name| redirected(redirects, name))
+                    .collect();
+            }
+        }
+    }
+
+    let rest = log
+        .into_iter()
+        .filter(|log_entry| {
+            !matches!(log_entry, FollowLogEntry::Snapshot { .. })
+                && high_water.is_none_or(|high| log_entry.clock() > high)
+        })
+        .collect();
+    (followees, rest)
+}
+
+// the followees a follow log adds up to
+fn replay_log(
+    redirects: &HashMap<String, String>,
+    log: Vec<FollowLogEntry>,
+    max_following: usize,
+) -> HashSet<String> {
+    let (mut followees, log) = from_snapshot(redirects, log);
+    for log_entry in log {
+        match log_entry {
+            FollowLogEntry::Follow { whom, .. } => {
+                let followee = redirected(redirects, &whom);
+                if !followees.contains(&followee) && followees.len() < max_following {
+                    followees.insert(followee);
                 }
             }
+            FollowLogEntry::Unfollow { whom, .. } => {
+                followees.remove(&redirected(redirects, &whom));
+            }
+            FollowLogEntry::Snapshot { .. } => {}
         }
-        let mut followee_vec = Vec::<String>::new();
-        for followee in followees {
-            followee_vec.push(followee.to_string());
+    }
+    followees
+}
+
+// follow the priority to sort the tribs
+fn sort_trib(a: &Arc<Trib>, b: &Arc<Trib>) -> Ordering {
+    trib_order(a, b)
+}
+
+// Keeps the last revision of every trib, of the ones stored more than once
+// while being archived or edited, sorted by priority.
+fn latest_revisions(tribs: impl IntoIterator<Item = Trib>) -> Vec<Arc<Trib>> {
+    let mut latest = HashMap::<(String, u64), Trib>::new();
+    for trib in tribs {
+        match latest.get(&(trib.user.clone(), trib.clock)) {
+            Some(kept) if kept.revision >= trib.revision => {}
+            _ => {
+                latest.insert((trib.user.clone(), trib.clock), trib);
+            }
         }
-        followee_vec.sort();
-        return Ok(followee_vec);
     }
+    let mut trib_vec: Vec<Arc<Trib>> = latest.into_values().map(Arc::new).collect();
+    trib_vec.sort_by(sort_trib);
+    trib_vec
+}
 
-    async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
-        // println!("home input: {}", user);
-        if !is_valid_username(user) {
-            // invalid user name
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+#[cfg(test)]
+mod test {
+    use super::{
+        read_log, replay_log, FrontendServer, SharedView, SignupCache, SCHEDULED_BIN, SIGNUP_TTL,
+        TRENDING_BIN,
+    };
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tribbler::config::Limits;
+    use tribbler::err::{TribResult, TribblerError};
+    use tribbler::storage::{KeyList, KeyString, KeyValue, Pattern};
+    use tribbler::testing::{conformance, Call, MockBinStorage, Op, Reply};
+    use tribbler::time::{ManualClock, SystemClock, TimeSource};
+    use tribbler::trib::Server;
+
+    // a front-end over `bins`, which takes writes
+    fn mock_front(bins: &MockBinStorage) -> FrontendServer {
+        limited_front(bins, Limits::default())
+    }
+
+    // a front-end over `bins`, which takes writes and enforces `limits`
+    fn limited_front(bins: &MockBinStorage, limits: Limits) -> FrontendServer {
+        FrontendServer {
+            bin_storage: Box::new(bins.clone()),
+            view: None,
+            signups: SignupCache::new(SIGNUP_TTL),
+            limits,
+            time: Arc::new(SystemClock),
         }
+    }
 
-        // use the general bin to check if the user has signed up
-        let general_bin = self.bin_storage.bin("").await?; // get the general bin
-        let signup_string = "signup_".to_owned() + user;
-        let signed = general_bin.get(&signup_string).await?;
-        if signed == None {
-            return Err(Box::new(TribblerError::UserDoesNotExist(user.to_string())));
+    // a front-end over `bins`, which takes writes and times them by `time`
+    fn timed_front(bins: &MockBinStorage, time: Arc<dyn TimeSource>) -> FrontendServer {
+        FrontendServer {
+            signups: SignupCache::with_time(SIGNUP_TTL, time.clone()),
+            time,
+            ..mock_front(bins)
         }
+    }
+
+    #[test]
+    fn signup_cache_remembers_users() {
+        let cache = SignupCache::new(Duration::from_secs(60));
+        assert!(!cache.contains("alice").unwrap());
+        cache.insert("alice").unwrap();
+        assert!(cache.contains("alice").unwrap());
+        cache.remove("alice").unwrap();
+        assert!(!cache.contains("alice").unwrap());
+    }
+
+    #[test]
+    fn signup_cache_entries_expire() {
+        let cache = SignupCache::new(Duration::ZERO);
+        cache.insert("alice").unwrap();
+        assert!(!cache.contains("alice").unwrap());
+    }
+
+    #[test]
+    fn signup_cache_entries_expire_after_the_ttl() {
+        let clock = Arc::new(ManualClock::new(0));
+        let cache = SignupCache::with_time(Duration::from_secs(30), clock.clone());
+        cache.insert("alice").unwrap();
+        clock.advance(Duration::from_millis(29_999));
+        assert!(cache.contains("alice").unwrap());
+        clock.advance(Duration::from_millis(1));
+        assert!(!cache.contains("alice").unwrap());
+    }
+
+    #[test]
+    fn corrupt_log_entries_are_skipped() {
+        let log: Vec<String> = [
+            "3::snapshot::bob,carol",
+            "4::unfollow::bob",
+            "4::follow",
+            "x::follow::erin",
+            "5::follow::dave",
+            "6::poke::frank",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+        let entries = read_log("alice", &log);
+        assert_eq!(3, entries.len());
+        let mut followees: Vec<String> = replay_log(&HashMap::new(), entries, 20)
+            .into_iter()
+            .collect();
+        followees.sort();
+        assert_eq!(vec!["carol", "dave"], followees);
+    }
+
+    #[tokio::test]
+    async fn signed_up_users_are_not_looked_up_again() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        front.sign_up("alice").await?;
+        bins.storage("").clear_calls();
+        assert!(front.is_signed_up("alice").await?);
+        assert!(bins.storage("").calls_of(Op::Get).is_empty());
+        Ok(())
+    }
 
-        // get the tribs of the user
-        let mut user_home = Vec::<Arc<Trib>>::new();
-        let mut user_tribs = self.tribs(user).await?;
-        user_home.append(&mut user_tribs);
+    #[tokio::test]
+    async fn failed_sign_up_is_not_remembered() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        bins.storage("")
+            .script(Op::Set, Err(TribblerError::RpcError("down".to_string())));
+        assert!(front.sign_up("alice").await.is_err());
+        assert!(!front.is_signed_up("alice").await?);
+        front.sign_up("alice").await?;
+        assert!(front.is_signed_up("alice").await?);
+        Ok(())
+    }
 
-        // get the tribs of the followees
-        let followees = self.following(user).await?;
-        for followee in followees {
-            let mut followee_tribs = self.tribs(&followee).await?;
-            user_home.append(&mut followee_tribs);
+    #[tokio::test]
+    async fn a_follow_failed_partway_is_settled_by_the_next() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        for user in ["alice", "bob", "carol"] {
+            front.sign_up(user).await?;
         }
 
-        // sort the tribbles based on the priority
-        user_home.sort_by(|a, b| sort_trib(a, b));
+        // the followee set took bob, but the followers list of bob didn't
+        bins.storage("bob").fail(Op::ListAppend);
+        assert!(front.follow("alice", "bob").await.is_err());
+        assert_eq!(vec!["bob"], front.following("alice").await?);
+        assert_eq!((0, 0), front.follow_counts("bob").await?);
+
+        bins.storage("bob").heal(Op::ListAppend);
+        front.follow("alice", "carol").await?;
+        assert_eq!((2, 0), front.follow_counts("alice").await?);
+        assert_eq!((0, 1), front.follow_counts("bob").await?);
+        assert_eq!((0, 1), front.follow_counts("carol").await?);
+        Ok(())
+    }
 
-        let trib_num = user_home.len();
-        if trib_num > MAX_TRIB_FETCH {
-            let old_num = trib_num - MAX_TRIB_FETCH;
-            user_home = user_home[old_num..].to_vec();
+    #[tokio::test]
+    async fn an_unfollow_after_a_rename_drops_the_old_name() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        for user in ["alice", "bob"] {
+            front.sign_up(user).await?;
         }
-        // println!("home output: {:?}", user_home);
-        return Ok(user_home);
+        front.follow("alice", "bob").await?;
+        front.rename_user("alice", "alicia").await?;
+        assert_eq!((0, 1), front.follow_counts("bob").await?);
+
+        // bob's followers list still has alice under her old name
+        front.unfollow("alicia", "bob").await?;
+        assert_eq!((0, 0), front.follow_counts("alicia").await?);
+        assert_eq!((0, 0), front.follow_counts("bob").await?);
+        assert!(front.followers("bob").await?.is_empty());
+        front.post("bob", "hi", 0).await?;
+        assert!(front.home("alicia").await?.is_empty());
+        Ok(())
     }
-}
 
-// follow the priority to sort the tribs
-fn sort_trib(a: &Arc<Trib>, b: &Arc<Trib>) -> Ordering {
-    if a.clock < b.clock {
-        return Ordering::Less;
-    } else if a.clock > b.clock {
-        return Ordering::Greater;
-    } else {
-        if a.time < b.time {
-            return Ordering::Less;
-        } else if a.time > b.time {
-            return Ordering::Greater;
-        } else {
-            if a.user < b.user {
-                return Ordering::Less;
-            } else if a.user > b.user {
-                return Ordering::Greater;
-            } else {
-                if a.message < b.message {
-                    return Ordering::Less;
-                } else if a.message > b.message {
-                    return Ordering::Greater;
-                } else {
-                    return Ordering::Equal;
-                }
-            }
+    #[tokio::test]
+    async fn tribs_drop_a_revision_an_edit_left_behind() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let limits = Limits {
+            max_trib_fetch: 2,
+            ..Limits::default()
+        };
+        let front = limited_front(&bins, limits);
+        front.sign_up("alice").await?;
+        front.post("alice", "first", 0).await?;
+        front.post("alice", "second", 0).await?;
+        let clock = front.tribs("alice").await?[0].clock;
+
+        // the edit appended its revision, but failed to remove the old one
+        bins.storage("alice").fail(Op::ListRemove);
+        assert!(front.edit_trib("alice", clock, "edited").await.is_err());
+        bins.storage("alice").heal(Op::ListRemove);
+
+        let tribs = front.tribs("alice").await?;
+        assert_eq!(
+            vec!["edited", "second"],
+            tribs
+                .iter()
+                .map(|t| t.message.as_str())
+                .collect::<Vec<&str>>()
+        );
+        let alice = bins.storage("alice");
+        assert_eq!(2, alice.list_get("tribs").await?.0.len());
+        assert!(alice.list_get("archived_tribs").await?.0.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn home_reads_the_archive_only_for_tribs_not_in_the_list() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        front.sign_up("alice").await?;
+        front.sign_up("bob").await?;
+        front.follow("alice", "bob").await?;
+        front.post("bob", "hello", 0).await?;
+        let read_archive = Call::ListGet("archived_tribs".to_string());
+
+        bins.storage("bob").clear_calls();
+        assert_eq!("hello", front.home("alice").await?[0].message);
+        assert!(!bins.storage("bob").calls().contains(&read_archive));
+
+        // the trib was archived since it was fanned out
+        let trib = serde_json::to_string(&*front.tribs("bob").await?[0])?;
+        let bob = bins.storage("bob");
+        bob.list_remove(&KeyValue::new("tribs", &trib)).await?;
+        bob.list_append(&KeyValue::new("archived_tribs", &trib))
+            .await?;
+        bob.clear_calls();
+        assert_eq!("hello", front.home("alice").await?[0].message);
+        assert!(bob.calls().contains(&read_archive));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_poisoned_view_fails_writes() {
+        let view = SharedView::default();
+        let poisoner = view.clone();
+        let _ = std::thread::spawn(move || {
+            let _held = poisoner.write().unwrap();
+            panic!("poisoning the view");
+        })
+        .join();
+        let front = FrontendServer {
+            view: Some(view),
+            ..mock_front(&MockBinStorage::new())
+        };
+        assert!(matches!(
+            front.sign_up("alice").await,
+            Err(TribblerError::CacheFailure(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn malformed_due_entries_are_skipped() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        front.sign_up("alice").await?;
+        front.schedule_post("alice", "later", 100).await?;
+        let due = bins.storage(SCHEDULED_BIN);
+        due.list_append(&KeyValue::new("due", "soon::alice"))
+            .await?;
+
+        // the malformed entry doesn't read as due at 0
+        front.publish_due(50).await?;
+        assert!(front.tribs("alice").await?.is_empty());
+        front.publish_due(100).await?;
+        assert_eq!("later", front.tribs("alice").await?[0].message);
+        assert_eq!(vec!["soon::alice"], due.list_get("due").await?.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn malformed_trending_counters_are_skipped() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let front = mock_front(&bins);
+        front.sign_up("alice").await?;
+        front.post("alice", "#rust", 0).await?;
+        let trending = bins.storage(TRENDING_BIN);
+        let slot = trending.keys(&Pattern::default()).await?.0[0].clone();
+        let (slot, _) = slot.split_once('_').unwrap();
+        trending
+            .set(&KeyValue::new(&format!("{}_go", slot), "many"))
+            .await?;
+        trending.set(&KeyValue::new("soon_zig", "3")).await?;
+
+        let tags: Vec<String> = front
+            .trending(10)
+            .await?
+            .into_iter()
+            .map(|trend| trend.tag)
+            .collect();
+        assert_eq!(vec!["rust"], tags);
+        // the malformed slot isn't taken for one slid out of the window
+        assert_eq!(Some("3".to_string()), trending.get("soon_zig").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn front_conforms() -> TribResult<()> {
+        let limits = Limits {
+            max_trib_fetch: 10,
+            max_following: 5,
+            ..Limits::default()
+        };
+        let front = limited_front(&MockBinStorage::new(), limits);
+        conformance::run(Box::new(front), limits).await
+    }
+
+    #[tokio::test]
+    async fn home_breaks_clock_ties_by_time_then_user() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        let clock = Arc::new(ManualClock::new(0));
+        let front = timed_front(&bins, clock.clone());
+        for user in ["alice", "bob", "carol"] {
+            front.sign_up(user).await?;
+        }
+        front.follow("carol", "alice").await?;
+        front.follow("carol", "bob").await?;
+
+        // each post takes the clock its bin answers with, as it would from
+        // the backend of that bin
+        let posts = [
+            ("alice", "a7", 7, 100),
+            ("bob", "b7", 7, 50),
+            ("carol", "c7", 7, 100),
+            ("bob", "b3", 3, 300),
+            ("alice", "a9", 9, 10),
+        ];
+        for (user, message, at, time) in posts {
+            bins.storage(user).script(Op::Clock, Ok(Reply::Clock(at)));
+            clock.set(time);
+            front.post(user, message, 0).await?;
         }
+
+        let home: Vec<(String, u64, u64)> = front
+            .home("carol")
+            .await?
+            .iter()
+            .map(|t| (t.message.clone(), t.clock, t.time))
+            .collect();
+        let expected = [
+            ("b3", 3, 300),
+            ("b7", 7, 50),
+            ("a7", 7, 100),
+            ("c7", 7, 100),
+            ("a9", 9, 10),
+        ];
+        let expected: Vec<(String, u64, u64)> = expected
+            .iter()
+            .map(|(m, c, t)| (m.to_string(), *c, *t))
+            .collect();
+        assert_eq!(expected, home);
+        Ok(())
     }
 }
@@ -1 +1,715 @@
+use crate::keeper::{
+    keeper_server::Keeper as KeeperRpc, BackendStatus, ClusterView, KeeperStatus, StatusRequest,
+    WatchRequest,
+};
+use crate::lab1::lab::new_client;
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tribbler::{
+    addr,
+    config::{KeeperTuning, Placement},
+    err::{TribResult, TribblerError},
+    time::{SystemClock, TimeSource},
+};
 
+/// Tracks the liveness of a single backend and applies an exponential
+/// hold-down to backends which keep transitioning between alive and dead, so
+/// they stay out of the write path until they have been stable for a while.
+#[derive(Debug, Clone)]
+pub struct FlapDamper {
+    alive: Option<bool>,       // last observed liveness, None before the first probe
+    up_since: Option<Instant>, // when the backend recovered, None if it has been up since the first probe
+    hold_down: Duration,       // how long a recovered backend must stay up before it is used again
+}
+
+impl FlapDamper {
+    pub fn new(tuning: &KeeperTuning) -> FlapDamper {
+        FlapDamper {
+            alive: None,
+            up_since: None,
+            hold_down: Duration::from_millis(tuning.stable_window_ms),
+        }
+    }
+
+    /// Records the result of a probe taken at `now`. Returns true when the
+    /// liveness of the backend changed since the last observation.
+    pub fn observe(&mut self, alive: bool, now: Instant, tuning: &KeeperTuning) -> bool {
+        let previous = self.alive.replace(alive);
+        match (previous, alive) {
+            (Some(true), false) => {
+                // A backend which dies again shortly after recovering is flapping, so
+                // it has to prove itself for twice as long the next time it comes back.
+                let base = Duration::from_millis(tuning.stable_window_ms);
+                let max = Duration::from_millis(tuning.max_hold_down_ms);
+                self.hold_down = match self.up_since {
+                    Some(since) if now.duration_since(since) < self.hold_down * 2 => {
+                        cmp::min(self.hold_down * 2, cmp::max(base, max))
+                    }
+                    _ => base,
+                };
+                self.up_since = None;
+                true
+            }
+            (Some(false), true) => {
+                self.up_since = Some(now);
+                true
+            }
+            (None, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true when the backend may be used for writes at `now`.
+    pub fn is_eligible(&self, now: Instant) -> bool {
+        match (self.alive, self.up_since) {
+            (Some(true), None) => true,
+            (Some(true), Some(since)) => now.duration_since(since) >= self.hold_down,
+            _ => false,
+        }
+    }
+
+    /// Returns true when the backend answered its last probe.
+    pub fn is_alive(&self) -> bool {
+        self.alive == Some(true)
+    }
+
+    /// The hold-down currently applied to this backend.
+    pub fn hold_down(&self) -> Duration {
+        self.hold_down
+    }
+}
+
+/// Spaces out the probes of a backend which keeps failing, so that one dead
+/// backend doesn't slow down the clock sync of the rest of the cluster.
+#[derive(Debug, Clone, Default)]
+pub struct Backoff {
+    failures: u32,             // consecutive failed probes
+    retry_at: Option<Instant>, // when the backend may be probed again, None if it's healthy
+}
+
+impl Backoff {
+    /// Returns true when the backend is due for a probe at `now`.
+    pub fn should_probe(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    /// The number of probes which failed in a row.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns true once enough probes failed in a row for the backend to
+    /// count as down.
+    pub fn is_down(&self, tuning: &KeeperTuning) -> bool {
+        self.failures >= cmp::max(tuning.failure_threshold, 1)
+    }
+
+    pub fn succeed(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    /// Records a failed probe at `now` and returns how long to wait before
+    /// the next attempt.
+    pub fn fail(&mut self, now: Instant, tuning: &KeeperTuning) -> Duration {
+        let base = Duration::from_millis(tuning.retry_backoff_ms);
+        let max = cmp::max(base, Duration::from_millis(tuning.max_retry_backoff_ms));
+        let delay = cmp::min(base.saturating_mul(1 << cmp::min(self.failures, 16)), max);
+        self.failures += 1;
+        self.retry_at = Some(now + delay);
+        delay
+    }
+}
+
+/// The keeper state: the backends it watches, the cluster clock, and the
+/// cluster view published to subscribed bin clients.
+pub struct Keeper {
+    backs: Vec<String>,
+    tuning: KeeperTuning,
+    placement: Placement,
+    dampers: Vec<FlapDamper>,
+    backoffs: Vec<Backoff>,
+    clocks: Vec<Option<u64>>, // last clock reported by each backend
+    lags: Vec<u64>,           // how far each backend was behind when last probed
+    clock: u64,
+    consistent: bool,
+    view: watch::Sender<ClusterView>,
+    status: watch::Sender<KeeperStatus>,
+    time: Arc<dyn TimeSource>, // what probes, backoffs, and hold-downs are timed by
+}
+
+impl Keeper {
+    pub fn new(backs: Vec<String>, tuning: KeeperTuning, placement: Placement) -> Keeper {
+        Keeper::with_time(backs, tuning, placement, Arc::new(SystemClock))
+    }
+
+    /// Like [Keeper::new], but the keeper times its backoffs and hold-downs
+    /// by `time`.
+    pub fn with_time(
+        backs: Vec<String>,
+        tuning: KeeperTuning,
+        placement: Placement,
+        time: Arc<dyn TimeSource>,
+    ) -> Keeper {
+        let dampers = backs.iter().map(|_| FlapDamper::new(&tuning)).collect();
+        let backoffs = backs.iter().map(|_| Backoff::default()).collect();
+        let (view, _) = watch::channel(ClusterView::default());
+        let (status, _) = watch::channel(KeeperStatus::default());
+        Keeper {
+            clocks: vec![None; backs.len()],
+            lags: vec![0; backs.len()],
+            backs,
+            tuning,
+            placement,
+            dampers,
+            backoffs,
+            clock: 0,
+            consistent: false,
+            view,
+            status,
+            time,
+        }
+    }
+
+    /// Replaces the backends the keeper watches, as a reloaded config lists
+    /// them. Backends it already watched keep their state; new ones start out
+    /// unprobed, so they join the write path once they answer. Bins hashed to
+    /// a slot which moves are not migrated.
+    pub fn set_backs(&mut self, backs: Vec<String>) {
+        if backs == self.backs {
+            return;
+        }
+        info!("watching backends {:?}, was {:?}", backs, self.backs);
+        let old = std::mem::take(&mut self.backs);
+        let mut dampers = Vec::with_capacity(backs.len());
+        let mut backoffs = Vec::with_capacity(backs.len());
+        let mut clocks = Vec::with_capacity(backs.len());
+        let mut lags = Vec::with_capacity(backs.len());
+        for back in backs.iter() {
+            match old.iter().position(|b| b == back) {
+                Some(i) => {
+                    dampers.push(self.dampers[i].clone());
+                    backoffs.push(self.backoffs[i].clone());
+                    clocks.push(self.clocks[i]);
+                    lags.push(self.lags[i]);
+                }
+                None => {
+                    dampers.push(FlapDamper::new(&self.tuning));
+                    backoffs.push(Backoff::default());
+                    clocks.push(None);
+                    lags.push(0);
+                }
+            }
+        }
+        self.backs = backs;
+        self.dampers = dampers;
+        self.backoffs = backoffs;
+        self.clocks = clocks;
+        self.lags = lags;
+    }
+
+    /// Creates the RPC service streaming this keeper's cluster view and
+    /// reporting its per-backend status.
+    pub fn service(&self) -> KeeperService {
+        KeeperService {
+            view: self.view.subscribe(),
+            status: self.status.subscribe(),
+        }
+    }
+
+    /// Probes every backend to update its liveness and collect the max clock,
+    /// then pushes the max clock to the backends which are in the write path.
+    /// The clocks read by the probes also give the replication lag of every
+    /// backend, i.e. how far it was behind the cluster clock.
+    ///
+    /// Errors from a single backend never abort the sync: the backend is
+    /// re-probed with an exponential backoff, and counted as down once the
+    /// failure threshold of probes failed in a row, while the remaining
+    /// backends keep being synced.
+    pub async fn sync(&mut self) {
+        let now = self.time.instant();
+        let timeout = Duration::from_millis(self.tuning.probe_timeout_ms);
+        let cluster_clock = self.clock;
+        let backends = self
+            .backs
+            .iter()
+            .zip(self.dampers.iter_mut())
+            .zip(self.backoffs.iter_mut())
+            .zip(self.clocks.iter_mut().zip(self.lags.iter_mut()));
+        for (((back, damper), backoff), (last_clock, lag)) in backends {
+            if !backoff.should_probe(now) {
+                continue; // still backing off, so it stays down
+            }
+            let alive = match probe(back, 0, timeout).await {
+                Ok(clock) => {
+                    self.clock = cmp::max(self.clock, clock);
+                    *last_clock = Some(clock);
+                    *lag = cluster_clock.saturating_sub(clock);
+                    backoff.succeed();
+                    true
+                }
+                Err(e) => {
+                    let delay = backoff.fail(now, &self.tuning);
+                    debug!(
+                        "probe of backend {} failed, retrying in {:?}: {}",
+                        back, delay, e
+                    );
+                    if !backoff.is_down(&self.tuning) {
+                        continue; // not failed often enough to count yet
+                    }
+                    false
+                }
+            };
+            if damper.observe(alive, now, &self.tuning) {
+                match alive {
+                    true => info!(
+                        "backend {} is up, holding it down for {:?}",
+                        back,
+                        damper.hold_down()
+                    ),
+                    false => warn!("backend {} is down", back),
+                }
+            }
+        }
+
+        // set all the clocks in the write path to the max clock
+        let live = self.live_backs();
+        let target = self.clock;
+        for back in live.iter() {
+            match probe(back, target, timeout).await {
+                Ok(clock) => self.clock = cmp::max(self.clock, clock),
+                Err(e) => warn!("failed to sync clock of backend {}: {}", back, e),
+            }
+        }
+
+        // read the clocks back to verify that they actually converged
+        let mut clocks = Vec::with_capacity(live.len());
+        for back in live.iter() {
+            let clock = probe(back, 0, timeout).await.ok();
+            if let Some(i) = self.backs.iter().position(|b| b == back) {
+                self.clocks[i] = clock.or(self.clocks[i]);
+            }
+            clocks.push(clock);
+        }
+        let consistent = converged(&clocks, target);
+        if consistent != self.consistent {
+            match consistent {
+                true => info!("backend clocks converged at {}", target),
+                false => warn!("backend clocks are no longer known to be consistent"),
+            }
+            self.consistent = consistent;
+        }
+
+        self.publish_view();
+        self.publish_status();
+    }
+
+    // refreshes the per-backend status served by the status RPC
+    fn publish_status(&self) {
+        let now = self.time.instant();
+        let backends = self
+            .backs
+            .iter()
+            .enumerate()
+            .map(|(i, back)| {
+                let status = BackendStatus {
+                    addr: back.to_string(),
+                    alive: self.dampers[i].is_alive(),
+                    eligible: self.dampers[i].is_eligible(now),
+                    clock: self.clocks[i].unwrap_or(0),
+                    lag: self.lags[i],
+                    hold_down_ms: self.dampers[i].hold_down().as_millis() as u64,
+                    failures: self.backoffs[i].failures(),
+                };
+                debug!(
+                    "backend {}: alive={} lag={} failures={}",
+                    back, status.alive, status.lag, status.failures
+                );
+                status
+            })
+            .collect();
+        self.status.send_replace(KeeperStatus {
+            clock: self.clock,
+            epoch: self.view.borrow().epoch,
+            consistent: self.consistent,
+            backends,
+        });
+    }
+
+    // bumps the epoch and notifies the watchers when the live set, or the
+    // backends watched, changed
+    fn publish_view(&self) {
+        let now = self.time.instant();
+        let eligible: Vec<bool> = self.dampers.iter().map(|d| d.is_eligible(now)).collect();
+        let live = self.live_backs();
+        let epoch = {
+            let current = self.view.borrow();
+            if current.epoch > 0
+                && current.live == live
+                && current.consistent == self.consistent
+                && current.backs == self.backs
+            {
+                return;
+            }
+            current.epoch + 1
+        };
+        info!("cluster view epoch {}: live backends {:?}", epoch, live);
+        let wanted = cmp::min(self.placement.replication_factor, self.backs.len());
+        if live.len() < wanted {
+            warn!(
+                "bins are kept on {} backends rather than {}, as the rest are down",
+                live.len(),
+                wanted
+            );
+        }
+        let layout = layout(&self.backs, self.placement.virtual_nodes);
+        self.view.send_replace(ClusterView {
+            epoch,
+            backs: self.backs.clone(),
+            live,
+            ring: ring(&eligible, &layout),
+            consistent: self.consistent,
+        });
+    }
+
+    /// Returns the backends which are alive and not held down, i.e. the
+    /// backends that may take writes.
+    pub fn live_backs(&self) -> Vec<String> {
+        let now = self.time.instant();
+        self.backs
+            .iter()
+            .zip(self.dampers.iter())
+            .filter(|(_, damper)| damper.is_eligible(now))
+            .map(|(back, _)| back.to_string())
+            .collect()
+    }
+}
+
+/// Returns true when every backend in the write path reported a clock of at
+/// least `target`. A cluster without any live backend is never consistent.
+pub fn converged(clocks: &[Option<u64>], target: u64) -> bool {
+    !clocks.is_empty()
+        && clocks
+            .iter()
+            .all(|c| matches!(c, Some(clock) if *clock >= target))
+}
+
+/// Lays out the hash slots of `backs`, giving the index of the backend
+/// owning each: every backend owns `virtual_nodes` slots, one in each round
+/// of `backs.len()` slots. The first round takes the backends in order, and
+/// every later one in an order of its own, hashed from the addresses, so
+/// that the slots of a backend are followed by different backends in every
+/// round. The bin clients lay the slots out alike when no keeper view has
+/// arrived, so an address is hashed as the URI it is dialed with, alike with
+/// or without its `http://`.
+pub fn layout(backs: &[String], virtual_nodes: usize) -> Vec<u32> {
+    let mut slots = Vec::with_capacity(backs.len() * virtual_nodes);
+    for round in 0..virtual_nodes {
+        let mut order: Vec<usize> = (0..backs.len()).collect();
+        if round > 0 {
+            order.sort_by_key(|i| {
+                let mut hasher = DefaultHasher::new();
+                hasher.write(addr::uri(&backs[*i]).as_bytes());
+                hasher.write_usize(round);
+                hasher.finish()
+            });
+        }
+        slots.extend(order.into_iter().map(|i| i as u32));
+    }
+    slots
+}
+
+/// Assigns every hash slot of `layout` to the first eligible backend owning
+/// a slot at or after it, wrapping around. Returns an empty ring when no
+/// backend is eligible.
+pub fn ring(eligible: &[bool], layout: &[u32]) -> Vec<u32> {
+    let n = layout.len();
+    if !eligible.iter().any(|e| *e) {
+        return vec![];
+    }
+    (0..n)
+        .map(|slot| {
+            (0..n)
+                .map(|offset| layout[(slot + offset) % n])
+                .find(|i| eligible[*i as usize])
+                .unwrap_or(layout[slot])
+        })
+        .collect()
+}
+
+/// The backends keeping the bins of hash slot `slot` of `ring`: the backend
+/// serving the slot, then the next other ones along the ring, up to `count`.
+pub fn replicas(ring: &[u32], slot: usize, count: usize) -> Vec<usize> {
+    let mut replicas = Vec::with_capacity(count);
+    for offset in 0..ring.len() {
+        if replicas.len() == count {
+            break;
+        }
+        let i = ring[(slot + offset) % ring.len()] as usize;
+        if !replicas.contains(&i) {
+            replicas.push(i);
+        }
+    }
+    replicas
+}
+
+/// The keeper RPC service, which streams the cluster view to bin clients and
+/// reports per-backend health to operators.
+pub struct KeeperService {
+    view: watch::Receiver<ClusterView>,
+    status: watch::Receiver<KeeperStatus>,
+}
+
+#[async_trait]
+impl KeeperRpc for KeeperService {
+    type WatchStream = ReceiverStream<Result<ClusterView, Status>>;
+
+    async fn watch(
+        &self,
+        _request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let mut view = self.view.clone();
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let current = view.borrow_and_update().clone();
+                if tx.send(Ok(current)).await.is_err() {
+                    break; // the subscriber went away
+                }
+                if view.changed().await.is_err() {
+                    break; // the keeper shut down
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<KeeperStatus>, Status> {
+        Ok(Response::new(self.status.borrow().clone()))
+    }
+}
+
+// calls clock() on a backend, which doubles as a liveness probe
+pub(crate) async fn probe(back: &str, at_least: u64, timeout: Duration) -> TribResult<u64> {
+    let call = async {
+        let client = new_client(&addr::uri(back)).await?;
+        client.clock(at_least).await
+    };
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => result,
+        Err(_) => Err(TribblerError::RpcError(format!(
+            "clock probe of {} timed out after {:?}",
+            back, timeout
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{converged, layout, replicas, ring, Backoff, FlapDamper, Keeper};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use tribbler::config::{KeeperTuning, Placement};
+    use tribbler::time::ManualClock;
+
+    fn tuning() -> KeeperTuning {
+        KeeperTuning {
+            stable_window_ms: 1_000,
+            max_hold_down_ms: 4_000,
+            retry_backoff_ms: 500,
+            max_retry_backoff_ms: 2_000,
+            ..KeeperTuning::default()
+        }
+    }
+
+    #[test]
+    fn initially_alive_is_eligible() {
+        let t = tuning();
+        let now = Instant::now();
+        let mut d = FlapDamper::new(&t);
+        d.observe(true, now, &t);
+        assert!(d.is_eligible(now));
+    }
+
+    #[test]
+    fn recovered_backend_is_held_down() {
+        let t = tuning();
+        let now = Instant::now();
+        let mut d = FlapDamper::new(&t);
+        d.observe(true, now, &t);
+        d.observe(false, now + Duration::from_secs(10), &t);
+        d.observe(true, now + Duration::from_secs(11), &t);
+        assert!(!d.is_eligible(now + Duration::from_millis(11_500)));
+        assert!(d.is_eligible(now + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn flapping_backend_hold_down_grows_and_caps() {
+        let t = tuning();
+        let start = Instant::now();
+        let mut d = FlapDamper::new(&t);
+        d.observe(true, start, &t);
+        let mut now = start + Duration::from_secs(10);
+        for expected in [1, 2, 4, 4] {
+            d.observe(false, now, &t);
+            now += Duration::from_millis(100);
+            d.observe(true, now, &t);
+            now += Duration::from_millis(100);
+            assert_eq!(Duration::from_secs(expected), d.hold_down());
+        }
+
+        // a long stable period resets the hold-down on the next failure
+        now += Duration::from_secs(60);
+        d.observe(false, now, &t);
+        assert_eq!(Duration::from_secs(1), d.hold_down());
+    }
+
+    #[test]
+    fn set_backs_keeps_known_backends() {
+        let t = tuning();
+        let now = Instant::now();
+        let mut k = Keeper::new(
+            vec!["a".to_string(), "b".to_string()],
+            t.clone(),
+            Placement::default(),
+        );
+        k.dampers[1].observe(true, now, &t);
+        k.clocks[1] = Some(7);
+        k.backoffs[0].fail(now, &t);
+
+        k.set_backs(vec!["b".to_string(), "c".to_string()]);
+        assert_eq!(vec!["b", "c"], k.backs);
+        assert!(k.dampers[0].is_eligible(now));
+        assert_eq!(vec![Some(7), None], k.clocks);
+        // c was never probed, and a's failures went with it
+        assert!(!k.dampers[1].is_alive());
+        assert_eq!(0, k.backoffs[1].failures());
+        assert_eq!(2, k.lags.len());
+    }
+
+    #[tokio::test]
+    async fn sync_backs_off_on_the_keeper_time() {
+        // no one listens there, so every probe fails right away
+        let dead = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let t = tuning();
+        let time = Arc::new(ManualClock::new(0));
+        let mut k = Keeper::with_time(vec![dead], t.clone(), Placement::default(), time.clone());
+        k.sync().await;
+        assert_eq!(1, k.backoffs[0].failures());
+        assert!(!k.dampers[0].is_alive());
+
+        // not probed again before the backoff passed
+        k.sync().await;
+        assert_eq!(1, k.backoffs[0].failures());
+        time.advance(Duration::from_millis(t.retry_backoff_ms));
+        k.sync().await;
+        assert_eq!(2, k.backoffs[0].failures());
+    }
+
+    #[test]
+    fn ring_skips_ineligible_backends() {
+        let flat = [0, 1, 2, 3];
+        assert_eq!(vec![0, 2, 2, 0], ring(&[true, false, true, false], &flat));
+        assert_eq!(vec![0, 1, 2], ring(&[true, true, true], &flat[..3]));
+        assert!(ring(&[false, false], &flat[..2]).is_empty());
+    }
+
+    #[test]
+    fn virtual_nodes_spread_the_slots_of_a_backend() {
+        let backs: Vec<String> = (0..4).map(|i| format!("localhost:{}", 3000 + i)).collect();
+        assert_eq!(vec![0, 1, 2, 3], layout(&backs, 1));
+        let slots = layout(&backs, 16);
+        assert_eq!(64, slots.len());
+        assert_eq!(&slots[..4], &[0, 1, 2, 3]);
+        for i in 0..4 {
+            assert_eq!(16, slots.iter().filter(|b| **b == i).count());
+        }
+        // spelling out the scheme a bin client dials with doesn't move the slots
+        let http: Vec<String> = backs.iter().map(|b| "http://".to_owned() + b).collect();
+        assert_eq!(slots, layout(&http, 16));
+
+        // with backend 0 down, its slots go to more than one other backend
+        let ring = ring(&[false, true, true, true], &slots);
+        let takers: Vec<u32> = (0..slots.len())
+            .filter(|slot| slots[*slot] == 0)
+            .map(|slot| ring[slot])
+            .collect();
+        assert!(!takers.contains(&0));
+        assert!(takers.iter().any(|b| *b != takers[0]));
+    }
+
+    #[test]
+    fn replicas_follow_the_ring() {
+        let ring = [0, 2, 2, 0];
+        assert_eq!(vec![0, 2], replicas(&ring, 0, 2));
+        assert_eq!(vec![2, 0], replicas(&ring, 1, 3));
+        assert_eq!(vec![0], replicas(&ring, 3, 1));
+        assert!(replicas(&[], 0, 2).is_empty());
+    }
+
+    #[test]
+    fn converged_requires_every_clock_to_catch_up() {
+        assert!(converged(&[Some(5), Some(7)], 5));
+        assert!(!converged(&[Some(5), Some(4)], 5));
+        assert!(!converged(&[Some(5), None], 5));
+        assert!(!converged(&[], 0));
+    }
+
+    #[test]
+    fn failing_backend_backs_off() {
+        let t = tuning();
+        let now = Instant::now();
+        let mut b = Backoff::default();
+        assert!(b.should_probe(now));
+        let delays: Vec<_> = (0..4).map(|_| b.fail(now, &t)).collect();
+        assert_eq!(
+            vec![500, 1_000, 2_000, 2_000],
+            delays.iter().map(|d| d.as_millis()).collect::<Vec<_>>()
+        );
+        assert!(!b.should_probe(now + Duration::from_millis(1_999)));
+        assert!(b.should_probe(now + Duration::from_millis(2_000)));
+
+        b.succeed();
+        assert!(b.should_probe(now));
+        assert_eq!(Duration::from_millis(500), b.fail(now, &t));
+    }
+
+    #[test]
+    fn backend_is_down_past_the_failure_threshold() {
+        let t = KeeperTuning {
+            failure_threshold: 3,
+            ..tuning()
+        };
+        let now = Instant::now();
+        let mut b = Backoff::default();
+        for _ in 0..2 {
+            b.fail(now, &t);
+            assert!(!b.is_down(&t));
+        }
+        b.fail(now, &t);
+        assert!(b.is_down(&t));
+        b.succeed();
+        assert!(!b.is_down(&t));
+
+        // by default a single failed probe is enough
+        b.fail(now, &tuning());
+        assert!(b.is_down(&tuning()));
+    }
+}
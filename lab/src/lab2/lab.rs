@@ -1,22 +1,82 @@
-use crate::lab1::lab::new_client;
-use crate::lab2::bin_client::BinStorageClient;
-use crate::lab2::front::FrontendServer;
+use crate::keeper::keeper_server::KeeperServer;
+use crate::lab1::mem::{self, MemListener};
+use crate::lab2::backup;
+use crate::lab2::bin_client::{http_backs, BinStorageClient};
+use crate::lab2::discovery::{discover, needs_discovery};
+use crate::lab2::front::{FrontendServer, SignupCache, SIGNUP_TTL};
+use crate::lab2::keeper::Keeper;
+use crate::lab2::scheduler;
 
-use std::cmp;
+use std::future;
 use std::string::String;
-use std::thread;
+use std::sync::Arc;
 use std::time;
-use tribbler::{config::KeeperConfig, err::TribResult, storage::BinStorage, trib::Server};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server as RpcServer;
+use tribbler::{
+    addr::Transport,
+    config::{Config, KeeperConfig, Limits},
+    err::TribResult,
+    storage::BinStorage,
+    time::SystemClock,
+    trib::Server,
+};
 
 /// This function accepts a list of backend addresses, and returns a type which
 /// should implement the [BinStorage] trait to access the underlying storage system.
+///
+/// Backends named by SRV records, as `dns+srv://<name>`, are looked up in DNS,
+/// and again every [RESOLVE_EVERY](crate::lab2::discovery::RESOLVE_EVERY).
 #[allow(unused_variables)]
 pub async fn new_bin_client(backs: Vec<String>) -> TribResult<Box<dyn BinStorage>> {
-    let mut http_backs = Vec::<String>::new();
-    for back in backs {
-        http_backs.push("http://".to_owned() + &back);
+    if needs_discovery(&backs) {
+        let (_, named) = watch::channel(Config {
+            backs,
+            ..Config::default()
+        });
+        let resolved = discover(named).await?;
+        let client = BinStorageClient::new(http_backs(&resolved.borrow().backs));
+        client.follow_backs(resolved);
+        return Ok(Box::new(client));
     }
-    return Ok(Box::new(BinStorageClient { backs: http_backs })); // We don't have to write "backs : backs" since they have the same name.
+    Ok(Box::new(BinStorageClient::new(http_backs(&backs))))
+}
+
+/// Like [new_bin_client], but also subscribes to the cluster view of the
+/// given keepers, so that bins are rerouted around failed backends without
+/// polling.
+pub async fn new_bin_client_with_keepers(
+    backs: Vec<String>,
+    keepers: Vec<String>,
+) -> TribResult<Box<dyn BinStorage>> {
+    let client = BinStorageClient::new(http_backs(&backs));
+    client.watch(keepers);
+    Ok(Box::new(client))
+}
+
+/// Like [new_bin_client_with_keepers], but takes the backends and keepers
+/// from whichever config `config` holds, following the keepers of a reloaded
+/// config, and keeps the bins the way its [Placement](tribbler::config::Placement)
+/// says. The backends the keeper watches take over from those of the config
+/// once its cluster view arrives. Backends named by SRV records are looked
+/// up as for [new_bin_client].
+pub async fn new_bin_client_following(
+    config: watch::Receiver<Config>,
+) -> TribResult<Box<dyn BinStorage>> {
+    Ok(Box::new(following_client(config).await?))
+}
+
+async fn following_client(config: watch::Receiver<Config>) -> TribResult<BinStorageClient> {
+    let config = discover(config).await?;
+    let placement = config.borrow().placement;
+    placement.check()?;
+    let client = BinStorageClient::with_placement(http_backs(&config.borrow().backs), placement);
+    client.follow(config.clone());
+    client.follow_backs(config);
+    Ok(client)
 }
 
 /// this async function accepts a [KeeperConfig] that should be used to start
@@ -24,66 +84,162 @@ pub async fn new_bin_client(backs: Vec<String>) -> TribResult<Box<dyn BinStorage
 ///
 /// This function should block indefinitely and only return upon erroring. Make
 /// sure to send the proper signal to the channel in `kc` when the keeper has
-/// started.
+/// started. The address may also be a `mem://` server in the process (see
+/// [mem]).
 // #[tokio::main]
 #[allow(unused_variables)]
 pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()> {
-    let mut clock = 0;
-    let backs = kc.backs;
-    let back_num = backs.len();
-    let mut id = 0;
+    serve_keeper_following(kc, None).await
+}
+
+/// Like [serve_keeper], but when given a `config`, watches the backends of
+/// whichever config it holds, so that a reloaded config adds and drops
+/// backends without a restart. The bin clients following the keeper route
+/// over the new backends as soon as its cluster view names them.
+///
+/// Backends named by SRV records, as `dns+srv://<name>`, are looked up in
+/// DNS before the keeper serves, and watched as they are found again.
+pub async fn serve_keeper_following(
+    kc: KeeperConfig,
+    config: Option<watch::Receiver<Config>>,
+) -> TribResult<()> {
+    let addr = kc.addr().to_string();
+    let config = match config {
+        Some(config) => Some(config),
+        None if needs_discovery(&kc.backs) => Some(
+            watch::channel(Config {
+                backs: kc.backs.clone(),
+                ..Config::default()
+            })
+            .1,
+        ),
+        None => None,
+    };
+    let config = match config {
+        Some(config) => match discover(config).await {
+            Ok(config) => Some(config),
+            Err(e) => {
+                if let Some(unwrapped_ready) = kc.ready {
+                    let _ = unwrapped_ready.send(false);
+                }
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+    let initial_backs = match &config {
+        Some(config) => config.borrow().backs.clone(),
+        None => kc.backs,
+    };
+    let (backs, watched_backs) = watch::channel(initial_backs.clone());
+    let backup_backs = watched_backs.clone();
+    let publish_backs = watched_backs;
+    let sync_interval = time::Duration::from_millis(kc.tuning.sync_interval_ms.max(1));
+    let placement = kc.placement;
+    let mut keeper = Keeper::new(initial_backs, kc.tuning, placement);
+    let service = keeper.service();
     let one_sec = time::Duration::from_secs(1);
 
+    // bind the keeper RPC address before reporting ready
+    let listener = match bind_keeper(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            if let Some(unwrapped_ready) = kc.ready {
+                let _ = unwrapped_ready.send(false);
+            }
+            return Err(e);
+        }
+    };
+
     // send true when the keeper is ready
     let _ = match kc.ready {
         Some(unwrapped_ready) => unwrapped_ready.send(true),
         None => Ok(()),
     };
 
-    let handle1 = tokio::spawn(async move {
-        while clock <= u64::MAX {
-            // get the max clock from the storages
-            while id < back_num {
-                let client = new_client(&backs[id]).await.unwrap();
-                clock = cmp::max(clock, client.clock(clock).await.unwrap());
-                id += 1; // next storage
+    // the tasks of the keeper go down with it, even when the task serving it
+    // is aborted rather than shut down
+    let mut config = config;
+    let _syncer = AbortOnDrop(tokio::spawn(async move {
+        loop {
+            // take on the backends of a reloaded config
+            if let Some(config) = config.as_mut() {
+                if config.has_changed().unwrap_or(false) {
+                    let reloaded = config.borrow_and_update().backs.clone();
+                    keeper.set_backs(reloaded.clone());
+                    backs.send_replace(reloaded);
+                }
             }
 
-            // set all clocks to the max clock
-            id = 0;
-            while id < back_num {
-                let client = new_client(&backs[id]).await.unwrap();
-                clock = cmp::max(clock, client.clock(clock).await.unwrap());
-                id += 1; // next storage
-            }
+            // probe the backends and synchronize the clocks of the live ones
+            keeper.sync().await;
 
             // prepare for the next synchornization
-            thread::sleep(one_sec); // sleep for one second
-            id = 0;
+            tokio::time::sleep(sync_interval).await;
         }
-    });
+    }));
+
+    // take snapshots of the backends on the configured schedule
+    let _backups = kc
+        .backup
+        .map(|cfg| AbortOnDrop(tokio::spawn(backup::run_schedule(backup_backs, cfg))));
 
-    let handle2 = tokio::spawn(async move {
-        match kc.shutdown {
-            None => {
-                let result = handle1.await;
-                println!("{:?}", result);
+    // post the scheduled tribs as they come due
+    let _publisher = AbortOnDrop(tokio::spawn(scheduler::run_publisher(
+        publish_backs,
+        placement,
+        one_sec,
+    )));
+
+    // serve the cluster view until a shutdown message is received
+    let mut shutdown = kc.shutdown;
+    let shutdown = async move {
+        if let Some(receiver) = shutdown.as_mut() {
+            if receiver.recv().await.is_some() {
+                return;
             }
-            Some(mut receiver) => match receiver.recv().await {
-                None => {
-                    let result = handle1.await;
-                    println!("{:?}", result);
-                }
-                Some(_) => {
-                    handle1.abort();
-                }
-            },
         }
-    });
+        future::pending::<()>().await
+    };
+    let router = RpcServer::builder().add_service(KeeperServer::new(service));
+    let result = match listener {
+        KeeperListener::Tcp(listener) => {
+            router
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+                .await
+        }
+        KeeperListener::Mem(listener) => {
+            router
+                .serve_with_incoming_shutdown(listener, shutdown)
+                .await
+        }
+    };
+    result?;
+    Ok(())
+}
+
+// aborts the task it holds once dropped
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// a listener a keeper serves its RPCs on
+enum KeeperListener {
+    Tcp(TcpListener),
+    Mem(MemListener),
+}
 
-    let result = handle2.await;
-    println!("{:?}", result);
-    return Ok(());
+// listens on the keeper address `addr`, which is a `mem://` server in the
+// process, or a TCP one
+async fn bind_keeper(addr: &str) -> TribResult<KeeperListener> {
+    match Transport::parse(addr)? {
+        Transport::Mem(name) => Ok(KeeperListener::Mem(mem::listen(&name)?)),
+        _ => Ok(KeeperListener::Tcp(TcpListener::bind(addr).await?)),
+    }
 }
 
 /// this function accepts a [BinStorage] client which should be used in order to
@@ -99,7 +255,56 @@ pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()> {
 pub async fn new_front(
     bin_storage: Box<dyn BinStorage>,
 ) -> TribResult<Box<dyn Server + Send + Sync>> {
-    return Ok(Box::new(FrontendServer { bin_storage }));
+    Ok(Box::new(FrontendServer {
+        bin_storage,
+        view: None,
+        signups: SignupCache::new(SIGNUP_TTL),
+        limits: Limits::default(),
+        time: Arc::new(SystemClock),
+    }))
+}
+
+/// Like [new_front], but builds its own bin client subscribed to the given
+/// keepers, and enforces the given [Limits]. Writes are refused until the
+/// keeper reports that the backend clocks have converged; without keepers
+/// there is no one to report it, and writes are taken from the start.
+pub async fn new_front_with_keepers(
+    backs: Vec<String>,
+    keepers: Vec<String>,
+    limits: Limits,
+) -> TribResult<Box<dyn Server + Send + Sync>> {
+    let client = BinStorageClient::new(http_backs(&backs));
+    let view = match keepers.is_empty() {
+        true => None,
+        false => Some(client.view.clone()),
+    };
+    client.watch(keepers);
+    Ok(Box::new(FrontendServer {
+        bin_storage: Box::new(client),
+        view,
+        signups: SignupCache::new(SIGNUP_TTL),
+        limits,
+        time: Arc::new(SystemClock),
+    }))
+}
+
+/// Like [new_front_with_keepers], but takes the backends, keepers, and
+/// limits of the front section from whichever config `config` holds, following the keepers of a
+/// reloaded config as [new_bin_client_following] does. The limits stay those
+/// the front-end started with.
+pub async fn new_front_following(
+    config: watch::Receiver<Config>,
+) -> TribResult<Box<dyn Server + Send + Sync>> {
+    let limits = config.borrow().front.limits;
+    let client = following_client(config).await?;
+    let view = client.view.clone();
+    Ok(Box::new(FrontendServer {
+        bin_storage: Box::new(client),
+        view: Some(view),
+        signups: SignupCache::new(SIGNUP_TTL),
+        limits,
+        time: Arc::new(SystemClock),
+    }))
 }
 
 // Questions
@@ -98,7 +98,7 @@
 //!
 //! A [Tribble](tribbler::trib::Trib) is a struct that has 4 fields:
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub struct Trib {
 //!     /// who posted this trib
 //!     pub user: String,
@@ -136,8 +136,8 @@
 //!
 //! --------------
 //!
-//! ```rust
-//! async fn sign_up(&self, user: &str) -> TribResult<()>;
+//! ```rust,ignore
+//! async fn sign_up(&self, user: &str) -> ServerResult<()>;
 //! ```
 //!
 //! Creates a new user. After a user is created, it will never disappear in the
@@ -155,8 +155,8 @@
 //!
 //! ---
 //!
-//! ```rust
-//! async fn list_users(&self) -> TribResult<Vec<String>>;
+//! ```rust,ignore
+//! async fn list_users(&self) -> ServerResult<Vec<String>>;
 //! ```
 //!
 //! Lists at least [MIN_LIST_USER](tribbler::trib::MIN_LIST_USER) different
@@ -170,8 +170,8 @@
 //!
 //! ---
 //!
-//! ```rust
-//! async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
+//! ```rust,ignore
+//! async fn post(&self, who: &str, post: &str, clock: u64) -> ServerResult<()>;
 //! ```
 //!
 //! Posts a tribble. `clock` is the maximum clock value this user client has
@@ -181,8 +181,8 @@
 //!
 //! ---
 //!
-//! ```rust
-//! async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+//! ```rust,ignore
+//! async fn tribs(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>>;
 //! ```
 //!
 //! Lists the recent [MAX_TRIB_FETCH](tribbler::trib::MAX_TRIB_FETCH) tribbles
@@ -191,11 +191,11 @@
 //!
 //! ---
 //!
-//! ```rust
-//! async fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
-//! async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
-//! async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
-//! async fn following(&self, who: &str) -> TribResult<Vec<String>>;
+//! ```rust,ignore
+//! async fn follow(&self, who: &str, whom: &str) -> ServerResult<()>;
+//! async fn unfollow(&self, who: &str, whom: &str) -> ServerResult<()>;
+//! async fn is_following(&self, who: &str, whom: &str) -> ServerResult<bool>;
+//! async fn following(&self, who: &str) -> ServerResult<Vec<String>>;
 //! ```
 //!
 //! These are functions to follow/unfollow, check following and listing all
@@ -212,8 +212,8 @@
 //!
 //! ---
 //!
-//! ```rust
-//! async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+//! ```rust,ignore
+//! async fn home(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>>;
 //! ```
 //!
 //! List the recent [MAX_TRIB_FETCH](tribbler::trib::MAX_TRIB_FETCH) tribbles
@@ -257,7 +257,7 @@
 //!
 //! You can find these entry functions in `lab/src/lab2/lab.rs`:
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn new_bin_client(backs: Vec<String>) -> TribResult<Box<dyn BinStorage>>
 //! ```
 //!
@@ -279,7 +279,7 @@
 //!
 //! ---
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn serve_keeper(kc: KeeperConfig) -> TribResult<()>
 //! ```
 //!
@@ -309,8 +309,8 @@
 //!   when this keeper was created relative to other keepers. For Lab 2, you may
 //!   ignore this field.
 //! - `ready: Option<Sender<bool>>`: A ready signal channel. It works similarly
-//!   to the `ready` channel in [tribbler::config::BackConfig] from Lab
-//!   1. When a keeper sends `true` on this channel, the distributed bin storage
+//!   to the `ready` channel in [tribbler::config::BackConfig] from
+//!   Lab 1. When a keeper sends `true` on this channel, the distributed bin storage
 //!   should be ready to serve. Therefore, if you need to initialize the
 //!   physical back-ends in some way, make sure you do it before you send a
 //!   signal over `ready`. Don't forget to send `false` to `ready` if the
@@ -347,7 +347,7 @@
 //!
 //! ---
 //!
-//! ```rust
+//! ```rust,ignore
 //! pub async fn new_front(bin_storage: Box<dyn BinStorage>) -> TribResult<Box<dyn Server>>
 //! ```
 //!
@@ -380,12 +380,32 @@
 //! the system expects a file named `bins.config`.
 //!
 //! `bins.json` is saved in JSON format, marshalling a
-//! [Config](tribbler::config::Config). We have a utility program called
-//! `bins-mkcfg` that can generate a `bins.json` file automatically.
+//! [Config](tribbler::config::Config); a config named `*.toml` or `*.yaml`
+//! is read as TOML or YAML instead. A `backs` entry is reached the way its
+//! scheme says (see [Transport](tribbler::addr::Transport)): plainly with
+//! none or `http://`, over TLS with `https://`, and over a unix domain socket
+//! with `unix://<path>`. A `backs` entry of the form
+//! `dns+srv://<name>` stands for the targets of the SRV records of `<name>`,
+//! which are looked up again every so often (see [discovery]). A config
+//! with a `discovery` section takes its backends and keepers from the etcd
+//! or Consul registry it names instead, and follows it as they come and go
+//! (see [Registry](tribbler::config::Registry)). The `replication_factor`,
+//! `virtual_nodes`, and `consistency` of a config say how many backends
+//! every bin is kept on, how many hash slots every backend owns, and how many
+//! replicas have to take a write (see [Placement](tribbler::config::Placement));
+//! the bin clients and keepers following the config place the bins that way.
+//! Past what the whole cluster shares, a config has a section for each role:
+//! `front` for the options of trib-front, such as its limits, seed, and TLS
+//! certificate, `back` for those of the backends, such as the snapshots they
+//! are restored from and the certificate their `https://` addresses are
+//! served with, and `keeper` for the tuning and backup schedule of the
+//! keepers.
+//! We have a utility program called `bins-mkcfg` that can generate a
+//! `bins.json` file automatically.
 //!
 //! Find a directory to use as your working directory, then run:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-mkcfg -- --backs 3
 //! ```
 //!
@@ -398,7 +418,7 @@
 //!
 //! With this configuration file generated, we can now launch the back-ends:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-back
 //! ```
 //!
@@ -412,7 +432,7 @@
 //!
 //! After the back-ends are ready, we can now start the keeper.
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin bins-keep
 //! ```
 //!
@@ -421,7 +441,7 @@
 //!
 //! To play with this distributed bin storage, we can use `bins-client`:
 //!
-//! ```
+//! ```text
 //! $ bins-client
 //! (working on bin "")
 //! > bin a
@@ -451,7 +471,7 @@
 //! Once our bin storage is up and running, we can launch our Tribbler
 //! front-end:
 //!
-//! ```
+//! ```text
 //! $ cargo run --bin trib-front -- -s lab
 //! ```
 //!
@@ -601,14 +621,29 @@
 //!
 //! ## Happy Lab 2!
 //!
+mod auth;
+mod backup;
 mod bin_client; // make BinStorageClient visible in the lab 2 module
 mod bin_user_client;
+pub mod discovery;
 mod front;
 mod keeper; // make Keeper visible in the lab 2 module
 mod lab; // make Front visible in the lab2 module
+mod replicated_client;
+mod scheduler;
+mod status;
 
 pub use crate::lab1::lab::new_client;
 pub use crate::lab1::lab::serve_back;
+pub use crate::lab2::auth::{Auth, BinAuth, MemAuth};
+pub use crate::lab2::backup::restore;
+pub use crate::lab2::bin_client::{bin_hash, bin_replicas};
 pub use crate::lab2::lab::new_bin_client;
+pub use crate::lab2::lab::new_bin_client_following;
+pub use crate::lab2::lab::new_bin_client_with_keepers;
 pub use crate::lab2::lab::new_front;
+pub use crate::lab2::lab::new_front_following;
+pub use crate::lab2::lab::new_front_with_keepers;
 pub use crate::lab2::lab::serve_keeper;
+pub use crate::lab2::lab::serve_keeper_following;
+pub use crate::lab2::status::{cluster_status, BackendReport, ClusterStatus};
@@ -6,13 +6,13 @@ use std::{
         atomic::{self, AtomicU64},
         Arc, RwLock,
     },
-    time::SystemTime,
 };
 
 use async_trait::async_trait;
 
 use tribbler::{
     err::{TribResult, TribblerError},
+    time::{SystemClock, TimeSource},
     trib::{
         is_valid_username, Server, Trib, MAX_FOLLOWING, MAX_TRIB_FETCH, MAX_TRIB_LEN, MIN_LIST_USER,
     },
@@ -135,15 +135,22 @@ pub struct FrontServer {
     users: Arc<RwLock<HashMap<String, User>>>,
     homes: Arc<RwLock<HashMap<String, Vec<Arc<Trib>>>>>,
     seq: AtomicU64,
+    time: Arc<dyn TimeSource>,
 }
 
 impl FrontServer {
     /// Creates a [RefServer] with no data
     pub fn new() -> FrontServer {
+        FrontServer::with_time(Arc::new(SystemClock))
+    }
+
+    /// Creates a [FrontServer] with no data, dating its tribs by `time`
+    pub fn with_time(time: Arc<dyn TimeSource>) -> FrontServer {
         FrontServer {
             users: Arc::new(RwLock::new(HashMap::new())),
             homes: Arc::new(RwLock::new(HashMap::new())),
             seq: AtomicU64::new(0),
+            time,
         }
     }
 
@@ -177,12 +184,12 @@ impl Default for FrontServer {
 impl Server for FrontServer {
     async fn sign_up(&self, user: &str) -> TribResult<()> {
         if !is_valid_username(user) {
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+            return Err(TribblerError::InvalidUsername(user.to_string()));
         }
         let mut users = self.users.write().unwrap(); // get exclusive write access
         match users.contains_key(user) {
             // repetitive users
-            true => Err(Box::new(TribblerError::UsernameTaken(user.to_string()))),
+            true => Err(TribblerError::UsernameTaken(user.to_string())),
             false => {
                 users.insert(user.to_string(), User::new());
                 let mut homes = self.homes.write().unwrap();
@@ -204,18 +211,22 @@ impl Server for FrontServer {
         Ok(res)
     }
 
+    async fn signed_up(&self, user: &str) -> TribResult<bool> {
+        Ok(self.users.read().unwrap().contains_key(user))
+    }
+
     // Tribs are not modified yet!!!!
     async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
         if post.len() > MAX_TRIB_LEN {
             // The post is too long.
-            return Err(Box::new(TribblerError::TribTooLong));
+            return Err(TribblerError::TribTooLong);
         }
         let mut users = self.users.write().unwrap();
         match users.get_mut(who) {
             // get a mutable reference of the value
             Some(user) => {
                 if self.seq.load(atomic::Ordering::SeqCst) == u64::MAX {
-                    return Err(Box::new(TribblerError::MaxedSeq));
+                    return Err(TribblerError::MaxedSeq);
                 }
                 let _ = self.seq.fetch_update(
                     atomic::Ordering::SeqCst,
@@ -232,10 +243,8 @@ impl Server for FrontServer {
                 let trib = user.post(
                     who,
                     post,
-                    self.seq.fetch_add(1, atomic::Ordering::SeqCst), 
-                    SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)?
-                        .as_secs(), // machine time
+                    self.seq.fetch_add(1, atomic::Ordering::SeqCst),
+                    self.time.now()?, // machine time
                 );
                 // add it to the timeline of my followers
                 let mut homes = self.homes.write().unwrap(); // get homes of all followers
@@ -250,7 +259,7 @@ impl Server for FrontServer {
                     .and_modify(|e| e.push(trib.clone()));
                 Ok(())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
@@ -268,36 +277,36 @@ impl Server for FrontServer {
                 }
                 Ok(user.list_tribs()[start..].to_vec())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
         }
     }
 
     async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
         }
         let mut users = self.users.write().unwrap();
         if !users.contains_key(whom) {
             // The followee doesn't exist.
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
         }
         match users.get_mut(who) {
             Some(u) => {
                 if u.is_following(whom) {
-                    return Err(Box::new(TribblerError::AlreadyFollowing(
+                    return Err(TribblerError::AlreadyFollowing(
                         who.to_string(),
                         whom.to_string(),
-                    )));
+                    ));
                 }
                 // cannot follow too many people
                 let followee_num = u.following.len();
                 if followee_num >= MAX_FOLLOWING {
-                    return Err(Box::new(TribblerError::FollowingTooMany));
+                    return Err(TribblerError::FollowingTooMany);
                 }
                 u.follow(whom);
             }
             // The follower doesn't exist.
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
         };
         // add a follower to the followee
         let _ = users
@@ -311,29 +320,29 @@ impl Server for FrontServer {
                 homes.insert(who.to_string(), self.rebuild_home(user, &users));
                 Ok(())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
     async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
         }
         let mut users = self.users.write().unwrap();
         if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
         }
         match users.get_mut(who) {
             Some(u) => {
                 if !u.is_following(whom) {
-                    return Err(Box::new(TribblerError::NotFollowing(
+                    return Err(TribblerError::NotFollowing(
                         who.to_string(),
                         whom.to_string(),
-                    )));
+                    ));
                 }
                 u.unfollow(whom);
             }
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string()))),
+            None => return Err(TribblerError::UserDoesNotExist(whom.to_string())),
         };
         let _ = users
             .entry(whom.to_string())
@@ -345,22 +354,22 @@ impl Server for FrontServer {
                 homes.insert(who.to_string(), self.rebuild_home(user, &users));
                 Ok(())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
     async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
         }
         let users = self.users.read().unwrap();
         if !users.contains_key(whom) {
             // The followee doesn't exist.
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
         }
         match users.get(who) {
             Some(user) => Ok(user.is_following(whom)),
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
             // The follower doesn't exist.
         }
     }
@@ -369,7 +378,7 @@ impl Server for FrontServer {
         let users = self.users.read().unwrap();
         match users.get(who) {
             Some(user) => Ok(user.list_following()),
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
@@ -385,7 +394,7 @@ impl Server for FrontServer {
                 };
                 Ok(home[start..].to_vec())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
         }
     }
 }
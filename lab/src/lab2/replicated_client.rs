@@ -0,0 +1,262 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::future::Future;
+use tribbler::{
+    config::Consistency,
+    err::{TribResult, TribblerError},
+    storage::{KeyList, KeyString, KeyValue, List, Pattern, Storage},
+};
+
+/// A bin kept on several backends: writes go to every replica at once, and
+/// succeed once as many of them took the write as the consistency asks for;
+/// reads go to the replicas in turn, until one answers.
+pub struct ReplicatedClient {
+    pub replicas: Vec<Box<dyn Storage>>, // the bin on each backend keeping it, the first serving its slot
+    pub consistency: Consistency,
+    pub wanted: usize, // how many replicas the bin should have, some of which may be down
+}
+
+impl ReplicatedClient {
+    // makes the call on every replica, answering with what the first one to
+    // take it said, once enough of them did
+    async fn write<'a, T, F, Fut>(&'a self, call: F) -> TribResult<T>
+    where
+        F: Fn(&'a dyn Storage) -> Fut,
+        Fut: Future<Output = TribResult<T>>,
+    {
+        let mut answers = self.write_all(call).await?;
+        Ok(answers.swap_remove(0))
+    }
+
+    // makes the call on every replica, answering with what each of the ones
+    // which took it said, once enough of them did
+    async fn write_all<'a, T, F, Fut>(&'a self, call: F) -> TribResult<Vec<T>>
+    where
+        F: Fn(&'a dyn Storage) -> Fut,
+        Fut: Future<Output = TribResult<T>>,
+    {
+        let results = join_all(self.replicas.iter().map(|replica| call(&**replica))).await;
+        self.acked(results)
+    }
+
+    // what each of the replicas which took a write said, once enough of them
+    // did
+    fn acked<T>(&self, results: Vec<TribResult<T>>) -> TribResult<Vec<T>> {
+        let needed = self.consistency.acks(self.wanted);
+        let mut answers = vec![];
+        let mut failure = None;
+        for result in results {
+            match result {
+                Ok(value) => answers.push(value),
+                Err(e) => failure = Some(e),
+            }
+        }
+        match answers.len() {
+            acks if acks > 0 && acks >= needed => Ok(answers),
+            acks => Err(TribblerError::ClusterDegraded(format!(
+                "{} of {} replicas took the write, {} needed{}",
+                acks,
+                self.wanted,
+                needed,
+                failure.map(|e| format!(": {}", e)).unwrap_or_default()
+            ))),
+        }
+    }
+
+    // makes the call on the replicas in turn, until one answers
+    async fn read<'a, T, F, Fut>(&'a self, call: F) -> TribResult<T>
+    where
+        F: Fn(&'a dyn Storage) -> Fut,
+        Fut: Future<Output = TribResult<T>>,
+    {
+        let mut failure = None;
+        for replica in self.replicas.iter() {
+            match call(&**replica).await {
+                Ok(value) => return Ok(value),
+                Err(e) => failure = Some(e),
+            }
+        }
+        Err(failure.unwrap_or_else(|| {
+            TribblerError::ClusterDegraded("no replica of the bin is up".to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl KeyString for ReplicatedClient {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        self.read(|replica| replica.get(key)).await
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        self.write(|replica| replica.set(kv)).await
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<List> {
+        self.read(|replica| replica.keys(p)).await
+    }
+
+    // the counter is kept on each replica apart, and a replica which missed
+    // an increment while down stays behind the others for good, as nothing
+    // copies the count over; the answer is the count of the replica furthest
+    // along of the ones which took this increment
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        let counts = self.write_all(|replica| replica.incr(key, delta)).await?;
+        Ok(counts.into_iter().max().unwrap_or_default())
+    }
+
+    // swapping on each replica apart would let one which missed a write
+    // while down take a swap the others refuse, so the first replica up
+    // decides, and the value it took is set on the others; the answer is
+    // what the deciding replica held
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let mut failure = None;
+        for (i, replica) in self.replicas.iter().enumerate() {
+            let held = match replica.compare_and_swap(key, expected, value).await {
+                Ok(held) => held,
+                Err(e) => {
+                    failure = Some(e);
+                    continue;
+                }
+            };
+            if held != expected {
+                return Ok(held);
+            }
+            let kv = KeyValue::new(key, value);
+            let others = self.replicas.iter().enumerate().filter(|(j, _)| *j != i);
+            let mut results = join_all(others.map(|(_, other)| other.set(&kv))).await;
+            results.push(Ok(true));
+            self.acked(results)?;
+            return Ok(held);
+        }
+        Err(failure.unwrap_or_else(|| {
+            TribblerError::ClusterDegraded("no replica of the bin is up".to_string())
+        }))
+    }
+}
+
+#[async_trait]
+impl KeyList for ReplicatedClient {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        self.read(|replica| replica.list_get(key)).await
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        self.write(|replica| replica.list_append(kv)).await
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        self.write(|replica| replica.list_remove(kv)).await
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        self.read(|replica| replica.list_keys(p)).await
+    }
+}
+
+#[async_trait]
+impl Storage for ReplicatedClient {
+    // the replicas tick their clocks apart, so they may answer alike to two
+    // calls; the highest clock of the ones which took the call is past what
+    // each of them handed out before
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        let clocks = self.write_all(|replica| replica.clock(at_least)).await?;
+        Ok(clocks.into_iter().max().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReplicatedClient;
+    use tribbler::{
+        config::Consistency,
+        err::TribblerError,
+        storage::{KeyList, KeyString, KeyValue, MemStorage, Storage},
+        testing::{MockStorage, Op},
+    };
+
+    fn kv(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_reach_every_replica() {
+        let client = ReplicatedClient {
+            replicas: vec![
+                Box::new(MemStorage::default()),
+                Box::new(MemStorage::default()),
+            ],
+            consistency: Consistency::All,
+            wanted: 2,
+        };
+        assert!(client.set(&kv("k", "v")).await.unwrap());
+        assert!(client.list_append(&kv("l", "a")).await.unwrap());
+        for replica in client.replicas.iter() {
+            assert_eq!(Some("v".to_string()), replica.get("k").await.unwrap());
+            assert_eq!(vec!["a"], replica.list_get("l").await.unwrap().0);
+        }
+        assert_eq!(1, client.list_remove(&kv("l", "a")).await.unwrap());
+        assert!(client.list_get("l").await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn writes_need_enough_replicas() {
+        // one of two wanted replicas is down
+        let client = |consistency| ReplicatedClient {
+            replicas: vec![Box::new(MemStorage::default()) as Box<dyn Storage>],
+            consistency,
+            wanted: 2,
+        };
+        assert!(client(Consistency::One).set(&kv("k", "v")).await.is_ok());
+        assert!(matches!(
+            client(Consistency::Quorum).set(&kv("k", "v")).await,
+            Err(TribblerError::ClusterDegraded(_))
+        ));
+        assert!(client(Consistency::All).set(&kv("k", "v")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn clocks_follow_the_replica_ahead() {
+        let client = ReplicatedClient {
+            replicas: vec![
+                Box::new(MemStorage::default()),
+                Box::new(MemStorage::default()),
+            ],
+            consistency: Consistency::All,
+            wanted: 2,
+        };
+        // the second replica took calls the first missed
+        let ahead = client.replicas[1].clock(10).await.unwrap();
+        client.replicas[1].incr("n", 5).await.unwrap();
+        assert!(client.clock(0).await.unwrap() > ahead);
+        assert_eq!(6, client.incr("n", 1).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn the_first_replica_up_decides_a_swap() {
+        let down = MockStorage::new();
+        for op in [Op::Get, Op::Set, Op::CompareAndSwap] {
+            down.fail(op);
+        }
+        let client = ReplicatedClient {
+            replicas: vec![
+                Box::new(down),
+                Box::new(MemStorage::default()),
+                Box::new(MemStorage::default()),
+            ],
+            consistency: Consistency::Quorum,
+            wanted: 3,
+        };
+        // the last replica took a write the one deciding missed
+        client.replicas[2].set(&kv("k", "stale")).await.unwrap();
+        assert_eq!("", client.compare_and_swap("k", "", "v").await.unwrap());
+        for replica in client.replicas[1..].iter() {
+            assert_eq!(Some("v".to_string()), replica.get("k").await.unwrap());
+        }
+        assert_eq!("v", client.compare_and_swap("k", "", "w").await.unwrap());
+        assert_eq!(Some("v".to_string()), client.get("k").await.unwrap());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::lab2::bin_client::{http_backs, BinStorageClient};
+use crate::lab2::front::{FrontendServer, SignupCache, SIGNUP_TTL};
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tribbler::config::{Limits, Placement};
+use tribbler::time::SystemClock;
+
+/// Posts the scheduled tribs as they come due, checking every `interval`,
+/// forever, on the backends `backs` holds at the time, keeping the bins the
+/// way `placement` says.
+pub async fn run_publisher(
+    mut backs: watch::Receiver<Vec<String>>,
+    placement: Placement,
+    interval: Duration,
+) {
+    let mut front = publisher(&backs.borrow_and_update(), placement);
+    loop {
+        tokio::time::sleep(interval).await;
+        if backs.has_changed().unwrap_or(false) {
+            front = publisher(&backs.borrow_and_update(), placement);
+        }
+        if let Err(e) = front.publish_due(front.time.now().unwrap_or(0)).await {
+            warn!("failed to publish the scheduled posts: {}", e);
+        }
+    }
+}
+
+// a front-end over the backends `backs`, to publish with
+fn publisher(backs: &[String], placement: Placement) -> FrontendServer {
+    FrontendServer {
+        bin_storage: Box::new(BinStorageClient::with_placement(
+            http_backs(backs),
+            placement,
+        )),
+        view: None,
+        signups: SignupCache::new(SIGNUP_TTL),
+        limits: Limits::default(),
+        time: Arc::new(SystemClock),
+    }
+}
@@ -0,0 +1,169 @@
+use crate::keeper::{
+    keeper_client::KeeperClient, ClusterView, KeeperStatus, StatusRequest, WatchRequest,
+};
+use crate::lab1::client::channel;
+use crate::lab2::keeper::probe;
+use log::debug;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tribbler::addr;
+use tribbler::err::{TribResult, TribblerError};
+
+// how long each keeper and backend gets to answer
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A snapshot of the cluster for its operators: what the keeper makes of
+/// every backend, what probing it from here finds, and how the hash slots
+/// the bins are spread over are served.
+#[derive(Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClusterStatus {
+    /// the keeper which answered, none when no keeper did
+    pub keeper: Option<String>,
+    /// the epoch of the keeper's cluster view
+    pub epoch: u64,
+    /// whether the keeper verified the backend clocks converged
+    pub consistent: bool,
+    /// the largest clock the keeper has seen
+    pub clock: u64,
+    /// every backend, in config order
+    pub backends: Vec<BackendReport>,
+}
+
+/// One backend, as seen by the keeper and by a probe from the caller.
+#[derive(Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BackendReport {
+    pub addr: String,
+    /// the clock the backend answered a probe from here with, none when it
+    /// did not answer
+    pub clock: Option<u64>,
+    /// whether the backend answered the keeper's last probe
+    pub alive: bool,
+    /// whether the keeper has the backend in the write path
+    pub eligible: bool,
+    /// how far the backend clock was behind the cluster clock
+    pub lag: u64,
+    /// the keeper's probes of the backend which failed in a row
+    pub failures: u32,
+    /// the hold-down the keeper applies to the backend for flapping
+    pub hold_down_ms: u64,
+    /// the hash slots whose bins the backend serves, out of one slot for
+    /// each virtual node of each backend
+    pub slots: Vec<usize>,
+}
+
+/// Collects the [ClusterStatus] of the backends `backs`, from the first of
+/// `keepers` which answers, probing every backend meanwhile. Without an
+/// answering keeper, the keeper's side of every backend is left blank and
+/// each backend serves its own slot, as the bin client then routes.
+pub async fn cluster_status(backs: &[String], keepers: &[String]) -> ClusterStatus {
+    let mut probes = JoinSet::new();
+    for (i, back) in backs.iter().enumerate() {
+        let back = back.clone();
+        probes.spawn(async move { (i, probe(&back, 0, STATUS_TIMEOUT).await.ok()) });
+    }
+
+    let mut keeper = None;
+    for addr in keepers {
+        match keeper_status(addr).await {
+            Ok(answer) => {
+                keeper = Some((addr.clone(), answer));
+                break;
+            }
+            Err(e) => debug!("keeper {} did not answer: {}", addr, e),
+        }
+    }
+
+    let mut clocks = vec![None; backs.len()];
+    while let Some(probed) = probes.join_next().await {
+        if let Ok((i, clock)) = probed {
+            clocks[i] = clock;
+        }
+    }
+
+    let (status, view) = match &keeper {
+        Some((_, (status, view))) => (status.clone(), Some(view)),
+        None => (KeeperStatus::default(), None),
+    };
+    let backends = backs
+        .iter()
+        .zip(clocks)
+        .enumerate()
+        .map(|(i, (addr, clock))| {
+            let seen = status
+                .backends
+                .iter()
+                .find(|b| &b.addr == addr)
+                .cloned()
+                .unwrap_or_default();
+            BackendReport {
+                addr: addr.clone(),
+                clock,
+                alive: seen.alive,
+                eligible: seen.eligible,
+                lag: seen.lag,
+                failures: seen.failures,
+                hold_down_ms: seen.hold_down_ms,
+                slots: slots_of(i, backs.len(), view.map(|v| v.ring.as_slice())),
+            }
+        })
+        .collect();
+    ClusterStatus {
+        keeper: keeper.map(|(addr, _)| addr),
+        epoch: status.epoch,
+        consistent: status.consistent,
+        clock: status.clock,
+        backends,
+    }
+}
+
+// asks a keeper for its status and its current cluster view, which the watch
+// stream starts with
+async fn keeper_status(addr: &str) -> TribResult<(KeeperStatus, ClusterView)> {
+    let call = async {
+        let mut client = KeeperClient::new(channel(&addr::uri(addr)).await?);
+        let status = client.status(StatusRequest {}).await?.into_inner();
+        let mut views = client.watch(WatchRequest {}).await?.into_inner();
+        let view = views.message().await?.unwrap_or_default();
+        TribResult::Ok((status, view))
+    };
+    tokio::time::timeout(STATUS_TIMEOUT, call)
+        .await
+        .map_err(|_| TribblerError::TimedOut(STATUS_TIMEOUT.as_secs()))?
+}
+
+// the hash slots backend `i` of `n` serves, going by the ring of a cluster
+// view, which has as many slots for every backend as it has virtual nodes; a
+// ring of no such size is ignored, as the bin client does
+fn slots_of(i: usize, n: usize, ring: Option<&[u32]>) -> Vec<usize> {
+    match ring {
+        Some(ring) if !ring.is_empty() && ring.len() % n == 0 => (0..ring.len())
+            .filter(|slot| ring[*slot] as usize == i)
+            .collect(),
+        _ => vec![i],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::slots_of;
+
+    #[test]
+    fn test_slots_of() {
+        // backend 1 is down, and backend 2 took over its slot
+        let ring = [0, 2, 2];
+        assert_eq!(vec![0], slots_of(0, 3, Some(&ring)));
+        assert!(slots_of(1, 3, Some(&ring)).is_empty());
+        assert_eq!(vec![1, 2], slots_of(2, 3, Some(&ring)));
+        // two virtual nodes each
+        let ring = [0, 1, 1, 0, 1, 1];
+        assert_eq!(vec![0, 3], slots_of(0, 3, Some(&ring)));
+        assert_eq!(vec![1, 2, 4, 5], slots_of(1, 3, Some(&ring)));
+        assert_eq!(vec![2], slots_of(2, 3, Some(&[0, 1])));
+        // no view, or none of this cluster: every backend serves its own
+        assert_eq!(vec![1], slots_of(1, 3, None));
+        assert_eq!(vec![1], slots_of(1, 3, Some(&[])));
+    }
+}
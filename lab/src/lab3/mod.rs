@@ -20,7 +20,10 @@
 //!
 //! Note that we don't provide great unit tests to test fault tolerance (as it's
 //! hard to spawn and kill processes from within unit tests). Make sure you test
-//! this sufficiently using a testing mechanism of your own design.
+//! this sufficiently using a testing mechanism of your own design. What does
+//! carry over is [tribbler::testing::conformance], which checks a front-end
+//! against the spec of [Server](tribbler::trib::Server) the same way
+//! `lab/tests/lab2_test.rs` runs it against a Lab 2 cluster.
 //!
 //! ## System Scale and Failure Model
 //!
@@ -128,8 +128,8 @@
 //!   semicolon `;` at the end of a statement.
 //! - Store items on the heap using the `Box<T>` type.
 //! - You can write interfaces in Rust using the `trait` keyword, and then
-//! implement a trait for a specific struct with `impl <Trait> for <Struct> {
-//! ... }`
+//!   implement a trait for a specific struct with `impl <Trait> for <Struct> {
+//!   ... }`
 //!
 //! ## The Tribbler Story
 //!
@@ -203,15 +203,40 @@
 //!   address with a random port number.
 //! - [tribbler::colon] provides helper functions that escape and unescape
 //!   colons in a string.
+//! - [tribbler::testing] provides storage test doubles which record calls and
+//!   answer as scripted, to unit test a front-end without backends.
+//! - [tribbler::time] provides the time source the servers date tribs, time
+//!   the keeper's probes and expire caches, sessions and rate limits by, and
+//!   one a test can move along, rather than sleep.
 //! - `lab/tests` provides several basic test cases for the interfaces.
+//! - [sim] runs a whole cluster in one process on virtual time, for tests
+//!   which hinge on timing.
+//! - [test_support] starts a cluster of backends and keepers on local ports in
+//!   one call, for integration tests which take its servers down and back up.
 //! - `cmd/src/trib_front.rs` is the web-server launcher that you run.
+//! - `cmd/src/trib_cli.rs` signs users up, posts, follows and lists timelines
+//!   from a terminal, through a cluster or a running front-end.
 //! - `cmd/src/kv_client.rs` is a command line key-value RPC client for quick
 //!   testing.
+//! - `cmd/src/kv_shell.rs` is a shell of key-value calls against a live
+//!   backend, to look into what an implementation stored there.
 //! - `cmd/src/kv_server.rs` runs a key-value service as an RPC server.
 //! - `cmd/src/bins_client.rs` is a bin storage service client.
 //! - `cmd/src/bins_back.rs` is a bin storage service back-end launcher.
 //! - `cmd/src/bins_keep.rs` is a bin storage service keeper launcher.
 //! - `cmd/src/bins_mkcfg.rs` generates a bin storage configuration file.
+//! - `cmd/src/trib_ring.rs` prints the backends the bins of a config are kept
+//!   on, and which bins move when backends go down.
+//! - `cmd/src/trib_status.rs` prints a table of what the keeper and a probe
+//!   find of every backend of a config, with the keys each one keeps.
+//! - `cmd/src/bin_inspect.rs` prints the backends a bin is kept on and what
+//!   each of them holds of it, or where its replicas disagree.
+//! - `cmd/src/trib_chaos.rs` kills and brings back the servers of a cluster
+//!   under user traffic, checking nothing acknowledged is lost.
+//! - `cmd/src/trib_replay.rs` serves back the RPCs `trib-front --record`
+//!   recorded, standing in for the cluster they were made to.
+//! - `cmd/src/trib_bench.rs` drives a front-end at a target rate of calls,
+//!   reporting the throughput and latencies seen.
 //! - `www/` contains the static files (html, css, js, etc.) for the web
 //!   front-end.
 //!
@@ -276,7 +301,10 @@
 #![doc(
     html_favicon_url = "https://upload.wikimedia.org/wikipedia/commons/thumb/f/f8/Creative-Tail-Animal-penguin.svg/128px-Creative-Tail-Animal-penguin.svg.png?20160314145218"
 )]
-mod keeper;
+/// protobuf-generated keeper RPC stubs and message structs
+pub mod keeper;
 pub mod lab1;
 pub mod lab2;
 pub mod lab3;
+pub mod sim;
+pub mod test_support;
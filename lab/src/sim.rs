@@ -0,0 +1,233 @@
+//! A deterministic simulation of a cluster, for tests: backends, keepers and
+//! front-ends run in one process, dialing each other over in-memory pipes
+//! (see [mem](crate::lab1::mem)) on a single-threaded runtime whose clock is
+//! virtual. The clock moves only once every task waits on it, jumping to the
+//! next timer due, so keeper syncs, probe timeouts, hold-downs and
+//! heartbeats take no real time, and land on the same virtual instant on
+//! every run.
+//!
+//! The seed drives the choices of the simulation itself, such as the backend
+//! [Sim::pick_back] picks to take down; a test draws any choices of its own
+//! from [Sim::rng] to keep a failing run reproducible from its seed.
+//! Timestamps read off the wall clock, as those of tribs, are left real.
+//!
+//! ```rust
+//! use lab::sim::Sim;
+//! use std::time::Duration;
+//! use tribbler::config::KeeperTuning;
+//!
+//! Sim::run(7, |mut sim| async move {
+//!     sim.start_backs(3).await.unwrap();
+//!     sim.start_keeper(KeeperTuning::default()).await.unwrap();
+//!     let back = sim.pick_back();
+//!     sim.kill(&back).await;
+//!     sim.advance(Duration::from_secs(5)).await;
+//!     let status = sim.status().await;
+//!     assert!(status.backends.iter().any(|b| b.addr == back && !b.alive));
+//! });
+//! ```
+use crate::lab1::lab::serve_back;
+use crate::lab2::{cluster_status, new_bin_client_with_keepers, new_front_with_keepers};
+use crate::lab2::{serve_keeper, ClusterStatus};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tribbler::addr::MEM;
+use tribbler::config::{BackConfig, KeeperConfig, KeeperTuning, Limits, Placement};
+use tribbler::err::{TribResult, TribblerError};
+use tribbler::storage::{BinStorage, MemStorage};
+use tribbler::trib::Server;
+
+// the simulations of a process take turns, as the names their servers listen
+// under are the same from one run to the next
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// A cluster simulated in the process, on virtual time.
+pub struct Sim {
+    rng: StdRng,
+    start: Instant,
+    backs: Vec<String>,
+    keepers: Vec<String>,
+    servers: HashMap<String, JoinHandle<TribResult<()>>>, // the task serving each address up
+}
+
+impl Sim {
+    /// Runs `test` on a new simulation seeded with `seed`, on a
+    /// single-threaded runtime whose clock starts out paused, and returns
+    /// what it does. Every server still up is taken down once it is done.
+    pub fn run<F, Fut, T>(seed: u64, test: F) -> T
+    where
+        F: FnOnce(Sim) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let _turn = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("cannot build the simulation runtime");
+        runtime.block_on(async {
+            test(Sim {
+                rng: StdRng::seed_from_u64(seed),
+                start: Instant::now(),
+                backs: vec![],
+                keepers: vec![],
+                servers: HashMap::new(),
+            })
+            .await
+        })
+    }
+
+    /// The random number generator of the simulation, seeded with its seed.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// How much virtual time passed since the simulation started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Lets the cluster run for `duration` of virtual time.
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    /// The addresses of every backend started, in the order they were.
+    pub fn backs(&self) -> &[String] {
+        &self.backs
+    }
+
+    /// The addresses of every keeper started, in the order they were.
+    pub fn keepers(&self) -> &[String] {
+        &self.keepers
+    }
+
+    /// Starts a backend on empty storage, returning its address.
+    pub async fn start_back(&mut self) -> TribResult<String> {
+        let addr = format!("{}back-{}", MEM, self.backs.len());
+        self.serve_back(&addr).await?;
+        self.backs.push(addr.clone());
+        Ok(addr)
+    }
+
+    /// Starts `count` backends, as [Sim::start_back] does.
+    pub async fn start_backs(&mut self, count: usize) -> TribResult<Vec<String>> {
+        let mut addrs = Vec::with_capacity(count);
+        for _ in 0..count {
+            addrs.push(self.start_back().await?);
+        }
+        Ok(addrs)
+    }
+
+    /// Picks one of the backends up at random.
+    pub fn pick_back(&mut self) -> String {
+        let up: Vec<&String> = self
+            .backs
+            .iter()
+            .filter(|back| self.servers.contains_key(*back))
+            .collect();
+        up.choose(&mut self.rng)
+            .map(|back| back.to_string())
+            .expect("no backend is up")
+    }
+
+    /// Takes down the server at `addr` as a crash would, refusing every
+    /// connection made to it from then on.
+    pub async fn kill(&mut self, addr: &str) {
+        if let Some(server) = self.servers.remove(addr) {
+            server.abort();
+            let _ = server.await;
+        }
+    }
+
+    /// Brings a backend taken down back up, on empty storage, as it comes
+    /// back after losing what it kept in memory.
+    pub async fn revive_back(&mut self, addr: &str) -> TribResult<()> {
+        if self.servers.contains_key(addr) {
+            return Ok(());
+        }
+        self.serve_back(addr).await
+    }
+
+    /// Starts a keeper watching every backend started so far, returning its
+    /// address.
+    pub async fn start_keeper(&mut self, tuning: KeeperTuning) -> TribResult<String> {
+        let addr = format!("{}keeper-{}", MEM, self.keepers.len());
+        let (ready, started) = mpsc::channel();
+        let server = tokio::spawn(serve_keeper(KeeperConfig {
+            backs: self.backs.clone(),
+            addrs: vec![addr.clone()],
+            this: 0,
+            id: self.keepers.len() as u128,
+            tuning,
+            placement: Placement::default(),
+            backup: None,
+            ready: Some(ready),
+            shutdown: None,
+        }));
+        self.wait_up(&addr, server, started).await?;
+        self.keepers.push(addr.clone());
+        Ok(addr)
+    }
+
+    /// A bin client of the backends, following the keepers.
+    pub async fn bin_client(&self) -> TribResult<Box<dyn BinStorage>> {
+        new_bin_client_with_keepers(self.backs.clone(), self.keepers.clone()).await
+    }
+
+    /// A front-end of the cluster, following the keepers, and enforcing
+    /// `limits`.
+    pub async fn front(&self, limits: Limits) -> TribResult<Box<dyn Server + Send + Sync>> {
+        new_front_with_keepers(self.backs.clone(), self.keepers.clone(), limits).await
+    }
+
+    /// The status of the cluster as its keepers and probes from here have
+    /// it, as [cluster_status] collects it.
+    pub async fn status(&self) -> ClusterStatus {
+        cluster_status(&self.backs, &self.keepers).await
+    }
+
+    // serves a backend on empty storage at `addr`
+    async fn serve_back(&mut self, addr: &str) -> TribResult<()> {
+        let (ready, started) = mpsc::channel();
+        let server = tokio::spawn(serve_back(BackConfig {
+            addr: addr.to_string(),
+            storage: Box::new(MemStorage::new()),
+            ready: Some(ready),
+            shutdown: None,
+        }));
+        self.wait_up(addr, server, started).await
+    }
+
+    // waits for the server at `addr` to report whether it is up, keeping its
+    // task once it is
+    async fn wait_up(
+        &mut self,
+        addr: &str,
+        server: JoinHandle<TribResult<()>>,
+        started: mpsc::Receiver<bool>,
+    ) -> TribResult<()> {
+        loop {
+            match started.try_recv() {
+                Ok(true) => break,
+                Ok(false) | Err(TryRecvError::Disconnected) => {
+                    return Err(match server.await {
+                        Ok(Err(e)) => e,
+                        _ => TribblerError::Unknown(format!("{} failed to start", addr)),
+                    });
+                }
+                Err(TryRecvError::Empty) => tokio::task::yield_now().await,
+            }
+        }
+        self.servers.insert(addr.to_string(), server);
+        Ok(())
+    }
+}
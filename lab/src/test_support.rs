@@ -0,0 +1,485 @@
+//! A cluster of backends and keepers served in the process on local ports,
+//! for integration tests: started in one call, with handles to take its
+//! servers down and bring them back up, and taken down in full once it is
+//! dropped.
+//!
+//! In a cluster started partitionable, every keeper reaches the backends
+//! through proxies of its own, so the network between them can be
+//! partitioned into groups and healed again, for tests of what keepers on
+//! either side of a split make of the cluster. The link between a keeper and
+//! a backend can also be cut or slowed down on its own, for tests of how the
+//! keeper tells a slow backend from a dead one. The clients of the cluster
+//! reach the backends the way the keeper they follow does.
+//!
+//! ```rust
+//! use lab::test_support::Cluster;
+//! use tribbler::config::Limits;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> tribbler::err::TribResult<()> {
+//! let mut cluster = Cluster::start(3, 1).await?;
+//! let front = cluster.front(Limits::default()).await?;
+//! cluster.settled().await?;
+//! front.sign_up("alice").await?;
+//!
+//! let back = cluster.backs()[0].clone();
+//! cluster.kill(&back).await;
+//! cluster.restart(&back).await?;
+//! cluster.stop().await
+//! # }
+//! ```
+use crate::lab1::lab::serve_back;
+use crate::lab2::{
+    cluster_status, new_bin_client, new_bin_client_with_keepers, new_front_with_keepers,
+    serve_keeper, ClusterStatus,
+};
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tribbler::config::{BackConfig, KeeperConfig, KeeperTuning, Limits, Placement};
+use tribbler::err::{TribResult, TribblerError};
+use tribbler::storage::{BinStorage, MemStorage};
+use tribbler::trib::Server;
+
+// how long a server gets to come up, and the cluster to settle
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how often the keepers are asked whether the cluster settled
+const SETTLE_POLL: Duration = Duration::from_millis(100);
+
+// a server up, and the sender which shuts it down gracefully
+struct Node {
+    task: JoinHandle<TribResult<()>>,
+    shutdown: tokio::sync::mpsc::Sender<()>,
+}
+
+// what a link does to the traffic between a keeper and a backend
+#[derive(Debug, Clone, Copy, Default)]
+struct Fault {
+    cut: bool,       // whether connections are closed and new ones refused
+    delay: Duration, // how long every chunk of data is held back
+}
+
+// a proxy forwarding the connections made to it to a server, which can be
+// cut, closing the connections it forwards and refusing new ones, or slowed
+// down
+struct Link {
+    addr: String,
+    fault: watch::Sender<Fault>,
+    task: JoinHandle<()>,
+}
+
+impl Link {
+    async fn open(to: &str) -> TribResult<Link> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        let (fault, watching) = watch::channel(Fault::default());
+        let task = tokio::spawn(forward(listener, to.to_string(), watching));
+        Ok(Link { addr, fault, task })
+    }
+}
+
+/// Backends and keepers served in the process on local ports.
+pub struct Cluster {
+    backs: Vec<String>,
+    keepers: Vec<String>,
+    tuning: KeeperTuning,
+    nodes: HashMap<String, Node>,      // the servers up, by address
+    links: HashMap<String, Vec<Link>>, // the proxies of each keeper, one per backend
+}
+
+impl Cluster {
+    /// Starts `backs` backends on empty storage, then `keepers` keepers
+    /// watching them, with the default [KeeperTuning].
+    pub async fn start(backs: usize, keepers: usize) -> TribResult<Cluster> {
+        Cluster::start_with(backs, keepers, KeeperTuning::default()).await
+    }
+
+    /// Like [Cluster::start], but the keepers run with `tuning`.
+    pub async fn start_with(
+        backs: usize,
+        keepers: usize,
+        tuning: KeeperTuning,
+    ) -> TribResult<Cluster> {
+        Cluster::launch(backs, keepers, tuning, false).await
+    }
+
+    /// Like [Cluster::start], but every keeper reaches the backends through
+    /// proxies of its own, so that the cluster can be
+    /// [partitioned](Cluster::partition). The keepers then know the backends
+    /// by the addresses of their proxies.
+    pub async fn start_partitionable(backs: usize, keepers: usize) -> TribResult<Cluster> {
+        Cluster::start_partitionable_with(backs, keepers, KeeperTuning::default()).await
+    }
+
+    /// Like [Cluster::start_partitionable], but the keepers run with
+    /// `tuning`.
+    pub async fn start_partitionable_with(
+        backs: usize,
+        keepers: usize,
+        tuning: KeeperTuning,
+    ) -> TribResult<Cluster> {
+        Cluster::launch(backs, keepers, tuning, true).await
+    }
+
+    async fn launch(
+        backs: usize,
+        keepers: usize,
+        tuning: KeeperTuning,
+        proxied: bool,
+    ) -> TribResult<Cluster> {
+        let mut cluster = Cluster {
+            backs: (0..backs).map(|_| free_addr()).collect::<TribResult<_>>()?,
+            keepers: (0..keepers)
+                .map(|_| free_addr())
+                .collect::<TribResult<_>>()?,
+            tuning,
+            nodes: HashMap::new(),
+            links: HashMap::new(),
+        };
+        for back in cluster.backs.clone() {
+            cluster.restart(&back).await?;
+        }
+        for keeper in cluster.keepers.iter().filter(|_| proxied) {
+            let mut links = Vec::with_capacity(cluster.backs.len());
+            for back in cluster.backs.iter() {
+                links.push(Link::open(back).await?);
+            }
+            cluster.links.insert(keeper.clone(), links);
+        }
+        for keeper in cluster.keepers.clone() {
+            cluster.restart(&keeper).await?;
+        }
+        Ok(cluster)
+    }
+
+    /// The addresses of the backends, in the order they were started.
+    pub fn backs(&self) -> &[String] {
+        &self.backs
+    }
+
+    /// The addresses of the keepers, in the order they were started.
+    pub fn keepers(&self) -> &[String] {
+        &self.keepers
+    }
+
+    /// Whether the server at `addr` is up.
+    pub fn is_up(&self, addr: &str) -> bool {
+        self.nodes.contains_key(addr)
+    }
+
+    /// Takes down the server at `addr` as a crash would.
+    pub async fn kill(&mut self, addr: &str) {
+        if let Some(node) = self.nodes.remove(addr) {
+            node.task.abort();
+            let _ = node.task.await;
+        }
+    }
+
+    /// Shuts down the server at `addr` gracefully, returning what it
+    /// returned. A keeper waits for the clients watching it to go away.
+    pub async fn shutdown(&mut self, addr: &str) -> TribResult<()> {
+        let node = match self.nodes.remove(addr) {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+        let _ = node.shutdown.send(()).await;
+        node.task
+            .await
+            .map_err(|e| TribblerError::Unknown(format!("{} failed: {}", addr, e)))?
+    }
+
+    /// Brings the server at `addr` back up after it was taken down: a
+    /// backend on empty storage, as it comes back after losing what it kept
+    /// in memory, a keeper as it was started.
+    pub async fn restart(&mut self, addr: &str) -> TribResult<()> {
+        if self.is_up(addr) {
+            return Ok(());
+        }
+        let (ready, started) = mpsc::channel();
+        let (shutdown, stop) = tokio::sync::mpsc::channel(1);
+        let task = if let Some(this) = self.keepers.iter().position(|k| k == addr) {
+            tokio::spawn(serve_keeper(KeeperConfig {
+                backs: self.backs_of(addr),
+                addrs: self.keepers.clone(),
+                this,
+                id: this as u128,
+                tuning: self.tuning.clone(),
+                placement: Placement::default(),
+                backup: None,
+                ready: Some(ready),
+                shutdown: Some(stop),
+            }))
+        } else if self.backs.iter().any(|b| b == addr) {
+            tokio::spawn(serve_back(BackConfig {
+                addr: addr.to_string(),
+                storage: Box::new(MemStorage::new()),
+                ready: Some(ready),
+                shutdown: Some(stop),
+            }))
+        } else {
+            return Err(TribblerError::Unknown(format!(
+                "{} is not a server of the cluster",
+                addr
+            )));
+        };
+        let up = tokio::task::spawn_blocking(move || started.recv_timeout(READY_TIMEOUT)).await;
+        if !matches!(up, Ok(Ok(true))) {
+            return Err(match task.await {
+                Ok(Err(e)) => e,
+                _ => TribblerError::Unknown(format!("{} failed to start", addr)),
+            });
+        }
+        self.nodes.insert(addr.to_string(), Node { task, shutdown });
+        Ok(())
+    }
+
+    /// A bin client of the backends, following the keepers if there are any.
+    pub async fn bin_client(&self) -> TribResult<Box<dyn BinStorage>> {
+        match self.keepers.is_empty() {
+            true => new_bin_client(self.backs.clone()).await,
+            false => new_bin_client_with_keepers(self.backs.clone(), self.keepers.clone()).await,
+        }
+    }
+
+    /// A front-end of the cluster, following the keepers, and enforcing
+    /// `limits`. It takes writes once the keepers report the cluster settled
+    /// (see [Cluster::settled]).
+    pub async fn front(&self, limits: Limits) -> TribResult<Box<dyn Server + Send + Sync>> {
+        new_front_with_keepers(self.backs.clone(), self.keepers.clone(), limits).await
+    }
+
+    /// Like [Cluster::front], but following `keeper` alone, so that it
+    /// stays on the side of `keeper` when the cluster is partitioned.
+    pub async fn front_of(
+        &self,
+        keeper: &str,
+        limits: Limits,
+    ) -> TribResult<Box<dyn Server + Send + Sync>> {
+        new_front_with_keepers(self.backs.clone(), vec![keeper.to_string()], limits).await
+    }
+
+    /// The status of the cluster, as [cluster_status] collects it from the
+    /// first keeper which answers.
+    pub async fn status(&self) -> ClusterStatus {
+        for keeper in self.keepers.iter() {
+            let status = self.status_of(keeper).await;
+            if status.keeper.is_some() {
+                return status;
+            }
+        }
+        cluster_status(&self.backs, &[]).await
+    }
+
+    /// The status of the cluster as `keeper` sees it, probing the backends
+    /// from its side of a partition. The backends go by their own addresses
+    /// even when `keeper` reaches them through proxies.
+    pub async fn status_of(&self, keeper: &str) -> ClusterStatus {
+        let backs = self.backs_of(keeper);
+        let mut status = cluster_status(&backs, &[keeper.to_string()]).await;
+        for (report, back) in status.backends.iter_mut().zip(self.backs.iter()) {
+            report.addr = back.clone();
+        }
+        status
+    }
+
+    /// Partitions the network between the keepers and the backends into
+    /// `groups` of their addresses: a keeper reaches the backends of its
+    /// group alone, and a server of no group is cut off from every other.
+    /// Connections across groups are closed, and new ones refused, until the
+    /// cluster is partitioned again or [healed](Cluster::heal).
+    pub fn partition(&self, groups: &[Vec<String>]) -> TribResult<()> {
+        self.check_partitionable()?;
+        for addr in groups.iter().flatten() {
+            if !self.backs.contains(addr) && !self.keepers.contains(addr) {
+                return Err(TribblerError::Unknown(format!(
+                    "{} is not a server of the cluster",
+                    addr
+                )));
+            }
+        }
+        let group_of = |addr: &String| groups.iter().position(|g| g.contains(addr));
+        for (keeper, links) in self.links.iter() {
+            for (back, link) in self.backs.iter().zip(links) {
+                let joined = group_of(keeper).is_some() && group_of(keeper) == group_of(back);
+                link.fault.send_modify(|fault| fault.cut = !joined);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cuts `keeper` off from the backend `back` alone, closing the
+    /// connections between them and refusing new ones, until the cluster is
+    /// [healed](Cluster::heal).
+    pub fn cut(&self, keeper: &str, back: &str) -> TribResult<()> {
+        self.link(keeper, back)?
+            .fault
+            .send_modify(|fault| fault.cut = true);
+        Ok(())
+    }
+
+    /// Holds back everything sent either way between `keeper` and the
+    /// backend `back` for `delay`, until the cluster is
+    /// [healed](Cluster::heal). A zero `delay` stops holding it back.
+    pub fn delay(&self, keeper: &str, back: &str, delay: Duration) -> TribResult<()> {
+        self.link(keeper, back)?
+            .fault
+            .send_modify(|fault| fault.delay = delay);
+        Ok(())
+    }
+
+    /// Undoes a [partition](Cluster::partition), and every
+    /// [cut](Cluster::cut) and [delay](Cluster::delay), letting every keeper
+    /// reach every backend again.
+    pub fn heal(&self) {
+        for link in self.links.values().flatten() {
+            link.fault.send_replace(Fault::default());
+        }
+    }
+
+    /// Waits for the keepers to find every backend up eligible and their
+    /// clocks consistent. Without keepers, there is nothing to wait for.
+    pub async fn settled(&self) -> TribResult<()> {
+        if self.keepers.is_empty() {
+            return Ok(());
+        }
+        let deadline = Instant::now() + READY_TIMEOUT;
+        loop {
+            let status = self.status().await;
+            let settled = status.consistent
+                && status
+                    .backends
+                    .iter()
+                    .all(|b| b.eligible || !self.is_up(&b.addr));
+            if settled {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(TribblerError::Unknown(
+                    "timed out waiting for the cluster to settle".to_string(),
+                ));
+            }
+            tokio::time::sleep(SETTLE_POLL).await;
+        }
+    }
+
+    /// Takes every server down, returning the first error a backend returned.
+    /// The keepers go first, as a crash would, since one only finishes a
+    /// graceful shutdown once every client watching its cluster view has gone
+    /// away; the backends are then shut down gracefully.
+    pub async fn stop(mut self) -> TribResult<()> {
+        for addr in self.keepers.clone() {
+            self.kill(&addr).await;
+        }
+        let mut result = Ok(());
+        for addr in self.backs.clone() {
+            let stopped = self.shutdown(&addr).await;
+            if result.is_ok() {
+                result = stopped;
+            }
+        }
+        result
+    }
+
+    fn check_partitionable(&self) -> TribResult<()> {
+        if self.links.is_empty() && !self.keepers.is_empty() {
+            return Err(TribblerError::Unknown(
+                "the cluster was not started partitionable".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // the proxy `keeper` reaches the backend `back` through
+    fn link(&self, keeper: &str, back: &str) -> TribResult<&Link> {
+        self.check_partitionable()?;
+        let links = self.links.get(keeper);
+        let i = self.backs.iter().position(|b| b == back);
+        match (links, i) {
+            (Some(links), Some(i)) => Ok(&links[i]),
+            _ => Err(TribblerError::Unknown(format!(
+                "no link between keeper {} and backend {}",
+                keeper, back
+            ))),
+        }
+    }
+
+    // the addresses `keeper` reaches the backends at, those of its proxies
+    fn backs_of(&self, keeper: &str) -> Vec<String> {
+        match self.links.get(keeper) {
+            Some(links) => links.iter().map(|link| link.addr.clone()).collect(),
+            None => self.backs.clone(),
+        }
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for node in self.nodes.values() {
+            node.task.abort();
+        }
+        for link in self.links.values().flatten() {
+            link.fault.send_modify(|fault| fault.cut = true);
+            link.task.abort();
+        }
+    }
+}
+
+// forwards the connections `listener` accepts to `to` while the link is not
+// cut, as slowly as the link says
+async fn forward(listener: TcpListener, to: String, fault: watch::Receiver<Fault>) {
+    loop {
+        let inbound = match listener.accept().await {
+            Ok((inbound, _)) => inbound,
+            Err(_) => continue,
+        };
+        if fault.borrow().cut {
+            continue; // dropping it refuses it
+        }
+        let to = to.clone();
+        let mut fault = fault.clone();
+        tokio::spawn(async move {
+            let outbound = match TcpStream::connect(&to).await {
+                Ok(outbound) => outbound,
+                Err(_) => return,
+            };
+            let (in_read, in_write) = inbound.into_split();
+            let (out_read, out_write) = outbound.into_split();
+            tokio::select! {
+                _ = pump(in_read, out_write, fault.clone()) => {}
+                _ = pump(out_read, in_write, fault.clone()) => {}
+                _ = fault.wait_for(|fault| fault.cut) => {}
+            }
+        });
+    }
+}
+
+// copies what comes from `from` to `to` until either end closes, holding
+// every chunk back for the delay of the link at the time
+async fn pump(mut from: OwnedReadHalf, mut to: OwnedWriteHalf, fault: watch::Receiver<Fault>) {
+    let mut buf = vec![0; 16 * 1024];
+    loop {
+        let n = match from.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let delay = fault.borrow().delay;
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        if to.write_all(&buf[..n]).await.is_err() {
+            return;
+        }
+    }
+}
+
+// a local address no one listens on
+fn free_addr() -> TribResult<String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.to_string())
+}
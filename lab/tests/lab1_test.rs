@@ -20,12 +20,16 @@ use tribbler::{
     storage::{KeyList, KeyString, KeyValue, MemStorage, Pattern, Storage},
 };
 
+// what the tests return, which takes the errors of the channels and tasks
+// they drive along with those of the service
+type TestResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
 const DEFAULT_HOST: &str = "localhost:3000";
 
 async fn setup(
     addr: Option<&str>,
     storage: Option<Box<dyn Storage + Send + Sync>>,
-) -> TribResult<(Box<dyn Storage>, JoinHandle<TribResult<()>>, MpscSender<()>)> {
+) -> TestResult<(Box<dyn Storage>, JoinHandle<TribResult<()>>, MpscSender<()>)> {
     let _ = env_logger::builder()
         .default_format()
         .filter_level(LevelFilter::Info)
@@ -42,7 +46,7 @@ async fn setup(
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
     let cfg = BackConfig {
         addr: addr.to_string(),
-        storage: storage,
+        storage,
         ready: Some(tx.clone()),
         shutdown: Some(shut_rx),
     };
@@ -77,7 +81,7 @@ fn pat(prefix: &str, suffix: &str) -> Pattern {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_get_set() -> TribResult<()> {
+async fn test_get_set() -> TestResult<()> {
     let (client, _handle, _tx) = setup(None, None).await?;
     assert_eq!(None, client.get("").await?);
     assert_eq!(None, client.get("hello").await?);
@@ -85,7 +89,7 @@ async fn test_get_set() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_get_set_overwrite() -> TribResult<()> {
+async fn test_get_set_overwrite() -> TestResult<()> {
     let (client, _handle, _tx) = setup(None, None).await?;
     client.set(&kv("h8liu", "run")).await?;
     assert_eq!(Some("run".to_string()), client.get("h8liu").await?);
@@ -95,7 +99,7 @@ async fn test_get_set_overwrite() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_set_none() -> TribResult<()> {
+async fn test_set_none() -> TestResult<()> {
     let (client, _handle, _shut) = setup(None, None).await?;
     client.set(&kv("h8liu", "")).await?;
     assert_eq!(None, client.get("h8liu").await?);
@@ -107,7 +111,7 @@ async fn test_set_none() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_keys() -> TribResult<()> {
+async fn test_keys() -> TestResult<()> {
     let (client, _handle, _tx) = setup(None, None).await?;
     let _ = client.set(&kv("h8liu", "1")).await?;
     let _ = client.set(&kv("h8he", "2")).await?;
@@ -121,7 +125,7 @@ async fn test_keys() -> TribResult<()> {
     Ok(())
 }
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_list() -> TribResult<()> {
+async fn test_list() -> TestResult<()> {
     let (client, _handle, _shut) = setup(None, None).await?;
     client.list_append(&kv("lst", "a")).await?;
     let l = client.list_get("lst").await?.0;
@@ -155,7 +159,7 @@ async fn test_list() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_list_keys() -> TribResult<()> {
+async fn test_list_keys() -> TestResult<()> {
     let (client, _srv, _shut) = setup(None, None).await?;
     let _ = client.list_append(&kv("t1", "v1")).await?;
     let _ = client.list_append(&kv("t2", "v2")).await?;
@@ -165,7 +169,7 @@ async fn test_list_keys() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_awaited() -> TribResult<()> {
+async fn test_awaited() -> TestResult<()> {
     let (_client, srv, _shut) = setup(None, None).await?;
     tokio::time::sleep(Duration::from_secs(2)).await;
     srv.abort();
@@ -176,7 +180,7 @@ async fn test_awaited() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_bad_address() -> TribResult<()> {
+async fn test_bad_address() -> TestResult<()> {
     let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let cfg = BackConfig {
         addr: "^_^".to_string(),
@@ -196,7 +200,7 @@ async fn test_bad_address() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_store_before_serve() -> TribResult<()> {
+async fn test_store_before_serve() -> TestResult<()> {
     let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let store = MemStorage::default();
     store.set(&kv("hello", "hi")).await?;
@@ -217,7 +221,7 @@ async fn test_store_before_serve() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_multi_serve() -> TribResult<()> {
+async fn test_multi_serve() -> TestResult<()> {
     let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let cfg = BackConfig {
         addr: DEFAULT_HOST.to_string(),
@@ -242,7 +246,7 @@ async fn test_multi_serve() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_clock() -> TribResult<()> {
+async fn test_clock() -> TestResult<()> {
     let (client, _srv, _shut) = setup(None, None).await?;
     assert_eq!(2999, client.clock(2999).await?);
     assert_eq!(3000, client.clock(0).await?);
@@ -251,7 +255,7 @@ async fn test_clock() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_spawn_same_addr() -> TribResult<()> {
+async fn test_spawn_same_addr() -> TestResult<()> {
     let addr = DEFAULT_HOST.to_string();
     let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
@@ -262,7 +266,7 @@ async fn test_spawn_same_addr() -> TribResult<()> {
         shutdown: Some(shut_rx),
     };
     let handle = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     let _ = shut_tx.send(()).await;
     let _ = handle.await;
     thread::sleep(Duration::from_millis(500));
@@ -272,8 +276,8 @@ async fn test_spawn_same_addr() -> TribResult<()> {
         ready: Some(tx),
         shutdown: None,
     };
-    let _ = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    drop(spawn_back(cfg));
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
 
     let client = lab1::new_client(format!("http://{}", addr.clone()).as_str()).await?;
     client.set(&kv("hello", "hi")).await?;
@@ -282,7 +286,7 @@ async fn test_spawn_same_addr() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_back_spawn_new_storage() -> TribResult<()> {
+async fn test_back_spawn_new_storage() -> TestResult<()> {
     let host = format!("localhost:{}", rand_port());
     let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
@@ -293,10 +297,10 @@ async fn test_back_spawn_new_storage() -> TribResult<()> {
         shutdown: Some(shut_rx),
     };
     let handle = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     let client = lab1::new_client(format!("http://{}", host).as_mut()).await?;
     client.set(&kv("hello", "hi")).await?;
-    let _ = shut_tx.send(()).await?;
+    shut_tx.send(()).await?;
     let _ = handle.await;
     tokio::time::sleep(Duration::from_millis(500)).await;
     let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
@@ -306,15 +310,15 @@ async fn test_back_spawn_new_storage() -> TribResult<()> {
         ready: Some(tx),
         shutdown: Some(shut_rx),
     };
-    let _ = spawn_back(cfg);
-    assert_eq!(true, rx.recv_timeout(Duration::from_secs(2))?);
+    drop(spawn_back(cfg));
+    assert!(rx.recv_timeout(Duration::from_secs(2))?);
     assert_eq!(None, client.get("hello").await?);
     let _ = shut_tx.send(()).await;
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_concurrent_cli_ops() -> TribResult<()> {
+async fn test_concurrent_cli_ops() -> TestResult<()> {
     let (client, _srv, _shut) = setup(None, None).await?;
     let client = Arc::new(client);
     let mut handles = vec![];
@@ -343,18 +347,131 @@ async fn test_concurrent_cli_ops() -> TribResult<()> {
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-async fn test_shutdown() -> TribResult<()> {
+async fn test_concurrent_incr() -> TestResult<()> {
+    let (client, _srv, _shut) = setup(None, None).await?;
+    let mut handles = vec![];
+    for _ in 0..5 {
+        let addr = format!("http://{}", DEFAULT_HOST);
+        let jh = tokio::spawn(async move {
+            let client = match lab1::new_client(&addr).await {
+                Ok(c) => c,
+                Err(e) => return Err(TribblerError::Unknown(e.to_string())),
+            };
+            for _ in 0..10 {
+                if let Err(e) = client.incr("n", 2).await {
+                    return Err(TribblerError::Unknown(e.to_string()));
+                };
+            }
+            Ok(())
+        });
+        handles.push(jh);
+    }
+    for handle in handles {
+        let res = handle.await;
+        assert!(res.is_ok());
+    }
+    assert_eq!(99, client.incr("n", -1).await?);
+    assert_eq!(Some("99".to_string()), client.get("n").await?);
+    assert!(client.set(&kv("s", "text")).await?);
+    // the backend's error comes back as it left
+    match client.incr("s", 1).await {
+        Err(TribblerError::NotACounter(key)) => assert_eq!("s", key),
+        res => panic!("incr on text answered {:?}", res),
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_compare_and_swap() -> TestResult<()> {
+    let (client, _srv, _shut) = setup(None, None).await?;
+    let mut handles = vec![];
+    for _ in 0..5 {
+        let addr = format!("http://{}", DEFAULT_HOST);
+        let jh = tokio::spawn(async move {
+            let client = match lab1::new_client(&addr).await {
+                Ok(c) => c,
+                Err(e) => return Err(TribblerError::Unknown(e.to_string())),
+            };
+            for _ in 0..10 {
+                // count up by swapping, starting over from what won the race
+                let mut held = String::new();
+                loop {
+                    let next = (held.parse::<u64>().unwrap_or(0) + 1).to_string();
+                    match client.compare_and_swap("n", &held, &next).await {
+                        Ok(swapped) if swapped == held => break,
+                        Ok(swapped) => held = swapped,
+                        Err(e) => return Err(TribblerError::Unknown(e.to_string())),
+                    }
+                }
+            }
+            Ok(())
+        });
+        handles.push(jh);
+    }
+    for handle in handles {
+        assert!(handle.await.is_ok_and(|res| res.is_ok()));
+    }
+    assert_eq!(Some("50".to_string()), client.get("n").await?);
+    // a swap that misses leaves the value be
+    assert_eq!("50", client.compare_and_swap("n", "49", "0").await?);
+    assert_eq!(Some("50".to_string()), client.get("n").await?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_shutdown() -> TestResult<()> {
     let (client, srv, shutdown) = setup(None, None).await?;
     assert!(client.set(&kv("hello", "hi")).await?);
     let _ = shutdown.send(()).await;
     let r = srv.await.unwrap();
     assert!(r.is_ok());
-    match client.get("hello").await {
-        Ok(v) => panic!(
+    if let Ok(v) = client.get("hello").await {
+        panic!(
             "uh oh..somehow the client still completed this request: {:?}",
             v
-        ),
-        Err(_) => (),
+        )
     };
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_unix_socket() -> TestResult<()> {
+    let path = std::env::temp_dir().join(format!("trib-back-{}.sock", rand_port()));
+    let addr = format!("unix://{}", path.display());
+    for round in 0..2 {
+        // the second round binds over the socket the first one left behind
+        let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
+        let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
+        let cfg = BackConfig {
+            addr: addr.clone(),
+            storage: Box::new(MemStorage::default()),
+            ready: Some(tx),
+            shutdown: Some(shut_rx),
+        };
+        let handle = spawn_back(cfg);
+        assert!(rx.recv_timeout(Duration::from_secs(2))?);
+        let client = lab1::new_client(&addr).await?;
+        assert_eq!(None, client.get("hello").await?, "round {}", round);
+        client.set(&kv("hello", "hi")).await?;
+        assert_eq!(Some("hi".to_string()), client.get("hello").await?);
+        shut_tx.send(()).await?;
+        let _ = handle.await;
+    }
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_https_needs_certificate() -> TestResult<()> {
+    let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
+    let cfg = BackConfig {
+        addr: format!("https://localhost:{}", rand_port()),
+        storage: Box::new(MemStorage::default()),
+        ready: Some(tx),
+        shutdown: None,
+    };
+    let handle = spawn_back(cfg);
+    assert!(!rx.recv_timeout(Duration::from_secs(1))?);
+    assert!(handle.await?.is_err());
+    Ok(())
+}
@@ -1,91 +1,56 @@
-use std::{
-    sync::{
-        mpsc::{self, Receiver, Sender},
-    },
-};
+use std::sync::Arc;
 use std::time::Duration;
 
-use lab::{self, lab1, lab2};
-use tokio::{sync::mpsc::Sender as MpscSender, task::JoinHandle};
+use lab::keeper::{keeper_client::KeeperClient, BackendStatus, StatusRequest};
+use lab::test_support::Cluster;
+use lab::{self, lab2};
 
-use tribbler::{config::KeeperConfig, trib::{MAX_TRIB_LEN, MAX_TRIB_FETCH}, storage::List};
 #[allow(unused_imports)]
 use tribbler::{
     self,
     config::BackConfig,
     err::{TribResult, TribblerError},
     storage::{KeyList, KeyString, KeyValue, MemStorage, Pattern, Storage},
+    testing::conformance,
+};
+use tribbler::{
+    config::{KeeperTuning, Limits},
+    storage::List,
+    trib::{
+        Attachment, NotificationKind, Profile, Server, Trend, Trib, TribRef, MAX_ATTACHMENTS,
+        MAX_BIO_LEN, MAX_DRAFTS, MAX_TRIB_FETCH, MAX_TRIB_LEN,
+    },
 };
 
-const DEFAULT_KEEPER: &str = "localhost:32243";
-const DEFAULT_ADDR: &str = "localhost";
-const DEFAULT_PORT: u32 = 32244;
-
-async fn setup_n(s: u32) -> TribResult<(Vec<String>, Vec<JoinHandle<TribResult<()>>>, Vec<tokio::sync::mpsc::Sender<()>>, JoinHandle<TribResult<()>>, MpscSender<()>)> {
-    let mut backs = Vec::new();
-    let mut handles = Vec::new();
-    let mut back_shutdowns = Vec::new();
-
-    // Setup Backs
-    for i in 0..s {
-        let back = format!("{}:{}", DEFAULT_ADDR, (DEFAULT_PORT + i));
-        backs.push(back.clone());
-
-        let storage = Box::new(MemStorage::new());
-        let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
-        let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
-        let cfg = BackConfig {
-            addr: back.clone(),
-            storage: storage,
-            ready: Some(tx.clone()),
-            shutdown: Some(shut_rx),
-        };
-
-        let handle = spawn_back(cfg);
-        handles.push(handle);
-        back_shutdowns.push(shut_tx.clone());
-        let ready = rx.recv_timeout(Duration::from_secs(5))?;
-        if !ready {
-            return Err(Box::new(TribblerError::Unknown(
-                "back failed to start".to_string(),
-            )));
-        }
-    }
-
-    // Setup Keeper
-    let (tx, rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
-    let (shut_tx, shut_rx) = tokio::sync::mpsc::channel(1);
-    let cfg_keeper = KeeperConfig {
-        backs: backs.clone(),
-        addrs: vec![DEFAULT_KEEPER.to_string()],
-        this: 0 as usize,
-        id: 0 as u128,
-        ready: Some(tx.clone()),
-        shutdown: Some(shut_rx),
-    };
+// what the tests return, which takes the errors of the channels and tasks
+// they drive along with those of the service
+type TestResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-    let keeper_handle = tokio::spawn(lab2::serve_keeper(cfg_keeper));
-    let ready = rx.recv_timeout(Duration::from_secs(5))?;
-    if !ready {
-        return Err(Box::new(TribblerError::Unknown(
-            "back failed to start".to_string(),
-        )));
-    }
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_keeper_shutdown() -> TestResult<()> {
+    let mut cluster = Cluster::start(1, 1).await?;
+    let keeper = cluster.keepers()[0].clone();
 
-    Ok((backs, handles, back_shutdowns, keeper_handle, shut_tx.clone()))
-}
+    let r = cluster.shutdown(&keeper).await;
+    assert!(r.is_ok());
+    assert!(!cluster.is_up(&keeper));
 
-fn spawn_back(cfg: BackConfig) -> tokio::task::JoinHandle<TribResult<()>> {
-    tokio::spawn(lab1::serve_back(cfg))
+    Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_keeper_shutdown() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(1).await?;
+async fn test_teardown() -> TestResult<()> {
+    let mut cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
 
-    let _ = shutdown_keeper.send(()).await;
-    let r = keeper_handle.await.unwrap();
+    for back in back_addrs.iter() {
+        let r = cluster.shutdown(back).await;
+        assert!(r.is_ok());
+    }
+
+    let r = cluster.stop().await;
     assert!(r.is_ok());
 
     Ok(())
@@ -93,29 +58,214 @@ async fn test_keeper_shutdown() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_teardown() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_restart_back() -> TestResult<()> {
+    let mut cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    cluster.settled().await?;
+
+    // a crashed backend drops out of the write path, and comes back into it
+    cluster.kill(&back_addrs[1]).await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let status = cluster.status().await;
+    assert!(!status.backends[1].alive && !status.backends[1].eligible);
+
+    cluster.restart(&back_addrs[1]).await?;
+    cluster.settled().await?;
+    let status = cluster.status().await;
+    assert!(status.backends.iter().all(|b| b.alive && b.eligible));
+
+    // restarting one which is up leaves it be
+    cluster.restart(&back_addrs[0]).await?;
+    assert!(cluster.restart("localhost:1").await.is_err());
+
+    Ok(())
+}
 
-    for s in shutdown_backs {
-        let _ = s.send(()).await?;
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_partition() -> TestResult<()> {
+    let cluster = Cluster::start_partitionable(3, 2).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keepers = cluster.keepers().to_vec();
+    cluster.settled().await?;
+
+    // each keeper finds the backends across the split dead
+    cluster.partition(&[
+        vec![
+            keepers[0].clone(),
+            back_addrs[0].clone(),
+            back_addrs[1].clone(),
+        ],
+        vec![keepers[1].clone(), back_addrs[2].clone()],
+    ])?;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let alive = |status: lab2::ClusterStatus| -> Vec<bool> {
+        status.backends.iter().map(|b| b.alive).collect()
+    };
+    assert_eq!(
+        vec![true, true, false],
+        alive(cluster.status_of(&keepers[0]).await)
+    );
+    assert_eq!(
+        vec![false, false, true],
+        alive(cluster.status_of(&keepers[1]).await)
+    );
+
+    // and every one alive again once healed
+    cluster.heal();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    for keeper in keepers.iter() {
+        assert_eq!(vec![true; 3], alive(cluster.status_of(keeper).await));
     }
 
-    for b in backs {
-        let r = b.await.unwrap();
-        assert!(r.is_ok());
+    assert!(cluster
+        .partition(&[vec!["localhost:1".to_string()]])
+        .is_err());
+    let unproxied = Cluster::start(1, 1).await?;
+    assert!(unproxied.partition(&[]).is_err());
+
+    Ok(())
+}
+
+// a keeper quick to act on what its probes find, for the tests which fail
+// its links
+fn quick_tuning() -> KeeperTuning {
+    KeeperTuning {
+        sync_interval_ms: 200,
+        stable_window_ms: 1_000,
+        max_hold_down_ms: 4_000,
+        probe_timeout_ms: 300,
+        failure_threshold: 3,
+        retry_backoff_ms: 100,
+        max_retry_backoff_ms: 200,
     }
+}
 
-    let _ = shutdown_keeper.send(()).await;
-    let r = keeper_handle.await.unwrap();
-    assert!(r.is_ok());
+// polls what `keeper` reports of its backend `i` until `until` holds of it
+async fn await_backend(
+    keeper: &str,
+    i: usize,
+    until: impl Fn(&BackendStatus) -> bool,
+) -> TestResult<BackendStatus> {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    let mut client = KeeperClient::connect(format!("http://{}", keeper)).await?;
+    loop {
+        let report = client.status(StatusRequest {}).await?.into_inner().backends[i].clone();
+        if until(&report) {
+            return Ok(report);
+        }
+        if tokio::time::Instant::now() > deadline {
+            return Err(format!("timed out waiting on backend {}: {:?}", i, report).into());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_keeper_tells_slow_from_dead() -> TestResult<()> {
+    let tuning = quick_tuning();
+    let cluster = Cluster::start_partitionable_with(2, 1, tuning.clone()).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    cluster.settled().await?;
+
+    // answering within the probe timeout, a slow backend stays up
+    cluster.delay(&keeper, &back_addrs[1], Duration::from_millis(10))?;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let report = await_backend(&keeper, 1, |_| true).await?;
+    assert!(report.alive && report.eligible);
+
+    // past it, the backend counts as down once enough probes failed in a row
+    cluster.delay(&keeper, &back_addrs[1], Duration::from_secs(1))?;
+    let report = await_backend(&keeper, 1, |b| !b.alive).await?;
+    assert!(report.failures >= tuning.failure_threshold);
+    assert!(!report.eligible);
+    let other = await_backend(&keeper, 0, |_| true).await?;
+    assert!(other.alive && other.eligible);
+
+    // and back up once it answers in time again, but held down for a while
+    cluster.heal();
+    let report = await_backend(&keeper, 1, |b| b.alive).await?;
+    assert!(!report.eligible);
+    assert_eq!(0, report.failures);
+    await_backend(&keeper, 1, |b| b.eligible).await?;
+
+    assert!(cluster
+        .delay(&keeper, "localhost:1", Duration::ZERO)
+        .is_err());
+    let unproxied = Cluster::start(1, 1).await?;
+    let (k, b) = (unproxied.keepers()[0].clone(), unproxied.backs()[0].clone());
+    assert!(unproxied.cut(&k, &b).is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_keeper_holds_down_flapping_backend() -> TestResult<()> {
+    let tuning = quick_tuning();
+    let cluster = Cluster::start_partitionable_with(2, 1, tuning.clone()).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    cluster.settled().await?;
+
+    // a backend which dies once is held down for the stable window
+    cluster.cut(&keeper, &back_addrs[1])?;
+    let report = await_backend(&keeper, 1, |b| !b.alive).await?;
+    assert_eq!(tuning.stable_window_ms, report.hold_down_ms);
+    cluster.heal();
+    await_backend(&keeper, 1, |b| b.alive).await?;
+
+    // dying again before it proved itself doubles the hold-down
+    cluster.cut(&keeper, &back_addrs[1])?;
+    let report = await_backend(&keeper, 1, |b| !b.alive).await?;
+    assert_eq!(2 * tuning.stable_window_ms, report.hold_down_ms);
+    cluster.heal();
+    let up = await_backend(&keeper, 1, |b| b.alive).await?;
+    assert!(!up.eligible);
+    let since = tokio::time::Instant::now();
+    await_backend(&keeper, 1, |b| b.eligible).await?;
+    // it came up at most a probe round before it was seen to
+    let held = since.elapsed() + Duration::from_millis(2 * tuning.sync_interval_ms);
+    assert!(held >= Duration::from_millis(up.hold_down_ms));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_keeper_syncs_recovered_clock() -> TestResult<()> {
+    let cluster = Cluster::start_partitionable_with(2, 1, quick_tuning()).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    cluster.settled().await?;
+
+    // the clock moves on while the keeper cannot reach a backend
+    cluster.cut(&keeper, &back_addrs[1])?;
+    await_backend(&keeper, 1, |b| !b.alive).await?;
+    let back0 = lab2::new_client(&format!("http://{}", back_addrs[0])).await?;
+    back0.clock(1000).await?;
+    let back1 = lab2::new_client(&format!("http://{}", back_addrs[1])).await?;
+    assert!(back1.clock(0).await? < 1000);
+
+    // which catches up once it is back in the write path
+    cluster.heal();
+    cluster.settled().await?;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while back1.clock(0).await? < 1000 {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "the clock never caught up"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(cluster.status_of(&keeper).await.clock >= 1000);
 
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_signup() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_signup() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
@@ -146,16 +296,20 @@ async fn test_signup() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_list_users() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_list_users() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
-    let names = vec!["z", "y", "x", "w", "v", "u", "t", "s", "r", "q", "p", "o", "n", "m", "l", "k", "j", "i", "h", "g", "f", "e", "d", "c", "b", "a"];
-    let names_string:Vec<String> = names.iter().map(|ch| ch.to_string()).collect();
+    let names = vec![
+        "z", "y", "x", "w", "v", "u", "t", "s", "r", "q", "p", "o", "n", "m", "l", "k", "j", "i",
+        "h", "g", "f", "e", "d", "c", "b", "a",
+    ];
+    let names_string: Vec<String> = names.iter().map(|ch| ch.to_string()).collect();
 
     for n in names {
-        tribserver.sign_up(&n).await?;
+        tribserver.sign_up(n).await?;
     }
 
     let mut expected = names_string[0..20].to_vec();
@@ -169,13 +323,14 @@ async fn test_list_users() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_post() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_post() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
     // Post too long
-    let too_long = (0..(MAX_TRIB_LEN+1)).map(|_| "X").collect::<String>();
+    let too_long = (0..(MAX_TRIB_LEN + 1)).map(|_| "X").collect::<String>();
     let r = tribserver.post("bob", &too_long, 0).await;
     assert!(r.is_err());
 
@@ -185,15 +340,15 @@ async fn test_post() -> TribResult<()> {
     let r = tribserver.post("bob", &valid_post, 0).await;
     assert!(r.is_err());
 
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     let r = tribserver.post("bob", &valid_post, 0).await;
     assert!(r.is_ok());
 
     // garbage collection
-    for i in 0..(MAX_TRIB_FETCH+50) {
+    for i in 0..(MAX_TRIB_FETCH + 50) {
         let post = i.to_string();
-        let _ = tribserver.post("bob", &post, 0).await?;
+        tribserver.post("bob", &post, 0).await?;
     }
     tribserver.tribs("bob").await?; // garbage collection
 
@@ -207,24 +362,25 @@ async fn test_post() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_tribs() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_tribs() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     // fetch 2 tribs
     let post = "a";
-    let _ = tribserver.post("bob", &post, 0).await?;
-    let _ = tribserver.post("bob", &post, 0).await?;
+    tribserver.post("bob", post, 0).await?;
+    tribserver.post("bob", post, 0).await?;
     let tribs = tribserver.tribs("bob").await?;
     assert_eq!(tribs.len(), 2);
 
     // fetch 100 tribs
     for i in 0..MAX_TRIB_FETCH {
         let post = "b";
-        let _ = tribserver.post("bob", &post, 0).await?;
+        tribserver.post("bob", post, 0).await?;
     }
     let tribs = tribserver.tribs("bob").await?;
     assert_eq!(tribs.len(), 100);
@@ -238,26 +394,27 @@ async fn test_tribs() -> TribResult<()> {
     let bin = bin_storage_2.bin("bob").await?;
     let List(serialized_tribs) = bin.list_get("tribs").await?;
     assert_eq!(serialized_tribs.len(), MAX_TRIB_FETCH);
-    
+
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_follow() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_follow() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
     // Bob doesn't exist
     let r = tribserver.follow("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     // Alice doesn't exist
     let r = tribserver.follow("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("alice").await?;
+    tribserver.sign_up("alice").await?;
 
     // Bob can't follow Bob
     let r = tribserver.follow("bob", "bob").await;
@@ -273,12 +430,12 @@ async fn test_follow() -> TribResult<()> {
 
     for i in 0..1999 {
         let name = format!("alice{}", i);
-        let _ = tribserver.sign_up(&name).await?;
-        let _ = tribserver.follow("bob", &name).await?;
+        tribserver.sign_up(&name).await?;
+        tribserver.follow("bob", &name).await?;
     }
 
     // Too many follows
-    let _ = tribserver.sign_up("malory").await?;
+    tribserver.sign_up("malory").await?;
     let r = tribserver.follow("bob", "malory").await;
     assert!(r.is_err());
 
@@ -287,20 +444,21 @@ async fn test_follow() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_unfollow() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_unfollow() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
     // Bob doesn't exist
     let r = tribserver.unfollow("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     // Alice doesn't exist
     let r = tribserver.unfollow("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("alice").await?;
+    tribserver.sign_up("alice").await?;
 
     // Bob can't unfollow Bob
     let r = tribserver.unfollow("bob", "bob").await;
@@ -311,7 +469,7 @@ async fn test_unfollow() -> TribResult<()> {
     assert!(r.is_err());
 
     // Bob can unfollow Alice after following
-    let _ = tribserver.follow("bob", "alice").await?;
+    tribserver.follow("bob", "alice").await?;
     let r = tribserver.unfollow("bob", "alice").await;
     assert!(r.is_ok());
 
@@ -320,20 +478,21 @@ async fn test_unfollow() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_is_following() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_is_following() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
     // Bob doesn't exist
     let r = tribserver.is_following("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     // Alice doesn't exist
     let r = tribserver.is_following("bob", "alice").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("alice").await?;
+    tribserver.sign_up("alice").await?;
 
     // Bob can't follow Bob
     let r = tribserver.is_following("bob", "bob").await;
@@ -344,12 +503,12 @@ async fn test_is_following() -> TribResult<()> {
     assert!(!r);
 
     // Bob follows Alice
-    let _ = tribserver.follow("bob", "alice").await?;
+    tribserver.follow("bob", "alice").await?;
     let r = tribserver.is_following("bob", "alice").await?;
     assert!(r);
 
     // Bob no longer follows Alice
-    let _ = tribserver.unfollow("bob", "alice").await?;
+    tribserver.unfollow("bob", "alice").await?;
     let r = tribserver.is_following("bob", "alice").await?;
     assert!(!r);
 
@@ -358,37 +517,38 @@ async fn test_is_following() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_following() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_following() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
     // Bob doesn't exist
     let r = tribserver.following("bob").await;
     assert!(r.is_err());
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
 
     // Bob following no one
     let r = tribserver.following("bob").await?;
     assert_eq!(r.len(), 0);
 
     // Bob following Alice
-    let _ = tribserver.sign_up("alice").await?;
-    let r = tribserver.follow("bob", "alice").await?;
+    tribserver.sign_up("alice").await?;
+    tribserver.follow("bob", "alice").await?;
     let r = tribserver.following("bob").await?;
     let expected = vec!["alice".to_string()];
     assert_eq!(r, expected);
 
     // Bob no longer following Alice
-    let r = tribserver.unfollow("bob", "alice").await?;
+    tribserver.unfollow("bob", "alice").await?;
     let r = tribserver.following("bob").await?;
     assert_eq!(r.len(), 0);
 
     // Bob following 2000 users
     for i in 0..2000 {
         let name = format!("alice{}", i);
-        let _ = tribserver.sign_up(&name).await?;
-        let _ = tribserver.follow("bob", &name).await?;
+        tribserver.sign_up(&name).await?;
+        tribserver.follow("bob", &name).await?;
     }
 
     let r = tribserver.following("bob").await?;
@@ -397,7 +557,7 @@ async fn test_following() -> TribResult<()> {
     // Bob unfollow 2000 users
     for i in 0..2000 {
         let name = format!("alice{}", i);
-        let _ = tribserver.unfollow("bob", &name).await?;
+        tribserver.unfollow("bob", &name).await?;
     }
 
     let r = tribserver.following("bob").await?;
@@ -408,8 +568,9 @@ async fn test_following() -> TribResult<()> {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 #[allow(unused_variables)]
-async fn test_home() -> TribResult<()> {
-    let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
+async fn test_home() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
     let tribserver = lab2::new_front(bin_storage).await?;
 
@@ -419,7 +580,7 @@ async fn test_home() -> TribResult<()> {
     let r = tribserver.following("bob").await;
     assert!(r.is_err());
 
-    let _ = tribserver.sign_up("bob").await?;
+    tribserver.sign_up("bob").await?;
     names.push("bob".to_string());
 
     // Should have no tribs
@@ -429,15 +590,14 @@ async fn test_home() -> TribResult<()> {
     // Create 4 other users that bob follows
     for i in 0..4 {
         let name = format!("alice{}", i);
-        let _ = tribserver.sign_up(&name).await?;
-        let _ = tribserver.follow("bob", &name).await?;
+        tribserver.sign_up(&name).await?;
+        tribserver.follow("bob", &name).await?;
         names.push(name);
     }
 
-    for i in 0..names.len() {
-        let name = &names[i];
-        for c in 0..(i+1) {
-            let _ = tribserver.post(name, "post", 0).await?;
+    for (i, name) in names.iter().enumerate() {
+        for c in 0..(i + 1) {
+            tribserver.post(name, "post", 0).await?;
         }
     }
 
@@ -445,10 +605,9 @@ async fn test_home() -> TribResult<()> {
     let home = tribserver.home("bob").await?;
     assert_eq!(home.len(), 15);
 
-    for i in 0..names.len() {
-        let name = &names[i];
+    for name in names.iter() {
         for c in 0..20 {
-            let _ = tribserver.post(name, "post", 0).await?;
+            tribserver.post(name, "post", 0).await?;
         }
     }
 
@@ -459,39 +618,1596 @@ async fn test_home() -> TribResult<()> {
     Ok(())
 }
 
-// #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
-// #[allow(unused_variables)]
-// async fn test_concurrent_follow() -> TribResult<()> {
-//     let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await?;
-//     let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
-//     let tribserver = lab2::new_front(bin_storage).await?;
-//     let _ = tribserver.sign_up("bob").await?;
-//     let _ = tribserver.sign_up("alice").await?;
-
-//     let mut counter = 0;
-//     let mut handles: Vec<JoinHandle<()>> = Vec::new();
-//     while (counter < 2) {
-//         let temp = tokio::spawn(async move{
-//             let (back_addrs, backs, shutdown_backs, keeper_handle, shutdown_keeper) = setup_n(3).await.unwrap();
-//             let bin_storage = lab2::new_bin_client(back_addrs.clone()).await.unwrap();
-//             let tribserver = lab2::new_front(bin_storage).await.unwrap();
-//             let r = tribserver.follow("bob", "alice").await.unwrap();
-//         });
-
-//         counter += 1;
-//         handles.push(temp);
-//     }
-//     let mut result_counter = 0;
-
-//     for item in handles {
-//         let temp = item.await;
-//         dbg!(&temp);
-//         if temp.is_ok() {
-//             result_counter += 1;
-//         }
-//     }
-
-//     assert_eq!(result_counter, 1);
-
-//     return Ok(());
-// }
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_conformance() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let limits = Limits {
+        max_trib_fetch: 10,
+        max_following: 5,
+        ..Limits::default()
+    };
+    let front = cluster.front(limits).await?;
+    cluster.settled().await?;
+    conformance::run(front, limits).await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_follow() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let mut fronts: Vec<Arc<dyn Server + Send + Sync>> = vec![];
+    for _ in 0..4 {
+        fronts.push(Arc::from(cluster.front(Limits::default()).await?));
+    }
+    cluster.settled().await?;
+    fronts[0].sign_up("bob").await?;
+    fronts[0].sign_up("alice").await?;
+
+    // of many racing follows across the front-ends, exactly one takes, and
+    // the others find bob already following
+    let mut racing = tokio::task::JoinSet::new();
+    for i in 0..32 {
+        let front = fronts[i % fronts.len()].clone();
+        racing.spawn(async move { front.follow("bob", "alice").await });
+    }
+    let mut followed = 0;
+    while let Some(r) = racing.join_next().await {
+        match r? {
+            Ok(()) => followed += 1,
+            Err(TribblerError::AlreadyFollowing(_, _)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    assert_eq!(1, followed);
+    for front in fronts.iter() {
+        assert_eq!(vec!["alice".to_string()], front.following("bob").await?);
+        assert!(front.is_following("bob", "alice").await?);
+    }
+
+    // the same for unfollows
+    for i in 0..32 {
+        let front = fronts[i % fronts.len()].clone();
+        racing.spawn(async move { front.unfollow("bob", "alice").await });
+    }
+    let mut unfollowed = 0;
+    while let Some(r) = racing.join_next().await {
+        match r? {
+            Ok(()) => unfollowed += 1,
+            Err(TribblerError::NotFollowing(_, _)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    assert_eq!(1, unfollowed);
+    for front in fronts.iter() {
+        assert!(front.following("bob").await?.is_empty());
+        assert!(!front.is_following("bob", "alice").await?);
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_bin_client_reroutes() -> TestResult<()> {
+    let mut cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    let bin_storage =
+        lab2::new_bin_client_with_keepers(back_addrs.clone(), vec![keeper.to_string()]).await?;
+
+    // take down one backend and give the keeper time to push a new view
+    cluster.shutdown(&back_addrs[0]).await?;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    for i in 0..10 {
+        let bin = bin_storage.bin(&format!("bin{}", i)).await?;
+        let kv = KeyValue {
+            key: "k".to_string(),
+            value: "v".to_string(),
+        };
+        assert!(bin.set(&kv).await?);
+        assert_eq!(Some("v".to_string()), bin.get("k").await?);
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_front_waits_for_clock_convergence() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    let tribserver = lab2::new_front_with_keepers(
+        back_addrs.clone(),
+        vec![keeper.to_string()],
+        Limits::default(),
+    )
+    .await?;
+
+    // no view from the keeper yet, so writes are refused
+    assert!(tribserver.sign_up("bob").await.is_err());
+
+    // writes go through once the keeper has verified the backend clocks
+    let mut ready = false;
+    for _ in 0..50 {
+        match tribserver.sign_up("alice").await {
+            Ok(()) => {
+                ready = true;
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+    assert!(ready);
+    tribserver.post("alice", "hello", 0).await?;
+    assert_eq!(1, tribserver.tribs("alice").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_front_without_keepers_takes_writes() -> TestResult<()> {
+    let cluster = Cluster::start(3, 0).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let tribserver = lab2::new_front_with_keepers(back_addrs, vec![], Limits::default()).await?;
+
+    // no keeper will ever report, so writes are not held back for one
+    tribserver.sign_up("alice").await?;
+    tribserver.post("alice", "hello", 0).await?;
+    assert_eq!(1, tribserver.tribs("alice").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_keeper_status() -> TestResult<()> {
+    let mut cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    cluster.shutdown(&back_addrs[2]).await?;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let mut client = KeeperClient::connect(format!("http://{}", keeper)).await?;
+    let status = client.status(StatusRequest {}).await?.into_inner();
+    assert_eq!(3, status.backends.len());
+    for (back, addr) in status.backends.iter().zip(back_addrs.iter()) {
+        assert_eq!(addr, &back.addr);
+    }
+    assert!(status.backends[0].alive && status.backends[0].eligible);
+    assert!(status.backends[1].alive && status.backends[1].eligible);
+    assert!(!status.backends[2].alive && !status.backends[2].eligible);
+    assert!(status.backends[2].failures > 0);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_edit_trib() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.follow("bob", "alice").await?;
+    tribserver.post("alice", "helo", 0).await?;
+    let original = tribserver.tribs("alice").await?[0].clone();
+
+    // unknown clock and too long edits are refused
+    assert!(tribserver
+        .edit_trib("alice", original.clock + 1, "hello")
+        .await
+        .is_err());
+    let too_long = "X".repeat(MAX_TRIB_LEN + 1);
+    assert!(tribserver
+        .edit_trib("alice", original.clock, &too_long)
+        .await
+        .is_err());
+
+    tribserver
+        .edit_trib("alice", original.clock, "hello")
+        .await?;
+    let tribs = tribserver.tribs("alice").await?;
+    assert_eq!(1, tribs.len());
+    assert_eq!("hello", tribs[0].message);
+    assert_eq!(original.clock, tribs[0].clock);
+    assert_eq!(original.time, tribs[0].time);
+    assert_eq!(1, tribs[0].revision);
+
+    let home = tribserver.home("bob").await?;
+    assert_eq!("hello", home[0].message);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_likes() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.sign_up("carol").await?;
+    tribserver.post("alice", "like me", 0).await?;
+    let clock = tribserver.tribs("alice").await?[0].clock;
+
+    assert!(tribserver.like("bob", "alice", clock + 1).await.is_err());
+    assert!(tribserver.like("dave", "alice", clock).await.is_err());
+    tribserver.like("carol", "alice", clock).await?;
+    tribserver.like("bob", "alice", clock).await?;
+    assert!(tribserver.like("bob", "alice", clock).await.is_err());
+    assert_eq!(
+        vec!["bob".to_string(), "carol".to_string()],
+        tribserver.likes("alice", clock).await?
+    );
+
+    tribserver.unlike("bob", "alice", clock).await?;
+    assert!(tribserver.unlike("bob", "alice", clock).await.is_err());
+    assert_eq!(
+        vec!["carol".to_string()],
+        tribserver.likes("alice", clock).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_retrib() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.follow("carol", "bob").await?;
+    tribserver.post("alice", "worth sharing", 0).await?;
+    let original = tribserver.tribs("alice").await?[0].clone();
+
+    assert!(tribserver
+        .retrib("bob", "alice", original.clock + 1)
+        .await
+        .is_err());
+    tribserver.retrib("bob", "alice", original.clock).await?;
+    let retrib = tribserver.tribs("bob").await?[0].clone();
+    assert_eq!("worth sharing", retrib.message);
+    assert!(retrib.clock > original.clock);
+    let expected = TribRef {
+        user: "alice".to_string(),
+        clock: original.clock,
+    };
+    assert_eq!(Some(expected.clone()), retrib.retrib_of);
+
+    // carol sees the retrib through bob, retribbing it refers to alice's trib
+    let home = tribserver.home("carol").await?;
+    assert_eq!(1, home.len());
+    tribserver.retrib("carol", "bob", retrib.clock).await?;
+    assert_eq!(
+        Some(expected),
+        tribserver.tribs("carol").await?[0].retrib_of
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_replies() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.post("alice", "what do you think?", 0).await?;
+    let parent = tribserver.tribs("alice").await?[0].clone();
+    assert!(tribserver.replies("alice", parent.clock).await?.is_empty());
+
+    assert!(tribserver
+        .reply("bob", "alice", parent.clock + 1, "hm")
+        .await
+        .is_err());
+    tribserver
+        .reply("bob", "alice", parent.clock, "great")
+        .await?;
+    tribserver
+        .reply("carol", "alice", parent.clock, "meh")
+        .await?;
+
+    let replies = tribserver.replies("alice", parent.clock).await?;
+    assert_eq!(2, replies.len());
+    let parent_ref = TribRef {
+        user: "alice".to_string(),
+        clock: parent.clock,
+    };
+    for reply in replies.iter() {
+        assert_eq!(Some(parent_ref.clone()), reply.reply_to);
+        assert!(reply.clock > parent.clock);
+    }
+    assert_eq!("great", replies[0].message);
+    assert_eq!("meh", replies[1].message);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_tribs_by_tag() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.post("alice", "learning #Rust today", 0).await?;
+    tribserver.post("bob", "#rust #tokio", 0).await?;
+    tribserver.post("bob", "nothing to see", 0).await?;
+
+    let tagged = tribserver.tribs_by_tag("#rust").await?;
+    assert_eq!(2, tagged.len());
+    assert_eq!(1, tribserver.tribs_by_tag("tokio").await?.len());
+    assert!(tribserver.tribs_by_tag("go").await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_mentions() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver
+        .post("alice", "hey @bob, meet @carol and @nobody", 0)
+        .await?;
+    tribserver.post("carol", "thanks @bob", 0).await?;
+
+    let bob = tribserver.mentions("bob").await?;
+    assert_eq!(2, bob.len());
+    assert_eq!("alice", bob[0].user);
+    assert_eq!("carol", bob[1].user);
+    assert_eq!(1, tribserver.mentions("carol").await?.len());
+    assert!(tribserver.mentions("alice").await?.is_empty());
+    assert!(tribserver.mentions("nobody").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_pages() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.follow("bob", "alice").await?;
+    for i in 0..120 {
+        tribserver.post("alice", &format!("a{}", i), 0).await?;
+    }
+    // archives the older tribs of alice
+    let alice = tribserver.tribs("alice").await?;
+    assert_eq!(MAX_TRIB_FETCH, alice.len());
+    let clock = alice[MAX_TRIB_FETCH - 1].clock;
+    for i in 0..5 {
+        tribserver.post("bob", &format!("b{}", i), clock).await?;
+    }
+
+    let mut seen = Vec::<String>::new();
+    let mut before = u64::MAX;
+    loop {
+        let page = tribserver.tribs_page("alice", before, 50).await?;
+        if page.is_empty() {
+            break;
+        }
+        assert!(page.len() <= 50);
+        before = page[0].clock;
+        let mut messages: Vec<String> = page.iter().map(|t| t.message.clone()).collect();
+        messages.append(&mut seen);
+        seen = messages;
+    }
+    let expected: Vec<String> = (0..120).map(|i| format!("a{}", i)).collect();
+    assert_eq!(expected, seen);
+
+    let first = tribserver.home_page("bob", u64::MAX, 1000).await?;
+    assert_eq!(MAX_TRIB_FETCH, first.len());
+    assert_eq!("b4", first[MAX_TRIB_FETCH - 1].message);
+    let rest = tribserver.home_page("bob", first[0].clock, 1000).await?;
+    assert_eq!(125 - MAX_TRIB_FETCH, rest.len());
+    assert_eq!("a0", rest[0].message);
+    assert!(tribserver.tribs_page("nobody", u64::MAX, 10).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_archived_tribs() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    for i in 0..MAX_TRIB_FETCH + 1 {
+        tribserver.post("alice", &format!("a{}", i), 0).await?;
+    }
+    // archives the first trib of alice
+    let alice = tribserver.tribs("alice").await?;
+    assert_eq!("a1", alice[0].message);
+    let first = tribserver.tribs_page("alice", alice[0].clock, 10).await?;
+    assert_eq!(1, first.len());
+    let clock = first[0].clock;
+
+    // an archived trib can still be liked, replied to and edited
+    tribserver.like("bob", "alice", clock).await?;
+    assert_eq!(vec!["bob"], tribserver.likes("alice", clock).await?);
+    tribserver.reply("bob", "alice", clock, "old news").await?;
+    assert_eq!(1, tribserver.replies("alice", clock).await?.len());
+    tribserver.edit_trib("alice", clock, "a0, edited").await?;
+    let first = tribserver.tribs_page("alice", alice[0].clock, 10).await?;
+    assert_eq!(1, first.len());
+    assert_eq!("a0, edited", first[0].message);
+    assert_eq!(1, first[0].revision);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_follow_counts() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    assert_eq!((0, 0), tribserver.follow_counts("alice").await?);
+    tribserver.follow("alice", "bob").await?;
+    tribserver.follow("alice", "carol").await?;
+    tribserver.follow("bob", "carol").await?;
+    // refused follows and unfollows leave the counts alone
+    assert!(tribserver.follow("alice", "bob").await.is_err());
+    assert!(tribserver.unfollow("carol", "bob").await.is_err());
+
+    assert_eq!((2, 0), tribserver.follow_counts("alice").await?);
+    assert_eq!((1, 1), tribserver.follow_counts("bob").await?);
+    assert_eq!((0, 2), tribserver.follow_counts("carol").await?);
+
+    tribserver.unfollow("alice", "carol").await?;
+    assert_eq!((1, 0), tribserver.follow_counts("alice").await?);
+    assert_eq!((0, 1), tribserver.follow_counts("carol").await?);
+    assert!(tribserver.follow_counts("nobody").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_profiles() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    assert_eq!(Profile::default(), tribserver.get_profile("alice").await?);
+
+    let profile = Profile {
+        display_name: "Alice A.".to_string(),
+        bio: "posts about #rust".to_string(),
+        avatar: "https://example.com/alice.png".to_string(),
+    };
+    tribserver.set_profile("alice", &profile).await?;
+    assert_eq!(profile, tribserver.get_profile("alice").await?);
+
+    let too_long = Profile {
+        bio: "b".repeat(MAX_BIO_LEN + 1),
+        ..profile.clone()
+    };
+    assert!(tribserver.set_profile("alice", &too_long).await.is_err());
+    let bad_avatar = Profile {
+        avatar: "not an avatar".to_string(),
+        ..profile.clone()
+    };
+    assert!(tribserver.set_profile("alice", &bad_avatar).await.is_err());
+    assert_eq!(profile, tribserver.get_profile("alice").await?);
+    assert!(tribserver.set_profile("nobody", &profile).await.is_err());
+    assert!(tribserver.get_profile("nobody").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_block() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.follow("alice", "bob").await?;
+    tribserver.follow("alice", "carol").await?;
+    tribserver.post("bob", "hi from bob", 0).await?;
+    tribserver.post("carol", "hi from carol", 0).await?;
+    assert_eq!(2, tribserver.home("alice").await?.len());
+
+    tribserver.block("alice", "bob").await?;
+    assert!(tribserver.block("alice", "bob").await.is_err());
+    assert!(tribserver.block("alice", "alice").await.is_err());
+    assert!(tribserver.block("alice", "nobody").await.is_err());
+    assert_eq!(vec!["bob".to_string()], tribserver.blocked("alice").await?);
+
+    let home = tribserver.home("alice").await?;
+    assert_eq!(1, home.len());
+    assert_eq!("carol", home[0].user);
+    assert_eq!(1, tribserver.home_page("alice", u64::MAX, 10).await?.len());
+    // bob can't follow alice back
+    assert!(tribserver.follow("bob", "alice").await.is_err());
+    tribserver.follow("carol", "alice").await?;
+
+    tribserver.unblock("alice", "bob").await?;
+    assert!(tribserver.unblock("alice", "bob").await.is_err());
+    assert!(tribserver.blocked("alice").await?.is_empty());
+    assert_eq!(2, tribserver.home("alice").await?.len());
+    tribserver.follow("bob", "alice").await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_mute() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.follow("alice", "bob").await?;
+    tribserver.follow("alice", "carol").await?;
+    tribserver.post("bob", "hi from bob", 0).await?;
+    tribserver.post("carol", "hi from carol", 0).await?;
+
+    tribserver.mute("alice", "bob").await?;
+    assert!(tribserver.mute("alice", "bob").await.is_err());
+    assert!(tribserver.mute("alice", "alice").await.is_err());
+    // the follow edge stays
+    assert!(tribserver.is_following("alice", "bob").await?);
+    let home = tribserver.home("alice").await?;
+    assert_eq!(1, home.len());
+    assert_eq!("carol", home[0].user);
+    // muting doesn't keep anyone from following
+    tribserver.follow("bob", "alice").await?;
+
+    tribserver.unmute("alice", "bob").await?;
+    assert!(tribserver.unmute("alice", "bob").await.is_err());
+    assert_eq!(2, tribserver.home("alice").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_rename_user() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.follow("bob", "alice").await?;
+    tribserver.post("alice", "hello", 0).await?;
+    let clock = tribserver.tribs("alice").await?[0].clock;
+    tribserver.like("bob", "alice", clock).await?;
+
+    assert!(tribserver.rename_user("alice", "bob").await.is_err());
+    assert!(tribserver.rename_user("nobody", "somebody").await.is_err());
+    assert!(tribserver.rename_user("alice", "Not Valid").await.is_err());
+    tribserver.rename_user("alice", "alicia").await?;
+
+    assert!(tribserver.tribs("alice").await.is_err());
+    let tribs = tribserver.tribs("alicia").await?;
+    assert_eq!(1, tribs.len());
+    assert_eq!("alicia", tribs[0].user);
+    assert_eq!(
+        vec!["alicia".to_string()],
+        tribserver.following("bob").await?
+    );
+    assert!(tribserver.is_following("bob", "alicia").await?);
+    assert_eq!("alicia", tribserver.home("bob").await?[0].user);
+    assert_eq!((0, 1), tribserver.follow_counts("alicia").await?);
+    let users = tribserver.list_users().await?;
+    assert!(users.contains(&"alicia".to_string()));
+    assert!(!users.contains(&"alice".to_string()));
+
+    // a second rename keeps the first one resolving
+    tribserver.rename_user("bob", "robert").await?;
+    assert_eq!(
+        vec!["robert".to_string()],
+        tribserver.likes("alicia", clock).await?
+    );
+    tribserver.rename_user("alicia", "ally").await?;
+    assert_eq!(
+        vec!["ally".to_string()],
+        tribserver.following("robert").await?
+    );
+
+    // old names stay taken, except to the user they redirect to
+    assert!(tribserver.sign_up("alice").await.is_err());
+    assert!(tribserver.rename_user("robert", "alicia").await.is_err());
+    tribserver.rename_user("ally", "alice").await?;
+    assert_eq!(
+        vec!["alice".to_string()],
+        tribserver.following("robert").await?
+    );
+    tribserver.rename_user("alice", "ally").await?;
+    tribserver.post("ally", "still here", 0).await?;
+    let tribs = tribserver.tribs("ally").await?;
+    assert_eq!(2, tribs.len());
+    assert_eq!("still here", tribs[1].message);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_home_fan_out() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.follow("bob", "alice").await?;
+    for i in 0..(MAX_TRIB_FETCH + 20) {
+        tribserver.post("alice", &format!("a{}", i), 0).await?;
+    }
+    // the home is trimmed as tribs fan out to it, before bob ever reads it,
+    // and only refers to the tribs
+    let bob_bin = lab2::new_bin_client(back_addrs.clone())
+        .await?
+        .bin("bob")
+        .await?;
+    let stored = bob_bin.list_get("home").await?.0;
+    assert_eq!(MAX_TRIB_FETCH, stored.len());
+    assert!(stored.iter().all(|r| !r.contains("message")));
+    let home = tribserver.home("bob").await?;
+    assert_eq!(MAX_TRIB_FETCH, home.len());
+    assert_eq!(
+        format!("a{}", MAX_TRIB_FETCH + 19),
+        home[MAX_TRIB_FETCH - 1].message
+    );
+    // the trimmed home stays the same
+    assert_eq!(MAX_TRIB_FETCH, tribserver.home("bob").await?.len());
+
+    // edits reach the followers
+    let clock = home[MAX_TRIB_FETCH - 1].clock;
+    tribserver.edit_trib("alice", clock, "edited").await?;
+    let home = tribserver.home("bob").await?;
+    assert_eq!(MAX_TRIB_FETCH, home.len());
+    assert_eq!("edited", home[MAX_TRIB_FETCH - 1].message);
+
+    // unfollowing takes the tribs off the home, following brings them back
+    tribserver.unfollow("bob", "alice").await?;
+    assert!(tribserver.home("bob").await?.is_empty());
+    tribserver.follow("bob", "alice").await?;
+    assert_eq!(MAX_TRIB_FETCH, tribserver.home("bob").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_legacy_follow_log() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.sign_up("carol").await?;
+    tribserver.sign_up("dave").await?;
+
+    // bob followed before followee sets were kept, so all he has is a log,
+    // compacted into a snapshot, with what he did since
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let bob_bin = bin_storage.bin("bob").await?;
+    for entry in [
+        "1::follow::alice",
+        "3::snapshot::carol",
+        "2::unfollow::alice",
+        "3::follow::carol",
+        "4::follow::dave",
+    ] {
+        bob_bin
+            .list_append(&KeyValue {
+                key: "log".to_string(),
+                value: entry.to_string(),
+            })
+            .await?;
+    }
+    assert_eq!(vec!["carol", "dave"], tribserver.following("bob").await?);
+
+    // the first change starts the set off from the log
+    assert!(tribserver.follow("bob", "carol").await.is_err());
+    tribserver.follow("bob", "alice").await?;
+    tribserver.unfollow("bob", "dave").await?;
+    assert_eq!(vec!["alice", "carol"], tribserver.following("bob").await?);
+    assert!(bob_bin.get("followees").await?.is_some());
+
+    // whatever is appended to the log since is no longer read
+    bob_bin
+        .list_append(&KeyValue {
+            key: "log".to_string(),
+            value: "9::unfollow::carol".to_string(),
+        })
+        .await?;
+    assert!(tribserver.is_following("bob", "carol").await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_concurrent_followee_swaps() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let mut fronts: Vec<Arc<dyn Server + Send + Sync>> = vec![];
+    for _ in 0..4 {
+        fronts.push(Arc::from(cluster.front(Limits::default()).await?));
+    }
+    cluster.settled().await?;
+    let users: Vec<String> = (0..20).map(|i| format!("user{}", i)).collect();
+    fronts[0].sign_up("bob").await?;
+    for user in users.iter() {
+        fronts[0].sign_up(user).await?;
+    }
+
+    // the follows race to swap their followee into the set of bob, and the
+    // ones that lose start over from the set that won
+    let mut racing = tokio::task::JoinSet::new();
+    for (i, user) in users.iter().enumerate() {
+        let front = fronts[i % fronts.len()].clone();
+        let user = user.clone();
+        racing.spawn(async move { front.follow("bob", &user).await });
+    }
+    while let Some(r) = racing.join_next().await {
+        r??;
+    }
+
+    // none is lost, and every one was counted
+    let mut expected = users.clone();
+    expected.sort();
+    for front in fronts.iter() {
+        assert_eq!(expected, front.following("bob").await?);
+    }
+    assert_eq!((users.len(), 0), fronts[0].follow_counts("bob").await?);
+    for user in users.iter() {
+        assert_eq!((0, 1), fronts[0].follow_counts(user).await?);
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_trending() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    assert!(tribserver.trending(10).await?.is_empty());
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    tribserver.post("alice", "#rust #go", 0).await?;
+    tribserver.post("bob", "#Rust again", 0).await?;
+    tribserver.post("bob", "just #go", 0).await?;
+    tribserver.post("alice", "more #rust", 0).await?;
+    tribserver.post("alice", "and #zig", 0).await?;
+
+    let trend = |tag: &str, count| Trend {
+        tag: tag.to_string(),
+        count,
+    };
+    assert_eq!(
+        vec![trend("rust", 3), trend("go", 2), trend("zig", 1)],
+        tribserver.trending(10).await?
+    );
+    assert_eq!(vec![trend("rust", 3)], tribserver.trending(1).await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_notifications() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    assert!(tribserver.notifications("alice", 0).await?.is_empty());
+    assert!(tribserver.notifications("carol", 0).await.is_err());
+
+    tribserver.post("alice", "hello", 0).await?;
+    let clock = tribserver.tribs("alice").await?[0].clock;
+    tribserver.follow("bob", "alice").await?;
+    tribserver.post("bob", "hi @alice", 0).await?;
+    tribserver.like("bob", "alice", clock).await?;
+    tribserver.reply("bob", "alice", clock, "welcome").await?;
+    // nobody is notified of what they do themselves
+    tribserver.like("alice", "alice", clock).await?;
+    tribserver.post("alice", "me, @alice", 0).await?;
+
+    let notifications = tribserver.notifications("alice", 0).await?;
+    let kinds: Vec<NotificationKind> = notifications.iter().map(|n| n.kind).collect();
+    assert_eq!(
+        vec![
+            NotificationKind::Follow,
+            NotificationKind::Mention,
+            NotificationKind::Like,
+            NotificationKind::Reply
+        ],
+        kinds
+    );
+    assert!(notifications.iter().all(|n| n.from == "bob" && !n.read));
+    assert_eq!(4, tribserver.unread_count("alice").await?);
+    assert_eq!("alice", notifications[2].trib.as_ref().unwrap().user);
+    assert_eq!(clock, notifications[2].trib.as_ref().unwrap().clock);
+    assert!(tribserver.notifications("bob", 0).await?.is_empty());
+
+    // only the newer ones come after since
+    let since = notifications[1].clock;
+    assert_eq!(2, tribserver.notifications("alice", since).await?.len());
+
+    // the read mark never moves back
+    tribserver.mark_read("alice", since).await?;
+    tribserver.mark_read("alice", 0).await?;
+    let read: Vec<bool> = tribserver
+        .notifications("alice", 0)
+        .await?
+        .iter()
+        .map(|n| n.read)
+        .collect();
+    assert_eq!(vec![true, true, false, false], read);
+    assert_eq!(2, tribserver.unread_count("alice").await?);
+    tribserver.mark_read("alice", u64::MAX).await?;
+    assert_eq!(0, tribserver.unread_count("alice").await?);
+    tribserver.follow("alice", "bob").await?;
+    assert_eq!(1, tribserver.unread_count("bob").await?);
+
+    // renamed users show up under their new name
+    tribserver.rename_user("bob", "robert").await?;
+    let notifications = tribserver.notifications("alice", 0).await?;
+    assert!(notifications.iter().all(|n| n.from == "robert"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_lists() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol", "dave"] {
+        tribserver.sign_up(user).await?;
+    }
+    assert!(tribserver.create_list("alice", "Friends!").await.is_err());
+    assert!(tribserver.create_list("zed", "friends").await.is_err());
+    tribserver.create_list("alice", "friends").await?;
+    assert!(tribserver.create_list("alice", "friends").await.is_err());
+    assert!(tribserver.list_timeline("alice", "work").await.is_err());
+    assert!(tribserver
+        .add_to_list("alice", "work", "bob")
+        .await
+        .is_err());
+    assert!(tribserver
+        .list_timeline("alice", "friends")
+        .await?
+        .is_empty());
+
+    tribserver.add_to_list("alice", "friends", "bob").await?;
+    tribserver.add_to_list("alice", "friends", "carol").await?;
+    assert!(tribserver
+        .add_to_list("alice", "friends", "bob")
+        .await
+        .is_err());
+    assert!(tribserver
+        .add_to_list("alice", "friends", "zed")
+        .await
+        .is_err());
+
+    tribserver.post("bob", "b1", 0).await?;
+    tribserver.post("dave", "d1", 0).await?;
+    tribserver.post("carol", "c1", 0).await?;
+    tribserver.post("alice", "a1", 0).await?;
+    tribserver.post("bob", "b2", 0).await?;
+
+    // only the members show up, merged like the home timeline
+    let messages = |tribs: Vec<Arc<Trib>>| -> Vec<String> {
+        tribs.iter().map(|t| t.message.to_string()).collect()
+    };
+    assert_eq!(
+        vec!["b1", "c1", "b2"],
+        messages(tribserver.list_timeline("alice", "friends").await?)
+    );
+
+    // muted members are left out, renamed ones follow along
+    tribserver.mute("alice", "carol").await?;
+    tribserver.rename_user("bob", "robert").await?;
+    let timeline = tribserver.list_timeline("alice", "friends").await?;
+    assert_eq!(vec!["b1", "b2"], messages(timeline.clone()));
+    assert!(timeline.iter().all(|t| t.user == "robert"));
+    assert!(tribserver
+        .add_to_list("alice", "friends", "robert")
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_drafts() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    assert!(tribserver.save_draft("bob", "hi").await.is_err());
+    assert!(tribserver
+        .save_draft("alice", &"a".repeat(MAX_TRIB_LEN + 1))
+        .await
+        .is_err());
+    assert!(tribserver.list_drafts("alice").await?.is_empty());
+
+    let first = tribserver.save_draft("alice", "first").await?;
+    let second = tribserver.save_draft("alice", "second").await?;
+    let drafts = tribserver.list_drafts("alice").await?;
+    assert_eq!(
+        vec![first, second],
+        drafts.iter().map(|d| d.id).collect::<Vec<u64>>()
+    );
+    assert_eq!("first", drafts[0].message);
+
+    // drafts stay off the timelines
+    assert!(tribserver.tribs("alice").await?.is_empty());
+    assert!(tribserver.home("alice").await?.is_empty());
+
+    tribserver.delete_draft("alice", first).await?;
+    assert!(tribserver.delete_draft("alice", first).await.is_err());
+    let drafts = tribserver.list_drafts("alice").await?;
+    assert_eq!(1, drafts.len());
+    assert_eq!("second", drafts[0].message);
+
+    // but their count is bounded
+    for i in 1..MAX_DRAFTS {
+        tribserver.save_draft("alice", &format!("d{}", i)).await?;
+    }
+    assert!(tribserver
+        .save_draft("alice", "one too many")
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_scheduled_posts() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    assert!(tribserver.schedule_post("bob", "hi", 0).await.is_err());
+    assert!(tribserver
+        .schedule_post("alice", &"a".repeat(MAX_TRIB_LEN + 1), 0)
+        .await
+        .is_err());
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    tribserver.post("alice", "now", 0).await?;
+    tribserver.schedule_post("alice", "later", now + 2).await?;
+    tribserver
+        .schedule_post("alice", "much later", now + 3600)
+        .await?;
+    assert_eq!(1, tribserver.tribs("alice").await?.len());
+
+    // the keeper posts it once it is due
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let tribs = tribserver.tribs("alice").await?;
+    assert_eq!(
+        vec!["now", "later"],
+        tribs
+            .iter()
+            .map(|t| t.message.as_str())
+            .collect::<Vec<&str>>()
+    );
+    assert!(tribs[1].clock > tribs[0].clock);
+    assert_eq!(2, tribserver.home("alice").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_attachments() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    let image = Attachment {
+        url: "https://example.com/cat.png".to_string(),
+        hash: "ab".repeat(32),
+        mime_type: "image/png".to_string(),
+    };
+    let broken = Attachment {
+        url: "not a url".to_string(),
+        ..image.clone()
+    };
+    assert!(tribserver
+        .post_with_attachments("alice", "cat", 0, &[broken])
+        .await
+        .is_err());
+    assert!(tribserver
+        .post_with_attachments(
+            "alice",
+            "cats",
+            0,
+            &vec![image.clone(); MAX_ATTACHMENTS + 1]
+        )
+        .await
+        .is_err());
+
+    // attachments leave the message length alone
+    let message = "a".repeat(MAX_TRIB_LEN);
+    tribserver
+        .post_with_attachments("alice", &message, 0, std::slice::from_ref(&image))
+        .await?;
+    tribserver.post("alice", "plain", 0).await?;
+    let tribs = tribserver.tribs("alice").await?;
+    assert_eq!(vec![image.clone()], tribs[0].attachments);
+    assert!(tribs[1].attachments.is_empty());
+
+    // retribs carry the attachments along
+    tribserver.retrib("bob", "alice", tribs[0].clock).await?;
+    assert_eq!(vec![image], tribserver.tribs("bob").await?[0].attachments);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_moderation() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver.follow("alice", "bob").await?;
+    tribserver.post("bob", "spam", 0).await?;
+    tribserver.post("bob", "more spam", 0).await?;
+    let spam = tribserver.tribs("bob").await?[0].clock;
+
+    // deleted tribs are gone from every timeline
+    tribserver.admin_delete_trib("bob", spam).await?;
+    assert!(tribserver.admin_delete_trib("bob", spam).await.is_err());
+    assert_eq!(1, tribserver.tribs("bob").await?.len());
+    assert_eq!(1, tribserver.home("alice").await?.len());
+
+    // banned users can neither post nor follow, and their tribs are hidden
+    assert!(tribserver.admin_ban_user("dave").await.is_err());
+    tribserver.admin_ban_user("bob").await?;
+    assert!(tribserver.admin_ban_user("bob").await.is_err());
+    assert!(tribserver.post("bob", "even more spam", 0).await.is_err());
+    assert!(tribserver.follow("bob", "carol").await.is_err());
+    assert!(tribserver.home("alice").await?.is_empty());
+
+    tribserver.admin_unban("bob").await?;
+    assert!(tribserver.admin_unban("bob").await.is_err());
+    tribserver.post("bob", "sorry", 0).await?;
+    assert_eq!(2, tribserver.home("alice").await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_export_user() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    assert!(tribserver.export_user("alice").await.is_err());
+    for user in ["alice", "bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+    tribserver
+        .set_profile(
+            "alice",
+            &Profile {
+                display_name: "Alice".to_string(),
+                ..Profile::default()
+            },
+        )
+        .await?;
+    tribserver.follow("alice", "carol").await?;
+    tribserver.follow("alice", "bob").await?;
+    tribserver.post("alice", "mine", 0).await?;
+    tribserver.post("bob", "liked", 0).await?;
+    tribserver.post("bob", "unliked", 0).await?;
+    let bob_tribs = tribserver.tribs("bob").await?;
+    tribserver.like("alice", "bob", bob_tribs[0].clock).await?;
+    tribserver.like("alice", "bob", bob_tribs[1].clock).await?;
+    tribserver
+        .unlike("alice", "bob", bob_tribs[1].clock)
+        .await?;
+
+    let export = tribserver.export_user("alice").await?;
+    assert_eq!("alice", export.user);
+    assert_eq!("Alice", export.profile.display_name);
+    assert_eq!(
+        vec!["mine"],
+        export
+            .tribs
+            .iter()
+            .map(|t| t.message.as_str())
+            .collect::<Vec<&str>>()
+    );
+    assert_eq!(
+        vec!["bob".to_string(), "carol".to_string()],
+        export.following
+    );
+    assert_eq!(
+        vec![TribRef {
+            user: "bob".to_string(),
+            clock: bob_tribs[0].clock,
+        }],
+        export.likes
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_import() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    let source = tribbler::ref_impl::RefServer::new();
+    for user in ["alice", "bob"] {
+        source.sign_up(user).await?;
+    }
+    source
+        .set_profile(
+            "alice",
+            &Profile {
+                display_name: "Alice".to_string(),
+                ..Profile::default()
+            },
+        )
+        .await?;
+    source.post("alice", "first", 0).await?;
+    source.post("bob", "second", 0).await?;
+    source.post("alice", "third", 0).await?;
+    source.follow("alice", "bob").await?;
+    let bob_tribs = source.tribs("bob").await?;
+    source.like("alice", "bob", bob_tribs[0].clock).await?;
+    let exports = vec![
+        source.export_user("alice").await?,
+        source.export_user("bob").await?,
+    ];
+
+    tribserver.import(&exports).await?;
+    assert_eq!("Alice", tribserver.get_profile("alice").await?.display_name);
+    assert_eq!(
+        source
+            .tribs("alice")
+            .await?
+            .iter()
+            .map(|t| (t.message.clone(), t.clock))
+            .collect::<Vec<(String, u64)>>(),
+        tribserver
+            .tribs("alice")
+            .await?
+            .iter()
+            .map(|t| (t.message.clone(), t.clock))
+            .collect::<Vec<(String, u64)>>()
+    );
+    assert!(tribserver.is_following("alice", "bob").await?);
+    assert_eq!(3, tribserver.home("alice").await?.len());
+    assert_eq!(
+        vec!["alice".to_string()],
+        tribserver.likes("bob", bob_tribs[0].clock).await?
+    );
+
+    // new posts still sort after the imported ones
+    tribserver.post("alice", "fourth", 0).await?;
+    let home = tribserver.home("alice").await?;
+    assert_eq!("fourth", home[home.len() - 1].message);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_import_one_user() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    let source = tribbler::ref_impl::RefServer::new();
+    for user in ["alice", "bob"] {
+        source.sign_up(user).await?;
+    }
+    source.post("bob", "first", 0).await?;
+    let bob_trib = source.tribs("bob").await?[0].clock;
+    source.reply("alice", "bob", bob_trib, "second").await?;
+    source.follow("alice", "bob").await?;
+    source.like("alice", "bob", bob_trib).await?;
+
+    // bob isn't part of the import, so what alice did with him is skipped
+    let exports = vec![source.export_user("alice").await?];
+    tribserver.import(&exports).await?;
+    assert_eq!(
+        vec!["second"],
+        tribserver
+            .tribs("alice")
+            .await?
+            .iter()
+            .map(|t| t.message.as_str())
+            .collect::<Vec<&str>>()
+    );
+    assert!(tribserver.following("alice").await?.is_empty());
+    assert!(!tribserver.signed_up("bob").await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_import_twice() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    let source = tribbler::ref_impl::RefServer::new();
+    for user in ["alice", "bob"] {
+        source.sign_up(user).await?;
+    }
+    source.post("bob", "first", 0).await?;
+    let bob_trib = source.tribs("bob").await?[0].clock;
+    source.reply("alice", "bob", bob_trib, "second").await?;
+    source.follow("alice", "bob").await?;
+    source.like("alice", "bob", bob_trib).await?;
+    let exports = vec![
+        source.export_user("alice").await?,
+        source.export_user("bob").await?,
+    ];
+
+    // importing again, as a restarted front-end does, changes nothing
+    tribserver.import(&exports).await?;
+    tribserver.import(&exports).await?;
+    assert_eq!(1, tribserver.tribs("alice").await?.len());
+    assert_eq!(1, tribserver.tribs("bob").await?.len());
+    assert_eq!(2, tribserver.home("alice").await?.len());
+    assert_eq!(
+        vec!["bob".to_string()],
+        tribserver.following("alice").await?
+    );
+    assert_eq!(
+        vec!["alice".to_string()],
+        tribserver.likes("bob", bob_trib).await?
+    );
+    assert_eq!(1, tribserver.replies("bob", bob_trib).await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_limits() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let keeper = cluster.keepers()[0].clone();
+    let limits = Limits {
+        max_trib_len: 10,
+        max_trib_fetch: 3,
+        max_following: 1,
+        min_list_user: 2,
+    };
+    let tribserver =
+        lab2::new_front_with_keepers(back_addrs.clone(), vec![keeper.to_string()], limits).await?;
+
+    let mut ready = false;
+    for _ in 0..50 {
+        match tribserver.sign_up("alice").await {
+            Ok(()) => {
+                ready = true;
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+    assert!(ready);
+    for user in ["bob", "carol"] {
+        tribserver.sign_up(user).await?;
+    }
+
+    assert!(tribserver.post("alice", &"a".repeat(11), 0).await.is_err());
+    for i in 0..5 {
+        tribserver.post("alice", &format!("a{}", i), 0).await?;
+    }
+    assert_eq!(3, tribserver.tribs("alice").await?.len());
+    tribserver.follow("alice", "bob").await?;
+    assert!(tribserver.follow("alice", "carol").await.is_err());
+    assert_eq!(2, tribserver.list_users().await?.len());
+
+    // the reference server enforces the same limits
+    let refserver = tribbler::ref_impl::RefServer::with_limits(limits);
+    for user in ["alice", "bob", "carol"] {
+        refserver.sign_up(user).await?;
+    }
+    assert!(refserver.post("alice", &"a".repeat(11), 0).await.is_err());
+    for i in 0..5 {
+        refserver.post("alice", &format!("a{}", i), 0).await?;
+    }
+    assert_eq!(3, refserver.tribs("alice").await?.len());
+    refserver.follow("alice", "bob").await?;
+    assert!(refserver.follow("alice", "carol").await.is_err());
+    assert_eq!(2, refserver.list_users().await?.len());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_clock() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    let clock = tribserver.clock().await?;
+    assert!(tribserver.clock().await? > clock);
+
+    // a post given the clock sorts after everything posted before it
+    let clock = tribserver.clock().await?;
+    tribserver.post("alice", "first", clock).await?;
+    assert!(tribserver.tribs("alice").await?[0].clock >= clock);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_mutuals() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    assert!(tribserver.mutuals("alice").await.is_err());
+    for user in ["alice", "bob", "carol", "dave"] {
+        tribserver.sign_up(user).await?;
+    }
+    assert!(tribserver.mutuals("alice").await?.is_empty());
+
+    tribserver.follow("alice", "bob").await?;
+    tribserver.follow("bob", "alice").await?;
+    tribserver.follow("alice", "carol").await?;
+    tribserver.follow("dave", "alice").await?;
+    tribserver.follow("carol", "alice").await?;
+    tribserver.follow("alice", "dave").await?;
+    assert_eq!(
+        vec!["bob", "carol", "dave"],
+        tribserver.mutuals("alice").await?
+    );
+    assert_eq!(vec!["alice"], tribserver.mutuals("bob").await?);
+
+    tribserver.unfollow("alice", "carol").await?;
+    tribserver.unfollow("dave", "alice").await?;
+    assert_eq!(vec!["bob"], tribserver.mutuals("alice").await?);
+    assert!(tribserver.mutuals("carol").await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_last_active() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    assert!(tribserver.last_active("alice").await.is_err());
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    assert_eq!(None, tribserver.last_active("alice").await?);
+
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    tribserver.post("alice", "hello", 0).await?;
+    let after = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let active = tribserver.last_active("alice").await?.unwrap();
+    assert!(before <= active && active <= after);
+
+    // reading does not count as acting, nor does a refused call, following
+    // does
+    tribserver.home("bob").await?;
+    assert_eq!(None, tribserver.last_active("bob").await?);
+    assert!(tribserver.follow("bob", "bob").await.is_err());
+    assert!(tribserver.follow("bob", "carol").await.is_err());
+    assert_eq!(None, tribserver.last_active("bob").await?);
+    tribserver.follow("bob", "alice").await?;
+    assert!(tribserver.last_active("bob").await?.is_some());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_typed_errors() -> TestResult<()> {
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+    assert!(matches!(
+        tribserver.sign_up("alice").await,
+        Err(TribblerError::UsernameTaken(user)) if user == "alice"
+    ));
+    assert!(matches!(
+        tribserver.sign_up("Alice").await,
+        Err(TribblerError::InvalidUsername(_))
+    ));
+    assert!(matches!(
+        tribserver.tribs("carol").await,
+        Err(TribblerError::UserDoesNotExist(user)) if user == "carol"
+    ));
+    assert!(matches!(
+        tribserver
+            .post("alice", &"a".repeat(MAX_TRIB_LEN + 1), 0)
+            .await,
+        Err(TribblerError::TribTooLong)
+    ));
+    tribserver.follow("alice", "bob").await?;
+    assert!(matches!(
+        tribserver.follow("alice", "bob").await,
+        Err(TribblerError::AlreadyFollowing(_, _))
+    ));
+    assert!(matches!(
+        tribserver.like("alice", "bob", 42).await,
+        Err(TribblerError::TribNotFound(user, 42)) if user == "bob"
+    ));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[allow(unused_variables)]
+async fn test_passwords() -> TestResult<()> {
+    use lab2::Auth;
+    let cluster = Cluster::start(3, 1).await?;
+    let back_addrs = cluster.backs().to_vec();
+    let bin_storage = lab2::new_bin_client(back_addrs.clone()).await?;
+    let tribserver = lab2::new_front(bin_storage).await?;
+    let auth = lab2::BinAuth {
+        bin_storage: lab2::new_bin_client(back_addrs.clone()).await?,
+    };
+
+    tribserver.sign_up("alice").await?;
+    tribserver.sign_up("bob").await?;
+
+    // a user without a password can't sign in with one
+    assert!(!auth.check_password("bob", "").await?);
+    assert!(!auth.check_password("bob", "hunter2").await?);
+    assert!(matches!(
+        auth.set_password("bob", "").await,
+        Err(TribblerError::InvalidPassword)
+    ));
+    assert!(matches!(
+        auth.check_password("Bob!", "x").await,
+        Err(TribblerError::InvalidUsername(_))
+    ));
+
+    auth.set_password("alice", "hunter2").await?;
+    assert!(auth.check_password("alice", "hunter2").await?);
+    assert!(!auth.check_password("alice", "hunter3").await?);
+    assert!(!auth.check_password("alice", "").await?);
+
+    // only the hash is kept
+    let alice_bin = lab2::new_bin_client(back_addrs.clone())
+        .await?
+        .bin("alice")
+        .await?;
+    let stored = alice_bin.get("password").await?.unwrap();
+    assert!(stored.starts_with("$argon2"));
+    assert!(!stored.contains("hunter2"));
+
+    auth.set_password("alice", "correct horse").await?;
+    assert!(!auth.check_password("alice", "hunter2").await?);
+    assert!(auth.check_password("alice", "correct horse").await?);
+
+    // the password moves along with a renamed user
+    tribserver.rename_user("alice", "alicia").await?;
+    assert!(auth.check_password("alicia", "correct horse").await?);
+    assert!(!auth.check_password("alicia", "hunter2").await?);
+
+    // a password is only claimed for a user who has none, and taking it back
+    // leaves one set since alone
+    assert_eq!(None, auth.claim_password("alicia", "mine now").await?);
+    assert!(auth.check_password("alicia", "correct horse").await?);
+    let hashed = auth.claim_password("carol", "hunter2").await?.unwrap();
+    assert!(auth.check_password("carol", "hunter2").await?);
+    auth.release_password("carol", &hashed).await?;
+    assert!(!auth.check_password("carol", "hunter2").await?);
+    let hashed = auth.claim_password("carol", "hunter2").await?.unwrap();
+    auth.set_password("carol", "hunter3").await?;
+    auth.release_password("carol", &hashed).await?;
+    assert!(auth.check_password("carol", "hunter3").await?);
+
+    Ok(())
+}
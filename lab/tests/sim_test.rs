@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use lab::sim::Sim;
+use rand::Rng;
+use tribbler::config::{KeeperTuning, Limits};
+use tribbler::err::TribResult;
+
+// how often the tests look at the cluster
+const TICK: Duration = Duration::from_millis(100);
+
+// advances the simulation a tick at a time until the keeper has `back` alive
+// as `alive` says, returning how long that took
+async fn until_alive(sim: &Sim, back: &str, alive: bool) -> Duration {
+    let start = sim.elapsed();
+    loop {
+        let status = sim.status().await;
+        if status
+            .backends
+            .iter()
+            .any(|b| b.addr == back && b.alive == alive)
+        {
+            return sim.elapsed() - start;
+        }
+        sim.advance(TICK).await;
+    }
+}
+
+#[test]
+fn test_keeper_notices_a_dead_backend() -> TribResult<()> {
+    Sim::run(1, |mut sim| async move {
+        let tuning = KeeperTuning::default();
+        sim.start_backs(3).await?;
+        sim.start_keeper(tuning.clone()).await?;
+        sim.advance(Duration::from_secs(2)).await;
+        let status = sim.status().await;
+        assert!(status.backends.iter().all(|b| b.alive && b.eligible));
+        assert!(status.consistent);
+
+        let back = sim.pick_back();
+        sim.kill(&back).await;
+        let noticed = until_alive(&sim, &back, false).await;
+        assert!(noticed <= Duration::from_millis(tuning.sync_interval_ms) + TICK);
+        let status = sim.status().await;
+        let dead = status.backends.iter().find(|b| b.addr == back).unwrap();
+        assert!(!dead.eligible);
+        assert_eq!(None, dead.clock);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_revived_backend_is_held_down() -> TribResult<()> {
+    Sim::run(2, |mut sim| async move {
+        let tuning = KeeperTuning::default();
+        sim.start_backs(3).await?;
+        sim.start_keeper(tuning.clone()).await?;
+        sim.advance(Duration::from_secs(2)).await;
+
+        let back = sim.pick_back();
+        sim.kill(&back).await;
+        until_alive(&sim, &back, false).await;
+        sim.revive_back(&back).await?;
+
+        // a dead backend is probed again once its backoff runs out
+        let revived = until_alive(&sim, &back, true).await;
+        assert!(revived <= Duration::from_millis(tuning.max_retry_backoff_ms) + TICK);
+        let status = sim.status().await;
+        let held = status.backends.iter().find(|b| b.addr == back).unwrap();
+        assert!(!held.eligible);
+        assert_eq!(tuning.stable_window_ms, held.hold_down_ms);
+
+        // and takes writes once it stayed up through the hold-down
+        let hold_down = Duration::from_millis(held.hold_down_ms);
+        sim.advance(hold_down + Duration::from_millis(tuning.sync_interval_ms))
+            .await;
+        let status = sim.status().await;
+        assert!(status.backends.iter().all(|b| b.alive && b.eligible));
+        Ok(())
+    })
+}
+
+#[test]
+fn test_front_serves_through_the_keeper() -> TribResult<()> {
+    Sim::run(3, |mut sim| async move {
+        sim.start_backs(3).await?;
+        sim.start_keeper(KeeperTuning::default()).await?;
+        let front = sim.front(Limits::default()).await?;
+        sim.advance(Duration::from_secs(2)).await;
+
+        front.sign_up("alice").await?;
+        front.sign_up("bob").await?;
+        front.follow("alice", "bob").await?;
+        front.post("bob", "hello", 0).await?;
+        let tribs = front.home("alice").await?;
+        assert_eq!(1, tribs.len());
+        assert_eq!("hello", tribs[0].message);
+        Ok(())
+    })
+}
+
+// kills and revives backends at times the seed picks, returning every change
+// of the keeper's view of a backend, with the virtual time it was seen at
+fn trace(seed: u64) -> TribResult<Vec<(Duration, String, bool, bool)>> {
+    Sim::run(seed, |mut sim| async move {
+        sim.start_backs(4).await?;
+        sim.start_keeper(KeeperTuning::default()).await?;
+        let mut seen = vec![];
+        let mut last = vec![];
+        for _ in 0..6 {
+            let back = sim.pick_back();
+            sim.kill(&back).await;
+            let down = sim.rng().gen_range(1..5);
+            sim.advance(Duration::from_secs(down)).await;
+            sim.revive_back(&back).await?;
+            for _ in 0..20 {
+                sim.advance(TICK * 5).await;
+                let now: Vec<(String, bool, bool)> = sim
+                    .status()
+                    .await
+                    .backends
+                    .into_iter()
+                    .map(|b| (b.addr, b.alive, b.eligible))
+                    .collect();
+                for (addr, alive, eligible) in now.iter() {
+                    if !last.contains(&(addr.clone(), *alive, *eligible)) {
+                        seen.push((sim.elapsed(), addr.clone(), *alive, *eligible));
+                    }
+                }
+                last = now;
+            }
+        }
+        Ok(seen)
+    })
+}
+
+#[test]
+fn test_runs_of_a_seed_are_the_same() -> TribResult<()> {
+    let first = trace(42)?;
+    assert!(first.iter().any(|(_, _, alive, _)| !alive));
+    assert_eq!(first, trace(42)?);
+    Ok(())
+}
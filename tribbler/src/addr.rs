@@ -1,29 +1,130 @@
 //! this module provides functions for getting interacting with the local
-//! system's set of network interfaces.
-use crate::err::TribResult;
+//! system's set of network interfaces, and for telling how an address in a
+//! config is to be reached.
+use crate::err::{TribResult, TribblerError};
 use std::net::{IpAddr, ToSocketAddrs};
+use std::path::PathBuf;
+
+/// the scheme of an address served over plain HTTP/2, which an address with
+/// no scheme is served over too
+pub const HTTP: &str = "http://";
+
+/// the scheme of an address served over TLS
+pub const HTTPS: &str = "https://";
+
+/// the scheme of an address naming a unix domain socket, followed by the
+/// path of the socket, as in `unix:///run/trib/back-0.sock`
+pub const UNIX: &str = "unix://";
+
+/// the scheme of an address naming a server in the same process, reached
+/// over in-memory pipes rather than the network, as in `mem://back-0`; the
+/// simulated clusters of the tests serve on such addresses
+pub const MEM: &str = "mem://";
+
+/// How an address is reached, going by its scheme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transport {
+    /// plain HTTP/2 to `<host>:<port>`
+    Tcp(String),
+    /// HTTP/2 over TLS to `<host>:<port>`
+    Tls(String),
+    /// plain HTTP/2 over the unix domain socket at the path
+    Unix(PathBuf),
+    /// plain HTTP/2 over in-memory pipes to the server of the name
+    Mem(String),
+}
+
+impl Transport {
+    /// Parses an address of the form `<host>:<port>`, `http://<host>:<port>`,
+    /// `https://<host>:<port>`, `unix://<path>` or `mem://<name>`; any other
+    /// scheme is an error.
+    ///
+    /// ```rust
+    /// use tribbler::addr::Transport;
+    /// assert_eq!(
+    ///     Transport::Tls("localhost:3000".to_string()),
+    ///     Transport::parse("https://localhost:3000").unwrap()
+    /// );
+    /// assert!(Transport::parse("ftp://localhost:3000").is_err());
+    /// ```
+    pub fn parse(addr: &str) -> TribResult<Transport> {
+        if let Some(path) = addr.strip_prefix(UNIX) {
+            if path.is_empty() {
+                return Err(TribblerError::Unknown(format!("{} names no socket", addr)));
+            }
+            return Ok(Transport::Unix(PathBuf::from(path)));
+        }
+        if let Some(name) = addr.strip_prefix(MEM) {
+            if name.is_empty() {
+                return Err(TribblerError::Unknown(format!("{} names no server", addr)));
+            }
+            return Ok(Transport::Mem(name.to_string()));
+        }
+        if let Some(host_port) = addr.strip_prefix(HTTPS) {
+            return Ok(Transport::Tls(host_port.trim_end_matches('/').to_string()));
+        }
+        let host_port = addr.strip_prefix(HTTP).unwrap_or(addr);
+        if let Some((scheme, _)) = host_port.split_once("://") {
+            return Err(TribblerError::Unknown(format!(
+                "{} has the unknown scheme {}://; use {}, {}, {} or {}",
+                addr, scheme, HTTP, HTTPS, UNIX, MEM
+            )));
+        }
+        Ok(Transport::Tcp(host_port.trim_end_matches('/').to_string()))
+    }
+
+    /// The URI the address is dialed with.
+    pub fn uri(&self) -> String {
+        match self {
+            Transport::Tcp(host_port) => format!("{}{}", HTTP, host_port),
+            Transport::Tls(host_port) => format!("{}{}", HTTPS, host_port),
+            Transport::Unix(path) => format!("{}{}", UNIX, path.display()),
+            Transport::Mem(name) => format!("{}{}", MEM, name),
+        }
+    }
+}
+
+/// Returns the URI an address in a config is dialed with: an address with no
+/// scheme is reached over plain HTTP/2, and one with a scheme as it says.
+///
+/// ```rust
+/// use tribbler::addr::uri;
+/// assert_eq!("http://localhost:3000", uri("localhost:3000"));
+/// assert_eq!("https://localhost:3000", uri("https://localhost:3000"));
+/// assert_eq!("unix:///tmp/back.sock", uri("unix:///tmp/back.sock"));
+/// assert_eq!("mem://back-0", uri("mem://back-0"));
+/// ```
+pub fn uri(addr: &str) -> String {
+    match Transport::parse(addr) {
+        Ok(transport) => transport.uri(),
+        Err(_) => addr.to_string(), // left for dialing it to fail on
+    }
+}
 
 /// returns a list of the IP addresses on the current system's network
 /// interfaces
 pub fn get_local_addrs() -> TribResult<Vec<IpAddr>> {
-    let r = local_ip_address::list_afinet_netifas()?;
+    let r = local_ip_address::list_afinet_netifas().map_err(|e| {
+        TribblerError::Unknown(format!("cannot list the network interfaces: {}", e))
+    })?;
     Ok(r.iter().map(|x| x.1).collect())
 }
 
 /// checks if the address provided in `addr` resolves to an IP address which is
-/// currently served by one of the operating system's network interfaces.
+/// currently served by one of the operating system's network interfaces. The
+/// scheme of the address, if any, is left out; a unix domain socket, or a
+/// server in the same process, is always local.
 pub fn check(addr: &str) -> TribResult<bool> {
-    let addrs = addr.to_socket_addrs()?;
+    let addrs = match Transport::parse(addr)? {
+        Transport::Tcp(host_port) | Transport::Tls(host_port) => host_port.to_socket_addrs()?,
+        Transport::Unix(_) | Transport::Mem(_) => return Ok(true),
+    };
     let local_addrs = get_local_addrs()?;
-    Ok(local_addrs
-        .iter()
-        .position(|&x| {
-            addrs
-                .clone()
-                .map(|y| y.to_string().starts_with(&x.to_string()))
-                .all(|i| i)
-        })
-        .map_or(false, |_| true))
+    Ok(local_addrs.iter().any(|&x| {
+        addrs
+            .clone()
+            .all(|y| y.to_string().starts_with(&x.to_string()))
+    }))
 }
 
 /// module used to generate random ports to use in network addresses
@@ -10,11 +10,79 @@ use std::time::SystemTime;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Receiver;
 
-use crate::err::TribResult;
+use crate::err::{TribResult, TribblerError};
 use crate::storage::Storage;
+use crate::trib::{MAX_FOLLOWING, MAX_TRIB_FETCH, MAX_TRIB_LEN, MIN_LIST_USER};
+
+#[cfg(feature = "discovery")]
+pub mod discovery;
 
 pub const DEFAULT_CONFIG_LOCATION: &str = "bins.json";
 
+/// The formats a [Config] file can be kept in, told apart by the extension
+/// of its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// The format of the file at `location`: TOML for `.toml`, YAML for
+    /// `.yaml` or `.yml`, and JSON for anything else.
+    pub fn of(location: &str) -> ConfigFormat {
+        let ext = std::path::Path::new(location)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// Parses a [Config] kept in this format. The `limits` and `backup` of
+    /// a config written before the role sections are taken as those of the
+    /// `front` and `keeper` sections.
+    pub fn parse(self, contents: &[u8]) -> TribResult<Config> {
+        let mut value: serde_json::Value = match self {
+            ConfigFormat::Json => serde_json::from_slice(contents)?,
+            ConfigFormat::Toml => toml::from_str(std::str::from_utf8(contents)?)?,
+            ConfigFormat::Yaml => serde_yaml::from_slice(contents)?,
+        };
+        if let Some(fields) = value.as_object_mut() {
+            for (field, section) in [("limits", "front"), ("backup", "keeper")] {
+                let moved = match fields.remove(field) {
+                    Some(moved) => moved,
+                    None => continue,
+                };
+                let section = fields
+                    .entry(section)
+                    .or_insert_with(|| serde_json::Value::Object(Default::default()));
+                if let Some(section) = section.as_object_mut() {
+                    section.entry(field).or_insert(moved);
+                }
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Renders a [Config] in this format, ending in a newline.
+    pub fn render(self, config: &Config) -> TribResult<String> {
+        let mut contents = match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(config)?,
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+        };
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        Ok(contents)
+    }
+}
+
 /// a struct which represents the configuration for a particular storage backend
 pub struct BackConfig {
     /// the address `<host>:<port>` combination to serve on
@@ -56,6 +124,12 @@ pub struct KeeperConfig {
     pub this: usize,
     /// Non zero incarnation identifier
     pub id: u128,
+    /// Knobs controlling how the keeper reacts to backend failures.
+    pub tuning: KeeperTuning,
+    /// Where the bins are kept, which the keeper lays the hash slots out by.
+    pub placement: Placement,
+    /// When present, the keeper periodically snapshots every backend.
+    pub backup: Option<BackupConfig>,
     /// Send a value when the keeper is ready. The distributed key-value
     /// service should be ready to serve when *any* of the keepers is
     /// ready.
@@ -66,17 +140,297 @@ pub struct KeeperConfig {
     pub shutdown: Option<Receiver<()>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+/// Tunable parameters for the keeper's failure handling, read from the
+/// `keeper` section of a config (see [KeeperSection]). Parameters left out
+/// of the section keep their defaults.
+pub struct KeeperTuning {
+    /// How long (in milliseconds) the keeper waits between two rounds of
+    /// probing the backends and syncing their clocks.
+    pub sync_interval_ms: u64,
+    /// How long (in milliseconds) a recovered backend must stay up before the
+    /// keeper puts it back into the write path. This is also the initial
+    /// hold-down applied to a backend that starts flapping.
+    pub stable_window_ms: u64,
+    /// Upper bound (in milliseconds) for the hold-down of a backend which
+    /// keeps transitioning between alive and dead. The hold-down doubles on
+    /// every flap until it reaches this value.
+    pub max_hold_down_ms: u64,
+    /// How long (in milliseconds) the keeper waits for a backend to answer a
+    /// clock probe, its heartbeat, before counting it as a failure.
+    #[serde(alias = "heartbeat_timeout_ms")]
+    pub probe_timeout_ms: u64,
+    /// How many probes of a backend have to fail in a row before the keeper
+    /// counts it as down and routes around it.
+    pub failure_threshold: u32,
+    /// Delay (in milliseconds) before re-probing a backend which failed a
+    /// probe. The delay doubles on every consecutive failure.
+    pub retry_backoff_ms: u64,
+    /// Upper bound (in milliseconds) for the delay between probes of a
+    /// backend which keeps failing.
+    pub max_retry_backoff_ms: u64,
+}
+
+impl Default for KeeperTuning {
+    fn default() -> Self {
+        KeeperTuning {
+            sync_interval_ms: 1_000,
+            stable_window_ms: 3_000,
+            max_hold_down_ms: 60_000,
+            probe_timeout_ms: 1_000,
+            failure_threshold: 1,
+            retry_backoff_ms: 1_000,
+            max_retry_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl KeeperTuning {
+    /// Checks that a keeper can run with these parameters.
+    pub fn check(&self) -> TribResult<()> {
+        let problem = if self.sync_interval_ms == 0 {
+            "the sync interval has to be at least 1 ms".to_string()
+        } else if self.failure_threshold == 0 {
+            "the failure threshold has to be at least 1".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(TribblerError::InvalidConfig(format!("keeper: {}", problem)))
+    }
+}
+
+/// The most hash slots a backend may own.
+pub const MAX_VIRTUAL_NODES: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+/// How many of the replicas of a bin have to take a write for it to succeed.
+pub enum Consistency {
+    /// Any one replica.
+    One,
+    /// More than half of the replicas.
+    #[default]
+    Quorum,
+    /// Every replica.
+    All,
+}
+
+impl Consistency {
+    /// How many of `replicas` replicas have to take a write.
+    pub fn acks(self, replicas: usize) -> usize {
+        match self {
+            Consistency::One => 1,
+            Consistency::Quorum => replicas / 2 + 1,
+            Consistency::All => replicas,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+/// Where the bins of a cluster are kept, which its bin clients and keepers
+/// have to agree on. The fields sit at the top of the config file, next to
+/// the backends; those left out keep their defaults, one backend per bin
+/// and one hash slot per backend.
+///
+/// A backend which was down is not caught up on the writes it missed, so
+/// reads, which go to the first replica of a bin which answers, may miss
+/// them.
+pub struct Placement {
+    /// How many backends every bin is kept on: the backend its hash slot
+    /// routes to, and the next other ones along the ring. A cluster of fewer
+    /// backends keeps every bin on all of them.
+    pub replication_factor: usize,
+    /// How many hash slots every backend owns. With more than one, the bins
+    /// of a backend which is down are spread over several others, rather
+    /// than all moved to the next one.
+    pub virtual_nodes: usize,
+    /// How many replicas of a bin have to take a write.
+    pub consistency: Consistency,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement {
+            replication_factor: 1,
+            virtual_nodes: 1,
+            consistency: Consistency::default(),
+        }
+    }
+}
+
+impl Placement {
+    /// Checks that bins can be placed this way.
+    pub fn check(&self) -> TribResult<()> {
+        let problem = if self.replication_factor == 0 {
+            "the replication factor has to be at least 1".to_string()
+        } else if self.virtual_nodes == 0 || self.virtual_nodes > MAX_VIRTUAL_NODES {
+            format!(
+                "the virtual nodes have to be between 1 and {}",
+                MAX_VIRTUAL_NODES
+            )
+        } else {
+            return Ok(());
+        };
+        Err(TribblerError::InvalidConfig(format!(
+            "placement: {}",
+            problem
+        )))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// Schedule and destination for keeper-orchestrated backend snapshots.
+///
+/// Like a cron entry, the schedule is anchored to wall-clock time: a snapshot
+/// is taken whenever the number of seconds since the unix epoch, minus
+/// `offset_secs`, is a multiple of `interval_secs`. For example an interval of
+/// `86400` with an offset of `7200` takes a snapshot every day at 02:00 UTC.
+pub struct BackupConfig {
+    /// Directory the snapshots and the backup log are written to.
+    pub dir: String,
+    /// Seconds between two snapshots.
+    pub interval_secs: u64,
+    /// Shifts the schedule away from multiples of the interval.
+    #[serde(default)]
+    pub offset_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "registry", rename_all = "lowercase")]
+/// A service registry the backends and keepers of a cluster are registered
+/// in, which the front-ends and keepers look them up in and watch, in place
+/// of the lists of the config. Looking them up takes the `discovery` feature.
+pub enum Registry {
+    /// The healthy instances of two Consul services.
+    Consul {
+        /// The Consul HTTP API, e.g. `http://127.0.0.1:8500`.
+        url: String,
+        backs_service: String,
+        keepers_service: String,
+    },
+    /// The keys under two etcd prefixes, each holding one address.
+    Etcd {
+        /// The etcd v3 JSON gateway, e.g. `http://127.0.0.1:2379`.
+        url: String,
+        backs_prefix: String,
+        keepers_prefix: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+/// Limits the front-ends enforce on users, so different deployments can
+/// tune them without recompiling. Limits left out of the config file keep
+/// their defaults.
+pub struct Limits {
+    /// Maximum length of a tribble.
+    pub max_trib_len: usize,
+    /// Maximum count of tribbles returned by a timeline.
+    pub max_trib_fetch: usize,
+    /// Maximum count of users that one can follow.
+    pub max_following: usize,
+    /// Minimum count of users required for `list_users()`.
+    pub min_list_user: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_trib_len: MAX_TRIB_LEN,
+            max_trib_fetch: MAX_TRIB_FETCH,
+            max_following: MAX_FOLLOWING,
+            min_list_user: MIN_LIST_USER,
+        }
+    }
+}
+
 impl KeeperConfig {
     pub fn addr(&self) -> &str {
-        &self.addrs[self.this as usize]
+        &self.addrs[self.this]
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+/// The `front` section of a config: the options of the front-ends over the
+/// cluster. The options trib-front is given on its command line win over
+/// those of the section.
+pub struct FrontSection {
+    pub limits: Limits,
+    /// The host address to bind to, e.g. `127.0.0.1` or `0.0.0.0`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// What to seed the service with on startup: `off`, `default`, or
+    /// `file:<path>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub populate: Option<String>,
+    /// A JSON array of user exports to import before serving.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import: Option<String>,
+    /// The password the users seeded or imported on startup sign in with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_password: Option<String>,
+    /// A PEM file with the certificate chain to serve HTTPS with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<String>,
+    /// A PEM file with the private key of the certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<String>,
+    /// A directory to serve the web UI from instead of the built-in copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub www: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+/// The `back` section of a config: the options of the backends.
+pub struct BackSection {
+    /// A directory of snapshots taken by the keepers (see
+    /// [BackupConfig]), the newest of which a backend loads on start, so
+    /// that restarting it doesn't lose what it kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restore_dir: Option<String>,
+    /// A PEM file with the certificate chain the backends serve their
+    /// `https://` addresses with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<String>,
+    /// A PEM file with the private key of the certificate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+/// The `keeper` section of a config: the tuning of the keepers, with the
+/// schedule of the snapshots they take, if any.
+pub struct KeeperSection {
+    #[serde(flatten)]
+    pub tuning: KeeperTuning,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupConfig>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
-/// A config file defining the backend and keeper network addresses
+/// A config file defining the backend and keeper network addresses, shared
+/// by every process of the cluster, with a section for the options of each
+/// role.
 pub struct Config {
     pub backs: Vec<String>,
     pub keepers: Vec<String>,
+    #[serde(flatten)]
+    pub placement: Placement,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<Registry>,
+    #[serde(default)]
+    pub front: FrontSection,
+    #[serde(default)]
+    pub back: BackSection,
+    #[serde(default)]
+    pub keeper: KeeperSection,
 }
 
 impl Config {
@@ -84,18 +438,53 @@ impl Config {
         l.unwrap_or(DEFAULT_CONFIG_LOCATION)
     }
 
+    /// A config for a cluster of `n_backs` backends and `n_keepers` keepers
+    /// all on this machine, serving on consecutive ports from `base_port`:
+    /// the backends first, then the keepers. The limits are the defaults.
+    ///
+    /// ```rust
+    /// use tribbler::config::Config;
+    /// let cfg = Config::template(3, 1, 3000).unwrap();
+    /// assert_eq!("localhost:3002", cfg.backs[2]);
+    /// assert_eq!("localhost:3003", cfg.keepers[0]);
+    /// ```
+    pub fn template(n_backs: usize, n_keepers: usize, base_port: u16) -> TribResult<Config> {
+        let ports = (base_port as usize)..(base_port as usize + n_backs + n_keepers);
+        if ports.end > u16::MAX as usize + 1 {
+            return Err(TribblerError::InvalidConfig(format!(
+                "{} servers from port {} run past the last port",
+                ports.len(),
+                base_port
+            )));
+        }
+        let mut addrs = ports.map(|port| format!("localhost:{}", port));
+        Ok(Config {
+            backs: addrs.by_ref().take(n_backs).collect(),
+            keepers: addrs.collect(),
+            placement: Placement::default(),
+            discovery: None,
+            front: FrontSection::default(),
+            back: BackSection::default(),
+            keeper: KeeperSection::default(),
+        })
+    }
+
     /// Reads from an optional path a tribbler configuration into a [Config]
     /// struct. If [None] is provided, [DEFAULT_CONFIG_LOCATION] is used.
+    ///
+    /// The file is parsed as JSON, TOML, or YAML, going by its extension, as
+    /// told by [ConfigFormat::of].
     pub fn read(location: Option<&str>) -> TribResult<Config> {
         let file = Config::location(location);
         let pth = fs::canonicalize(file)?;
-        Ok(serde_json::from_slice::<Config>(&fs::read(pth)?)?)
+        ConfigFormat::of(file).parse(&fs::read(pth)?)
     }
 
     /// Writes a [Config] out to a file at a particular location. If [None] is
     /// specified, the location is [DEFAULT_CONFIG_LOCATION].
     ///
-    /// If the specified location is `-`, then it will write to stdout
+    /// If the specified location is `-`, then it will write JSON to stdout;
+    /// otherwise the format goes by the extension, as for [Config::read].
     pub fn write(&self, location: Option<&str>) -> TribResult<()> {
         let file = Config::location(location);
         let mut handle: Box<dyn Write> = match file {
@@ -110,9 +499,8 @@ impl Config {
                 Box::new(handle)
             }
         };
-        let mut contents = serde_json::to_vec_pretty(&self)?;
-        contents.append(&mut "\n".as_bytes().to_vec());
-        let _ = handle.write_all(&contents)?;
+        let contents = ConfigFormat::of(file).render(self)?;
+        handle.write_all(contents.as_bytes())?;
         Ok(())
     }
 
@@ -148,7 +536,9 @@ impl Config {
     }
 
     /// build a [KeeperConfig] for the given index `i` in the list of keeper
-    /// addresses. `i` must be a valid index into the list of keepers.
+    /// addresses. `i` must be a valid index into the list of keepers. The
+    /// keeper is tuned by the `keeper` section, whose tuning has to
+    /// [check](KeeperTuning::check) out, as does the [Placement].
     ///
     /// You can choose to pass in a [Sender] where the receiving end of the
     /// channel can get a message when the backend using this configuration
@@ -159,6 +549,8 @@ impl Config {
         ready: Option<Sender<bool>>,
         shutdown: Option<Receiver<()>>,
     ) -> TribResult<KeeperConfig> {
+        self.keeper.tuning.check()?;
+        self.placement.check()?;
         Ok(KeeperConfig {
             backs: self.backs.clone(),
             addrs: self.keepers.clone(),
@@ -166,8 +558,295 @@ impl Config {
             id: SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_nanos(),
+            tuning: self.keeper.tuning.clone(),
+            placement: self.placement,
+            backup: self.keeper.backup.clone(),
             ready,
             shutdown,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BackSection, BackupConfig, Config, ConfigFormat, Consistency, FrontSection, KeeperSection,
+        KeeperTuning, Limits, Placement, Registry, TribblerError,
+    };
+
+    #[test]
+    fn test_format_of() {
+        assert_eq!(ConfigFormat::Json, ConfigFormat::of("bins.json"));
+        assert_eq!(ConfigFormat::Toml, ConfigFormat::of("conf/bins.toml"));
+        assert_eq!(ConfigFormat::Yaml, ConfigFormat::of("bins.yaml"));
+        assert_eq!(ConfigFormat::Yaml, ConfigFormat::of("BINS.YML"));
+        assert_eq!(ConfigFormat::Json, ConfigFormat::of("bins"));
+        assert_eq!(ConfigFormat::Json, ConfigFormat::of("-"));
+    }
+
+    #[test]
+    fn test_template() {
+        let config = Config::template(2, 2, 4000).unwrap();
+        assert_eq!(vec!["localhost:4000", "localhost:4001"], config.backs);
+        assert_eq!(vec!["localhost:4002", "localhost:4003"], config.keepers);
+        assert_eq!(None, config.keeper.backup);
+
+        let last = Config::template(1, 1, u16::MAX - 1).unwrap();
+        assert_eq!(vec!["localhost:65535"], last.keepers);
+        assert!(Config::template(2, 1, u16::MAX - 1).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            backs: vec![
+                "127.0.0.1:3000".to_string(),
+                "https://127.0.0.1:3001".to_string(),
+                "unix:///run/trib/back-2.sock".to_string(),
+            ],
+            keepers: vec!["127.0.0.1:3010".to_string()],
+            placement: Placement {
+                replication_factor: 2,
+                virtual_nodes: 8,
+                consistency: Consistency::All,
+            },
+            discovery: Some(Registry::Consul {
+                url: "http://127.0.0.1:8500".to_string(),
+                backs_service: "trib-back".to_string(),
+                keepers_service: "trib-keeper".to_string(),
+            }),
+            front: FrontSection {
+                limits: Limits {
+                    max_trib_len: 280,
+                    ..Limits::default()
+                },
+                port: Some(8443),
+                populate: Some("off".to_string()),
+                tls_cert: Some("cert.pem".to_string()),
+                tls_key: Some("key.pem".to_string()),
+                ..FrontSection::default()
+            },
+            back: BackSection {
+                restore_dir: Some("backups".to_string()),
+                tls_cert: Some("back-cert.pem".to_string()),
+                tls_key: Some("back-key.pem".to_string()),
+            },
+            keeper: KeeperSection {
+                tuning: KeeperTuning {
+                    failure_threshold: 3,
+                    ..KeeperTuning::default()
+                },
+                backup: Some(BackupConfig {
+                    dir: "backups".to_string(),
+                    interval_secs: 86400,
+                    offset_secs: 7200,
+                }),
+            },
+        };
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let contents = format.render(&config).unwrap();
+            let parsed = format.parse(contents.as_bytes()).unwrap();
+            assert_eq!(config.backs, parsed.backs, "{:?}", format);
+            assert_eq!(config.keepers, parsed.keepers, "{:?}", format);
+            assert_eq!(config.placement, parsed.placement, "{:?}", format);
+            assert_eq!(config.discovery, parsed.discovery, "{:?}", format);
+            assert_eq!(config.front, parsed.front, "{:?}", format);
+            assert_eq!(config.back, parsed.back, "{:?}", format);
+            assert_eq!(config.keeper, parsed.keeper, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_parse_hand_written() {
+        let toml = r#"
+backs = ["127.0.0.1:3000"]
+keepers = ["127.0.0.1:3010"]
+
+[front]
+port = 9090
+
+[front.limits]
+max_following = 10
+"#;
+        let yaml = "
+backs:
+  - 127.0.0.1:3000
+keepers:
+  - 127.0.0.1:3010
+front:
+  port: 9090
+  limits:
+    max_following: 10
+";
+        for (format, contents) in [(ConfigFormat::Toml, toml), (ConfigFormat::Yaml, yaml)] {
+            let config = format.parse(contents.as_bytes()).unwrap();
+            assert_eq!(vec!["127.0.0.1:3000"], config.backs);
+            assert_eq!(vec!["127.0.0.1:3010"], config.keepers);
+            assert_eq!(None, config.keeper.backup);
+            assert_eq!(Some(9090), config.front.port);
+            assert_eq!(None, config.front.host);
+            assert_eq!(10, config.front.limits.max_following);
+            assert_eq!(
+                Limits::default().max_trib_len,
+                config.front.limits.max_trib_len
+            );
+            assert_eq!(BackSection::default(), config.back);
+        }
+        assert!(matches!(
+            ConfigFormat::Toml.parse(b"backs = ["),
+            Err(TribblerError::TomlDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_sections_of_old_configs() {
+        let json = br#"{
+            "backs": ["127.0.0.1:3000"],
+            "keepers": ["127.0.0.1:3010"],
+            "limits": {"max_following": 10},
+            "backup": {"dir": "backups", "interval_secs": 60},
+            "keeper": {"failure_threshold": 3}
+        }"#;
+        let config = ConfigFormat::Json.parse(json).unwrap();
+        assert_eq!(10, config.front.limits.max_following);
+        assert_eq!(3, config.keeper.tuning.failure_threshold);
+        assert_eq!(
+            Some(BackupConfig {
+                dir: "backups".to_string(),
+                interval_secs: 60,
+                offset_secs: 0,
+            }),
+            config.keeper.backup
+        );
+
+        // the sections win over what they would be given
+        let json = br#"{
+            "backs": [], "keepers": [],
+            "limits": {"max_following": 10},
+            "front": {"limits": {"max_following": 20}}
+        }"#;
+        let config = ConfigFormat::Json.parse(json).unwrap();
+        assert_eq!(20, config.front.limits.max_following);
+    }
+
+    #[test]
+    fn test_discovery_section() {
+        let yaml = "
+backs: []
+keepers: []
+discovery:
+  registry: etcd
+  url: http://127.0.0.1:2379
+  backs_prefix: /trib/backs/
+  keepers_prefix: /trib/keepers/
+";
+        let config = ConfigFormat::Yaml.parse(yaml.as_bytes()).unwrap();
+        assert_eq!(
+            Some(Registry::Etcd {
+                url: "http://127.0.0.1:2379".to_string(),
+                backs_prefix: "/trib/backs/".to_string(),
+                keepers_prefix: "/trib/keepers/".to_string(),
+            }),
+            config.discovery
+        );
+        let config = ConfigFormat::Json
+            .parse(br#"{"backs":[],"keepers":[]}"#)
+            .unwrap();
+        assert_eq!(None, config.discovery);
+    }
+
+    #[test]
+    fn test_keeper_section() {
+        let toml = r#"
+backs = ["127.0.0.1:3000"]
+keepers = ["127.0.0.1:3010"]
+
+[keeper]
+sync_interval_ms = 500
+heartbeat_timeout_ms = 250
+failure_threshold = 3
+"#;
+        let config = ConfigFormat::Toml.parse(toml.as_bytes()).unwrap();
+        assert_eq!(
+            KeeperTuning {
+                sync_interval_ms: 500,
+                probe_timeout_ms: 250,
+                failure_threshold: 3,
+                ..KeeperTuning::default()
+            },
+            config.keeper.tuning
+        );
+        let keeper = config.keeper_config(0, None, None).unwrap();
+        assert_eq!(config.keeper.tuning, keeper.tuning);
+
+        // a config without the section keeps the defaults
+        let config = ConfigFormat::Json
+            .parse(br#"{"backs":[],"keepers":["127.0.0.1:3010"]}"#)
+            .unwrap();
+        assert_eq!(KeeperSection::default(), config.keeper);
+
+        for bad in [
+            KeeperTuning {
+                sync_interval_ms: 0,
+                ..KeeperTuning::default()
+            },
+            KeeperTuning {
+                failure_threshold: 0,
+                ..KeeperTuning::default()
+            },
+        ] {
+            assert!(
+                matches!(bad.check(), Err(TribblerError::InvalidConfig(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_placement() {
+        let toml = r#"
+backs = ["127.0.0.1:3000", "127.0.0.1:3001", "127.0.0.1:3002"]
+keepers = ["127.0.0.1:3010"]
+replication_factor = 3
+consistency = "one"
+"#;
+        let config = ConfigFormat::Toml.parse(toml.as_bytes()).unwrap();
+        assert_eq!(
+            Placement {
+                replication_factor: 3,
+                consistency: Consistency::One,
+                ..Placement::default()
+            },
+            config.placement
+        );
+        let keeper = config.keeper_config(0, None, None).unwrap();
+        assert_eq!(config.placement, keeper.placement);
+
+        assert_eq!(1, Consistency::One.acks(3));
+        assert_eq!(2, Consistency::Quorum.acks(3));
+        assert_eq!(2, Consistency::Quorum.acks(2));
+        assert_eq!(3, Consistency::All.acks(3));
+
+        for bad in [
+            Placement {
+                replication_factor: 0,
+                ..Placement::default()
+            },
+            Placement {
+                virtual_nodes: 0,
+                ..Placement::default()
+            },
+            Placement {
+                virtual_nodes: 1000,
+                ..Placement::default()
+            },
+        ] {
+            assert!(
+                matches!(bad.check(), Err(TribblerError::InvalidConfig(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! Looking up the backends and keepers of a cluster in the [Registry] a
+//! config names, etcd or Consul, and watching it for changes, so that the
+//! membership of the cluster is kept in one place rather than in the config
+//! of every process.
+use super::{Config, Registry};
+use crate::err::{TribResult, TribblerError};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// how long a lookup in the registry may take
+pub const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how long to wait before watching the registry again when watching failed
+pub const RETRY_AFTER: Duration = Duration::from_secs(5);
+
+// how long a blocking query to Consul waits for a change before answering
+// anyway
+const CONSUL_WAIT: &str = "60s";
+
+/// The backends and keepers registered, and how far along the registry was
+/// when they were looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Membership {
+    pub backs: Vec<String>,
+    pub keepers: Vec<String>,
+    // the Consul index of each service, or the etcd revision of each prefix
+    versions: (u64, u64),
+}
+
+/// Looks up the backends and keepers registered in `registry`, each list
+/// sorted so that every process lays the hash slots out alike.
+pub async fn fetch(client: &reqwest::Client, registry: &Registry) -> TribResult<Membership> {
+    let ((backs, back_version), (keepers, keeper_version)) = match registry {
+        Registry::Consul {
+            url,
+            backs_service,
+            keepers_service,
+        } => (
+            consul_service(client, url, backs_service, None).await?,
+            consul_service(client, url, keepers_service, None).await?,
+        ),
+        Registry::Etcd {
+            url,
+            backs_prefix,
+            keepers_prefix,
+        } => (
+            etcd_range(client, url, backs_prefix).await?,
+            etcd_range(client, url, keepers_prefix).await?,
+        ),
+    };
+    Ok(Membership {
+        backs,
+        keepers,
+        versions: (back_version, keeper_version),
+    })
+}
+
+/// Hands out the configs `config` holds with the backends and keepers looked
+/// up in their [Registry], looking them up again whenever the registry or the
+/// config changes. They are looked up once before this returns, which fails
+/// when they can't be, or no backend is registered; later, a failed lookup,
+/// or one finding no backend, is reported and the last membership kept. A
+/// config naming no registry is handed out as it is.
+pub async fn discover(mut config: watch::Receiver<Config>) -> TribResult<watch::Receiver<Config>> {
+    let named = config.borrow_and_update().clone();
+    let registry = match &named.discovery {
+        Some(registry) => registry.clone(),
+        None => return Ok(config),
+    };
+    let client = reqwest::Client::new();
+    let mut membership = fetch(&client, &registry).await?;
+    if membership.backs.is_empty() {
+        return Err(TribblerError::Unknown(format!(
+            "no backend is registered in {:?}",
+            registry
+        )));
+    }
+    info!(
+        "found backends {:?} and keepers {:?} in the registry",
+        membership.backs, membership.keepers
+    );
+    let (tx, rx) = watch::channel(with_members(&named, &membership));
+    tokio::spawn(async move {
+        let mut named = named;
+        // whether the config can change still
+        let mut following = true;
+        loop {
+            let registry = match &named.discovery {
+                Some(registry) => registry.clone(),
+                None => {
+                    // the config dropped the registry, so its lists stand
+                    tx.send_replace(named.clone());
+                    match following && config.changed().await.is_ok() {
+                        true => {
+                            named = config.borrow_and_update().clone();
+                            continue;
+                        }
+                        false => return,
+                    }
+                }
+            };
+            tokio::select! {
+                changed = changed(&client, &registry, &membership) => {
+                    if let Err(e) = changed {
+                        warn!("failed to watch the registry, retrying in {:?}: {}", RETRY_AFTER, e);
+                        tokio::time::sleep(RETRY_AFTER).await;
+                    }
+                }
+                changed = config.changed(), if following => match changed {
+                    Ok(()) => named = config.borrow_and_update().clone(),
+                    Err(_) => following = false,
+                },
+            }
+            if tx.is_closed() {
+                return; // nothing follows the membership anymore
+            }
+            let registry = match &named.discovery {
+                Some(registry) => registry,
+                None => continue,
+            };
+            match fetch(&client, registry).await {
+                Ok(found) if found.backs.is_empty() => {
+                    warn!("keeping the backends, as none is registered anymore");
+                    membership.versions = found.versions;
+                }
+                Ok(found) => membership = found,
+                Err(e) => {
+                    warn!("keeping the membership, as the registry failed: {}", e);
+                    continue;
+                }
+            }
+            tx.send_if_modified(|current| {
+                let update = with_members(&named, &membership);
+                if current.backs == update.backs && current.keepers == update.keepers {
+                    return false;
+                }
+                info!(
+                    "found backends {:?} and keepers {:?} in the registry",
+                    update.backs, update.keepers
+                );
+                *current = update;
+                true
+            });
+        }
+    });
+    Ok(rx)
+}
+
+// `config` with the members of `membership`
+fn with_members(config: &Config, membership: &Membership) -> Config {
+    Config {
+        backs: membership.backs.clone(),
+        keepers: membership.keepers.clone(),
+        ..config.clone()
+    }
+}
+
+// resolves once the registry may have changed since `since` was looked up
+async fn changed(
+    client: &reqwest::Client,
+    registry: &Registry,
+    since: &Membership,
+) -> TribResult<()> {
+    match registry {
+        Registry::Consul {
+            url,
+            backs_service,
+            keepers_service,
+        } => {
+            tokio::select! {
+                r = consul_service(client, url, backs_service, Some(since.versions.0)) => r.map(|_| ()),
+                r = consul_service(client, url, keepers_service, Some(since.versions.1)) => r.map(|_| ()),
+            }
+        }
+        Registry::Etcd {
+            url,
+            backs_prefix,
+            keepers_prefix,
+        } => {
+            tokio::select! {
+                r = etcd_watch(client, url, backs_prefix, since.versions.0 + 1) => r,
+                r = etcd_watch(client, url, keepers_prefix, since.versions.1 + 1) => r,
+            }
+        }
+    }
+}
+
+// the addresses of the healthy instances of a Consul service, with the index
+// of the answer; given an index, waits for the answer to move past it first
+async fn consul_service(
+    client: &reqwest::Client,
+    url: &str,
+    service: &str,
+    index: Option<u64>,
+) -> TribResult<(Vec<String>, u64)> {
+    let mut request = client
+        .get(format!(
+            "{}/v1/health/service/{}",
+            url.trim_end_matches('/'),
+            service
+        ))
+        .query(&[("passing", "true")]);
+    request = match index {
+        Some(index) => request.query(&[
+            ("index", index.to_string()),
+            ("wait", CONSUL_WAIT.to_string()),
+        ]),
+        None => request.timeout(LOOKUP_TIMEOUT),
+    };
+    let response = request.send().await?.error_for_status()?;
+    let index = response
+        .headers()
+        .get("X-Consul-Index")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let entries: Value = response.json().await?;
+    Ok((consul_addrs(&entries), index))
+}
+
+// the `address:port` of every entry of a Consul health answer, sorted; an
+// instance without an address of its own serves on the address of its node
+fn consul_addrs(entries: &Value) -> Vec<String> {
+    let mut addrs: Vec<String> = entries
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let service = entry.get("Service")?;
+            let port = service.get("Port")?.as_u64()?;
+            let address = match service.get("Address").and_then(Value::as_str) {
+                Some(address) if !address.is_empty() => address,
+                _ => entry.get("Node")?.get("Address")?.as_str()?,
+            };
+            Some(format!("{}:{}", address, port))
+        })
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+// the addresses held under an etcd prefix, with the revision of the store
+async fn etcd_range(
+    client: &reqwest::Client,
+    url: &str,
+    prefix: &str,
+) -> TribResult<(Vec<String>, u64)> {
+    let body = json!({
+        "key": STANDARD.encode(prefix),
+        "range_end": STANDARD.encode(prefix_end(prefix.as_bytes())),
+    });
+    let answer: Value = client
+        .post(format!("{}/v3/kv/range", url.trim_end_matches('/')))
+        .timeout(LOOKUP_TIMEOUT)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(etcd_values(&answer))
+}
+
+// the values of an etcd range answer, sorted, with its revision; the gateway
+// hands out bytes in base64 and 64-bit numbers as strings
+fn etcd_values(answer: &Value) -> (Vec<String>, u64) {
+    let revision = answer
+        .pointer("/header/revision")
+        .and_then(|v| {
+            v.as_str()
+                .and_then(|v| v.parse().ok())
+                .or_else(|| v.as_u64())
+        })
+        .unwrap_or(0);
+    let mut values: Vec<String> = answer
+        .get("kvs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|kv| kv.get("value")?.as_str())
+        .filter_map(|value| String::from_utf8(STANDARD.decode(value).ok()?).ok())
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort();
+    values.dedup();
+    (values, revision)
+}
+
+// resolves once a key under an etcd prefix changes from revision `from` on
+async fn etcd_watch(
+    client: &reqwest::Client,
+    url: &str,
+    prefix: &str,
+    from: u64,
+) -> TribResult<()> {
+    let body = json!({
+        "create_request": {
+            "key": STANDARD.encode(prefix),
+            "range_end": STANDARD.encode(prefix_end(prefix.as_bytes())),
+            "start_revision": from.to_string(),
+        }
+    });
+    let mut response = client
+        .post(format!("{}/v3/watch", url.trim_end_matches('/')))
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?;
+    // the gateway streams one JSON object a line
+    let mut pending = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if let Ok(message) = serde_json::from_slice::<Value>(&line) {
+                let events = message.pointer("/result/events").and_then(Value::as_array);
+                if events.is_some_and(|events| !events.is_empty()) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(TribblerError::Unknown(format!(
+        "the etcd watch of {} ended",
+        prefix
+    )))
+}
+
+// the end of the etcd range holding every key starting with `prefix`
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // every key is past a prefix of only 0xff bytes
+    vec![0]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{consul_addrs, etcd_values, prefix_end};
+    use serde_json::json;
+
+    #[test]
+    fn test_consul_addrs() {
+        let entries = json!([
+            {"Node": {"Address": "10.0.0.2"}, "Service": {"Address": "", "Port": 3001}},
+            {"Node": {"Address": "10.0.0.1"}, "Service": {"Address": "10.1.0.1", "Port": 3000}},
+            {"Node": {"Address": "10.0.0.3"}, "Service": {"Port": 3002}},
+        ]);
+        assert_eq!(
+            vec!["10.0.0.2:3001", "10.0.0.3:3002", "10.1.0.1:3000"],
+            consul_addrs(&entries)
+        );
+        assert!(consul_addrs(&json!([])).is_empty());
+    }
+
+    #[test]
+    fn test_etcd_values() {
+        // localhost:3001 and localhost:3000, under /trib/backs/
+        let answer = json!({
+            "header": {"revision": "42"},
+            "kvs": [
+                {"key": "L3RyaWIvYmFja3MvMQ==", "value": "bG9jYWxob3N0OjMwMDE="},
+                {"key": "L3RyaWIvYmFja3MvMA==", "value": "bG9jYWxob3N0OjMwMDA="},
+            ]
+        });
+        assert_eq!(
+            (
+                vec!["localhost:3000".to_string(), "localhost:3001".to_string()],
+                42
+            ),
+            etcd_values(&answer)
+        );
+        assert_eq!(
+            (vec![], 7),
+            etcd_values(&json!({"header": {"revision": "7"}}))
+        );
+    }
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(b"/trib/backs0".to_vec(), prefix_end(b"/trib/backs/"));
+        assert_eq!(vec![b'a' + 1], prefix_end(&[b'a', 0xff]));
+        assert_eq!(vec![0], prefix_end(&[0xff]));
+    }
+}
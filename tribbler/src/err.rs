@@ -1,76 +1,718 @@
 //! This module contains implementation and functions for returning [std::error::Error] and [Result] type
 //! objects from Tribbler related functions.
-use std::{error::Error, fmt::Display};
+use prost::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-/// basic error types that can occur when running the tribbler service.
-#[derive(Debug, Clone)]
+/// basic error types that can occur when running the tribbler service, and
+/// the errors of the libraries it is built on, which convert into it with `?`.
+#[derive(Debug, Error)]
 pub enum TribblerError {
     /// used when an operation is called for a particular user who does not
     /// exist
+    #[error("user \"{0}\" does not exist")]
     UserDoesNotExist(String),
     /// when a user tries to sign up and the name is already taken
+    #[error("username \"{0}\" already taken")]
     UsernameTaken(String),
     /// when a username is invalid in any way
+    #[error("username \"{0}\" is invalid")]
     InvalidUsername(String),
     /// generic error for anything that occurs with RPC communication
+    #[error("rpc error: {0}")]
     RpcError(String),
     /// raised when too a user tries to follow more than
-    /// [crate::trib::MAX_FOLLOWING]
+    /// [crate::config::Limits::max_following] users
+    #[error("following too many users")]
     FollowingTooMany,
     /// raised when a user tries to follow a user they are already following
+    #[error("{0} already following {1}")]
     AlreadyFollowing(String, String),
     /// raised when a user tries to unfollow a user they are not following
+    #[error("{0} doesn't follow {1}")]
     NotFollowing(String, String),
-    /// raised when a trib message exceeds [crate::trib::MAX_TRIB_LEN]
+    /// raised when a trib message exceeds
+    /// [crate::config::Limits::max_trib_len]
+    #[error("tribbler post exceed character limit")]
     TribTooLong,
     /// when someone tries to follow or check if a user is following themselves
+    #[error("user {0} can't follow themself")]
     WhoWhom(String),
     /// when there are no more seq numbers to give out
+    #[error("MaxedSeq")]
     MaxedSeq,
+    /// raised when a user has no trib with the given clock
+    #[error("{0} has no trib at clock {1}")]
+    TribNotFound(String, u64),
+    /// raised when a user likes a trib they already like
+    #[error("{0} already likes the trib {1} posted at {2}")]
+    AlreadyLiked(String, String, u64),
+    /// raised when a user takes back a like they never gave
+    #[error("{0} doesn't like the trib {1} posted at {2}")]
+    NotLiked(String, String, u64),
+    /// raised when a write is refused because the keeper has not yet
+    /// verified that the backend clocks converged
+    #[error("cluster is not consistent yet, try again later")]
+    ClusterNotReady,
+    /// raised when a counter is updated but the key holds something else
+    #[error("value of \"{0}\" is not a counter")]
+    NotACounter(String),
+    /// raised when a field of a profile exceeds its length limit
+    #[error("profile {0} is too long")]
+    ProfileTooLong(String),
+    /// raised when a user blocks a user they already block
+    #[error("{0} already blocks {1}")]
+    AlreadyBlocked(String, String),
+    /// raised when a user takes back a block they never made
+    #[error("{0} doesn't block {1}")]
+    NotBlocked(String, String),
+    /// raised when a user mutes a user they already mute
+    #[error("{0} already mutes {1}")]
+    AlreadyMuted(String, String),
+    /// raised when a user takes back a mute they never made
+    #[error("{0} doesn't mute {1}")]
+    NotMuted(String, String),
+    /// raised when a user tries to follow a user who blocks them
+    #[error("{0} is blocked by {1}")]
+    BlockedBy(String, String),
+    /// raised when an avatar is neither an http(s) URL nor a hex digest
+    #[error("avatar \"{0}\" is invalid")]
+    InvalidAvatar(String),
+    /// raised when a list name does not follow the rules of usernames
+    #[error("list name \"{0}\" is invalid")]
+    InvalidListName(String),
+    /// raised when a user creates a list under a name they already use
+    #[error("{0} already has a list named {1}")]
+    ListExists(String, String),
+    /// raised when a user has no list with the given name
+    #[error("{0} has no list named {1}")]
+    NoSuchList(String, String),
+    /// raised when a user adds someone to a list they are already on
+    #[error("{0} is already on list {1}")]
+    AlreadyInList(String, String),
+    /// raised when a user saves a draft while keeping
+    /// [crate::trib::MAX_DRAFTS] already
+    #[error("too many drafts")]
+    TooManyDrafts,
+    /// raised when a user has no draft with the given id
+    #[error("{0} has no draft {1}")]
+    NoSuchDraft(String, u64),
+    /// raised when a trib has more than [crate::trib::MAX_ATTACHMENTS]
+    /// attachments
+    #[error("too many attachments")]
+    TooManyAttachments,
+    /// raised when the URL, digest or media type of an attachment is invalid
+    #[error("attachment \"{0}\" is invalid")]
+    InvalidAttachment(String),
+    /// raised when a banned user tries to post or follow
+    #[error("user \"{0}\" is banned")]
+    Banned(String),
+    /// raised when a moderator bans a user who is already banned
+    #[error("user \"{0}\" is already banned")]
+    AlreadyBanned(String),
+    /// raised when a moderator takes back a ban that was never made
+    #[error("user \"{0}\" is not banned")]
+    NotBanned(String),
+    /// raised when an admin request comes without the right admin token
+    #[error("admin token missing or wrong")]
+    NotAuthorized,
+    /// raised when a browser acts for a user it is not signed in as
+    #[error("not signed in as \"{0}\"")]
+    NotSignedIn(String),
+    /// raised when a user sets an empty password
+    #[error("password must not be empty")]
+    InvalidPassword,
+    /// raised when a user signs in with a password other than the one set
+    #[error("wrong password for \"{0}\"")]
+    WrongPassword(String),
+    /// raised when signing in through a single sign-on provider fails
+    #[error("single sign-on failed: {0}")]
+    SsoFailed(String),
+    /// raised when a call is not answered within its deadline, in seconds
+    #[error("call timed out after {0}s")]
+    TimedOut(u64),
+    /// raised when a user has used up what a quota allows, the quota named
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
+    /// raised when a client makes calls faster than it may, which it can
+    /// make again after `retry_after` seconds
+    #[error("too many requests, retry in {retry_after}s")]
+    RateLimited { retry_after: u64 },
+    /// raised when a backend has no room left for a write, the backend named
+    #[error("storage full on {0}")]
+    StorageFull(String),
+    /// raised when too few replicas of a bin are up for a call to go
+    /// through, with what was missing
+    #[error("cluster degraded: {0}")]
+    ClusterDegraded(String),
+    /// raised when an entry of a follow log can't be read, the entry given
+    #[error("corrupt follow log entry \"{0}\"")]
+    CorruptLogEntry(String),
+    /// raised when a cache a front-end keeps can't be used, with why
+    #[error("cache failure: {0}")]
+    CacheFailure(String),
+    /// raised when a server answers an RPC with an error status, boxed as
+    /// it dwarfs the other errors
+    #[error("rpc error: {}", .0.message())]
+    Status(Box<tonic::Status>),
+    /// raised when a server can't be reached
+    #[error("rpc error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    /// raised when reading or writing a file or socket fails
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// raised when a value fails to encode to or decode from JSON
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// raised when a config fails to decode from TOML
+    #[error("toml error: {0}")]
+    TomlDecode(#[from] toml::de::Error),
+    /// raised when a config fails to encode to TOML
+    #[error("toml error: {0}")]
+    TomlEncode(#[from] toml::ser::Error),
+    /// raised when a config fails to encode to or decode from YAML
+    #[error("yaml error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    /// raised when text which should be UTF-8 isn't
+    #[error("invalid utf-8: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    /// raised when a number fails to parse
+    #[error("invalid number: {0}")]
+    ParseInt(#[from] std::num::ParseIntError),
+    /// raised when the system clock reads before the unix epoch
+    #[error("time error: {0}")]
+    Time(#[from] std::time::SystemTimeError),
+    /// raised when a registry the membership is looked up in can't be reached
+    #[cfg(feature = "discovery")]
+    #[error("registry error: {0}")]
+    Registry(#[from] reqwest::Error),
+    /// an error along with what was being done when it happened, such as
+    /// the bin, backend, and operation of a failed storage call, added by
+    /// [TribblerError::context]
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        source: Box<TribblerError>,
+    },
+    /// raised when a config can't be run with, with what is wrong with it
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
     /// catch-all error for other issues
+    #[error("unknown error: {0}")]
     Unknown(String),
 }
 
-impl Display for TribblerError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let x = match self {
-            TribblerError::UserDoesNotExist(x) => format!("user \"{}\" does not exist", x),
-            TribblerError::UsernameTaken(x) => format!("username \"{}\" already taken", x),
-            TribblerError::InvalidUsername(x) => format!("username \"{}\" is invalid", x),
-            TribblerError::RpcError(x) => format!("rpc error: {}", x),
-            TribblerError::FollowingTooMany => "following too many users".to_string(),
-            TribblerError::AlreadyFollowing(who, whom) => {
-                format!("{} already following {}", who, whom)
+impl TribblerError {
+    /// Wraps the error with what was being done when it happened, which its
+    /// message then starts with; the error is kept as the
+    /// [source](std::error::Error::source), and still decides the
+    /// [code](TribblerError::code).
+    ///
+    /// ```
+    /// use tribbler::err::TribblerError;
+    ///
+    /// let err = TribblerError::NotACounter("n".to_string()).context("incr in bin alice");
+    /// assert_eq!("incr in bin alice: value of \"n\" is not a counter", err.to_string());
+    /// assert_eq!("not_a_counter", err.code());
+    /// ```
+    pub fn context(self, context: impl Into<String>) -> TribblerError {
+        TribblerError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The error beneath all the context added to it, to match on what went
+    /// wrong.
+    pub fn root(&self) -> &TribblerError {
+        match self {
+            TribblerError::Context { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// A stable code for what went wrong, such as `username_taken`, which
+    /// clients can branch on where the message may change between versions.
+    /// A status an RPC was answered with, which could not be turned back into
+    /// the error the server ran into, has the code of that error as the status
+    /// carries it (see the [From] impl for [tonic::Status]), and `rpc` if it
+    /// carries none.
+    pub fn code(&self) -> &str {
+        match self {
+            TribblerError::UserDoesNotExist(_) => "user_does_not_exist",
+            TribblerError::UsernameTaken(_) => "username_taken",
+            TribblerError::InvalidUsername(_) => "invalid_username",
+            TribblerError::RpcError(_) | TribblerError::Transport(_) => "rpc",
+            TribblerError::FollowingTooMany => "following_too_many",
+            TribblerError::AlreadyFollowing(_, _) => "already_following",
+            TribblerError::NotFollowing(_, _) => "not_following",
+            TribblerError::TribTooLong => "trib_too_long",
+            TribblerError::WhoWhom(_) => "follow_self",
+            TribblerError::MaxedSeq => "maxed_seq",
+            TribblerError::TribNotFound(_, _) => "trib_not_found",
+            TribblerError::AlreadyLiked(_, _, _) => "already_liked",
+            TribblerError::NotLiked(_, _, _) => "not_liked",
+            TribblerError::ClusterNotReady => "cluster_not_ready",
+            TribblerError::NotACounter(_) => "not_a_counter",
+            TribblerError::ProfileTooLong(_) => "profile_too_long",
+            TribblerError::AlreadyBlocked(_, _) => "already_blocked",
+            TribblerError::NotBlocked(_, _) => "not_blocked",
+            TribblerError::AlreadyMuted(_, _) => "already_muted",
+            TribblerError::NotMuted(_, _) => "not_muted",
+            TribblerError::BlockedBy(_, _) => "blocked_by",
+            TribblerError::InvalidAvatar(_) => "invalid_avatar",
+            TribblerError::InvalidListName(_) => "invalid_list_name",
+            TribblerError::ListExists(_, _) => "list_exists",
+            TribblerError::NoSuchList(_, _) => "no_such_list",
+            TribblerError::AlreadyInList(_, _) => "already_in_list",
+            TribblerError::TooManyDrafts => "too_many_drafts",
+            TribblerError::NoSuchDraft(_, _) => "no_such_draft",
+            TribblerError::TooManyAttachments => "too_many_attachments",
+            TribblerError::InvalidAttachment(_) => "invalid_attachment",
+            TribblerError::Banned(_) => "banned",
+            TribblerError::AlreadyBanned(_) => "already_banned",
+            TribblerError::NotBanned(_) => "not_banned",
+            TribblerError::NotAuthorized => "not_authorized",
+            TribblerError::NotSignedIn(_) => "not_signed_in",
+            TribblerError::InvalidPassword => "invalid_password",
+            TribblerError::WrongPassword(_) => "wrong_password",
+            TribblerError::SsoFailed(_) => "sso_failed",
+            TribblerError::TimedOut(_) => "timed_out",
+            TribblerError::QuotaExceeded(_) => "quota_exceeded",
+            TribblerError::RateLimited { .. } => "rate_limited",
+            TribblerError::StorageFull(_) => "storage_full",
+            TribblerError::ClusterDegraded(_) => "cluster_degraded",
+            TribblerError::CorruptLogEntry(_) => "corrupt_log_entry",
+            TribblerError::CacheFailure(_) => "cache_failure",
+            TribblerError::Status(status) => {
+                match serde_json::from_slice::<WireCode>(status.details()) {
+                    Ok(WireCode { code }) if is_code(code) => code,
+                    _ => "rpc",
+                }
             }
-            TribblerError::NotFollowing(who, whom) => format!("{} doesn't follow {}", who, whom),
-            TribblerError::TribTooLong => "tribbler post exceed character limit".to_string(),
-            TribblerError::WhoWhom(x) => format!("user {} can't follow themself", x),
-            TribblerError::Unknown(x) => format!("unknown error: {}", x),
-            x => format!("{:?}", x),
-        };
-        write!(f, "{}", x)
+            TribblerError::Io(_) => "io",
+            TribblerError::Json(_)
+            | TribblerError::TomlDecode(_)
+            | TribblerError::TomlEncode(_)
+            | TribblerError::Yaml(_)
+            | TribblerError::Utf8(_) => "encoding",
+            TribblerError::ParseInt(_) => "invalid_number",
+            TribblerError::Time(_) => "clock",
+            #[cfg(feature = "discovery")]
+            TribblerError::Registry(_) => "registry",
+            TribblerError::InvalidConfig(_) => "invalid_config",
+            TribblerError::Context { source, .. } => source.code(),
+            TribblerError::Unknown(_) => "unknown",
+        }
     }
 }
 
-impl std::error::Error for TribblerError {}
+// whether the code a status carries looks like the code of an error, rather
+// than whatever some other server put there
+fn is_code(code: &str) -> bool {
+    !code.is_empty()
+        && code
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+// an error as the details of a status carry it across an RPC: the code of the
+// error beneath any context, the fields of its variant, and the context added
+// to it, outermost first
+#[derive(Serialize, Deserialize, Default)]
+struct Wire {
+    code: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    context: Vec<String>,
+}
+
+// just the code of a [Wire], borrowed from the details of a status
+#[derive(Deserialize)]
+struct WireCode<'a> {
+    code: &'a str,
+}
+
+impl TribblerError {
+    // the error as the details of a status carry it
+    fn wire(&self) -> Wire {
+        let mut context = vec![];
+        let mut err = self;
+        while let TribblerError::Context { context: c, source } = err {
+            context.push(c.to_string());
+            err = source;
+        }
+        if let TribblerError::Status(status) = err {
+            // relayed, with what the server it came from said
+            let mut wire: Wire = serde_json::from_slice(status.details()).unwrap_or_default();
+            if !is_code(&wire.code) {
+                wire = Wire {
+                    code: "rpc".to_string(),
+                    ..Wire::default()
+                };
+            }
+            context.append(&mut wire.context);
+            wire.context = context;
+            return wire;
+        }
+        Wire {
+            code: err.code().to_string(),
+            args: err.args().unwrap_or_default(),
+            context,
+        }
+    }
 
+    // the fields of the variant, as text, for the variants which can be made
+    // again from them by [TribblerError::from_parts]
+    fn args(&self) -> Option<Vec<String>> {
+        let args = match self {
+            TribblerError::UserDoesNotExist(a)
+            | TribblerError::UsernameTaken(a)
+            | TribblerError::InvalidUsername(a)
+            | TribblerError::RpcError(a)
+            | TribblerError::WhoWhom(a)
+            | TribblerError::NotACounter(a)
+            | TribblerError::ProfileTooLong(a)
+            | TribblerError::InvalidAvatar(a)
+            | TribblerError::InvalidListName(a)
+            | TribblerError::InvalidAttachment(a)
+            | TribblerError::Banned(a)
+            | TribblerError::AlreadyBanned(a)
+            | TribblerError::NotBanned(a)
+            | TribblerError::NotSignedIn(a)
+            | TribblerError::WrongPassword(a)
+            | TribblerError::SsoFailed(a)
+            | TribblerError::QuotaExceeded(a)
+            | TribblerError::StorageFull(a)
+            | TribblerError::ClusterDegraded(a)
+            | TribblerError::CorruptLogEntry(a)
+            | TribblerError::CacheFailure(a)
+            | TribblerError::InvalidConfig(a)
+            | TribblerError::Unknown(a) => vec![a.to_string()],
+            TribblerError::AlreadyFollowing(a, b)
+            | TribblerError::NotFollowing(a, b)
+            | TribblerError::AlreadyBlocked(a, b)
+            | TribblerError::NotBlocked(a, b)
+            | TribblerError::AlreadyMuted(a, b)
+            | TribblerError::NotMuted(a, b)
+            | TribblerError::BlockedBy(a, b)
+            | TribblerError::ListExists(a, b)
+            | TribblerError::NoSuchList(a, b)
+            | TribblerError::AlreadyInList(a, b) => vec![a.to_string(), b.to_string()],
+            TribblerError::TribNotFound(a, n) | TribblerError::NoSuchDraft(a, n) => {
+                vec![a.to_string(), n.to_string()]
+            }
+            TribblerError::AlreadyLiked(a, b, n) | TribblerError::NotLiked(a, b, n) => {
+                vec![a.to_string(), b.to_string(), n.to_string()]
+            }
+            TribblerError::TimedOut(n) | TribblerError::RateLimited { retry_after: n } => {
+                vec![n.to_string()]
+            }
+            TribblerError::FollowingTooMany
+            | TribblerError::TribTooLong
+            | TribblerError::MaxedSeq
+            | TribblerError::ClusterNotReady
+            | TribblerError::TooManyDrafts
+            | TribblerError::TooManyAttachments
+            | TribblerError::NotAuthorized
+            | TribblerError::InvalidPassword => vec![],
+            // the errors of libraries, and those already relayed, cross as
+            // the status they came in
+            _ => return None,
+        };
+        Some(args)
+    }
+
+    // the variant of `code` with the fields `args`, if there is one
+    fn from_parts(code: &str, args: &[String]) -> Option<TribblerError> {
+        let s = |a: &String| a.to_string();
+        let n = |a: &String| a.parse::<u64>().ok();
+        let err = match (code, args) {
+            ("user_does_not_exist", [a]) => TribblerError::UserDoesNotExist(s(a)),
+            ("username_taken", [a]) => TribblerError::UsernameTaken(s(a)),
+            ("invalid_username", [a]) => TribblerError::InvalidUsername(s(a)),
+            ("rpc", [a]) => TribblerError::RpcError(s(a)),
+            ("following_too_many", []) => TribblerError::FollowingTooMany,
+            ("already_following", [a, b]) => TribblerError::AlreadyFollowing(s(a), s(b)),
+            ("not_following", [a, b]) => TribblerError::NotFollowing(s(a), s(b)),
+            ("trib_too_long", []) => TribblerError::TribTooLong,
+            ("follow_self", [a]) => TribblerError::WhoWhom(s(a)),
+            ("maxed_seq", []) => TribblerError::MaxedSeq,
+            ("trib_not_found", [a, c]) => TribblerError::TribNotFound(s(a), n(c)?),
+            ("already_liked", [a, b, c]) => TribblerError::AlreadyLiked(s(a), s(b), n(c)?),
+            ("not_liked", [a, b, c]) => TribblerError::NotLiked(s(a), s(b), n(c)?),
+            ("cluster_not_ready", []) => TribblerError::ClusterNotReady,
+            ("not_a_counter", [a]) => TribblerError::NotACounter(s(a)),
+            ("profile_too_long", [a]) => TribblerError::ProfileTooLong(s(a)),
+            ("already_blocked", [a, b]) => TribblerError::AlreadyBlocked(s(a), s(b)),
+            ("not_blocked", [a, b]) => TribblerError::NotBlocked(s(a), s(b)),
+            ("already_muted", [a, b]) => TribblerError::AlreadyMuted(s(a), s(b)),
+            ("not_muted", [a, b]) => TribblerError::NotMuted(s(a), s(b)),
+            ("blocked_by", [a, b]) => TribblerError::BlockedBy(s(a), s(b)),
+            ("invalid_avatar", [a]) => TribblerError::InvalidAvatar(s(a)),
+            ("invalid_list_name", [a]) => TribblerError::InvalidListName(s(a)),
+            ("list_exists", [a, b]) => TribblerError::ListExists(s(a), s(b)),
+            ("no_such_list", [a, b]) => TribblerError::NoSuchList(s(a), s(b)),
+            ("already_in_list", [a, b]) => TribblerError::AlreadyInList(s(a), s(b)),
+            ("too_many_drafts", []) => TribblerError::TooManyDrafts,
+            ("no_such_draft", [a, c]) => TribblerError::NoSuchDraft(s(a), n(c)?),
+            ("too_many_attachments", []) => TribblerError::TooManyAttachments,
+            ("invalid_attachment", [a]) => TribblerError::InvalidAttachment(s(a)),
+            ("banned", [a]) => TribblerError::Banned(s(a)),
+            ("already_banned", [a]) => TribblerError::AlreadyBanned(s(a)),
+            ("not_banned", [a]) => TribblerError::NotBanned(s(a)),
+            ("not_authorized", []) => TribblerError::NotAuthorized,
+            ("not_signed_in", [a]) => TribblerError::NotSignedIn(s(a)),
+            ("invalid_password", []) => TribblerError::InvalidPassword,
+            ("wrong_password", [a]) => TribblerError::WrongPassword(s(a)),
+            ("sso_failed", [a]) => TribblerError::SsoFailed(s(a)),
+            ("timed_out", [c]) => TribblerError::TimedOut(n(c)?),
+            ("quota_exceeded", [a]) => TribblerError::QuotaExceeded(s(a)),
+            ("rate_limited", [c]) => TribblerError::RateLimited { retry_after: n(c)? },
+            ("storage_full", [a]) => TribblerError::StorageFull(s(a)),
+            ("cluster_degraded", [a]) => TribblerError::ClusterDegraded(s(a)),
+            ("corrupt_log_entry", [a]) => TribblerError::CorruptLogEntry(s(a)),
+            ("cache_failure", [a]) => TribblerError::CacheFailure(s(a)),
+            ("invalid_config", [a]) => TribblerError::InvalidConfig(s(a)),
+            ("unknown", [a]) => TribblerError::Unknown(s(a)),
+            _ => return None,
+        };
+        Some(err)
+    }
+}
+
+/// Takes a status an RPC was answered with back to the error the server ran
+/// into, the same variant with the same fields and context, as the details of
+/// the status carry it. A status which carries none, or the error of a
+/// library, which can't be made again, is kept as [TribblerError::Status].
 impl From<tonic::Status> for TribblerError {
-    fn from(v: tonic::Status) -> Self {
-        TribblerError::RpcError(format!("{:?}", v))
+    fn from(status: tonic::Status) -> Self {
+        let wire = match serde_json::from_slice::<Wire>(status.details()) {
+            Ok(wire) => wire,
+            Err(_) => return TribblerError::Status(Box::new(status)),
+        };
+        match TribblerError::from_parts(&wire.code, &wire.args) {
+            Some(err) => wire
+                .context
+                .into_iter()
+                .rev()
+                .fold(err, |err, context| err.context(context)),
+            None => TribblerError::Status(Box::new(status)),
+        }
+    }
+}
+
+/// Answers an RPC with the error a server ran into: the status has the
+/// message of the error, and the details carry the error itself, its
+/// [code](TribblerError::code) along with its fields and context, so that
+/// the client gets the same error back, or tells what went wrong at least.
+impl From<TribblerError> for tonic::Status {
+    fn from(err: TribblerError) -> Self {
+        if let TribblerError::Status(status) = err {
+            return *status; // relayed as the server it came from put it
+        }
+        let code = match err.root() {
+            TribblerError::UserDoesNotExist(_)
+            | TribblerError::TribNotFound(_, _)
+            | TribblerError::NoSuchList(_, _)
+            | TribblerError::NoSuchDraft(_, _) => tonic::Code::NotFound,
+            TribblerError::UsernameTaken(_) | TribblerError::ListExists(_, _) => {
+                tonic::Code::AlreadyExists
+            }
+            TribblerError::NotAuthorized
+            | TribblerError::NotSignedIn(_)
+            | TribblerError::WrongPassword(_)
+            | TribblerError::SsoFailed(_) => tonic::Code::Unauthenticated,
+            TribblerError::Banned(_) | TribblerError::BlockedBy(_, _) => {
+                tonic::Code::PermissionDenied
+            }
+            TribblerError::ClusterNotReady | TribblerError::ClusterDegraded(_) => {
+                tonic::Code::Unavailable
+            }
+            TribblerError::TimedOut(_) => tonic::Code::DeadlineExceeded,
+            TribblerError::QuotaExceeded(_)
+            | TribblerError::RateLimited { .. }
+            | TribblerError::StorageFull(_) => tonic::Code::ResourceExhausted,
+            TribblerError::Status(status) => status.code(),
+            TribblerError::InvalidUsername(_)
+            | TribblerError::TribTooLong
+            | TribblerError::WhoWhom(_)
+            | TribblerError::ProfileTooLong(_)
+            | TribblerError::InvalidAvatar(_)
+            | TribblerError::InvalidListName(_)
+            | TribblerError::InvalidAttachment(_)
+            | TribblerError::InvalidPassword => tonic::Code::InvalidArgument,
+            TribblerError::FollowingTooMany
+            | TribblerError::AlreadyFollowing(_, _)
+            | TribblerError::NotFollowing(_, _)
+            | TribblerError::AlreadyLiked(_, _, _)
+            | TribblerError::NotLiked(_, _, _)
+            | TribblerError::NotACounter(_)
+            | TribblerError::AlreadyBlocked(_, _)
+            | TribblerError::NotBlocked(_, _)
+            | TribblerError::AlreadyMuted(_, _)
+            | TribblerError::NotMuted(_, _)
+            | TribblerError::AlreadyInList(_, _)
+            | TribblerError::TooManyDrafts
+            | TribblerError::TooManyAttachments
+            | TribblerError::AlreadyBanned(_)
+            | TribblerError::NotBanned(_)
+            | TribblerError::InvalidConfig(_) => tonic::Code::FailedPrecondition,
+            _ => tonic::Code::Internal,
+        };
+        let details = serde_json::to_vec(&err.wire()).unwrap_or_default();
+        tonic::Status::with_details(code, err.to_string(), Bytes::from(details))
     }
 }
 
-impl From<tonic::transport::Error> for TribblerError {
-    fn from(v: tonic::transport::Error) -> Self {
-        TribblerError::RpcError(format!("{:?}", v))
+/// A [Result] type which either returns `T` or a [TribblerError], so callers
+/// can match on what went wrong.
+pub type TribResult<T> = Result<T, TribblerError>;
+
+/// Adds context to the error of a result, as [TribblerError::context] does,
+/// converting it into a [TribblerError] first.
+pub trait Context<T> {
+    /// Wraps the error, if any, with `context`.
+    fn context(self, context: impl Into<String>) -> TribResult<T>;
+
+    /// Wraps the error, if any, with the context `f` makes, which is only
+    /// made when there is an error.
+    fn with_context<S: Into<String>>(self, f: impl FnOnce() -> S) -> TribResult<T>;
+}
+
+impl<T, E: Into<TribblerError>> Context<T> for Result<T, E> {
+    fn context(self, context: impl Into<String>) -> TribResult<T> {
+        self.map_err(|e| e.into().context(context))
+    }
+
+    fn with_context<S: Into<String>>(self, f: impl FnOnce() -> S) -> TribResult<T> {
+        self.map_err(|e| e.into().context(f()))
     }
 }
 
-/// A [Result] type which either returns `T` or a [boxed error](https://doc.rust-lang.org/rust-by-example/error/multiple_error_types/boxing_errors.html)
-pub type TribResult<T> = Result<T, Box<(dyn Error + Send + Sync)>>;
+/// The [Result] type of the [crate::trib::Server] methods, the same as
+/// [TribResult].
+pub type ServerResult<T> = TribResult<T>;
+
+#[cfg(test)]
+mod test {
+    use super::{Context, TribResult, TribblerError};
+    use std::error::Error;
+
+    // the error as it comes out of an RPC answered with it
+    fn across(err: TribblerError) -> TribblerError {
+        TribblerError::from(tonic::Status::from(err))
+    }
+
+    #[test]
+    fn test_code_crosses_rpc() {
+        let sent = TribblerError::UsernameTaken("alice".to_string());
+        let status = tonic::Status::from(sent);
+        assert_eq!(tonic::Code::AlreadyExists, status.code());
+        assert_eq!("username \"alice\" already taken", status.message());
+        let received = TribblerError::from(status);
+        assert_eq!("username_taken", received.code());
+
+        // the error of a library crosses as a status with its code, and is
+        // relayed as it came
+        let parsed = "x".parse::<u64>().unwrap_err();
+        let received = across(TribblerError::from(parsed).context("reading the seq"));
+        assert!(matches!(received, TribblerError::Status(_)));
+        assert_eq!("invalid_number", received.code());
+        assert!(received
+            .to_string()
+            .starts_with("rpc error: reading the seq: "));
+        let relayed = across(received.context("relaying"));
+        assert!(matches!(relayed, TribblerError::Status(_)));
+        assert_eq!("invalid_number", relayed.code());
+
+        // a status which carries no code of ours
+        let foreign = tonic::Status::with_details(tonic::Code::Internal, "oops", "\x08\x01".into());
+        assert_eq!("rpc", TribblerError::from(foreign).code());
+        assert_eq!(
+            "rpc",
+            TribblerError::from(tonic::Status::unknown("oops")).code()
+        );
+    }
+
+    #[test]
+    fn test_variant_crosses_rpc() {
+        let name = || "alice".to_string();
+        let sent = vec![
+            TribblerError::UserDoesNotExist(name()),
+            TribblerError::RpcError("no replica of the bin is up".to_string()),
+            TribblerError::FollowingTooMany,
+            TribblerError::AlreadyFollowing(name(), "bob".to_string()),
+            TribblerError::TribNotFound(name(), 42),
+            TribblerError::NotLiked(name(), "bob".to_string(), u64::MAX),
+            TribblerError::NotACounter("alice::n".to_string()),
+            TribblerError::NoSuchDraft(name(), 7),
+            TribblerError::TimedOut(5),
+            TribblerError::QuotaExceeded("tribs of alice".to_string()),
+            TribblerError::RateLimited { retry_after: 3 },
+            TribblerError::StorageFull("localhost:3000".to_string()),
+            TribblerError::ClusterDegraded("1 of 3 replicas took the write, 2 needed".to_string()),
+            TribblerError::CorruptLogEntry("7::follow".to_string()),
+            TribblerError::CacheFailure("signup cache poisoned".to_string()),
+            TribblerError::InvalidConfig("keeper: the failure threshold is 0".to_string()),
+            TribblerError::Unknown("lock poisoned".to_string()),
+        ];
+        for err in sent {
+            let message = err.to_string();
+            let received = across(err);
+            assert_eq!(message, received.to_string());
+            assert!(!matches!(received, TribblerError::Status(_)), "{}", message);
+        }
+        assert!(matches!(
+            across(TribblerError::NotLiked(name(), "bob".to_string(), 9)),
+            TribblerError::NotLiked(a, b, 9) if a == "alice" && b == "bob"
+        ));
+
+        // the context crosses along with the error
+        let sent = TribblerError::NotACounter("n".to_string())
+            .context("incr in bin alice")
+            .context("posting for alice");
+        let message = sent.to_string();
+        let received = across(sent);
+        assert_eq!(message, received.to_string());
+        assert!(matches!(received.root(), TribblerError::NotACounter(key) if key == "n"));
+    }
+
+    #[test]
+    fn test_context_chain() {
+        let failed: TribResult<()> = Err(TribblerError::UserDoesNotExist("bob".to_string()));
+        let err = failed
+            .context("list_get in bin bob on http://127.0.0.1:3000")
+            .with_context(|| "home of bob")
+            .unwrap_err();
+        assert_eq!(
+            "home of bob: list_get in bin bob on http://127.0.0.1:3000: user \"bob\" does not exist",
+            err.to_string()
+        );
+        assert!(matches!(err.root(), TribblerError::UserDoesNotExist(_)));
+        assert_eq!("user_does_not_exist", err.code());
+
+        // the source chain walks down to the error beneath
+        let mut chain = vec![];
+        let mut next: Option<&dyn Error> = Some(&err);
+        while let Some(e) = next {
+            chain.push(e.to_string());
+            next = e.source();
+        }
+        assert_eq!(3, chain.len());
+        assert_eq!("user \"bob\" does not exist", chain[2]);
+
+        // the status of an RPC answered with it is that of the error beneath
+        let status = tonic::Status::from(err);
+        assert_eq!(tonic::Code::NotFound, status.code());
+        assert_eq!("user_does_not_exist", TribblerError::from(status).code());
 
-impl From<Box<dyn Error>> for TribblerError {
-    fn from(x: Box<dyn Error>) -> Self {
-        TribblerError::Unknown(x.to_string())
+        // other errors convert on the way
+        let parsed = "x"
+            .parse::<i64>()
+            .context("reading the counter")
+            .unwrap_err();
+        assert_eq!("invalid_number", parsed.code());
     }
 }
@@ -20,4 +20,6 @@ pub mod ref_impl;
 /// protobuf-generated RPC stubs and message structs
 pub mod rpc;
 pub mod storage;
+pub mod testing;
+pub mod time;
 pub mod trib;
@@ -3,7 +3,7 @@
 #![allow(dead_code)]
 use std::{
     cmp::{min, Ordering},
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{
         atomic::{self, AtomicU64},
         Arc, RwLock,
@@ -13,8 +13,15 @@ use std::{
 use async_trait::async_trait;
 
 use crate::{
-    err::{TribResult, TribblerError},
-    trib::{is_valid_username, Server, Trib, MAX_TRIB_FETCH, MAX_TRIB_LEN, MIN_LIST_USER},
+    config::Limits,
+    err::{ServerResult, TribblerError},
+    time::{SystemClock, TimeSource},
+    trib::{
+        first_trending_slot, hashtags, is_valid_username, mentioned_users, normalize_tag,
+        top_trends, trending_slot, validate_attachments, Attachment, Draft, Notification,
+        NotificationKind, Profile, ScheduledPost, Server, Trend, Trib, TribRef, UserExport,
+        MAX_DRAFTS,
+    },
 };
 
 /// The [User] type holds the data on tribs the user has posted along with
@@ -25,6 +32,19 @@ struct User {
     followers: HashSet<String>,
     seq_tribs: Vec<SeqTrib>,
     tribs: Vec<Arc<Trib>>,
+    likes: HashMap<u64, BTreeSet<String>>,
+    replies: HashMap<u64, Vec<TribRef>>,
+    mentions: Vec<TribRef>,
+    profile: Profile,
+    blocked: BTreeSet<String>,
+    muted: BTreeSet<String>,
+    notifications: Vec<Notification>,
+    read_upto: u64,
+    lists: HashMap<String, BTreeSet<String>>,
+    drafts: Vec<Draft>,
+    next_draft: u64,
+    scheduled: Vec<ScheduledPost>,
+    last_active: Option<u64>,
 }
 
 /// A [Trib] type with an additional sequence number
@@ -44,7 +64,7 @@ impl Eq for SeqTrib {}
 
 impl PartialOrd for SeqTrib {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.seq.partial_cmp(&other.seq)
+        Some(self.cmp(other))
     }
 }
 
@@ -62,6 +82,19 @@ impl User {
             followers: HashSet::new(),
             seq_tribs: vec![],
             tribs: vec![],
+            likes: HashMap::new(),
+            replies: HashMap::new(),
+            mentions: vec![],
+            profile: Profile::default(),
+            blocked: BTreeSet::new(),
+            muted: BTreeSet::new(),
+            notifications: vec![],
+            read_upto: 0,
+            lists: HashMap::new(),
+            drafts: vec![],
+            next_draft: 1,
+            scheduled: vec![],
+            last_active: None,
         }
     }
 
@@ -90,24 +123,30 @@ impl User {
         self.followers.remove(who);
     }
 
+    /// lets this [User] know that `from` did something involving them
+    fn notify(&mut self, kind: NotificationKind, from: &str, trib: Option<TribRef>) {
+        let clock = self.notifications.last().map_or(1, |n| n.clock + 1);
+        self.notifications.push(Notification {
+            kind,
+            from: from.to_string(),
+            trib,
+            clock,
+            read: false,
+        });
+    }
+
     /// lists the [User]s that this user follows
     fn list_following(&self) -> Vec<String> {
         self.following.iter().map(String::clone).collect()
     }
 
-    /// instructs this [User] to post a new [Trib] with the given parameters
+    /// instructs this [User] to post the given [Trib], sequenced by its clock
     /// returns a reference to the posted [Trib]
-    fn post(&mut self, who: &str, msg: &str, seq: u64, time: u64) -> Arc<Trib> {
-        // make the new trib
-        let trib = Arc::new(Trib {
-            user: who.to_string(),
-            message: msg.to_string(),
-            time,
-            clock: seq,
-        });
+    fn post(&mut self, trib: Trib) -> Arc<Trib> {
+        let trib = Arc::new(trib);
         // append sequential number
         let seq_trib = SeqTrib {
-            seq,
+            seq: trib.clock,
             trib: trib.clone(),
         };
 
@@ -117,11 +156,50 @@ impl User {
         trib
     }
 
-    /// Gets the list of [Trib]s posted by this [User]
-    fn list_tribs(&self) -> &[Arc<Trib>] {
+    /// replaces the message of the [Trib] posted at `clock`, returning the old
+    /// and the new version of the trib
+    fn edit(&mut self, clock: u64, msg: &str) -> Option<(Arc<Trib>, Arc<Trib>)> {
+        let old = self.tribs.iter().find(|t| t.clock == clock)?.clone();
+        let new = Arc::new(Trib {
+            message: msg.to_string(),
+            revision: old.revision + 1,
+            ..(*old).clone()
+        });
+        for trib in self.tribs.iter_mut() {
+            if Arc::ptr_eq(trib, &old) {
+                *trib = new.clone();
+            }
+        }
+        for seq_trib in self.seq_tribs.iter_mut() {
+            if Arc::ptr_eq(&seq_trib.trib, &old) {
+                seq_trib.trib = new.clone();
+            }
+        }
+        Some((old, new))
+    }
+
+    /// hands every [Trib] of this [User] over to `new`
+    fn rename(&mut self, new: &str) {
+        let mut renamed = HashMap::<u64, Arc<Trib>>::new();
+        for trib in self.tribs.iter_mut() {
+            *trib = Arc::new(Trib {
+                user: new.to_string(),
+                ..(**trib).clone()
+            });
+            renamed.insert(trib.clock, trib.clone());
+        }
+        for seq_trib in self.seq_tribs.iter_mut() {
+            if let Some(trib) = renamed.get(&seq_trib.trib.clock) {
+                seq_trib.trib = trib.clone();
+            }
+        }
+    }
+
+    /// Gets the `max` most recent [Trib]s posted by this [User]
+    fn list_tribs(&self, max: usize) -> &[Arc<Trib>] {
         let ntrib = self.tribs.len();
-        let start = match ntrib.cmp(&MAX_TRIB_FETCH) {
-            Ordering::Greater => ntrib - MAX_TRIB_FETCH,
+        let start = match ntrib.cmp(&max) {
+            Ordering::Greater => ntrib - max,
             _ => 0,
         };
         &self.tribs[start..]
@@ -140,53 +218,212 @@ impl User {
 ///
 /// ```rust
 /// use std::sync::Arc;
-/// use std::thread;
 /// use tribbler::ref_impl::RefServer;
 /// use tribbler::trib::Server;
 ///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
 /// let server = Arc::new(RefServer::default());
 /// let s = server.clone();
-/// let handle = thread::spawn(move ||{
-///     s.sign_up("user2");
+/// let handle = tokio::spawn(async move {
+///     s.sign_up("user2").await.unwrap();
 /// });
-/// server.sign_up("user1");
-/// let _ = handle.join();
-/// assert_eq!(server.list_users().unwrap().len(), 2);
-///
+/// server.sign_up("user1").await.unwrap();
+/// let _ = handle.await;
+/// assert_eq!(server.list_users().await.unwrap().len(), 2);
+/// # });
 /// ```
 pub struct RefServer {
     users: Arc<RwLock<HashMap<String, User>>>,
     homes: Arc<RwLock<HashMap<String, Vec<Arc<Trib>>>>>,
+    tags: Arc<RwLock<HashMap<String, Vec<TribRef>>>>,
+    trends: Arc<RwLock<HashMap<u64, HashMap<String, u64>>>>,
+    banned: Arc<RwLock<BTreeSet<String>>>,
     seq: AtomicU64,
+    limits: Limits,
+    time: Arc<dyn TimeSource>,
 }
 
 impl RefServer {
     /// Creates a [RefServer] with no data
     pub fn new() -> RefServer {
+        RefServer::with_limits(Limits::default())
+    }
+
+    /// Creates a [RefServer] with no data, enforcing the given [Limits]
+    pub fn with_limits(limits: Limits) -> RefServer {
+        RefServer::with_time(limits, Arc::new(SystemClock))
+    }
+
+    /// Creates a [RefServer] with no data, enforcing the given [Limits] and
+    /// dating everything by `time`
+    pub fn with_time(limits: Limits, time: Arc<dyn TimeSource>) -> RefServer {
         RefServer {
             users: Arc::new(RwLock::new(HashMap::new())),
             homes: Arc::new(RwLock::new(HashMap::new())),
+            tags: Arc::new(RwLock::new(HashMap::new())),
+            trends: Arc::new(RwLock::new(HashMap::new())),
+            banned: Arc::new(RwLock::new(BTreeSet::new())),
             seq: AtomicU64::new(0),
+            limits,
+            time,
         }
     }
 
     /// rebuilds the users' homepage based on the current set of [SeqTrib]s and
     /// other users' tribs
     fn rebuild_home(&self, who: &User, users: &HashMap<String, User>) -> Vec<Arc<Trib>> {
-        let mut home: Vec<SeqTrib> = vec![];
-        home.append(&mut who.seq_tribs.clone());
-        for user in who.following.iter() {
-            match users.get(user) {
-                Some(v) => {
-                    home.append(&mut v.seq_tribs.clone());
+        let followees = who.following.iter().filter_map(|user| users.get(user));
+        merge_timelines(std::iter::once(who).chain(followees))
+    }
+
+    /// records that `who` acted just now, for [Server::last_active]
+    fn touch(&self, who: &str) -> ServerResult<()> {
+        let now = self.time.now()?;
+        if let Some(user) = self.users.write().unwrap().get_mut(who) {
+            user.last_active = Some(now);
+        }
+        Ok(())
+    }
+
+    /// the home timeline of `user` without the tribs of the users they block
+    /// or mute, or of the banned users
+    fn visible_home(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let homes = self.homes.read().unwrap();
+        let banned = self.banned.read().unwrap();
+        match (users.get(user), homes.get(user)) {
+            (Some(u), Some(home)) => Ok(home
+                .iter()
+                .filter(|t| !u.blocked.contains(&t.user) && !u.muted.contains(&t.user))
+                .filter(|t| !banned.contains(&t.user))
+                .cloned()
+                .collect()),
+            _ => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    /// posts a [Trib] for `who` and puts it on the timelines of `who` and
+    /// their followers
+    fn publish(
+        &self,
+        who: &str,
+        post: &str,
+        clock: u64,
+        retrib_of: Option<TribRef>,
+        reply_to: Option<TribRef>,
+        attachments: Vec<Attachment>,
+    ) -> ServerResult<Arc<Trib>> {
+        if post.len() > self.limits.max_trib_len {
+            return Err(TribblerError::TribTooLong);
+        }
+        validate_attachments(&attachments)?;
+        if self.banned.read().unwrap().contains(who) {
+            return Err(TribblerError::Banned(who.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        match users.get(who) {
+            Some(_) => {
+                if self.seq.load(atomic::Ordering::SeqCst) == u64::MAX {
+                    return Err(TribblerError::MaxedSeq);
                 }
-                None => continue,
-            };
+                let _ = self.seq.fetch_update(
+                    atomic::Ordering::SeqCst,
+                    atomic::Ordering::SeqCst,
+                    |v| {
+                        if v < clock {
+                            Some(clock)
+                        } else {
+                            None
+                        }
+                    },
+                );
+
+                let trib = Trib {
+                    user: who.to_string(),
+                    message: post.to_string(),
+                    time: self.time.now()?,
+                    clock: self.seq.fetch_add(1, atomic::Ordering::SeqCst),
+                    revision: 0,
+                    retrib_of,
+                    reply_to,
+                    attachments,
+                };
+                self.store(&mut users, trib)
+            }
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
-        home.sort();
-        home.iter()
-            .map(|x| x.trib.clone())
-            .collect::<Vec<Arc<Trib>>>()
+    }
+
+    /// stores a [Trib], clock and all, as posted by its user: on their own
+    /// tribs, on the timelines of the user and their followers, and in the
+    /// indexes of its mentions and hashtags
+    fn store(&self, users: &mut HashMap<String, User>, trib: Trib) -> ServerResult<Arc<Trib>> {
+        let who = trib.user.to_string();
+        let user = match users.get_mut(&who) {
+            Some(user) => user,
+            None => return Err(TribblerError::UserDoesNotExist(who)),
+        };
+        let trib = user.post(trib);
+        // add it to the timeline of my followers
+        let mut homes = self.homes.write().unwrap();
+        for follower in user.followers.iter() {
+            homes
+                .entry(follower.to_string())
+                .and_modify(|e| e.push(trib.clone()));
+        }
+        // add it to my own timeline
+        homes
+            .entry(who.to_string())
+            .and_modify(|e| e.push(trib.clone()));
+        // let the mentioned users know
+        for name in mentioned_users(&trib.message) {
+            if let Some(mentioned) = users.get_mut(&name) {
+                let mention = TribRef {
+                    user: who.to_string(),
+                    clock: trib.clock,
+                };
+                mentioned.mentions.push(mention.clone());
+                if name != who {
+                    mentioned.notify(NotificationKind::Mention, &who, Some(mention));
+                }
+            }
+        }
+        // index it under its hashtags, and count them towards trending
+        let mut tags = self.tags.write().unwrap();
+        let mut trends = self.trends.write().unwrap();
+        let slot = trends.entry(trending_slot(self.time.now()?)).or_default();
+        for tag in hashtags(&trib.message) {
+            *slot.entry(tag.clone()).or_default() += 1;
+            tags.entry(tag).or_default().push(TribRef {
+                user: who.to_string(),
+                clock: trib.clock,
+            });
+        }
+        Ok(trib)
+    }
+
+    /// posts the scheduled [Trib]s whose time has come. The reference server
+    /// has no keeper to do this, so it is done whenever a timeline is read.
+    fn publish_due(&self) -> ServerResult<()> {
+        let now = self.time.now()?;
+        let mut due = vec![];
+        {
+            let mut users = self.users.write().unwrap();
+            for (name, user) in users.iter_mut() {
+                let (ready, waiting): (Vec<ScheduledPost>, Vec<ScheduledPost>) =
+                    user.scheduled.drain(..).partition(|p| p.publish_at <= now);
+                user.scheduled = waiting;
+                due.extend(ready.into_iter().map(|p| (name.to_string(), p)));
+            }
+        }
+        // the scheduled posts of banned users are dropped
+        let banned = self.banned.read().unwrap().clone();
+        due.retain(|(who, _)| !banned.contains(who));
+        due.sort_by_key(|(_, p)| (p.publish_at, p.id));
+        for (who, post) in due {
+            self.publish(&who, &post.message, 0, None, None, vec![])?;
+        }
+        Ok(())
     }
 }
 
@@ -198,13 +435,13 @@ impl Default for RefServer {
 
 #[async_trait]
 impl Server for RefServer {
-    async fn sign_up(&self, user: &str) -> TribResult<()> {
+    async fn sign_up(&self, user: &str) -> ServerResult<()> {
         let mut users = self.users.write().unwrap();
         if !is_valid_username(user) {
-            return Err(Box::new(TribblerError::InvalidUsername(user.to_string())));
+            return Err(TribblerError::InvalidUsername(user.to_string()));
         }
         match users.contains_key(user) {
-            true => Err(Box::new(TribblerError::UsernameTaken(user.to_string()))),
+            true => Err(TribblerError::UsernameTaken(user.to_string())),
             false => {
                 users.insert(user.to_string(), User::new());
                 let mut homes = self.homes.write().unwrap();
@@ -214,11 +451,11 @@ impl Server for RefServer {
         }
     }
 
-    async fn list_users(&self) -> TribResult<Vec<String>> {
+    async fn list_users(&self) -> ServerResult<Vec<String>> {
         let users = self.users.read().unwrap();
         let mut k: Vec<&String> = users.keys().collect();
         k.sort();
-        let sorted = k[..min(MIN_LIST_USER, k.len())].to_vec();
+        let sorted = k[..min(self.limits.min_list_user, k.len())].to_vec();
         let res: Vec<String> = sorted
             .iter()
             .map(|x| x.to_string())
@@ -226,82 +463,74 @@ impl Server for RefServer {
         Ok(res)
     }
 
-    async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()> {
-        if post.len() > MAX_TRIB_LEN {
-            return Err(Box::new(TribblerError::TribTooLong));
-        }
-        let mut users = self.users.write().unwrap();
-        match users.get_mut(who) {
-            Some(user) => {
-                if self.seq.load(atomic::Ordering::SeqCst) == u64::MAX {
-                    return Err(Box::new(TribblerError::MaxedSeq));
-                }
-                let _ = self.seq.fetch_update(
-                    atomic::Ordering::SeqCst,
-                    atomic::Ordering::SeqCst,
-                    |v| {
-                        if v < clock {
-                            Some(clock)
-                        } else {
-                            None
-                        }
-                    },
-                );
+    async fn signed_up(&self, user: &str) -> ServerResult<bool> {
+        Ok(self.users.read().unwrap().contains_key(user))
+    }
 
-                let trib = user.post(
-                    who,
-                    post,
-                    self.seq.fetch_add(1, atomic::Ordering::SeqCst),
-                    clock,
-                );
-                // add it to the timeline of my followers
-                let mut homes = self.homes.write().unwrap();
-                for follower in user.followers.iter() {
-                    homes
-                        .entry(follower.to_string())
-                        .and_modify(|e| e.push(trib.clone()));
-                }
-                // add it to my own timeline
-                homes
-                    .entry(who.to_string())
-                    .and_modify(|e| e.push(trib.clone()));
-                Ok(())
-            }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
-        }
+    async fn post(&self, who: &str, post: &str, clock: u64) -> ServerResult<()> {
+        self.post_with_attachments(who, post, clock, &[]).await
     }
 
-    async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
+    async fn post_with_attachments(
+        &self,
+        who: &str,
+        post: &str,
+        clock: u64,
+        attachments: &[Attachment],
+    ) -> ServerResult<()> {
+        self.touch(who)?;
+        self.publish(who, post, clock, None, None, attachments.to_vec())?;
+        Ok(())
+    }
+
+    async fn clock(&self) -> ServerResult<u64> {
+        Ok(self.seq.load(atomic::Ordering::SeqCst))
+    }
+
+    async fn tribs(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        self.publish_due()?;
         let users = self.users.read().unwrap();
         match users.get(user) {
-            Some(user) => Ok(user.list_tribs().to_vec()),
-            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
+            Some(user) => Ok(user.list_tribs(self.limits.max_trib_fetch).to_vec()),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
         }
     }
 
-    async fn follow(&self, who: &str, whom: &str) -> TribResult<()> {
+    async fn follow(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+        if self.banned.read().unwrap().contains(who) {
+            return Err(TribblerError::Banned(who.to_string()));
         }
         let mut users = self.users.write().unwrap();
-        if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string())));
+        match users.get(whom) {
+            Some(u) if u.blocked.contains(who) => {
+                return Err(TribblerError::BlockedBy(who.to_string(), whom.to_string()));
+            }
+            Some(_) => {}
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
         match users.get_mut(who) {
             Some(u) => {
                 if u.is_following(whom) {
-                    return Err(Box::new(TribblerError::AlreadyFollowing(
+                    return Err(TribblerError::AlreadyFollowing(
                         who.to_string(),
                         whom.to_string(),
-                    )));
+                    ));
+                }
+                if u.following.len() >= self.limits.max_following {
+                    return Err(TribblerError::FollowingTooMany);
                 }
                 u.follow(whom);
             }
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
         };
-        let _ = users
-            .entry(whom.to_string())
-            .and_modify(|e| e.add_follower(who));
+        let _ = users.entry(whom.to_string()).and_modify(|e| {
+            e.add_follower(who);
+            e.notify(NotificationKind::Follow, who, None);
+        });
         // rebuild home
         match users.get(who) {
             Some(user) => {
@@ -309,29 +538,30 @@ impl Server for RefServer {
                 homes.insert(who.to_string(), self.rebuild_home(user, &users));
                 Ok(())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
-    async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()> {
+    async fn unfollow(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
         }
         let mut users = self.users.write().unwrap();
         if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
         }
         match users.get_mut(who) {
             Some(u) => {
                 if !u.is_following(whom) {
-                    return Err(Box::new(TribblerError::NotFollowing(
+                    return Err(TribblerError::NotFollowing(
                         who.to_string(),
                         whom.to_string(),
-                    )));
+                    ));
                 }
                 u.unfollow(whom);
             }
-            None => return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string()))),
+            None => return Err(TribblerError::UserDoesNotExist(whom.to_string())),
         };
         let _ = users
             .entry(whom.to_string())
@@ -343,45 +573,908 @@ impl Server for RefServer {
                 homes.insert(who.to_string(), self.rebuild_home(user, &users));
                 Ok(())
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
-    async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool> {
+    async fn is_following(&self, who: &str, whom: &str) -> ServerResult<bool> {
         if who == whom {
-            return Err(Box::new(TribblerError::WhoWhom(who.to_string())));
+            return Err(TribblerError::WhoWhom(who.to_string()));
         }
         let users = self.users.read().unwrap();
         if !users.contains_key(whom) {
-            return Err(Box::new(TribblerError::UserDoesNotExist(whom.to_string())));
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
         }
         match users.get(who) {
             Some(user) => Ok(user.is_following(whom)),
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
-    async fn following(&self, who: &str) -> TribResult<Vec<String>> {
+    async fn following(&self, who: &str) -> ServerResult<Vec<String>> {
         let users = self.users.read().unwrap();
         match users.get(who) {
             Some(user) => Ok(user.list_following()),
-            None => Err(Box::new(TribblerError::UserDoesNotExist(who.to_string()))),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
         }
     }
 
-    async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>> {
-        let homes = self.homes.read().unwrap();
-        match homes.get(user) {
-            Some(home) => {
-                let ntrib = home.len();
-                let start = match ntrib.cmp(&MAX_TRIB_FETCH) {
-                    Ordering::Greater => ntrib - MAX_TRIB_FETCH,
-                    _ => 0,
-                };
-                // let hm = &home[start..];
-                Ok(home[start..].to_vec())
+    async fn last_active(&self, user: &str) -> ServerResult<Option<u64>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u.last_active),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn mutuals(&self, user: &str) -> ServerResult<Vec<String>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => {
+                let mut mutuals: Vec<String> =
+                    u.following.intersection(&u.followers).cloned().collect();
+                mutuals.sort();
+                Ok(mutuals)
+            }
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn home(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        self.publish_due()?;
+        let home = self.visible_home(user)?;
+        let ntrib = home.len();
+        let start = match ntrib.cmp(&self.limits.max_trib_fetch) {
+            Ordering::Greater => ntrib - self.limits.max_trib_fetch,
+            _ => 0,
+        };
+        Ok(home[start..].to_vec())
+    }
+
+    async fn edit_trib(&self, who: &str, clock: u64, message: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        if message.len() > self.limits.max_trib_len {
+            return Err(TribblerError::TribTooLong);
+        }
+        let mut users = self.users.write().unwrap();
+        let user = match users.get_mut(who) {
+            Some(user) => user,
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
+        };
+        let (old, new) = match user.edit(clock, message) {
+            Some(edited) => edited,
+            None => return Err(TribblerError::TribNotFound(who.to_string(), clock)),
+        };
+        // the trib may be on the timeline of every user
+        let mut homes = self.homes.write().unwrap();
+        for home in homes.values_mut() {
+            for trib in home.iter_mut() {
+                if Arc::ptr_eq(trib, &old) {
+                    *trib = new.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn like(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(who) {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        let user = match users.get_mut(author) {
+            Some(user) => user,
+            None => return Err(TribblerError::UserDoesNotExist(author.to_string())),
+        };
+        if !user.tribs.iter().any(|t| t.clock == clock) {
+            return Err(TribblerError::TribNotFound(author.to_string(), clock));
+        }
+        match user.likes.entry(clock).or_default().insert(who.to_string()) {
+            true => {
+                if who != author {
+                    let liked = TribRef {
+                        user: author.to_string(),
+                        clock,
+                    };
+                    user.notify(NotificationKind::Like, who, Some(liked));
+                }
+                Ok(())
+            }
+            false => Err(TribblerError::AlreadyLiked(
+                who.to_string(),
+                author.to_string(),
+                clock,
+            )),
+        }
+    }
+
+    async fn unlike(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(who) {
+            return Err(TribblerError::UserDoesNotExist(who.to_string()));
+        }
+        let user = match users.get_mut(author) {
+            Some(user) => user,
+            None => return Err(TribblerError::UserDoesNotExist(author.to_string())),
+        };
+        match user.likes.get_mut(&clock).map(|l| l.remove(who)) {
+            Some(true) => Ok(()),
+            _ => Err(TribblerError::NotLiked(
+                who.to_string(),
+                author.to_string(),
+                clock,
+            )),
+        }
+    }
+
+    async fn likes(&self, author: &str, clock: u64) -> ServerResult<Vec<String>> {
+        let users = self.users.read().unwrap();
+        match users.get(author) {
+            Some(user) => Ok(user
+                .likes
+                .get(&clock)
+                .map(|l| l.iter().cloned().collect())
+                .unwrap_or_default()),
+            None => Err(TribblerError::UserDoesNotExist(author.to_string())),
+        }
+    }
+
+    async fn retrib(&self, who: &str, author: &str, clock: u64) -> ServerResult<()> {
+        self.touch(who)?;
+        let original = {
+            let users = self.users.read().unwrap();
+            if !users.contains_key(who) {
+                return Err(TribblerError::UserDoesNotExist(who.to_string()));
+            }
+            let user = match users.get(author) {
+                Some(user) => user,
+                None => return Err(TribblerError::UserDoesNotExist(author.to_string())),
+            };
+            match user.tribs.iter().find(|t| t.clock == clock) {
+                Some(trib) => trib.clone(),
+                None => return Err(TribblerError::TribNotFound(author.to_string(), clock)),
+            }
+        };
+        let retrib_of = original.retrib_of.clone().unwrap_or(TribRef {
+            user: original.user.clone(),
+            clock: original.clock,
+        });
+        // the retrib has to be ordered after the original trib
+        let after = original.clock.saturating_add(1);
+        self.publish(
+            who,
+            &original.message,
+            after,
+            Some(retrib_of),
+            None,
+            original.attachments.clone(),
+        )?;
+        Ok(())
+    }
+
+    async fn reply(
+        &self,
+        who: &str,
+        parent_author: &str,
+        parent_clock: u64,
+        message: &str,
+    ) -> ServerResult<()> {
+        self.touch(who)?;
+        {
+            let users = self.users.read().unwrap();
+            if !users.contains_key(who) {
+                return Err(TribblerError::UserDoesNotExist(who.to_string()));
+            }
+            match users.get(parent_author) {
+                Some(user) if user.tribs.iter().any(|t| t.clock == parent_clock) => (),
+                Some(_) => {
+                    return Err(TribblerError::TribNotFound(
+                        parent_author.to_string(),
+                        parent_clock,
+                    ))
+                }
+                None => return Err(TribblerError::UserDoesNotExist(parent_author.to_string())),
+            }
+        }
+        let parent = TribRef {
+            user: parent_author.to_string(),
+            clock: parent_clock,
+        };
+        // the reply has to be ordered after its parent
+        let after = parent_clock.saturating_add(1);
+        let trib = self.publish(who, message, after, None, Some(parent), vec![])?;
+        let mut users = self.users.write().unwrap();
+        if let Some(user) = users.get_mut(parent_author) {
+            let reply = TribRef {
+                user: who.to_string(),
+                clock: trib.clock,
+            };
+            user.replies
+                .entry(parent_clock)
+                .or_default()
+                .push(reply.clone());
+            if who != parent_author {
+                user.notify(NotificationKind::Reply, who, Some(reply));
+            }
+        }
+        Ok(())
+    }
+
+    async fn replies(&self, author: &str, clock: u64) -> ServerResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let user = match users.get(author) {
+            Some(user) => user,
+            None => return Err(TribblerError::UserDoesNotExist(author.to_string())),
+        };
+        let refs = user.replies.get(&clock).cloned().unwrap_or_default();
+        Ok(resolve(&users, &refs))
+    }
+
+    async fn tribs_by_tag(&self, tag: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let refs = {
+            let tags = self.tags.read().unwrap();
+            tags.get(&normalize_tag(tag)).cloned().unwrap_or_default()
+        };
+        let users = self.users.read().unwrap();
+        let tribs = resolve(&users, &refs);
+        let start = tribs.len().saturating_sub(self.limits.max_trib_fetch);
+        Ok(tribs[start..].to_vec())
+    }
+
+    async fn mentions(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => {
+                let tribs = resolve(&users, &u.mentions);
+                let start = tribs.len().saturating_sub(self.limits.max_trib_fetch);
+                Ok(tribs[start..].to_vec())
+            }
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn tribs_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>> {
+        self.publish_due()?;
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(page(
+                &u.tribs,
+                before_clock,
+                min(limit, self.limits.max_trib_fetch),
+            )),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn home_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>> {
+        self.publish_due()?;
+        Ok(page(
+            &self.visible_home(user)?,
+            before_clock,
+            min(limit, self.limits.max_trib_fetch),
+        ))
+    }
+
+    async fn follow_counts(&self, user: &str) -> ServerResult<(usize, usize)> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok((u.following.len(), u.followers.len())),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn set_profile(&self, who: &str, profile: &Profile) -> ServerResult<()> {
+        self.touch(who)?;
+        profile.validate()?;
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(who) {
+            Some(u) => {
+                u.profile = profile.clone();
+                Ok(())
+            }
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn get_profile(&self, user: &str) -> ServerResult<Profile> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u.profile.clone()),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn block(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        if who == whom {
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(whom) {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+        match users.get_mut(who) {
+            Some(u) => match u.blocked.insert(whom.to_string()) {
+                true => Ok(()),
+                false => Err(TribblerError::AlreadyBlocked(
+                    who.to_string(),
+                    whom.to_string(),
+                )),
+            },
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn unblock(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(whom) {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+        match users.get_mut(who) {
+            Some(u) => match u.blocked.remove(whom) {
+                true => Ok(()),
+                false => Err(TribblerError::NotBlocked(who.to_string(), whom.to_string())),
+            },
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn blocked(&self, who: &str) -> ServerResult<Vec<String>> {
+        let users = self.users.read().unwrap();
+        match users.get(who) {
+            Some(u) => Ok(u.blocked.iter().cloned().collect()),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn rename_user(&self, old: &str, new: &str) -> ServerResult<()> {
+        if !is_valid_username(new) {
+            return Err(TribblerError::InvalidUsername(new.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        if users.contains_key(new) {
+            return Err(TribblerError::UsernameTaken(new.to_string()));
+        }
+        let mut user = match users.remove(old) {
+            Some(u) => u,
+            None => return Err(TribblerError::UserDoesNotExist(old.to_string())),
+        };
+        user.rename(new);
+        users.insert(new.to_string(), user);
+
+        // everything other users keep about old now refers to new
+        for u in users.values_mut() {
+            if u.following.remove(old) {
+                u.following.insert(new.to_string());
+            }
+            if u.followers.remove(old) {
+                u.followers.insert(new.to_string());
+            }
+            if u.blocked.remove(old) {
+                u.blocked.insert(new.to_string());
+            }
+            if u.muted.remove(old) {
+                u.muted.insert(new.to_string());
+            }
+            for likers in u.likes.values_mut() {
+                if likers.remove(old) {
+                    likers.insert(new.to_string());
+                }
+            }
+            for refs in u.replies.values_mut() {
+                retarget(refs, old, new);
+            }
+            retarget(&mut u.mentions, old, new);
+            for members in u.lists.values_mut() {
+                if members.remove(old) {
+                    members.insert(new.to_string());
+                }
+            }
+            for n in u.notifications.iter_mut() {
+                if n.from == old {
+                    n.from = new.to_string();
+                }
+                if let Some(trib) = n.trib.as_mut() {
+                    retarget(std::slice::from_mut(trib), old, new);
+                }
+            }
+        }
+
+        // the homes hold the tribs of old, so rebuild them all
+        let mut homes = self.homes.write().unwrap();
+        homes.remove(old);
+        for (name, u) in users.iter() {
+            homes.insert(name.to_string(), self.rebuild_home(u, &users));
+        }
+        let mut tags = self.tags.write().unwrap();
+        for refs in tags.values_mut() {
+            retarget(refs, old, new);
+        }
+        let mut banned = self.banned.write().unwrap();
+        if banned.remove(old) {
+            banned.insert(new.to_string());
+        }
+        Ok(())
+    }
+
+    async fn mute(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        if who == whom {
+            return Err(TribblerError::WhoWhom(who.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(whom) {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+        match users.get_mut(who) {
+            Some(u) => match u.muted.insert(whom.to_string()) {
+                true => Ok(()),
+                false => Err(TribblerError::AlreadyMuted(
+                    who.to_string(),
+                    whom.to_string(),
+                )),
+            },
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn unmute(&self, who: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(whom) {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+        match users.get_mut(who) {
+            Some(u) => match u.muted.remove(whom) {
+                true => Ok(()),
+                false => Err(TribblerError::NotMuted(who.to_string(), whom.to_string())),
+            },
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn trending(&self, limit: usize) -> ServerResult<Vec<Trend>> {
+        let first = first_trending_slot(self.time.now()?);
+        let mut trends = self.trends.write().unwrap();
+        // slots that slid out of the window are of no use anymore
+        trends.retain(|slot, _| *slot >= first);
+        let mut counts = HashMap::<String, u64>::new();
+        for slot in trends.values() {
+            for (tag, count) in slot {
+                *counts.entry(tag.to_string()).or_default() += count;
+            }
+        }
+        Ok(top_trends(counts, limit))
+    }
+
+    async fn notifications(&self, user: &str, since: u64) -> ServerResult<Vec<Notification>> {
+        let users = self.users.read().unwrap();
+        let user = match users.get(user) {
+            Some(u) => u,
+            None => return Err(TribblerError::UserDoesNotExist(user.to_string())),
+        };
+        let newer: Vec<&Notification> = user
+            .notifications
+            .iter()
+            .filter(|n| n.clock > since)
+            .collect();
+        let start = newer.len().saturating_sub(self.limits.max_trib_fetch);
+        Ok(newer[start..]
+            .iter()
+            .map(|n| Notification {
+                read: n.clock <= user.read_upto,
+                ..(*n).clone()
+            })
+            .collect())
+    }
+
+    async fn mark_read(&self, user: &str, upto: u64) -> ServerResult<()> {
+        self.touch(user)?;
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(user) {
+            Some(u) => {
+                u.read_upto = u.read_upto.max(upto);
+                Ok(())
+            }
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn unread_count(&self, user: &str) -> ServerResult<usize> {
+        let users = self.users.read().unwrap();
+        match users.get(user) {
+            Some(u) => Ok(u
+                .notifications
+                .iter()
+                .filter(|n| n.clock > u.read_upto)
+                .count()),
+            None => Err(TribblerError::UserDoesNotExist(user.to_string())),
+        }
+    }
+
+    async fn create_list(&self, who: &str, name: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        if !is_valid_username(name) {
+            return Err(TribblerError::InvalidListName(name.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(who) {
+            Some(u) if u.lists.contains_key(name) => {
+                Err(TribblerError::ListExists(who.to_string(), name.to_string()))
+            }
+            Some(u) => {
+                u.lists.insert(name.to_string(), BTreeSet::new());
+                Ok(())
+            }
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn add_to_list(&self, who: &str, name: &str, whom: &str) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        if !users.contains_key(whom) {
+            return Err(TribblerError::UserDoesNotExist(whom.to_string()));
+        }
+        let members = match users.get_mut(who) {
+            Some(u) => match u.lists.get_mut(name) {
+                Some(members) => members,
+                None => return Err(TribblerError::NoSuchList(who.to_string(), name.to_string())),
+            },
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
+        };
+        if members.contains(whom) {
+            return Err(TribblerError::AlreadyInList(
+                whom.to_string(),
+                name.to_string(),
+            ));
+        }
+        if members.len() >= self.limits.max_following {
+            return Err(TribblerError::FollowingTooMany);
+        }
+        members.insert(whom.to_string());
+        Ok(())
+    }
+
+    async fn list_timeline(&self, who: &str, name: &str) -> ServerResult<Vec<Arc<Trib>>> {
+        let users = self.users.read().unwrap();
+        let user = match users.get(who) {
+            Some(u) => u,
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
+        };
+        let members = match user.lists.get(name) {
+            Some(members) => members,
+            None => return Err(TribblerError::NoSuchList(who.to_string(), name.to_string())),
+        };
+        let timeline = merge_timelines(
+            members
+                .iter()
+                .filter(|m| !user.blocked.contains(*m) && !user.muted.contains(*m))
+                .filter_map(|m| users.get(m)),
+        );
+        let start = timeline.len().saturating_sub(self.limits.max_trib_fetch);
+        Ok(timeline[start..].to_vec())
+    }
+
+    async fn save_draft(&self, who: &str, message: &str) -> ServerResult<u64> {
+        self.touch(who)?;
+        if message.len() > self.limits.max_trib_len {
+            return Err(TribblerError::TribTooLong);
+        }
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(who) {
+            Some(u) if u.drafts.len() >= MAX_DRAFTS => Err(TribblerError::TooManyDrafts),
+            Some(u) => {
+                let id = u.next_draft;
+                u.next_draft += 1;
+                u.drafts.push(Draft {
+                    id,
+                    message: message.to_string(),
+                });
+                Ok(id)
+            }
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn list_drafts(&self, who: &str) -> ServerResult<Vec<Draft>> {
+        let users = self.users.read().unwrap();
+        match users.get(who) {
+            Some(u) => Ok(u.drafts.clone()),
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn delete_draft(&self, who: &str, id: u64) -> ServerResult<()> {
+        self.touch(who)?;
+        let mut users = self.users.write().unwrap();
+        let user = match users.get_mut(who) {
+            Some(u) => u,
+            None => return Err(TribblerError::UserDoesNotExist(who.to_string())),
+        };
+        match user.drafts.iter().position(|d| d.id == id) {
+            Some(i) => {
+                user.drafts.remove(i);
+                Ok(())
+            }
+            None => Err(TribblerError::NoSuchDraft(who.to_string(), id)),
+        }
+    }
+
+    async fn schedule_post(&self, who: &str, message: &str, publish_at: u64) -> ServerResult<()> {
+        self.touch(who)?;
+        if message.len() > self.limits.max_trib_len {
+            return Err(TribblerError::TribTooLong);
+        }
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(who) {
+            Some(u) => {
+                let id = u.scheduled.last().map_or(1, |p| p.id + 1);
+                u.scheduled.push(ScheduledPost {
+                    id,
+                    message: message.to_string(),
+                    publish_at,
+                });
+                Ok(())
+            }
+            None => Err(TribblerError::UserDoesNotExist(who.to_string())),
+        }
+    }
+
+    async fn admin_delete_trib(&self, author: &str, clock: u64) -> ServerResult<()> {
+        let mut users = self.users.write().unwrap();
+        match users.get_mut(author) {
+            Some(u) if u.tribs.iter().any(|t| t.clock == clock) => {
+                u.tribs.retain(|t| t.clock != clock);
+                u.seq_tribs.retain(|t| t.trib.clock != clock);
+            }
+            Some(_) => return Err(TribblerError::TribNotFound(author.to_string(), clock)),
+            None => return Err(TribblerError::UserDoesNotExist(author.to_string())),
+        }
+        let mut homes = self.homes.write().unwrap();
+        for home in homes.values_mut() {
+            home.retain(|t| t.user != author || t.clock != clock);
+        }
+        Ok(())
+    }
+
+    async fn admin_ban_user(&self, user: &str) -> ServerResult<()> {
+        if !self.users.read().unwrap().contains_key(user) {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+        match self.banned.write().unwrap().insert(user.to_string()) {
+            true => Ok(()),
+            false => Err(TribblerError::AlreadyBanned(user.to_string())),
+        }
+    }
+
+    async fn admin_unban(&self, user: &str) -> ServerResult<()> {
+        if !self.users.read().unwrap().contains_key(user) {
+            return Err(TribblerError::UserDoesNotExist(user.to_string()));
+        }
+        match self.banned.write().unwrap().remove(user) {
+            true => Ok(()),
+            false => Err(TribblerError::NotBanned(user.to_string())),
+        }
+    }
+
+    async fn export_user(&self, user: &str) -> ServerResult<UserExport> {
+        let users = self.users.read().unwrap();
+        let u = match users.get(user) {
+            Some(u) => u,
+            None => return Err(TribblerError::UserDoesNotExist(user.to_string())),
+        };
+        let mut following = u.list_following();
+        following.sort();
+        let mut likes = vec![];
+        for (author, a) in users.iter() {
+            for (clock, likers) in a.likes.iter() {
+                if likers.contains(user) {
+                    likes.push(TribRef {
+                        user: author.to_string(),
+                        clock: *clock,
+                    });
+                }
+            }
+        }
+        likes.sort_by(|a, b| a.user.cmp(&b.user).then(a.clock.cmp(&b.clock)));
+        Ok(UserExport {
+            user: user.to_string(),
+            profile: u.profile.clone(),
+            tribs: u.tribs.iter().map(|t| (**t).clone()).collect(),
+            following,
+            likes,
+        })
+    }
+
+    async fn import(&self, data: &[UserExport]) -> ServerResult<()> {
+        for export in data {
+            for trib in export.tribs.iter() {
+                if trib.message.len() > self.limits.max_trib_len {
+                    return Err(TribblerError::TribTooLong);
+                }
+                validate_attachments(&trib.attachments)?;
+            }
+        }
+        for export in data {
+            match self.sign_up(&export.user).await {
+                Ok(()) => self.set_profile(&export.user, &export.profile).await?,
+                // a user imported before is left as it is
+                Err(TribblerError::UsernameTaken(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        // the tribs keep their clocks, and the sequence catches up with them
+        // so later posts still sort after them
+        {
+            let mut users = self.users.write().unwrap();
+            let mut tribs: Vec<Trib> = data
+                .iter()
+                .flat_map(|export| {
+                    export.tribs.iter().map(|trib| Trib {
+                        user: export.user.to_string(),
+                        ..trib.clone()
+                    })
+                })
+                .collect();
+            tribs.sort_by_key(|trib| trib.clock);
+            for trib in tribs {
+                let _ = self
+                    .seq
+                    .fetch_max(trib.clock.saturating_add(1), atomic::Ordering::SeqCst);
+                let stored = users
+                    .get(&trib.user)
+                    .is_some_and(|user| user.tribs.iter().any(|stored| stored.clock == trib.clock));
+                if stored {
+                    continue;
+                }
+                let trib = self.store(&mut users, trib)?;
+                if let Some(parent) = &trib.reply_to {
+                    // a reply to a trib that isn't there is kept unlinked
+                    let parent_user = users.get_mut(&parent.user).filter(|user| {
+                        user.tribs.iter().any(|stored| stored.clock == parent.clock)
+                    });
+                    if let Some(parent_user) = parent_user {
+                        parent_user
+                            .replies
+                            .entry(parent.clock)
+                            .or_default()
+                            .push(TribRef {
+                                user: trib.user.to_string(),
+                                clock: trib.clock,
+                            });
+                    }
+                }
+            }
+        }
+
+        // follows and likes of users or tribs that aren't there are skipped
+        for export in data {
+            for whom in export.following.iter() {
+                match self.follow(&export.user, whom).await {
+                    Ok(())
+                    | Err(TribblerError::AlreadyFollowing(..))
+                    | Err(TribblerError::UserDoesNotExist(_)) => {}
+                    Err(e) => return Err(e),
+                }
             }
-            None => Err(Box::new(TribblerError::UserDoesNotExist(user.to_string()))),
         }
+        for export in data {
+            for liked in export.likes.iter() {
+                match self.like(&export.user, &liked.user, liked.clock).await {
+                    Ok(())
+                    | Err(TribblerError::AlreadyLiked(..))
+                    | Err(TribblerError::UserDoesNotExist(_))
+                    | Err(TribblerError::TribNotFound(..)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// merges the tribs of `members` into one timeline, in the order they were
+/// posted
+fn merge_timelines<'a>(members: impl Iterator<Item = &'a User>) -> Vec<Arc<Trib>> {
+    let mut merged: Vec<SeqTrib> = vec![];
+    for member in members {
+        merged.append(&mut member.seq_tribs.clone());
+    }
+    merged.sort();
+    merged
+        .iter()
+        .map(|x| x.trib.clone())
+        .collect::<Vec<Arc<Trib>>>()
+}
+
+/// points the [TribRef]s to tribs of `old` at `new`
+fn retarget(refs: &mut [TribRef], old: &str, new: &str) {
+    for r in refs.iter_mut().filter(|r| r.user == old) {
+        r.user = new.to_string();
+    }
+}
+
+/// the last `limit` of the clock-ordered `tribs` older than `before_clock`
+fn page(tribs: &[Arc<Trib>], before_clock: u64, limit: usize) -> Vec<Arc<Trib>> {
+    let end = tribs.partition_point(|t| t.clock < before_clock);
+    let start = end.saturating_sub(limit);
+    tribs[start..end].to_vec()
+}
+
+/// looks up the [Trib]s referred to by `refs`, ordered by their clock
+fn resolve(users: &HashMap<String, User>, refs: &[TribRef]) -> Vec<Arc<Trib>> {
+    let mut tribs: Vec<Arc<Trib>> = refs
+        .iter()
+        .filter_map(|r| {
+            let user = users.get(&r.user)?;
+            user.tribs.iter().find(|t| t.clock == r.clock).cloned()
+        })
+        .collect();
+    tribs.sort_by_key(|t| t.clock);
+    tribs
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Limits;
+    use crate::err::TribResult;
+    use crate::ref_impl::RefServer;
+    use crate::time::ManualClock;
+    use crate::trib::Server;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn home_follows_the_crafted_clocks() -> TribResult<()> {
+        let clock = Arc::new(ManualClock::new(1000));
+        let server = RefServer::with_time(Limits::default(), clock.clone());
+        for user in ["alice", "bob", "carol"] {
+            server.sign_up(user).await?;
+        }
+        server.follow("carol", "alice").await?;
+        server.follow("carol", "bob").await?;
+
+        server.post("alice", "first", 0).await?;
+        // a client which has seen clock 10 lifts the clock past it
+        clock.set(900);
+        server.post("bob", "seen ten", 10).await?;
+        // an older clock than the server's is not gone back to
+        clock.set(1100);
+        server.post("alice", "stale", 3).await?;
+        server.post("carol", "own", 0).await?;
+
+        let home: Vec<(String, u64, u64)> = server
+            .home("carol")
+            .await?
+            .iter()
+            .map(|t| (t.message.clone(), t.clock, t.time))
+            .collect();
+        let expected = [
+            ("first", 0, 1000),
+            ("seen ten", 10, 900),
+            ("stale", 11, 1100),
+            ("own", 12, 1100),
+        ];
+        let expected: Vec<(String, u64, u64)> = expected
+            .iter()
+            .map(|(m, c, t)| (m.to_string(), *c, *t))
+            .collect();
+        assert_eq!(expected, home);
+        assert_eq!(13, server.clock().await?);
+        Ok(())
     }
 }
@@ -42,6 +42,27 @@ pub struct ListRemoveResponse {
     #[prost(uint32, tag = "1")]
     pub removed: u32,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Incr {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(int64, tag = "2")]
+    pub delta: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Counter {
+    #[prost(int64, tag = "1")]
+    pub value: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Swap {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub expected: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub value: ::prost::alloc::string::String,
+}
 #[doc = r" Generated client implementations."]
 pub mod trib_storage_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -214,6 +235,34 @@ pub mod trib_storage_client {
             let path = http::uri::PathAndQuery::from_static("/rpc.TribStorage/clock");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn incr(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Incr>,
+        ) -> Result<tonic::Response<super::Counter>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/rpc.TribStorage/incr");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn compare_and_swap(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Swap>,
+        ) -> Result<tonic::Response<super::Value>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/rpc.TribStorage/compareAndSwap");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 #[doc = r" Generated server implementations."]
@@ -255,6 +304,14 @@ pub mod trib_storage_server {
             &self,
             request: tonic::Request<super::Clock>,
         ) -> Result<tonic::Response<super::Clock>, tonic::Status>;
+        async fn incr(
+            &self,
+            request: tonic::Request<super::Incr>,
+        ) -> Result<tonic::Response<super::Counter>, tonic::Status>;
+        async fn compare_and_swap(
+            &self,
+            request: tonic::Request<super::Swap>,
+        ) -> Result<tonic::Response<super::Value>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct TribStorageServer<T: TribStorage> {
@@ -534,6 +591,62 @@ pub mod trib_storage_server {
                     };
                     Box::pin(fut)
                 }
+                "/rpc.TribStorage/incr" => {
+                    #[allow(non_camel_case_types)]
+                    struct incrSvc<T: TribStorage>(pub Arc<T>);
+                    impl<T: TribStorage> tonic::server::UnaryService<super::Incr> for incrSvc<T> {
+                        type Response = super::Counter;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(&mut self, request: tonic::Request<super::Incr>) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).incr(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = incrSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/rpc.TribStorage/compareAndSwap" => {
+                    #[allow(non_camel_case_types)]
+                    struct compareAndSwapSvc<T: TribStorage>(pub Arc<T>);
+                    impl<T: TribStorage> tonic::server::UnaryService<super::Swap> for compareAndSwapSvc<T> {
+                        type Response = super::Value;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(&mut self, request: tonic::Request<super::Swap>) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).compare_and_swap(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = compareAndSwapSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -1,11 +1,14 @@
 #![allow(dead_code)]
 //! module containing Tribbler storage-related structs and implementations
 use async_trait::async_trait;
-use std::{collections::HashMap, sync::RwLock};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
-use crate::err::TribResult;
+use crate::err::{TribResult, TribblerError};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 
 /// A type comprising key-value pair
 pub struct KeyValue {
@@ -25,7 +28,7 @@ impl KeyValue {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 /// A type which represents a pattern that can be used to match on a String.
 pub struct Pattern {
     /// exact-match string prefix
@@ -59,6 +62,16 @@ pub trait KeyString {
     /// List all the keys of non-empty pairs where the key matches
     /// the given pattern.
     async fn keys(&self, p: &Pattern) -> TribResult<List>;
+
+    /// Atomically adds `delta` to the integer stored at `key`, treating an
+    /// unset key as 0, and returns the new value.
+    /// Returns error when the value at `key` is not an integer.
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64>;
+
+    /// Atomically sets `key` to `value` if it holds `expected`, an unset key
+    /// holding the empty string, and returns what it held; the swap took
+    /// when that is `expected`.
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String>;
 }
 
 #[async_trait]
@@ -112,14 +125,22 @@ impl MemStorage {
 #[async_trait]
 impl KeyString for MemStorage {
     async fn get(&self, key: &str) -> TribResult<Option<String>> {
-        match self.kvs.read().map_err(|e| e.to_string())?.get(key) {
+        match self
+            .kvs
+            .read()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?
+            .get(key)
+        {
             Some(v) => Ok(Some(v.to_string())),
             None => Ok(None),
         }
     }
 
     async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
-        let mut entry = self.kvs.write().map_err(|e| e.to_string())?;
+        let mut entry = self
+            .kvs
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
         if kv.value.is_empty() {
             entry.remove(&kv.key);
         } else {
@@ -128,13 +149,45 @@ impl KeyString for MemStorage {
         Ok(true)
     }
 
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        let mut entry = self
+            .kvs
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
+        let current = match entry.get(key) {
+            Some(v) => v
+                .parse::<i64>()
+                .map_err(|_| TribblerError::NotACounter(key.to_string()))?,
+            None => 0,
+        };
+        let value = current.saturating_add(delta);
+        entry.insert(key.to_string(), value.to_string());
+        Ok(value)
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let mut entry = self
+            .kvs
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
+        let held = entry.get(key).cloned().unwrap_or_default();
+        if held == expected {
+            if value.is_empty() {
+                entry.remove(key);
+            } else {
+                entry.insert(key.to_string(), value.to_string());
+            }
+        }
+        Ok(held)
+    }
+
     async fn keys(&self, p: &Pattern) -> TribResult<List> {
         let result = self
             .kvs
             .read()
-            .map_err(|e| e.to_string())?
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?
             .iter()
-            .filter(|(k, _)| p.matches(*k))
+            .filter(|(k, _)| p.matches(k))
             .map(|(k, _)| k.to_string())
             .collect::<Vec<String>>();
         Ok(List(result))
@@ -144,14 +197,22 @@ impl KeyString for MemStorage {
 #[async_trait]
 impl KeyList for MemStorage {
     async fn list_get(&self, key: &str) -> TribResult<List> {
-        match self.kv_list.read().map_err(|e| e.to_string())?.get(key) {
+        match self
+            .kv_list
+            .read()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?
+            .get(key)
+        {
             Some(l) => Ok(l.clone()),
             None => Ok(List(vec![])),
         }
     }
 
     async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
-        let mut kvl = self.kv_list.write().map_err(|e| e.to_string())?;
+        let mut kvl = self
+            .kv_list
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
         match kvl.get_mut(&kv.key) {
             Some(list) => {
                 list.0.push(kv.value.clone());
@@ -167,7 +228,10 @@ impl KeyList for MemStorage {
 
     async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
         let mut removed = 0;
-        let mut kvl = self.kv_list.write().map_err(|e| e.to_string())?;
+        let mut kvl = self
+            .kv_list
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
         kvl.entry(kv.key.clone()).and_modify(|list| {
             let begin_size = list.0.len();
             *list = List(
@@ -193,9 +257,9 @@ impl KeyList for MemStorage {
         let mut result = vec![];
         self.kv_list
             .read()
-            .map_err(|e| e.to_string())?
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?
             .iter()
-            .filter(|(k, _)| p.matches(*k))
+            .filter(|(k, _)| p.matches(k))
             .for_each(|(v, _)| result.push((*v).clone()));
         result.sort();
         Ok(List(result))
@@ -205,20 +269,73 @@ impl KeyList for MemStorage {
 #[async_trait]
 impl Storage for MemStorage {
     async fn clock(&self, at_least: u64) -> TribResult<u64> {
-        let mut clk = self.clock.write().map_err(|e| e.to_string())?;
+        let mut clk = self
+            .clock
+            .write()
+            .map_err(|e| TribblerError::Unknown(e.to_string()))?;
         if *clk < at_least {
             *clk = at_least
         }
 
         let ret = *clk;
 
-        if *clk < u64::MAX {
-            *clk += 1;
-        }
+        *clk = clk.saturating_add(1);
         Ok(ret)
     }
 }
 
+// a storage shared between owners, as a backend which comes back after going
+// down serves the storage it had before
+
+#[async_trait]
+impl<T: KeyString + Send + Sync + ?Sized> KeyString for Arc<T> {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        (**self).get(key).await
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        (**self).set(kv).await
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<List> {
+        (**self).keys(p).await
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        (**self).incr(key, delta).await
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        (**self).compare_and_swap(key, expected, value).await
+    }
+}
+
+#[async_trait]
+impl<T: KeyList + Send + Sync + ?Sized> KeyList for Arc<T> {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        (**self).list_get(key).await
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        (**self).list_append(kv).await
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        (**self).list_remove(kv).await
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        (**self).list_keys(p).await
+    }
+}
+
+#[async_trait]
+impl<T: Storage + ?Sized> Storage for Arc<T> {
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        (**self).clock(at_least).await
+    }
+}
+
 #[async_trait]
 /// Bin Storage interface
 pub trait BinStorage: Send + Sync {
@@ -257,8 +374,7 @@ mod test {
     #[tokio::test]
     async fn storage_get_set() -> TribResult<()> {
         let storage = MemStorage::new();
-        assert_eq!(
-            true,
+        assert!(
             storage
                 .set(&KeyValue {
                     key: "test".to_string(),
@@ -270,6 +386,35 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn storage_incr() -> TribResult<()> {
+        let storage = MemStorage::new();
+        assert_eq!(2, storage.incr("n", 2).await?);
+        assert_eq!(1, storage.incr("n", -1).await?);
+        assert_eq!(Some("1".to_string()), storage.get("n").await?);
+        storage
+            .set(&KeyValue {
+                key: "s".to_string(),
+                value: "text".to_string(),
+            })
+            .await?;
+        assert!(storage.incr("s", 1).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn storage_compare_and_swap() -> TribResult<()> {
+        let storage = MemStorage::new();
+        assert_eq!("", storage.compare_and_swap("k", "", "a").await?);
+        assert_eq!("a", storage.compare_and_swap("k", "", "b").await?);
+        assert_eq!(Some("a".to_string()), storage.get("k").await?);
+        assert_eq!("a", storage.compare_and_swap("k", "a", "b").await?);
+        assert_eq!(Some("b".to_string()), storage.get("k").await?);
+        assert_eq!("b", storage.compare_and_swap("k", "b", "").await?);
+        assert_eq!(None, storage.get("k").await?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn storage_get_empty() -> TribResult<()> {
         let storage = setup_test_storage().await;
@@ -347,7 +492,7 @@ mod test {
                 value: "val2".to_string(),
             })
             .await?;
-        assert_eq!(true, res);
+        assert!(res);
         assert_eq!(2, storage.list_get("test").await.unwrap().0.len());
         Ok(())
     }
@@ -359,9 +504,9 @@ mod test {
             key: "test".to_string(),
             value: "val2".to_string(),
         };
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
-        assert_eq!(true, storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
+        assert!(storage.list_append(&kv).await.unwrap());
         assert_eq!(3, storage.list_remove(&kv).await.unwrap());
         println!("{:?}", storage.list_get("test").await.unwrap().0);
         assert_eq!("test-value", storage.list_get("test").await.unwrap().0[0]);
@@ -408,6 +553,6 @@ mod test {
         let storage = setup_test_storage().await;
         let c1 = storage.clock(1234).await.unwrap();
         let c2 = storage.clock(0).await.unwrap();
-        assert_eq!(true, c2 > c1);
+        assert!(c2 > c1);
     }
 }
@@ -0,0 +1,463 @@
+//! Test doubles for [Storage] and [BinStorage], so that code built on top of
+//! them, such as a front-end, can be tested without serving backends.
+//!
+//! A [MockStorage] keeps what it is told in a [MemStorage], records every
+//! call made to it, and can be scripted to answer the next calls of an
+//! operation with given replies or errors, or to fail every call of it. A
+//! [MockBinStorage] hands out a [MockStorage] per bin, the same one each
+//! time a bin is asked for.
+//!
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//! use tribbler::err::TribblerError;
+//! use tribbler::storage::{BinStorage, KeyString, KeyValue};
+//! use tribbler::testing::{Call, MockBinStorage, Op, Reply};
+//!
+//! let bins = MockBinStorage::new();
+//! let alice = bins.bin("alice").await.unwrap();
+//! alice.set(&KeyValue::new("k", "v")).await.unwrap();
+//! assert_eq!(
+//!     vec![Call::Set(KeyValue::new("k", "v"))],
+//!     bins.storage("alice").calls()
+//! );
+//!
+//! bins.storage("alice").script(Op::Get, Ok(Reply::Value(None)));
+//! assert_eq!(None, alice.get("k").await.unwrap());
+//! assert_eq!(Some("v".to_string()), alice.get("k").await.unwrap());
+//!
+//! bins.storage("alice")
+//!     .script(Op::Get, Err(TribblerError::RpcError("down".to_string())));
+//! assert!(alice.get("k").await.is_err());
+//! # }
+//! ```
+pub mod conformance;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::err::{TribResult, TribblerError};
+use crate::storage::{
+    BinStorage, KeyList, KeyString, KeyValue, List, MemStorage, Pattern, Storage,
+};
+
+/// An operation of [Storage].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Get,
+    Set,
+    Keys,
+    Incr,
+    CompareAndSwap,
+    ListGet,
+    ListAppend,
+    ListRemove,
+    ListKeys,
+    Clock,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Op::Get => "get",
+            Op::Set => "set",
+            Op::Keys => "keys",
+            Op::Incr => "incr",
+            Op::CompareAndSwap => "compare_and_swap",
+            Op::ListGet => "list_get",
+            Op::ListAppend => "list_append",
+            Op::ListRemove => "list_remove",
+            Op::ListKeys => "list_keys",
+            Op::Clock => "clock",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A call made to a [MockStorage], with its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Get(String),
+    Set(KeyValue),
+    Keys(Pattern),
+    Incr(String, i64),
+    CompareAndSwap(String, String, String),
+    ListGet(String),
+    ListAppend(KeyValue),
+    ListRemove(KeyValue),
+    ListKeys(Pattern),
+    Clock(u64),
+}
+
+impl Call {
+    /// The operation called.
+    pub fn op(&self) -> Op {
+        match self {
+            Call::Get(_) => Op::Get,
+            Call::Set(_) => Op::Set,
+            Call::Keys(_) => Op::Keys,
+            Call::Incr(_, _) => Op::Incr,
+            Call::CompareAndSwap(_, _, _) => Op::CompareAndSwap,
+            Call::ListGet(_) => Op::ListGet,
+            Call::ListAppend(_) => Op::ListAppend,
+            Call::ListRemove(_) => Op::ListRemove,
+            Call::ListKeys(_) => Op::ListKeys,
+            Call::Clock(_) => Op::Clock,
+        }
+    }
+}
+
+/// A reply scripted for a call, of the type the operation returns:
+/// [Reply::Value] for [Op::Get], [Reply::Done] for [Op::Set] and
+/// [Op::ListAppend], [Reply::List] for [Op::Keys], [Op::ListGet] and
+/// [Op::ListKeys], [Reply::Removed] for [Op::ListRemove], [Reply::Count] for
+/// [Op::Incr], [Reply::Value] holding what was held for
+/// [Op::CompareAndSwap], and [Reply::Clock] for [Op::Clock].
+#[derive(Debug, Clone)]
+pub enum Reply {
+    Value(Option<String>),
+    Done(bool),
+    List(List),
+    Removed(u32),
+    Count(i64),
+    Clock(u64),
+}
+
+#[derive(Default)]
+struct MockState {
+    calls: Vec<Call>,
+    scripts: HashMap<Op, VecDeque<TribResult<Reply>>>,
+    failing: HashSet<Op>,
+}
+
+/// A [Storage] which keeps what it is told in memory, records the calls
+/// made to it, and answers as it was scripted to. Clones share their
+/// storage, calls, and scripts, so a test can keep one while the code under
+/// test owns another.
+#[derive(Clone, Default)]
+pub struct MockStorage {
+    store: Arc<MemStorage>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockStorage {
+    /// Creates an empty [MockStorage], with nothing scripted.
+    pub fn new() -> MockStorage {
+        MockStorage::default()
+    }
+
+    /// The calls made so far, in the order they were made.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// The calls of `op` made so far, in the order they were made.
+    pub fn calls_of(&self, op: Op) -> Vec<Call> {
+        self.calls().into_iter().filter(|c| c.op() == op).collect()
+    }
+
+    /// Forgets the calls made so far.
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// Answers the next call of `op` not answered by an earlier script with
+    /// `result`, instead of the storage. The storage is left untouched by a
+    /// scripted call.
+    pub fn script(&self, op: Op, result: TribResult<Reply>) {
+        let mut state = self.state.lock().unwrap();
+        state.scripts.entry(op).or_default().push_back(result);
+    }
+
+    /// Fails every call of `op` from now on, until [MockStorage::heal]. A
+    /// scripted reply is still given first.
+    pub fn fail(&self, op: Op) {
+        self.state.lock().unwrap().failing.insert(op);
+    }
+
+    /// Stops failing the calls of `op`.
+    pub fn heal(&self, op: Op) {
+        self.state.lock().unwrap().failing.remove(&op);
+    }
+
+    // records `call`, and returns what was scripted for it, if anything
+    fn called(&self, call: Call) -> Option<TribResult<Reply>> {
+        let op = call.op();
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        if let Some(result) = state.scripts.get_mut(&op).and_then(|s| s.pop_front()) {
+            return Some(result);
+        }
+        match state.failing.contains(&op) {
+            true => Some(Err(TribblerError::RpcError(format!(
+                "{} is failing in the mock storage",
+                op
+            )))),
+            false => None,
+        }
+    }
+}
+
+// the scripted reply for a call of `op`, which must be of the type `op`
+// returns
+fn unexpected<T>(op: Op, reply: Reply) -> T {
+    panic!("{:?} was scripted for {}, which can't return it", reply, op)
+}
+
+#[async_trait]
+impl KeyString for MockStorage {
+    async fn get(&self, key: &str) -> TribResult<Option<String>> {
+        match self.called(Call::Get(key.to_string())) {
+            Some(Ok(Reply::Value(value))) => Ok(value),
+            Some(Ok(reply)) => unexpected(Op::Get, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.get(key).await,
+        }
+    }
+
+    async fn set(&self, kv: &KeyValue) -> TribResult<bool> {
+        match self.called(Call::Set(kv.clone())) {
+            Some(Ok(Reply::Done(done))) => Ok(done),
+            Some(Ok(reply)) => unexpected(Op::Set, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.set(kv).await,
+        }
+    }
+
+    async fn keys(&self, p: &Pattern) -> TribResult<List> {
+        match self.called(Call::Keys(p.clone())) {
+            Some(Ok(Reply::List(list))) => Ok(list),
+            Some(Ok(reply)) => unexpected(Op::Keys, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.keys(p).await,
+        }
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> TribResult<i64> {
+        match self.called(Call::Incr(key.to_string(), delta)) {
+            Some(Ok(Reply::Count(count))) => Ok(count),
+            Some(Ok(reply)) => unexpected(Op::Incr, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.incr(key, delta).await,
+        }
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &str, value: &str) -> TribResult<String> {
+        let call = Call::CompareAndSwap(key.to_string(), expected.to_string(), value.to_string());
+        match self.called(call) {
+            Some(Ok(Reply::Value(held))) => Ok(held.unwrap_or_default()),
+            Some(Ok(reply)) => unexpected(Op::CompareAndSwap, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.compare_and_swap(key, expected, value).await,
+        }
+    }
+}
+
+#[async_trait]
+impl KeyList for MockStorage {
+    async fn list_get(&self, key: &str) -> TribResult<List> {
+        match self.called(Call::ListGet(key.to_string())) {
+            Some(Ok(Reply::List(list))) => Ok(list),
+            Some(Ok(reply)) => unexpected(Op::ListGet, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.list_get(key).await,
+        }
+    }
+
+    async fn list_append(&self, kv: &KeyValue) -> TribResult<bool> {
+        match self.called(Call::ListAppend(kv.clone())) {
+            Some(Ok(Reply::Done(done))) => Ok(done),
+            Some(Ok(reply)) => unexpected(Op::ListAppend, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.list_append(kv).await,
+        }
+    }
+
+    async fn list_remove(&self, kv: &KeyValue) -> TribResult<u32> {
+        match self.called(Call::ListRemove(kv.clone())) {
+            Some(Ok(Reply::Removed(removed))) => Ok(removed),
+            Some(Ok(reply)) => unexpected(Op::ListRemove, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.list_remove(kv).await,
+        }
+    }
+
+    async fn list_keys(&self, p: &Pattern) -> TribResult<List> {
+        match self.called(Call::ListKeys(p.clone())) {
+            Some(Ok(Reply::List(list))) => Ok(list),
+            Some(Ok(reply)) => unexpected(Op::ListKeys, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.list_keys(p).await,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for MockStorage {
+    async fn clock(&self, at_least: u64) -> TribResult<u64> {
+        match self.called(Call::Clock(at_least)) {
+            Some(Ok(Reply::Clock(clock))) => Ok(clock),
+            Some(Ok(reply)) => unexpected(Op::Clock, reply),
+            Some(Err(e)) => Err(e),
+            None => self.store.clock(at_least).await,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MockBins {
+    bins: HashMap<String, MockStorage>,
+    opened: Vec<String>,
+    failing: HashSet<Op>,
+}
+
+/// A [BinStorage] handing out a [MockStorage] per bin, the same one each time
+/// a bin is asked for. Clones share their bins.
+#[derive(Clone, Default)]
+pub struct MockBinStorage {
+    state: Arc<Mutex<MockBins>>,
+}
+
+impl MockBinStorage {
+    /// Creates a [MockBinStorage] with every bin empty.
+    pub fn new() -> MockBinStorage {
+        MockBinStorage::default()
+    }
+
+    /// The storage of the bin `name`, to script or to look at the calls made
+    /// to it.
+    pub fn storage(&self, name: &str) -> MockStorage {
+        let mut state = self.state.lock().unwrap();
+        let failing = state.failing.clone();
+        state
+            .bins
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let storage = MockStorage::new();
+                failing.iter().for_each(|op| storage.fail(*op));
+                storage
+            })
+            .clone()
+    }
+
+    /// The names of the bins asked for so far, in the order they were.
+    pub fn opened(&self) -> Vec<String> {
+        self.state.lock().unwrap().opened.clone()
+    }
+
+    /// Fails every call of `op` to every bin, those asked for later too, until
+    /// [MockBinStorage::heal].
+    pub fn fail(&self, op: Op) {
+        let mut state = self.state.lock().unwrap();
+        state.failing.insert(op);
+        state.bins.values().for_each(|storage| storage.fail(op));
+    }
+
+    /// Stops failing the calls of `op` to every bin.
+    pub fn heal(&self, op: Op) {
+        let mut state = self.state.lock().unwrap();
+        state.failing.remove(&op);
+        state.bins.values().for_each(|storage| storage.heal(op));
+    }
+}
+
+#[async_trait]
+impl BinStorage for MockBinStorage {
+    async fn bin(&self, name: &str) -> TribResult<Box<dyn Storage>> {
+        self.state.lock().unwrap().opened.push(name.to_string());
+        Ok(Box::new(self.storage(name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Call, MockBinStorage, MockStorage, Op, Reply};
+    use crate::err::{TribResult, TribblerError};
+    use crate::storage::{BinStorage, KeyList, KeyString, KeyValue, List, Storage};
+
+    #[tokio::test]
+    async fn mock_storage_records_calls() -> TribResult<()> {
+        let storage = MockStorage::new();
+        storage.set(&KeyValue::new("k", "v")).await?;
+        storage.list_append(&KeyValue::new("l", "a")).await?;
+        assert_eq!(Some("v".to_string()), storage.get("k").await?);
+        assert_eq!(vec!["a".to_string()], storage.list_get("l").await?.0);
+        assert_eq!(
+            vec![
+                Call::Set(KeyValue::new("k", "v")),
+                Call::ListAppend(KeyValue::new("l", "a")),
+                Call::Get("k".to_string()),
+                Call::ListGet("l".to_string()),
+            ],
+            storage.calls()
+        );
+        assert_eq!(vec![Call::Get("k".to_string())], storage.calls_of(Op::Get));
+        storage.clear_calls();
+        assert!(storage.calls().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_storage_answers_as_scripted() -> TribResult<()> {
+        let storage = MockStorage::new();
+        storage.script(Op::ListGet, Ok(Reply::List(List(vec!["x".to_string()]))));
+        storage.script(Op::Set, Err(TribblerError::RpcError("down".to_string())));
+        storage.script(Op::Clock, Ok(Reply::Clock(42)));
+        assert_eq!(vec!["x".to_string()], storage.list_get("l").await?.0);
+        assert!(storage.list_get("l").await?.0.is_empty());
+        assert!(storage.set(&KeyValue::new("k", "v")).await.is_err());
+        assert_eq!(None, storage.get("k").await?);
+        assert_eq!(42, storage.clock(0).await?);
+        assert_eq!(0, storage.clock(0).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn mock_storage_rejects_mismatched_replies() {
+        let storage = MockStorage::new();
+        storage.script(Op::Get, Ok(Reply::Clock(1)));
+        let _ = storage.get("k").await;
+    }
+
+    #[tokio::test]
+    async fn mock_storage_fails_until_healed() -> TribResult<()> {
+        let storage = MockStorage::new();
+        storage.fail(Op::Incr);
+        assert!(storage.incr("n", 1).await.is_err());
+        assert!(storage.incr("n", 1).await.is_err());
+        storage.heal(Op::Incr);
+        assert_eq!(1, storage.incr("n", 1).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mock_bin_storage_keeps_bins_apart() -> TribResult<()> {
+        let bins = MockBinStorage::new();
+        bins.bin("alice")
+            .await?
+            .set(&KeyValue::new("k", "a"))
+            .await?;
+        bins.bin("bob").await?.set(&KeyValue::new("k", "b")).await?;
+        assert_eq!(
+            Some("a".to_string()),
+            bins.bin("alice").await?.get("k").await?
+        );
+        assert_eq!(vec!["alice", "bob", "alice"], bins.opened());
+        assert_eq!(2, bins.storage("alice").calls().len());
+        assert_eq!(1, bins.storage("bob").calls().len());
+
+        bins.fail(Op::Get);
+        assert!(bins.bin("bob").await?.get("k").await.is_err());
+        assert!(bins.bin("carol").await?.get("k").await.is_err());
+        bins.heal(Op::Get);
+        assert_eq!(
+            Some("b".to_string()),
+            bins.bin("bob").await?.get("k").await?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+//! A conformance suite for [Server], checking an implementation against the
+//! spec the trait documents: which calls are refused and with what error, the
+//! [Limits] enforced, the order timelines are listed in, and that repeating a
+//! call which already took effect is refused rather than applied twice.
+//!
+//! The suite only checks what the spec promises, so it runs alike against
+//! [crate::ref_impl::RefServer] and a front-end over a cluster. It fails by
+//! panicking, as a test does, and returns the errors the server gave where
+//! the spec says a call succeeds.
+//!
+//! ```rust
+//! use tribbler::config::Limits;
+//! use tribbler::ref_impl::RefServer;
+//! use tribbler::testing::conformance;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> tribbler::err::TribResult<()> {
+//! let limits = Limits {
+//!     max_trib_fetch: 10,
+//!     max_following: 5,
+//!     ..Limits::default()
+//! };
+//! conformance::run(Box::new(RefServer::with_limits(limits)), limits).await
+//! # }
+//! ```
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::config::Limits;
+use crate::err::{TribResult, TribblerError};
+use crate::trib::{trib_order, Server, Trib};
+
+// asserts that a call is refused with the given kind of error
+macro_rules! refused {
+    ($call:expr, $error:pat) => {{
+        let result = $call.await;
+        assert!(
+            matches!(result, Err($error)),
+            "{} should fail with {}, got {:?}",
+            stringify!($call),
+            stringify!($error),
+            result
+        );
+    }};
+}
+
+/// Runs the whole suite against `server`, which has to have no users yet and
+/// to enforce `limits`. Small limits keep the suite quick against a cluster.
+pub async fn run(server: Box<dyn Server>, limits: Limits) -> TribResult<()> {
+    let server = server.as_ref();
+    // before anyone else signs up, so every user is accounted for
+    list_users(server, &limits).await?;
+    sign_up(server).await?;
+    post(server, &limits).await?;
+    tribs(server, &limits).await?;
+    follow(server, &limits).await?;
+    unfollow(server).await?;
+    is_following(server).await?;
+    following(server).await?;
+    home(server, &limits).await?;
+    likes(server).await?;
+    Ok(())
+}
+
+async fn list_users(server: &dyn Server, limits: &Limits) -> TribResult<()> {
+    assert!(server.list_users().await?.is_empty());
+
+    // all of them while there are few
+    server.sign_up("lu1").await?;
+    server.sign_up("lu0").await?;
+    assert_eq!(vec!["lu0", "lu1"], server.list_users().await?);
+
+    // at least the minimum once there are more, sorted and each one once
+    let names: Vec<String> = (2..limits.min_list_user + 5)
+        .map(|i| format!("lu{}", i))
+        .collect();
+    for name in names.iter() {
+        server.sign_up(name).await?;
+    }
+    let users = server.list_users().await?;
+    assert!(users.len() >= limits.min_list_user, "{:?}", users);
+    assert!(users.windows(2).all(|w| w[0] < w[1]), "{:?}", users);
+    for user in users.iter() {
+        assert!(user == "lu0" || user == "lu1" || names.contains(user));
+    }
+    Ok(())
+}
+
+async fn sign_up(server: &dyn Server) -> TribResult<()> {
+    refused!(server.sign_up(""), TribblerError::InvalidUsername(_));
+    refused!(server.sign_up("Alice"), TribblerError::InvalidUsername(_));
+    refused!(server.sign_up("a-b"), TribblerError::InvalidUsername(_));
+    refused!(
+        server.sign_up("abcdefghijklmnop"),
+        TribblerError::InvalidUsername(_)
+    );
+
+    assert!(!server.signed_up("su").await?);
+    server.sign_up("abcdefghijklmno").await?;
+    server.sign_up("su").await?;
+    refused!(server.sign_up("su"), TribblerError::UsernameTaken(_));
+    assert!(server.signed_up("su").await?);
+    assert!(!server.signed_up("Su").await?);
+    Ok(())
+}
+
+async fn post(server: &dyn Server, limits: &Limits) -> TribResult<()> {
+    let longest = "x".repeat(limits.max_trib_len);
+    let too_long = "x".repeat(limits.max_trib_len + 1);
+    refused!(
+        server.post("po", &longest, 0),
+        TribblerError::UserDoesNotExist(_)
+    );
+
+    server.sign_up("po").await?;
+    refused!(server.post("po", &too_long, 0), TribblerError::TribTooLong);
+    assert!(server.tribs("po").await?.is_empty());
+    server.post("po", &longest, 0).await?;
+    server.post("po", "", 0).await?;
+    assert_eq!(2, server.tribs("po").await?.len());
+
+    // a trib is ordered after the clock its poster has seen
+    let seen = server.clock().await? + 1000;
+    server.post("po", "later", seen).await?;
+    let last = last(server.tribs("po").await?);
+    assert_eq!("later", last.message);
+    assert_eq!("po", last.user);
+    assert!(last.clock >= seen, "{} < {}", last.clock, seen);
+    Ok(())
+}
+
+async fn tribs(server: &dyn Server, limits: &Limits) -> TribResult<()> {
+    refused!(server.tribs("tr"), TribblerError::UserDoesNotExist(_));
+    server.sign_up("tr").await?;
+
+    // oldest first, each after the one before
+    let count = limits.max_trib_fetch + 5;
+    for i in 0..count {
+        server.post("tr", &i.to_string(), 0).await?;
+    }
+    let tribs = server.tribs("tr").await?;
+    assert_ordered(&tribs);
+    assert!(tribs.windows(2).all(|w| w[0].clock < w[1].clock));
+
+    // only the latest ones
+    let messages: Vec<String> = tribs.iter().map(|t| t.message.clone()).collect();
+    let latest: Vec<String> = (5..count).map(|i| i.to_string()).collect();
+    assert_eq!(latest, messages);
+
+    // reading leaves them be
+    let again: Vec<u64> = server.tribs("tr").await?.iter().map(|t| t.clock).collect();
+    let clocks: Vec<u64> = tribs.iter().map(|t| t.clock).collect();
+    assert_eq!(clocks, again);
+    Ok(())
+}
+
+async fn follow(server: &dyn Server, limits: &Limits) -> TribResult<()> {
+    refused!(
+        server.follow("fo", "fo0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    server.sign_up("fo").await?;
+    refused!(
+        server.follow("fo", "fo0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    refused!(server.follow("fo", "fo"), TribblerError::WhoWhom(_));
+
+    for i in 0..limits.max_following {
+        let name = format!("fo{}", i);
+        server.sign_up(&name).await?;
+        server.follow("fo", &name).await?;
+    }
+    refused!(
+        server.follow("fo", "fo0"),
+        TribblerError::AlreadyFollowing(_, _)
+    );
+
+    // one too many
+    let name = format!("fo{}", limits.max_following);
+    server.sign_up(&name).await?;
+    refused!(server.follow("fo", &name), TribblerError::FollowingTooMany);
+    assert_eq!(limits.max_following, server.following("fo").await?.len());
+
+    // room again after unfollowing someone
+    server.unfollow("fo", "fo0").await?;
+    server.follow("fo", &name).await?;
+    Ok(())
+}
+
+async fn unfollow(server: &dyn Server) -> TribResult<()> {
+    refused!(
+        server.unfollow("un", "un0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    server.sign_up("un").await?;
+    refused!(
+        server.unfollow("un", "un0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    refused!(server.unfollow("un", "un"), TribblerError::WhoWhom(_));
+
+    server.sign_up("un0").await?;
+    refused!(
+        server.unfollow("un", "un0"),
+        TribblerError::NotFollowing(_, _)
+    );
+    server.follow("un", "un0").await?;
+    server.unfollow("un", "un0").await?;
+    refused!(
+        server.unfollow("un", "un0"),
+        TribblerError::NotFollowing(_, _)
+    );
+
+    // and following again works
+    server.follow("un", "un0").await?;
+    assert!(server.is_following("un", "un0").await?);
+    Ok(())
+}
+
+async fn is_following(server: &dyn Server) -> TribResult<()> {
+    refused!(
+        server.is_following("is", "is0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    server.sign_up("is").await?;
+    refused!(
+        server.is_following("is", "is0"),
+        TribblerError::UserDoesNotExist(_)
+    );
+    refused!(server.is_following("is", "is"), TribblerError::WhoWhom(_));
+
+    server.sign_up("is0").await?;
+    assert!(!server.is_following("is", "is0").await?);
+    server.follow("is", "is0").await?;
+    assert!(server.is_following("is", "is0").await?);
+    // following is one way
+    assert!(!server.is_following("is0", "is").await?);
+    server.unfollow("is", "is0").await?;
+    assert!(!server.is_following("is", "is0").await?);
+    Ok(())
+}
+
+async fn following(server: &dyn Server) -> TribResult<()> {
+    refused!(server.following("fg"), TribblerError::UserDoesNotExist(_));
+    server.sign_up("fg").await?;
+    assert!(server.following("fg").await?.is_empty());
+
+    for name in ["fgb", "fga", "fgc"] {
+        server.sign_up(name).await?;
+        server.follow("fg", name).await?;
+    }
+    let mut followees = server.following("fg").await?;
+    followees.sort();
+    assert_eq!(vec!["fga", "fgb", "fgc"], followees);
+
+    server.unfollow("fg", "fgb").await?;
+    let mut followees = server.following("fg").await?;
+    followees.sort();
+    assert_eq!(vec!["fga", "fgc"], followees);
+    Ok(())
+}
+
+async fn home(server: &dyn Server, limits: &Limits) -> TribResult<()> {
+    refused!(server.home("ho"), TribblerError::UserDoesNotExist(_));
+    server.sign_up("ho").await?;
+    server.sign_up("ho0").await?;
+    server.sign_up("ho1").await?;
+    assert!(server.home("ho").await?.is_empty());
+
+    // one's own tribs and those of the users one follows, in order, the
+    // later one posted by someone who saw the earlier
+    server.follow("ho", "ho0").await?;
+    server.post("ho", "mine", 0).await?;
+    let seen = last(server.tribs("ho").await?).clock;
+    server.post("ho0", "followed", seen).await?;
+    server.post("ho1", "not followed", 0).await?;
+    let home = server.home("ho").await?;
+    assert_ordered(&home);
+    let messages: Vec<&str> = home.iter().map(|t| t.message.as_str()).collect();
+    assert_eq!(vec!["mine", "followed"], messages);
+
+    // the tribs of a user unfollowed since stop coming
+    server.unfollow("ho", "ho0").await?;
+    server.post("ho0", "unfollowed", 0).await?;
+    let home = server.home("ho").await?;
+    assert!(home.iter().all(|t| t.message != "unfollowed"), "{:?}", home);
+
+    // only the latest ones
+    let seen = last(home).clock;
+    for i in 0..limits.max_trib_fetch {
+        server.post("ho", &i.to_string(), seen).await?;
+    }
+    let home = server.home("ho").await?;
+    assert_ordered(&home);
+    assert_eq!(limits.max_trib_fetch, home.len());
+    let last = last(home);
+    assert_eq!((limits.max_trib_fetch - 1).to_string(), last.message);
+    Ok(())
+}
+
+async fn likes(server: &dyn Server) -> TribResult<()> {
+    server.sign_up("li").await?;
+    server.sign_up("li0").await?;
+    server.post("li0", "likeable", 0).await?;
+    let clock = last(server.tribs("li0").await?).clock;
+
+    refused!(
+        server.like("li", "li0", clock + 1),
+        TribblerError::TribNotFound(_, _)
+    );
+    refused!(
+        server.like("li", "li1", clock),
+        TribblerError::UserDoesNotExist(_)
+    );
+    assert!(server.likes("li0", clock).await?.is_empty());
+
+    server.like("li", "li0", clock).await?;
+    refused!(
+        server.like("li", "li0", clock),
+        TribblerError::AlreadyLiked(_, _, _)
+    );
+    assert_eq!(vec!["li"], server.likes("li0", clock).await?);
+
+    server.unlike("li", "li0", clock).await?;
+    refused!(
+        server.unlike("li", "li0", clock),
+        TribblerError::NotLiked(_, _, _)
+    );
+    assert!(server.likes("li0", clock).await?.is_empty());
+    Ok(())
+}
+
+// the last trib of a timeline the suite just posted to
+fn last(tribs: Vec<Arc<Trib>>) -> Arc<Trib> {
+    tribs.last().cloned().expect("a trib was posted")
+}
+
+fn assert_ordered(tribs: &[Arc<Trib>]) {
+    assert!(
+        tribs
+            .windows(2)
+            .all(|w| trib_order(&w[0], &w[1]) != Ordering::Greater),
+        "out of order: {:?}",
+        tribs
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+    use crate::config::Limits;
+    use crate::err::TribResult;
+    use crate::ref_impl::RefServer;
+
+    #[tokio::test]
+    async fn ref_server_conforms() -> TribResult<()> {
+        let limits = Limits {
+            max_trib_fetch: 10,
+            max_following: 5,
+            ..Limits::default()
+        };
+        run(Box::new(RefServer::with_limits(limits)), limits).await
+    }
+
+    #[tokio::test]
+    async fn ref_server_conforms_with_default_limits() -> TribResult<()> {
+        run(Box::new(RefServer::new()), Limits::default()).await
+    }
+}
@@ -0,0 +1,107 @@
+//! Where the servers read the time from.
+//!
+//! A trib is stamped with the wall-clock time of the server posting it, and
+//! so are the last activity of a user and the trending window; the keeper
+//! times its probes and hold-downs, and the front-end its caches, sessions,
+//! and rate limits. They all take a [TimeSource] as an `Arc<dyn TimeSource>`,
+//! [SystemClock] outside of tests, so a test can move the time along with a
+//! [ManualClock] rather than sleep, and know the exact order the tribs come
+//! out in.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use tokio::time::Instant;
+
+use crate::err::TribResult;
+
+/// A source of the wall-clock time, and of a monotonic one.
+pub trait TimeSource: Send + Sync {
+    /// The wall-clock time, as the time since the epoch.
+    fn since_epoch(&self) -> TribResult<Duration>;
+
+    /// A monotonic instant, to measure how long has passed.
+    fn instant(&self) -> Instant;
+
+    /// The wall-clock time, in seconds since the epoch.
+    fn now(&self) -> TribResult<u64> {
+        Ok(self.since_epoch()?.as_secs())
+    }
+}
+
+/// The [TimeSource] of the machine. Its instants come from the clock of
+/// tokio, so they stand still in a runtime started paused.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn since_epoch(&self) -> TribResult<Duration> {
+        Ok(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?)
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A [TimeSource] which stays at the time it is set to, for tests.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tribbler::time::{ManualClock, TimeSource};
+///
+/// let clock = ManualClock::new(100);
+/// let start = clock.instant();
+/// assert_eq!(100, clock.now().unwrap());
+/// clock.advance(Duration::from_millis(5_500));
+/// assert_eq!(105, clock.now().unwrap());
+/// assert_eq!(Duration::from_millis(5_500), clock.instant() - start);
+/// clock.set(7);
+/// assert_eq!(7, clock.now().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    epoch_ms: AtomicU64,   // the wall-clock time, in milliseconds
+    elapsed_ms: AtomicU64, // how far the monotonic time moved on from start
+    start: Instant,
+}
+
+impl ManualClock {
+    /// A clock standing at `now`, in seconds since the epoch.
+    pub fn new(now: u64) -> ManualClock {
+        ManualClock {
+            epoch_ms: AtomicU64::new(now * 1000),
+            elapsed_ms: AtomicU64::new(0),
+            start: Instant::now(),
+        }
+    }
+
+    /// Moves the wall-clock time to `now`, in seconds since the epoch, back
+    /// as well as forward. The monotonic time stays where it is.
+    pub fn set(&self, now: u64) {
+        self.epoch_ms.store(now * 1000, Ordering::SeqCst);
+    }
+
+    /// Moves the time forward by `by`, to the millisecond.
+    pub fn advance(&self, by: Duration) {
+        let ms = by.as_millis() as u64;
+        self.epoch_ms.fetch_add(ms, Ordering::SeqCst);
+        self.elapsed_ms.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new(0)
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn since_epoch(&self) -> TribResult<Duration> {
+        Ok(Duration::from_millis(self.epoch_ms.load(Ordering::SeqCst)))
+    }
+
+    fn instant(&self) -> Instant {
+        self.start + Duration::from_millis(self.elapsed_ms.load(Ordering::SeqCst))
+    }
+}
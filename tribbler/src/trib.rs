@@ -1,35 +1,74 @@
 #![allow(dead_code)]
 //! Package trib defines basic interfaces and constants
 //! for the Tribbler service implementation.
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::err::TribResult;
+use crate::err::{ServerResult, TribblerError};
 
 /// Maximum length of a username
 #[allow(dead_code)]
 pub const MAX_USERNAME_LEN: usize = 15;
 
-/// Maximum length of a tribble
+/// Default maximum length of a tribble, see [crate::config::Limits]
 #[allow(dead_code)]
 pub const MAX_TRIB_LEN: usize = 140;
 
-/// Maximum count of tribbles for `Home()` and `Tribs()`
+/// Default maximum count of tribbles for `Home()` and `Tribs()`, see
+/// [crate::config::Limits]
 #[allow(dead_code)]
 pub const MAX_TRIB_FETCH: usize = 100;
 
-/// Minimum count of users required for `ListUsers()`
+/// Default minimum count of users required for `ListUsers()`, see
+/// [crate::config::Limits]
 #[allow(dead_code)]
 pub const MIN_LIST_USER: usize = 20;
 
-/// Maximum count of users that one can follow
+/// Default maximum count of users that one can follow, see
+/// [crate::config::Limits]
 #[allow(dead_code)]
 pub const MAX_FOLLOWING: usize = 2000;
 
+/// Maximum length of the display name on a [Profile]
+#[allow(dead_code)]
+pub const MAX_DISPLAY_NAME_LEN: usize = 50;
+
+/// Maximum length of the bio on a [Profile]
+#[allow(dead_code)]
+pub const MAX_BIO_LEN: usize = 160;
+
+/// Maximum length of the avatar reference on a [Profile]
+#[allow(dead_code)]
+pub const MAX_AVATAR_LEN: usize = 256;
+
+/// Maximum count of attachments on a trib
+#[allow(dead_code)]
+pub const MAX_ATTACHMENTS: usize = 4;
+
+/// Maximum length of the URL of an [Attachment]
+#[allow(dead_code)]
+pub const MAX_ATTACHMENT_URL_LEN: usize = 256;
+
+/// Maximum count of drafts a user can keep
+#[allow(dead_code)]
+pub const MAX_DRAFTS: usize = 50;
+
+/// How long the uses of a hashtag count towards it trending, in seconds
+#[allow(dead_code)]
+pub const TRENDING_WINDOW: u64 = 24 * 60 * 60;
+
+/// How finely the trending window slides, in seconds. Uses of hashtags are
+/// counted per slot of this length.
+#[allow(dead_code)]
+pub const TRENDING_SLOT: u64 = 60 * 60;
+
 /// A [Trib] is a post by a user to the tribbler service.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Trib {
     /// who posted this trib
     pub user: String,
@@ -39,6 +78,382 @@ pub struct Trib {
     pub time: u64,
     /// the logical timestamp when posted
     pub clock: u64,
+    /// how many times the trib was edited since it was posted
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub revision: u32,
+    /// the trib this one reposts, when it is a retrib
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrib_of: Option<TribRef>,
+    /// the trib this one replies to, when it is part of a thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<TribRef>,
+    /// the media shown along with the message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Refers to media hosted elsewhere that a [Trib] shows along with its
+/// message. Attachments do not count towards [MAX_TRIB_LEN].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Attachment {
+    /// where the media is hosted, an http(s) URL
+    pub url: String,
+    /// the hex digest of the media, so a client can check what it fetched
+    pub hash: String,
+    /// the media type, e.g. `image/png`
+    pub mime_type: String,
+}
+
+impl Attachment {
+    /// Checks the URL, the digest and the media type.
+    pub fn validate(&self) -> ServerResult<()> {
+        if self.url.len() > MAX_ATTACHMENT_URL_LEN || !is_valid_url(&self.url) {
+            return Err(TribblerError::InvalidAttachment(self.url.to_string()));
+        }
+        if !is_hex_digest(&self.hash) {
+            return Err(TribblerError::InvalidAttachment(self.hash.to_string()));
+        }
+        if !is_valid_mime_type(&self.mime_type) {
+            return Err(TribblerError::InvalidAttachment(self.mime_type.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the attachments of a trib: at most [MAX_ATTACHMENTS] of them, each
+/// of them valid.
+pub fn validate_attachments(attachments: &[Attachment]) -> ServerResult<()> {
+    if attachments.len() > MAX_ATTACHMENTS {
+        return Err(TribblerError::TooManyAttachments);
+    }
+    for attachment in attachments {
+        attachment.validate()?;
+    }
+    Ok(())
+}
+
+/// Identifies a [Trib] by its author and the logical timestamp it was
+/// posted at.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TribRef {
+    /// who posted the trib
+    pub user: String,
+    /// the logical timestamp of the trib
+    pub clock: u64,
+}
+
+/// Text a user saved to post later.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Draft {
+    /// identifies the draft among the drafts of its user
+    pub id: u64,
+    /// the text saved
+    pub message: String,
+}
+
+/// A trib waiting to be posted at a later time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledPost {
+    /// tells apart posts scheduled with the same message and time
+    pub id: u64,
+    /// the content of the trib
+    pub message: String,
+    /// when to post it, in seconds since the epoch
+    pub publish_at: u64,
+}
+
+/// An entry of the follow log kept in the bin of a user, which the users they
+/// follow added up from before their [FolloweeSet] was kept. Entries are
+/// stored as `clock::follow::whom`, `clock::unfollow::whom`, or, for a log
+/// compacted up to `clock`, `clock::snapshot::followee,followee,...`, which
+/// is how they display.
+///
+/// ```
+/// use tribbler::trib::FollowLogEntry;
+///
+/// let entry = FollowLogEntry::parse("7::follow::bob").unwrap();
+/// assert_eq!(FollowLogEntry::Follow { clock: 7, whom: "bob".to_string() }, entry);
+/// assert_eq!("7::follow::bob", entry.to_string());
+/// assert!(FollowLogEntry::parse("7::follow").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowLogEntry {
+    /// the user started following `whom`
+    Follow { clock: u64, whom: String },
+    /// the user stopped following `whom`
+    Unfollow { clock: u64, whom: String },
+    /// the user followed `followees` once the entries up to `clock` were in
+    Snapshot { clock: u64, followees: Vec<String> },
+}
+
+impl FollowLogEntry {
+    /// Reads an entry as it is stored, failing with
+    /// [TribblerError::CorruptLogEntry] when it isn't one.
+    pub fn parse(entry: &str) -> ServerResult<FollowLogEntry> {
+        let corrupt = || TribblerError::CorruptLogEntry(entry.to_string());
+        let mut parts = entry.splitn(3, "::");
+        let (clock, kind, rest) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(clock), Some(kind), Some(rest)) => (clock, kind, rest),
+            _ => return Err(corrupt()),
+        };
+        let clock = clock.parse::<u64>().map_err(|_| corrupt())?;
+        let parsed = match kind {
+            "follow" | "unfollow" if rest.is_empty() || rest.contains("::") => {
+                return Err(corrupt())
+            }
+            "follow" => FollowLogEntry::Follow {
+                clock,
+                whom: rest.to_string(),
+            },
+            "unfollow" => FollowLogEntry::Unfollow {
+                clock,
+                whom: rest.to_string(),
+            },
+            "snapshot" => FollowLogEntry::Snapshot {
+                clock,
+                followees: rest
+                    .split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_string())
+                    .collect(),
+            },
+            _ => return Err(corrupt()),
+        };
+        Ok(parsed)
+    }
+
+    /// The clock the entry was written at.
+    pub fn clock(&self) -> u64 {
+        match self {
+            FollowLogEntry::Follow { clock, .. }
+            | FollowLogEntry::Unfollow { clock, .. }
+            | FollowLogEntry::Snapshot { clock, .. } => *clock,
+        }
+    }
+}
+
+impl std::fmt::Display for FollowLogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FollowLogEntry::Follow { clock, whom } => write!(f, "{}::follow::{}", clock, whom),
+            FollowLogEntry::Unfollow { clock, whom } => write!(f, "{}::unfollow::{}", clock, whom),
+            FollowLogEntry::Snapshot { clock, followees } => {
+                write!(f, "{}::snapshot::{}", clock, followees.join(","))
+            }
+        }
+    }
+}
+
+/// Everything a user put into the service, as handed out by
+/// [Server::export_user].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserExport {
+    /// the name of the user
+    pub user: String,
+    /// the profile of the user
+    pub profile: Profile,
+    /// every trib the user posted, oldest first
+    pub tribs: Vec<Trib>,
+    /// the users the user follows, sorted
+    pub following: Vec<String>,
+    /// the tribs the user likes
+    pub likes: Vec<TribRef>,
+}
+
+/// A hashtag and how many tribs used it over the [TRENDING_WINDOW].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Trend {
+    /// the hashtag, without the leading `#`
+    pub tag: String,
+    /// how many tribs used it
+    pub count: u64,
+}
+
+/// What a [Notification] is about.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    /// someone started following the user
+    Follow,
+    /// someone mentioned the user in a trib
+    Mention,
+    /// someone liked a trib of the user
+    Like,
+    /// someone replied to a trib of the user
+    Reply,
+}
+
+/// Lets a user know that another user did something involving them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Notification {
+    /// what happened
+    pub kind: NotificationKind,
+    /// who did it
+    pub from: String,
+    /// the trib it happened with: the mentioning trib, the liked trib or
+    /// the reply; none for follows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trib: Option<TribRef>,
+    /// the logical timestamp of the notification, increasing per user
+    pub clock: u64,
+    /// whether the user marked it read
+    #[serde(default)]
+    pub read: bool,
+}
+
+/// What a user tells others about themself. Every field is optional and
+/// empty by default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Profile {
+    /// the name shown instead of the username
+    #[serde(default)]
+    pub display_name: String,
+    /// a short description of the user
+    #[serde(default)]
+    pub bio: String,
+    /// the picture of the user, either an http(s) URL or the hex digest the
+    /// web UI looks the picture up by on Gravatar
+    #[serde(default)]
+    pub avatar: String,
+}
+
+impl Profile {
+    /// Checks every field against its length limit.
+    pub fn validate(&self) -> ServerResult<()> {
+        if self.display_name.len() > MAX_DISPLAY_NAME_LEN {
+            return Err(TribblerError::ProfileTooLong("display_name".to_string()));
+        }
+        if self.bio.len() > MAX_BIO_LEN {
+            return Err(TribblerError::ProfileTooLong("bio".to_string()));
+        }
+        if self.avatar.len() > MAX_AVATAR_LEN {
+            return Err(TribblerError::ProfileTooLong("avatar".to_string()));
+        }
+        if !is_valid_avatar(&self.avatar) {
+            return Err(TribblerError::InvalidAvatar(self.avatar.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Checks if an avatar reference is empty, an http(s) URL without
+/// whitespace, or a hex digest of 32 to 64 digits. Returns true if it is.
+pub fn is_valid_avatar(avatar: &str) -> bool {
+    if avatar.is_empty() {
+        return true;
+    }
+    if avatar.starts_with("http://") || avatar.starts_with("https://") {
+        return is_valid_url(avatar);
+    }
+    is_hex_digest(avatar)
+}
+
+// an http(s) URL without whitespace
+fn is_valid_url(url: &str) -> bool {
+    for scheme in ["http://", "https://"] {
+        if let Some(rest) = url.strip_prefix(scheme) {
+            return !rest.is_empty() && !rest.chars().any(|c| c.is_whitespace());
+        }
+    }
+    false
+}
+
+// a hex digest of 32 to 64 digits
+fn is_hex_digest(digest: &str) -> bool {
+    (32..=64).contains(&digest.len()) && digest.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// a media type like "image/png": a type and a subtype made of ascii letters,
+// digits and "+-."
+fn is_valid_mime_type(mime_type: &str) -> bool {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    };
+    match mime_type.split_once('/') {
+        Some((kind, subtype)) => valid_part(kind) && valid_part(subtype),
+        None => false,
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// The users a user follows, kept as one value in their bin and replaced as a
+/// whole by compare-and-swap. `version` counts the replacements, so a value
+/// read once is never mistaken for one written since. The users followed or
+/// unfollowed are kept `pending` until the followers lists and the home
+/// timeline show the change.
+///
+/// ```
+/// use tribbler::trib::FolloweeSet;
+///
+/// let set = FolloweeSet::default().with("bob");
+/// assert_eq!(1, set.version);
+/// assert!(set.followees.contains(&"bob".to_string()));
+/// assert_eq!(vec!["bob"], set.pending);
+/// assert!(set.without("bob").followees.is_empty());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolloweeSet {
+    /// how many times the set was replaced
+    pub version: u64,
+    /// the users followed, sorted
+    pub followees: Vec<String>,
+    /// the users followed or unfollowed since the followers lists and the
+    /// home timeline were last brought in line with the set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<String>,
+}
+
+impl FolloweeSet {
+    /// The next version of the set, following `whom` as well.
+    pub fn with(&self, whom: &str) -> FolloweeSet {
+        let mut followees = self.followees.clone();
+        if !followees.iter().any(|followee| followee == whom) {
+            followees.push(whom.to_string());
+            followees.sort();
+        }
+        FolloweeSet {
+            version: self.version + 1,
+            followees,
+            pending: self.pending_with(whom),
+        }
+    }
+
+    /// The next version of the set, no longer following `whom`.
+    pub fn without(&self, whom: &str) -> FolloweeSet {
+        FolloweeSet {
+            version: self.version + 1,
+            followees: self
+                .followees
+                .iter()
+                .filter(|followee| *followee != whom)
+                .cloned()
+                .collect(),
+            pending: self.pending_with(whom),
+        }
+    }
+
+    // the users pending, whom among them
+    fn pending_with(&self, whom: &str) -> Vec<String> {
+        let mut pending = self.pending.clone();
+        if !pending.iter().any(|user| user == whom) {
+            pending.push(whom.to_string());
+        }
+        pending
+    }
 }
 
 #[async_trait]
@@ -49,28 +464,53 @@ pub trait Server: Send + Sync {
     /// Returns error when the username is invalid;
     /// returns error when the user already exists.
     /// Concurrent sign ups on the same user might both succeed with no error.
-    async fn sign_up(&self, user: &str) -> TribResult<()>;
+    async fn sign_up(&self, user: &str) -> ServerResult<()>;
 
     /// List 20 registered users.  When there are less than 20 users that
     /// signed up the service, all of them needs to be listed.  When there
     /// are more than 20 users that signed up the service, an arbitrary set
     /// of at lest 20 of them needs to be listed.
     /// The result should be sorted in alphabetical order.
-    async fn list_users(&self) -> TribResult<Vec<String>>;
+    async fn list_users(&self) -> ServerResult<Vec<String>>;
+
+    /// Returns whether user has signed up. A user name which isn't valid
+    /// never has.
+    async fn signed_up(&self, user: &str) -> ServerResult<bool>;
 
     /// Post a tribble.  The clock is the maximum clock value this user has
     /// seen so far by reading tribbles or clock sync.
     /// Returns error when who does not exist;
+    /// returns error when who is banned;
     /// returns error when post is too long.
-    async fn post(&self, who: &str, post: &str, clock: u64) -> TribResult<()>;
+    async fn post(&self, who: &str, post: &str, clock: u64) -> ServerResult<()>;
+
+    /// Like [Server::post], but the trib also shows the given media.
+    /// Returns error when who does not exist;
+    /// returns error when post is too long;
+    /// returns error when there are more than [MAX_ATTACHMENTS] attachments
+    /// or one of them is invalid.
+    async fn post_with_attachments(
+        &self,
+        who: &str,
+        post: &str,
+        clock: u64,
+        attachments: &[Attachment],
+    ) -> ServerResult<()>;
+
+    /// Returns the current logical clock of the service. The clock is no
+    /// smaller than the clock of any trib posted before the call, so passing
+    /// it to [Server::post] orders the new trib after all of them.
+    async fn clock(&self) -> ServerResult<u64>;
 
     /// List the tribs that a particular user posted.
     /// Returns error when user has not signed up.
-    async fn tribs(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+    async fn tribs(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>>;
 
     /// Follow someone's timeline.
     /// Returns error when who == whom;
     /// returns error when who is already following whom;
+    /// returns error when whom blocks who;
+    /// returns error when who is banned;
     /// returns error when who is trying to following
     /// more than trib.MaxFollowing users.
     /// returns error when who or whom has not signed up.
@@ -78,29 +518,267 @@ pub trait Server: Send + Sync {
     /// The count of following users might exceed trib.MaxFollowing=2000,
     /// if and only if the 2000'th user is generated by concurrent Follow()
     /// calls.
-    async fn follow(&self, who: &str, whom: &str) -> TribResult<()>;
+    async fn follow(&self, who: &str, whom: &str) -> ServerResult<()>;
 
     /// Unfollow someone's timeline.
     /// Returns error when who == whom.
     /// returns error when who is not following whom;
     /// returns error when who or whom has not signed up.
-    async fn unfollow(&self, who: &str, whom: &str) -> TribResult<()>;
+    async fn unfollow(&self, who: &str, whom: &str) -> ServerResult<()>;
 
     /// Returns true when who following whom.
     /// Returns error when who == whom.
     /// Returns error when who or whom has not signed up.
-    async fn is_following(&self, who: &str, whom: &str) -> TribResult<bool>;
+    async fn is_following(&self, who: &str, whom: &str) -> ServerResult<bool>;
 
     /// Returns the list of following users.
     /// Returns error when who has not signed up.
     /// The list have users more than trib.MaxFollowing=2000,
     /// if and only if the 2000'th user is generate d by concurrent Follow()
     /// calls.
-    async fn following(&self, who: &str) -> TribResult<Vec<String>>;
+    async fn following(&self, who: &str) -> ServerResult<Vec<String>>;
 
-    /// List the tribs of someone's following users (including himself).
+    /// Returns the users who follow user and whom user follows back, sorted
+    /// by name.
     /// Returns error when user has not signed up.
-    async fn home(&self, user: &str) -> TribResult<Vec<Arc<Trib>>>;
+    async fn mutuals(&self, user: &str) -> ServerResult<Vec<String>>;
+
+    /// Returns when user last acted (posted, followed, liked and so on), in
+    /// seconds since the unix epoch, or [None] when they never have.
+    /// Returns error when user has not signed up.
+    async fn last_active(&self, user: &str) -> ServerResult<Option<u64>>;
+
+    /// List the tribs of someone's following users (including himself),
+    /// leaving out the users they block or mute and the banned users.
+    /// Returns error when user has not signed up.
+    async fn home(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Replaces the message of the trib `who` posted at `clock`. The trib
+    /// keeps its original clock and time, and its revision is bumped.
+    /// Returns error when who does not exist;
+    /// returns error when the new message is too long;
+    /// returns error when who has no trib with that clock.
+    async fn edit_trib(&self, who: &str, clock: u64, message: &str) -> ServerResult<()>;
+
+    /// Likes the trib `author` posted at `clock`.
+    /// Returns error when who or author does not exist;
+    /// returns error when author has no trib with that clock;
+    /// returns error when who already likes the trib.
+    async fn like(&self, who: &str, author: &str, clock: u64) -> ServerResult<()>;
+
+    /// Takes back a like of the trib `author` posted at `clock`.
+    /// Returns error when who or author does not exist;
+    /// returns error when who does not like the trib.
+    async fn unlike(&self, who: &str, author: &str, clock: u64) -> ServerResult<()>;
+
+    /// Lists the users who like the trib `author` posted at `clock`, sorted
+    /// in alphabetical order.
+    /// Returns error when author does not exist.
+    async fn likes(&self, author: &str, clock: u64) -> ServerResult<Vec<String>>;
+
+    /// Reposts the trib `author` posted at `clock` on who's timeline. The
+    /// retrib is ordered after the original trib and refers to it in
+    /// [Trib::retrib_of]; retribbing a retrib refers to the original trib.
+    /// Returns error when who or author does not exist;
+    /// returns error when author has no trib with that clock.
+    async fn retrib(&self, who: &str, author: &str, clock: u64) -> ServerResult<()>;
+
+    /// Posts a trib replying to the trib `parent_author` posted at
+    /// `parent_clock`. The reply is ordered after its parent and refers to
+    /// it in [Trib::reply_to].
+    /// Returns error when who or parent_author does not exist;
+    /// returns error when the message is too long;
+    /// returns error when parent_author has no trib with that clock.
+    async fn reply(
+        &self,
+        who: &str,
+        parent_author: &str,
+        parent_clock: u64,
+        message: &str,
+    ) -> ServerResult<()>;
+
+    /// Lists the direct replies to the trib `author` posted at `clock`,
+    /// oldest first.
+    /// Returns error when author does not exist.
+    async fn replies(&self, author: &str, clock: u64) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Lists the most recent tribs tagged with `#tag`, at most
+    /// [MAX_TRIB_FETCH] of them, oldest first. The tag is matched without
+    /// the leading `#` and case-insensitively.
+    async fn tribs_by_tag(&self, tag: &str) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Lists the most recent tribs mentioning `@user`, at most
+    /// [MAX_TRIB_FETCH] of them, oldest first.
+    /// Returns error when user has not signed up.
+    async fn mentions(&self, user: &str) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Lists the most recent tribs `user` posted with a clock less than
+    /// `before_clock`, at most `limit` (and never more than
+    /// [MAX_TRIB_FETCH]) of them, oldest first. Pass `u64::MAX` to get the
+    /// first page, and the clock of the oldest trib returned to get the next.
+    /// Returns error when user has not signed up.
+    async fn tribs_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Like [Server::tribs_page], but pages through the home timeline of
+    /// `user`.
+    /// Returns error when user has not signed up.
+    async fn home_page(
+        &self,
+        user: &str,
+        before_clock: u64,
+        limit: usize,
+    ) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Returns how many users `user` follows and how many follow `user`,
+    /// as `(following, followers)`.
+    /// Returns error when user has not signed up.
+    async fn follow_counts(&self, user: &str) -> ServerResult<(usize, usize)>;
+
+    /// Replaces the profile of `who`.
+    /// Returns error when who has not signed up;
+    /// returns error when a field of the profile is too long.
+    async fn set_profile(&self, who: &str, profile: &Profile) -> ServerResult<()>;
+
+    /// Returns the profile of `user`, empty when it was never set.
+    /// Returns error when user has not signed up.
+    async fn get_profile(&self, user: &str) -> ServerResult<Profile>;
+
+    /// Blocks `whom` for `who`: the tribs of whom no longer show up on the
+    /// home timeline of who, and whom can no longer follow who.
+    /// Returns error when who == whom;
+    /// returns error when who already blocks whom;
+    /// returns error when who or whom has not signed up.
+    async fn block(&self, who: &str, whom: &str) -> ServerResult<()>;
+
+    /// Takes back a block.
+    /// Returns error when who does not block whom;
+    /// returns error when who or whom has not signed up.
+    async fn unblock(&self, who: &str, whom: &str) -> ServerResult<()>;
+
+    /// Lists the users `who` blocks, sorted.
+    /// Returns error when who has not signed up.
+    async fn blocked(&self, who: &str) -> ServerResult<Vec<String>>;
+
+    /// Mutes `whom` for `who`: who keeps following whom, but the tribs of
+    /// whom no longer show up on the home timeline of who.
+    /// Returns error when who == whom;
+    /// returns error when who already mutes whom;
+    /// returns error when who or whom has not signed up.
+    async fn mute(&self, who: &str, whom: &str) -> ServerResult<()>;
+
+    /// Takes back a mute.
+    /// Returns error when who does not mute whom;
+    /// returns error when who or whom has not signed up.
+    async fn unmute(&self, who: &str, whom: &str) -> ServerResult<()>;
+
+    /// Renames `old` to `new`. The tribs, follows, likes and profile of old
+    /// move over to new, and whatever other users recorded about old, like
+    /// following them or being mentioned by them, refers to new from then
+    /// on. The old name may stay taken.
+    /// Returns error when new is invalid;
+    /// returns error when old has not signed up;
+    /// returns error when new is already taken.
+    async fn rename_user(&self, old: &str, new: &str) -> ServerResult<()>;
+
+    /// Lists the hashtags used the most over the last [TRENDING_WINDOW], at
+    /// most `limit` of them, the most used first.
+    async fn trending(&self, limit: usize) -> ServerResult<Vec<Trend>>;
+
+    /// Lists the notifications of `user` with a clock greater than `since`,
+    /// at most [MAX_TRIB_FETCH] of the most recent ones, oldest first. Pass
+    /// 0 to get the most recent ones.
+    /// Returns error when user has not signed up.
+    async fn notifications(&self, user: &str, since: u64) -> ServerResult<Vec<Notification>>;
+
+    /// Marks the notifications of `user` with a clock up to `upto` as read.
+    /// Notifications once read stay read.
+    /// Returns error when user has not signed up.
+    async fn mark_read(&self, user: &str, upto: u64) -> ServerResult<()>;
+
+    /// Returns how many notifications of `user` are not marked read yet.
+    /// Returns error when user has not signed up.
+    async fn unread_count(&self, user: &str) -> ServerResult<usize>;
+
+    /// Creates an empty list named `name` for `who` to group users in. List
+    /// names follow the rules of usernames.
+    /// Returns error when name is invalid;
+    /// returns error when who already has a list named name;
+    /// returns error when who has not signed up.
+    async fn create_list(&self, who: &str, name: &str) -> ServerResult<()>;
+
+    /// Adds `whom` to the list `name` of `who`.
+    /// Returns error when who has no such list;
+    /// returns error when whom is already on the list;
+    /// returns error when the list already holds [MAX_FOLLOWING] users;
+    /// returns error when who or whom has not signed up.
+    async fn add_to_list(&self, who: &str, name: &str, whom: &str) -> ServerResult<()>;
+
+    /// Lists the most recent tribs of the users on the list `name` of
+    /// `who`, merged like [Server::home] merges the users who follows, at
+    /// most [MAX_TRIB_FETCH] of them, oldest first. The users who blocks or
+    /// mutes are left out.
+    /// Returns error when who has no such list;
+    /// returns error when who has not signed up.
+    async fn list_timeline(&self, who: &str, name: &str) -> ServerResult<Vec<Arc<Trib>>>;
+
+    /// Saves `message` as a draft of `who` and returns its id. Drafts show
+    /// up on no timeline and do not count towards [MAX_TRIB_FETCH].
+    /// Returns error when who has not signed up;
+    /// returns error when the message is too long;
+    /// returns error when who already keeps [MAX_DRAFTS] drafts.
+    async fn save_draft(&self, who: &str, message: &str) -> ServerResult<u64>;
+
+    /// Lists the drafts of `who`, oldest first.
+    /// Returns error when who has not signed up.
+    async fn list_drafts(&self, who: &str) -> ServerResult<Vec<Draft>>;
+
+    /// Deletes the draft `id` of `who`, e.g. once it is posted.
+    /// Returns error when who has no such draft;
+    /// returns error when who has not signed up.
+    async fn delete_draft(&self, who: &str, id: u64) -> ServerResult<()>;
+
+    /// Posts `message` as `who` once `publish_at`, in seconds since the
+    /// epoch, has come. The trib gets a fresh clock when it is posted, so it
+    /// sorts after everything posted before then.
+    /// Returns error when who has not signed up;
+    /// returns error when the message is too long.
+    async fn schedule_post(&self, who: &str, message: &str, publish_at: u64) -> ServerResult<()>;
+
+    /// Deletes the trib `author` posted at `clock` from every timeline. Meant
+    /// for moderators; the REST API only serves it behind the admin token.
+    /// Returns error when author has not signed up;
+    /// returns error when author has no trib with that clock.
+    async fn admin_delete_trib(&self, author: &str, clock: u64) -> ServerResult<()>;
+
+    /// Bans `user`: they can no longer post or follow, and their tribs no
+    /// longer show up on anyone's home timeline. Meant for moderators.
+    /// Returns error when user is already banned;
+    /// returns error when user has not signed up.
+    async fn admin_ban_user(&self, user: &str) -> ServerResult<()>;
+
+    /// Takes back a ban. Meant for moderators.
+    /// Returns error when user is not banned;
+    /// returns error when user has not signed up.
+    async fn admin_unban(&self, user: &str) -> ServerResult<()>;
+
+    /// Gathers the profile, tribs, followees and likes of `user` into one
+    /// document, e.g. to hand it over to them.
+    /// Returns error when user has not signed up.
+    async fn export_user(&self, user: &str) -> ServerResult<UserExport>;
+
+    /// Brings in users exported by [Server::export_user]: signs them up,
+    /// sets their profiles, re-creates their tribs with their original
+    /// clocks, then their follows and likes. Users and tribs that are already
+    /// there are left as they are, so importing the same data again changes
+    /// nothing. Follows, likes, and replies pointing at a user or trib that
+    /// neither exists nor is part of `data` are skipped.
+    /// Returns error when a trib is too long or its attachments are invalid.
+    async fn import(&self, data: &[UserExport]) -> ServerResult<()>;
 }
 
 /// Checks if a username is a valid one. Returns true if it is.
@@ -114,21 +792,236 @@ pub fn is_valid_username(s: &str) -> bool {
     }
 
     for r in s.chars() {
-        if !('a'..='z').contains(&r) && !('0'..='9').contains(&r) {
+        if !r.is_ascii_lowercase() && !r.is_ascii_digit() {
             return false;
         }
     }
     true
 }
 
+/// Extracts the `#hashtags` of a trib message, lowercased, without the
+/// leading `#` and without duplicates, in order of appearance. A hashtag is
+/// made of ascii letters, digits and underscores.
+pub fn hashtags(message: &str) -> Vec<String> {
+    tokens_after(message, '#')
+}
+
+/// Extracts the users mentioned as `@user` in a trib message, without
+/// duplicates, in order of appearance. Only valid usernames are returned.
+pub fn mentioned_users(message: &str) -> Vec<String> {
+    tokens_after(message, '@')
+        .into_iter()
+        .filter(|user| is_valid_username(user))
+        .collect()
+}
+
+/// Normalizes a hashtag the way [hashtags] extracts it, i.e. without the
+/// leading `#` and lowercased.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim_start_matches('#').to_ascii_lowercase()
+}
+
+/// Returns the trending slot that a trib posted at `time`, in seconds since
+/// the epoch, is counted in.
+pub fn trending_slot(time: u64) -> u64 {
+    time / TRENDING_SLOT
+}
+
+/// Returns the oldest trending slot still inside the window at `now`, in
+/// seconds since the epoch.
+pub fn first_trending_slot(now: u64) -> u64 {
+    (trending_slot(now) + 1).saturating_sub(TRENDING_WINDOW / TRENDING_SLOT)
+}
+
+/// Ranks the uses of hashtags into at most `limit` [Trend]s, the most used
+/// first and ties broken by tag.
+pub fn top_trends(counts: HashMap<String, u64>, limit: usize) -> Vec<Trend> {
+    let mut trends: Vec<Trend> = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(tag, count)| Trend { tag, count })
+        .collect();
+    trends.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    trends.truncate(limit);
+    trends
+}
+
+/// Orders tribs the way timelines list them, oldest first: by logical clock,
+/// then by physical time, then by user, then by message.
+pub fn trib_order(a: &Trib, b: &Trib) -> Ordering {
+    a.clock
+        .cmp(&b.clock)
+        .then_with(|| a.time.cmp(&b.time))
+        .then_with(|| a.user.cmp(&b.user))
+        .then_with(|| a.message.cmp(&b.message))
+}
+
+// collects the words following `sigil` in message
+fn tokens_after(message: &str, sigil: char) -> Vec<String> {
+    let mut found: Vec<String> = vec![];
+    for (i, _) in message.match_indices(sigil) {
+        let token: String = message[i + sigil.len_utf8()..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if !token.is_empty() && !found.contains(&token) {
+            found.push(token);
+        }
+    }
+    found
+}
+
 #[cfg(test)]
 mod test {
-    use crate::trib::is_valid_username;
+    use crate::err::TribblerError;
+    use crate::trib::{
+        first_trending_slot, hashtags, is_valid_avatar, is_valid_username, mentioned_users,
+        top_trends, trending_slot, validate_attachments, Attachment, FollowLogEntry, Profile,
+        Trend, MAX_ATTACHMENTS, MAX_BIO_LEN, MAX_DISPLAY_NAME_LEN, TRENDING_SLOT, TRENDING_WINDOW,
+    };
+    use std::collections::HashMap;
 
     #[test]
     fn valid_usernames() {
-        assert_eq!(true, is_valid_username("h8liu"));
-        assert_eq!(true, is_valid_username("rkapoor"));
-        assert_eq!(true, is_valid_username("fenglu"));
+        assert!(is_valid_username("h8liu"));
+        assert!(is_valid_username("rkapoor"));
+        assert!(is_valid_username("fenglu"));
+    }
+
+    #[test]
+    fn extracts_hashtags() {
+        assert_eq!(
+            vec!["rust".to_string(), "async_io".to_string()],
+            hashtags("#Rust and #async_io, then #rust again")
+        );
+        assert!(hashtags("no tags # here").is_empty());
+    }
+
+    #[test]
+    fn profile_fields_are_length_checked() {
+        let mut profile = Profile {
+            display_name: "Alice".to_string(),
+            bio: "b".repeat(MAX_BIO_LEN),
+            avatar: "".to_string(),
+        };
+        assert!(profile.validate().is_ok());
+        profile.bio.push('b');
+        assert!(profile.validate().is_err());
+        profile.bio.clear();
+        profile.display_name = "d".repeat(MAX_DISPLAY_NAME_LEN + 1);
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn valid_avatars() {
+        assert!(is_valid_avatar(""));
+        assert!(is_valid_avatar("https://example.com/alice.png"));
+        assert!(is_valid_avatar("http://example.com/a"));
+        assert!(is_valid_avatar(&"0f".repeat(16)));
+        assert!(!is_valid_avatar("https://"));
+        assert!(!is_valid_avatar("https://example.com/a b.png"));
+        assert!(!is_valid_avatar("ftp://example.com/a.png"));
+        assert!(!is_valid_avatar("abc123"));
+        assert!(!is_valid_avatar(&"g".repeat(32)));
+    }
+
+    #[test]
+    fn attachments_are_validated() {
+        let image = Attachment {
+            url: "https://example.com/cat.png".to_string(),
+            hash: "ab".repeat(32),
+            mime_type: "image/png".to_string(),
+        };
+        assert!(validate_attachments(std::slice::from_ref(&image)).is_ok());
+        assert!(validate_attachments(&vec![image.clone(); MAX_ATTACHMENTS + 1]).is_err());
+        for broken in [
+            Attachment {
+                url: "ftp://example.com/cat.png".to_string(),
+                ..image.clone()
+            },
+            Attachment {
+                hash: "xyz".to_string(),
+                ..image.clone()
+            },
+            Attachment {
+                mime_type: "image".to_string(),
+                ..image.clone()
+            },
+        ] {
+            assert!(broken.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn extracts_mentions() {
+        assert_eq!(
+            vec!["alice".to_string(), "bob2".to_string()],
+            mentioned_users("hi @alice and @bob2, @alice again")
+        );
+        assert!(mentioned_users("mail me at @ or @this_is_way_too_long").is_empty());
+    }
+
+    #[test]
+    fn trending_window_slides_by_slot() {
+        let now = 1000 * TRENDING_SLOT + 5;
+        assert_eq!(1000, trending_slot(now));
+        let first = first_trending_slot(now);
+        assert_eq!(TRENDING_WINDOW / TRENDING_SLOT, 1000 - first + 1);
+        assert_eq!(0, first_trending_slot(0));
+    }
+
+    #[test]
+    fn ranks_trends() {
+        let mut counts = HashMap::new();
+        counts.insert("rust".to_string(), 3);
+        counts.insert("go".to_string(), 5);
+        counts.insert("c".to_string(), 3);
+        counts.insert("gone".to_string(), 0);
+        let trend = |tag: &str, count| Trend {
+            tag: tag.to_string(),
+            count,
+        };
+        assert_eq!(
+            vec![trend("go", 5), trend("c", 3), trend("rust", 3)],
+            top_trends(counts.clone(), 10)
+        );
+        assert_eq!(vec![trend("go", 5)], top_trends(counts, 1));
+    }
+
+    #[test]
+    fn test_follow_log_entry() {
+        for entry in [
+            "7::follow::bob",
+            "8::unfollow::bob",
+            "9::snapshot::bob,carol",
+            "9::snapshot::",
+        ] {
+            assert_eq!(entry, FollowLogEntry::parse(entry).unwrap().to_string());
+        }
+        assert_eq!(
+            FollowLogEntry::Snapshot {
+                clock: 9,
+                followees: vec![],
+            },
+            FollowLogEntry::parse("9::snapshot::").unwrap()
+        );
+        assert_eq!(
+            8,
+            FollowLogEntry::parse("8::unfollow::bob").unwrap().clock()
+        );
+        for entry in [
+            "",
+            "7::follow",
+            "7::follow::",
+            "x::follow::bob",
+            "7::poke::bob",
+            "7::follow::a::b",
+        ] {
+            assert!(matches!(
+                FollowLogEntry::parse(entry),
+                Err(TribblerError::CorruptLogEntry(e)) if e == entry
+            ));
+        }
     }
 }
@@ -1,6 +1,7 @@
 // Generated by CoffeeScript 2.6.1
 (function() {
-  var _postRetrib, _postTrib, _showHome, _showUser, _updateFollow, _updateFollowing, addUser, appendError, countPostLength, follow, hoveringFollow, lclock, listTribs, listUsers, main, me, postDone, postTrib, seenClock, showHome, showUser, showing, signIn, signOut, unfollow, updateFollow, updateFollowing, updateUsers;
+  var _postRetrib, _postTrib, _restoreDraft, _showHome, _showUser, _signIn, _updateFollow, _updateFollowing, _updateMutual, _updatePresence, _updateUnread, activeWindow, addUser, appendError, avatarUrl, closeLive, countPostLength, csrf, draftSaved, follow, hoveringFollow, lclock, listDrafts, listNotifications, listTrends, listTribs, listUsers, live, main, me, openLive, postDone, postTrib, restoreSession, saveDraft, seenClock, showHome, showNotifications, showUser, showing, signIn, signOut, syncClock, unfollow, updateDrafts, updateFollow, updateFollowing, updatePresence, updateTrends, updateUnread, updateUsers,
+    indexOf = [].indexOf;
 
   me = "";
 
@@ -8,6 +9,12 @@
 
   lclock = 0;
 
+  live = null;
+
+  // the token calls made for the signed in user carry, which pages of other
+  // sites can't read
+  csrf = "";
+
   seenClock = function(c) {
     if (c > lclock) {
       lclock = c;
@@ -15,8 +22,24 @@
     }
   };
 
+  syncClock = function() {
+    $.ajax({
+      url: "api/clock",
+      success: function(data) {
+        var ret;
+        ret = JSON.parse(data);
+        if (ret.err !== "") {
+          appendError(ret.err);
+          return;
+        }
+        seenClock(ret.n);
+      },
+      cache: false
+    });
+  };
+
   listTribs = function(data) {
-    var i, len1, li, ref, ret, retrib, trib, tribs, ul;
+    var attachment, i, j, len1, len2, li, ref, ref1, ret, retrib, trib, tribs, ul;
     ret = JSON.parse(data);
     if (ret.err !== "") {
       appendError(ret.err);
@@ -38,6 +61,13 @@
       li.append('<span class="author"><a class="author" href="#">@' + trib.user + '</a></span> ');
       li.append('<span class="time">' + trib.time + '</span> ');
       li.append($('<span class="trib" />').text(trib.message));
+      ref1 = trib.attachments || [];
+      for (j = 0, len2 = ref1.length; j < len2; j++) {
+        attachment = ref1[j];
+        if (attachment.mime_type.indexOf("image/") === 0) {
+          li.append($('<img class="attachment" />').attr("src", attachment.url));
+        }
+      }
       li.find("a.author").click(function(ev) {
         var name;
         ev.preventDefault();
@@ -116,10 +146,47 @@
     $("div#whom").show();
     $("a#follow").show();
     updateFollow();
+    updatePresence(name);
+  };
+
+  // how long, in seconds, a user counts as active recently after acting
+  activeWindow = 15 * 60;
+
+  _updatePresence = function(data) {
+    var now, ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    now = Date.now() / 1000;
+    if ((ret.at != null) && now - ret.at < activeWindow) {
+      $("span#presence").show();
+    } else {
+      $("span#presence").hide();
+    }
+  };
+
+  updatePresence = function(name) {
+    $("span#presence").hide();
+    $.ajax({
+      url: "api/last-active",
+      type: "POST",
+      data: name,
+      success: _updatePresence,
+      cache: false
+    });
+  };
+
+  avatarUrl = function(avatar) {
+    if (/^https?:\/\//.test(avatar)) {
+      return avatar;
+    }
+    return "https://www.gravatar.com/avatar/" + avatar;
   };
 
   updateUsers = function(data) {
-    var i, len1, name, ref, ret, ul, users;
+    var i, j, len1, li, name, ref, ret, ul, users;
     ret = JSON.parse(data);
     if (ret.err !== "") {
       appendError(ret.err);
@@ -133,26 +200,36 @@
     }
     ul = $("<ul/>");
     ref = ret.users;
-    for (i = 0, len1 = ref.length; i < len1; i++) {
+    for (i = j = 0, len1 = ref.length; j < len1; i = ++j) {
       name = ref[i];
-      ul.append('<li><a href="#">' + name + '</a></li>');
+      li = $('<li><a href="#">' + name + '</a></li>');
+      if ((ret.avatars != null) && ret.avatars[i]) {
+        li.find("a").prepend($('<img class="avatar"/>').attr("src", avatarUrl(ret.avatars[i])));
+      }
+      ul.append(li);
     }
     users.append(ul);
     $("#users li").click(showUser);
   };
 
   addUser = function() {
-    var name;
+    var name, password;
     name = $("form#adduser input#username").val();
-    if (name === "") {
+    password = $("form#adduser input#password").val();
+    // a user signs in with the password given here
+    if (name === "" || password === "") {
       return false;
     }
     $("form#adduser input#username").val("");
+    $("form#adduser input#password").val("");
     console.log("add user", name);
     $.ajax({
       url: "api/add-user",
       type: "POST",
-      data: name,
+      data: JSON.stringify({
+        user: name,
+        password: password
+      }),
       success: updateUsers,
       cache: false
     });
@@ -167,18 +244,72 @@
     });
   };
 
+  updateTrends = function(data) {
+    var j, len1, ref, ret, t, trends, ul;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    trends = $("#trends");
+    trends.empty();
+    if (ret.trends.length === 0) {
+      trends.append("Nothing yet.");
+      return;
+    }
+    ul = $("<ul/>");
+    ref = ret.trends;
+    for (j = 0, len1 = ref.length; j < len1; j++) {
+      t = ref[j];
+      ul.append($("<li/>").text("#" + t.tag + " (" + t.count + ")"));
+    }
+    trends.append(ul);
+  };
+
+  listTrends = function() {
+    $.ajax({
+      url: "api/trending",
+      success: updateTrends,
+      cache: false
+    });
+  };
+
   appendError = function(e) {
     $("div#errors").show();
     return $("div#errors").append('<div class="error">Error: ' + e + '</div>');
   };
 
   signIn = function(ev) {
+    var name, password;
     ev.preventDefault();
     if (showing === "" || showing === "!home") {
       return;
     }
     console.log("sign in as: " + showing);
-    me = showing;
+    // the server only lets a browser act for the user its session signed in as
+    name = showing;
+    password = $("input#signinpass").val();
+    $("input#signinpass").val("");
+    $.ajax({
+      url: "api/login",
+      type: "POST",
+      data: JSON.stringify({
+        user: name,
+        password: password
+      }),
+      success: function(data) {
+        csrf = JSON.parse(data).csrf;
+        _signIn(name);
+      },
+      error: function(xhr) {
+        appendError(xhr.responseText);
+      },
+      cache: false
+    });
+  };
+
+  _signIn = function(name) {
+    me = name;
     $("div#who").show();
     $("div#who h3").html("Signed in as " + me);
     $("div#compose").show();
@@ -187,21 +318,151 @@
     updateFollow();
     $("div#followings").empty();
     updateFollowing();
+    $("div#notes").empty();
+    $("div#notifications").show();
+    updateUnread();
+    updateDrafts();
+    closeLive();
+    openLive();
+  };
+
+  // the server pushes the tribs the home timeline gains over a websocket
+  openLive = function() {
+    var scheme;
+    if (!window.WebSocket) {
+      return;
+    }
+    scheme = location.protocol === "https:" ? "wss://" : "ws://";
+    live = new WebSocket(scheme + location.host + "/ws?user=" + encodeURIComponent(me));
+    live.onmessage = function(ev) {
+      var trib;
+      trib = JSON.parse(ev.data);
+      seenClock(trib.clock);
+      if (showing === "!home") {
+        _showHome();
+      }
+    };
+    live.onclose = function() {
+      live = null;
+    };
+  };
+
+  closeLive = function() {
+    if (live != null) {
+      live.onclose = null;
+      live.close();
+      live = null;
+    }
   };
 
   signOut = function(ev) {
     console.log("sign out");
     ev.preventDefault();
+    $.ajax({
+      url: "api/logout",
+      type: "POST",
+      cache: false
+    });
     me = "";
+    csrf = "";
+    closeLive();
     $("div#who").hide();
     $("div#compose").hide();
     $("div#following").hide();
+    $("div#notifications").hide();
+    $("div#drafts").empty();
     $("a#follow").hide();
     if (showing === "!home") {
       $("div#timeline").hide();
     }
   };
 
+  updateUnread = function() {
+    if (me === "") {
+      return;
+    }
+    $.ajax({
+      url: "api/unread-count",
+      type: "POST",
+      data: me,
+      success: _updateUnread,
+      cache: false
+    });
+  };
+
+  _updateUnread = function(data) {
+    var badge, ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    badge = $("span#unread");
+    if (ret.n > 0) {
+      badge.html(ret.n).show();
+    } else {
+      badge.hide();
+    }
+  };
+
+  showNotifications = function(ev) {
+    ev.preventDefault();
+    $.ajax({
+      url: "api/notifications",
+      type: "POST",
+      data: JSON.stringify({
+        user: me,
+        since: 0
+      }),
+      success: listNotifications,
+      cache: false
+    });
+  };
+
+  listNotifications = function(data) {
+    var div, j, last, len1, li, n, ref, ret, ul, what;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    div = $("div#notes");
+    div.empty();
+    if (ret.notifications.length === 0) {
+      div.append("Nothing yet.");
+      return;
+    }
+    what = {
+      follow: "followed you",
+      mention: "mentioned you",
+      like: "liked your tribble",
+      reply: "replied to you"
+    };
+    ul = $("<ul/>");
+    ref = ret.notifications;
+    for (j = 0, len1 = ref.length; j < len1; j++) {
+      n = ref[j];
+      li = $("<li/>").text("@" + n.from + " " + what[n.kind]);
+      if (!n.read) {
+        li.addClass("unread");
+      }
+      ul.prepend(li);
+    }
+    div.append(ul);
+    // everything shown is read now
+    last = ret.notifications[ret.notifications.length - 1].clock;
+    $.ajax({
+      url: "api/mark-read",
+      type: "POST",
+      data: JSON.stringify({
+        user: me,
+        upto: last
+      }),
+      success: updateUnread,
+      cache: false
+    });
+  };
+
   updateFollowing = function() {
     $.ajax({
       url: "api/following",
@@ -301,8 +562,23 @@
     });
   };
 
+  _updateMutual = function(data) {
+    var ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    if (indexOf.call(ret.users, showing) >= 0) {
+      $("span#followsyou").show();
+    } else {
+      $("span#followsyou").hide();
+    }
+  };
+
   updateFollow = function() {
     var but;
+    $("span#followsyou").hide();
     if (me === "" || showing === "!home") {
       $("a#follow").hide();
       return;
@@ -333,6 +609,13 @@
       success: _updateFollow,
       cache: false
     });
+    $.ajax({
+      url: "api/mutuals",
+      type: "POST",
+      data: me,
+      success: _updateMutual,
+      cache: false
+    });
   };
 
   countPostLength = function() {
@@ -386,6 +669,89 @@
     });
   };
 
+  saveDraft = function(ev) {
+    var text;
+    ev.preventDefault();
+    text = $("form#post textarea").val();
+    if (text.length === 0) {
+      return;
+    }
+    $.ajax({
+      url: "api/save-draft",
+      type: "POST",
+      data: JSON.stringify({
+        who: me,
+        message: text
+      }),
+      success: draftSaved,
+      cache: false
+    });
+  };
+
+  draftSaved = function(data) {
+    var ret;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    $("form#post textarea").val("");
+    countPostLength();
+    updateDrafts();
+  };
+
+  updateDrafts = function() {
+    $.ajax({
+      url: "api/list-drafts",
+      type: "POST",
+      data: me,
+      success: listDrafts,
+      cache: false
+    });
+  };
+
+  listDrafts = function(data) {
+    var div, draft, j, len1, li, ref, ret, ul;
+    ret = JSON.parse(data);
+    if (ret.err !== "") {
+      appendError(ret.err);
+      return;
+    }
+    div = $("div#drafts");
+    div.empty();
+    if (ret.drafts.length === 0) {
+      return;
+    }
+    ul = $("<ul/>");
+    ref = ret.drafts;
+    for (j = 0, len1 = ref.length; j < len1; j++) {
+      draft = ref[j];
+      li = $('<li><a href="#"></a></li>');
+      li.find("a").text(draft.message).click(_restoreDraft(draft));
+      ul.append(li);
+    }
+    div.append(ul);
+  };
+
+  // moves a draft back into the compose box
+  _restoreDraft = function(draft) {
+    return function(ev) {
+      ev.preventDefault();
+      $("form#post textarea").val(draft.message);
+      countPostLength();
+      $.ajax({
+        url: "api/delete-draft",
+        type: "POST",
+        data: JSON.stringify({
+          who: me,
+          id: draft.id
+        }),
+        success: updateDrafts,
+        cache: false
+      });
+    };
+  };
+
   postDone = function(data) {
     var ret;
     ret = JSON.parse(data);
@@ -393,16 +759,51 @@
       appendError(ret.err);
       return;
     }
+    listTrends();
     if (showing === "") {
       return;
     } else if (showing === "!home") {
-      _showHome();
+      // the live timeline brings the post in
+      if (live == null) {
+        _showHome();
+      }
     } else {
       _showUser(showing);
     }
   };
 
+  // a browser that signed in before, or just came back from single sign-on,
+  // is signed in already
+  restoreSession = function() {
+    $.ajax({
+      url: "api/whoami",
+      success: function(data) {
+        var ret;
+        ret = JSON.parse(data);
+        if (ret.err !== "") {
+          appendError(ret.err);
+          return;
+        }
+        if (ret.sso) {
+          $("a#sso").show();
+        }
+        if (ret.user !== "") {
+          csrf = ret.csrf;
+          _signIn(ret.user);
+        }
+      },
+      cache: false
+    });
+  };
+
   main = function() {
+    $.ajaxSetup({
+      beforeSend: function(xhr) {
+        if (csrf !== "") {
+          xhr.setRequestHeader("X-CSRF-Token", csrf);
+        }
+      }
+    });
     $("form#adduser").submit(addUser);
     $("form#post").submit(postTrib);
     $("div#errors").hide();
@@ -410,6 +811,8 @@
     $("a#signin").click(signIn);
     $("a#home").click(showHome);
     $("a#signout").click(signOut);
+    $("a#shownotes").click(showNotifications);
+    $("a#savedraft").click(saveDraft);
     $("form#post textarea").keydown(function() {
       return setTimeout((function() {
         return countPostLength();
@@ -422,7 +825,11 @@
     });
     $("form#post textarea").keyup(countPostLength);
     $("form#post textarea").change(countPostLength);
+    syncClock();
     listUsers();
+    listTrends();
+    restoreSession();
+    setInterval(updateUnread, 10000);
   };
 
   $(document).ready(main);